@@ -0,0 +1,243 @@
+//! # bb8 for UnReQL
+//!
+//! This crate implements a [`bb8`](https://crates.io/crates/bb8)
+//! connection manager for [`unreql`](https://crates.io/crates/unreql).
+//!
+//! ## Example
+//!
+//! ```rust
+//! use unreql::{r, cmd::connect};
+//! use unreql_bb8::{PoolWrapper, SessionManager};
+//!
+//! # async fn example() -> unreql::Result<()> {
+//! let cfg = connect::Options::default();
+//! let manager = SessionManager::new(cfg);
+//! let pool = PoolWrapper::build(bb8::Pool::builder().max_size(20), manager).await?;
+//! # #[derive(serde::Deserialize)] struct User;
+//! let user: User = r.table("users").get("id").exec(&pool).await?;
+//! # Ok(()) }
+//! ```
+
+use std::future::Future;
+use std::io;
+use std::ops::Deref;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bb8::{Builder, ManageConnection, Pool, RunError};
+
+use unreql::{
+    cmd::{
+        connect::{self, TcpStream},
+        run,
+    },
+    pool::SessionFactory,
+    Connection, Error, Session,
+};
+
+pub use unreql::pool::{BreakerState, RetryPolicy};
+
+#[derive(Debug, Clone)]
+pub struct SessionManager {
+    factory: SessionFactory,
+}
+
+impl SessionManager {
+    pub fn new(options: connect::Options) -> Self {
+        Self {
+            factory: SessionFactory::new(options),
+        }
+    }
+
+    /// Open the connections of new sessions with `connector`
+    ///
+    /// The closure returns a connected stream, the handshake is then done
+    /// over it with the options of the manager. `host` and `port` of the
+    /// options are not used.
+    ///
+    /// ## Example
+    /// Connect through a local SSH tunnel.
+    ///
+    /// ```rust
+    /// # use unreql::cmd::connect::{self, TcpStream};
+    /// # use unreql_bb8::SessionManager;
+    /// let manager = SessionManager::new(connect::Options::default())
+    ///     .with_connector(|| TcpStream::connect("127.0.0.1:28016"));
+    /// ```
+    pub fn with_connector<F, Fut>(mut self, connector: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<TcpStream>> + Send + 'static,
+    {
+        self.factory = self.factory.with_connector(connector);
+        self
+    }
+
+    /// Stop connecting to an unavailable cluster for a while.
+    ///
+    /// Works the same way as in `unreql_deadpool`, see
+    /// [CircuitBreaker](unreql::pool::CircuitBreaker).
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use unreql::cmd::connect;
+    /// # use unreql_bb8::SessionManager;
+    /// let manager = SessionManager::new(connect::Options::default())
+    ///     .with_circuit_breaker(5, Duration::from_secs(10));
+    /// ```
+    pub fn with_circuit_breaker(mut self, threshold: u32, cool_down: Duration) -> Self {
+        self.factory = self.factory.with_circuit_breaker(threshold, cool_down);
+        self
+    }
+
+    /// Retry to create a session while the server is unreachable, with
+    /// exponential backoff, instead of failing right away.
+    ///
+    /// See [RetryPolicy] for which errors are retried. With a circuit
+    /// breaker, all the attempts of one session count as one failure.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use unreql::cmd::connect;
+    /// # use unreql_bb8::{RetryPolicy, SessionManager};
+    /// let manager = SessionManager::new(connect::Options::default()).with_retry(
+    ///     RetryPolicy::new()
+    ///         .max_attempts(5)
+    ///         .base_delay(Duration::from_millis(200))
+    ///         .max_delay(Duration::from_secs(3)),
+    /// );
+    /// ```
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.factory = self.factory.with_retry(policy);
+        self
+    }
+
+    /// The state of the circuit breaker, `None` if it is not enabled
+    pub fn breaker_state(&self) -> Option<BreakerState> {
+        self.factory.breaker_state()
+    }
+
+    /// Get a new session outside the pool.
+    /// Use the new session to create a connection for changes
+    pub async fn new_session(&self) -> Result<Session, Error> {
+        self.factory.new_session().await
+    }
+}
+
+impl ManageConnection for SessionManager {
+    type Connection = Session;
+    type Error = Error;
+
+    async fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        self.factory.create().await
+    }
+
+    async fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        self.factory.recycle(conn).await
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        conn.is_broken()
+    }
+}
+
+/// A pool of sessions to run queries on
+///
+/// bb8 does not give access to the manager of a pool, so the wrapper keeps
+/// a copy of it, sharing the circuit breaker and the connector.
+#[derive(Debug, Clone)]
+pub struct PoolWrapper {
+    pool: Pool<SessionManager>,
+    manager: SessionManager,
+}
+
+impl Deref for PoolWrapper {
+    type Target = Pool<SessionManager>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pool
+    }
+}
+
+impl PoolWrapper {
+    /// Build the pool, opening its initial sessions
+    pub async fn build(
+        builder: Builder<SessionManager>,
+        manager: SessionManager,
+    ) -> Result<Self, Error> {
+        let pool = builder.build(manager.clone()).await?;
+        Ok(Self { pool, manager })
+    }
+
+    /// Build the pool, opening its initial sessions in the background
+    ///
+    /// Must be called within a tokio runtime.
+    pub fn build_unchecked(builder: Builder<SessionManager>, manager: SessionManager) -> Self {
+        let pool = builder.build_unchecked(manager.clone());
+        Self { pool, manager }
+    }
+
+    /// The state of the circuit breaker of the manager, `None` if it is
+    /// not enabled
+    ///
+    /// See [SessionManager::with_circuit_breaker]
+    pub fn breaker_state(&self) -> Option<BreakerState> {
+        self.manager.breaker_state()
+    }
+
+    async fn session(&self, for_changes: bool) -> Result<Session, Error> {
+        if for_changes {
+            // for `changes` create a separate new connection to DB
+            return self.manager.new_session().await;
+        }
+        // otherwise the available connection is used
+        match self.pool.get().await {
+            Ok(sess) => Ok(sess.clone()),
+            Err(RunError::User(err)) => Err(err),
+            Err(RunError::TimedOut) => Err(Error::Driver(unreql::Driver::Other(
+                "timed out waiting for a connection".into(),
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl run::Arg for &PoolWrapper {
+    async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, run::Options), Error> {
+        let sess = self.session(for_changes).await?;
+        sess.into_run_opts(for_changes).await
+    }
+}
+
+#[async_trait]
+impl run::Arg for PoolWrapper {
+    async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, run::Options), Error> {
+        (&self).into_run_opts(for_changes).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn connector_is_used() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let manager = SessionManager::new(connect::Options::default()).with_connector({
+            let calls = calls.clone();
+            move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Err(io::Error::other("no tunnel")) }
+            }
+        });
+        let err = manager.new_session().await.unwrap_err();
+        assert_eq!(err.to_string(), "client error; no tunnel");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+use unreql::r;
+use unreql_bb8::{PoolWrapper, SessionManager};
+use unreql_examples::fake_server;
+
+// A fake server counting the connections made to it
+async fn server() -> (SessionManager, Arc<AtomicUsize>) {
+    let (options, connections) = fake_server::start_counting().await;
+    (SessionManager::new(options), connections)
+}
+
+#[tokio::test]
+async fn queries_share_pooled_session() {
+    let (manager, connections) = server().await;
+    let pool = PoolWrapper::build(bb8::Pool::builder().max_size(1), manager)
+        .await
+        .unwrap();
+    for n in 0..3 {
+        assert_eq!(r.expr(n).exec::<i64>(&pool).await.unwrap(), n);
+    }
+    assert_eq!(connections.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn changefeed_gets_own_session() {
+    let (manager, connections) = server().await;
+    let pool = PoolWrapper::build(bb8::Pool::builder().max_size(1), manager)
+        .await
+        .unwrap();
+    r.table_create("games").exec::<Value>(&pool).await.unwrap();
+    let mut changes = r.table("games").changes(()).run::<Value>(&pool);
+    let change = changes.try_next().await.unwrap();
+    assert_eq!(change, Some(json!({ "state": "initializing" })));
+    assert_eq!(connections.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn breaker_state_is_shared() {
+    let (manager, _) = server().await;
+    let manager = manager.with_circuit_breaker(3, std::time::Duration::from_secs(10));
+    let pool = PoolWrapper::build(bb8::Pool::builder().max_size(1), manager)
+        .await
+        .unwrap();
+    assert_eq!(r.expr(1).exec::<i64>(&pool).await.unwrap(), 1);
+    assert_eq!(pool.breaker_state(), Some(unreql_bb8::BreakerState::Closed));
+}
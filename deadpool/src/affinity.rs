@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// The sessions pinned to keys by `PoolWrapper::get_affine`
+#[derive(Debug)]
+pub(crate) struct Affinity<T> {
+    window: Duration,
+    pinned: Mutex<HashMap<String, (T, Instant)>>,
+}
+
+impl<T: Clone> Affinity<T> {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            pinned: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // The value pinned to `key`, if it was used within the window and is
+    // still `usable`. Using it extends the window.
+    pub(crate) fn get(&self, key: &str, now: Instant, usable: impl Fn(&T) -> bool) -> Option<T> {
+        let mut pinned = self.pinned.lock().unwrap();
+        let (value, last_use) = pinned.get_mut(key)?;
+        if now.duration_since(*last_use) >= self.window || !usable(value) {
+            pinned.remove(key);
+            return None;
+        }
+        *last_use = now;
+        Some(value.clone())
+    }
+
+    pub(crate) fn pin(&self, key: String, value: T, now: Instant) {
+        let mut pinned = self.pinned.lock().unwrap();
+        // forget the keys whose window is over
+        pinned.retain(|_, (_, last_use)| now.duration_since(*last_use) < self.window);
+        pinned.insert(key, (value, now));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn same_value_within_window() {
+        let affinity = Affinity::new(WINDOW);
+        let start = Instant::now();
+        assert_eq!(affinity.get("user", start, |_| true), None);
+        affinity.pin("user".into(), 1, start);
+        affinity.pin("other".into(), 2, start);
+
+        // each use extends the window
+        let later = start + WINDOW / 2;
+        assert_eq!(affinity.get("user", later, |_| true), Some(1));
+        assert_eq!(affinity.get("user", start + WINDOW, |_| true), Some(1));
+        assert_eq!(affinity.get("other", start + WINDOW, |_| true), None);
+    }
+
+    #[test]
+    fn unusable_value_is_unpinned() {
+        let affinity = Affinity::new(WINDOW);
+        let now = Instant::now();
+        affinity.pin("user".into(), 1, now);
+        assert_eq!(affinity.get("user", now, |_| false), None);
+        assert_eq!(affinity.get("user", now, |_| true), None);
+    }
+
+    #[test]
+    fn expired_keys_are_forgotten() {
+        let affinity = Affinity::new(WINDOW);
+        let start = Instant::now();
+        affinity.pin("user".into(), 1, start);
+        affinity.pin("other".into(), 2, start + WINDOW);
+        assert_eq!(affinity.pinned.lock().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,170 @@
+use std::time::Duration;
+
+use async_io::Timer;
+use async_stream::stream;
+use futures::{Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use unreql::{cmd::options::ChangesOptions, Command, Driver, Error, Runtime};
+
+use crate::PoolWrapper;
+
+/// Delays between resubscriptions of [resilient_changes](PoolWrapper::resilient_changes)
+///
+/// The delay starts at `initial` and doubles after every failed attempt
+/// up to `max`. It is reset once the feed yields a value again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    /// The delay before the first resubscription, by default 100ms.
+    pub initial: Duration,
+    /// The upper bound of the delay, by default 30s.
+    pub max: Duration,
+    /// Give up and return the error after this many failed attempts in a row.
+    /// By default the feed is resubscribed forever.
+    pub max_retries: Option<u32>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(30),
+            max_retries: None,
+        }
+    }
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn initial(mut self, initial: Duration) -> Self {
+        self.initial = initial;
+        self
+    }
+
+    pub fn max(mut self, max: Duration) -> Self {
+        self.max = max;
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::MAX);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+// Errors after which the feed may come back on a new connection
+fn is_recoverable(err: &Error) -> bool {
+    err.is_unreachable()
+        || matches!(
+            err,
+            Error::Driver(Driver::CircuitOpen | Driver::Reconnected)
+                | Error::Runtime(Runtime::Availability(_))
+        )
+}
+
+impl PoolWrapper {
+    /// Subscribe to the changes of `query`, resubscribing when the feed fails.
+    ///
+    /// Every subscription uses a dedicated session outside the pool, the same
+    /// way as running [changes](unreql::Command::changes) on the pool does.
+    /// Broken connections, IO errors, connect timeouts, an open circuit
+    /// breaker, reconnected sessions and availability errors (e.g. the table
+    /// became unavailable) make the feed resubscribe after a delay given by
+    /// `backoff`. Any other error is returned and ends the stream.
+    ///
+    /// Changes made while the feed is being resubscribed are not delivered,
+    /// use `include_initial` to receive the current state after every
+    /// resubscription.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use unreql::{r, cmd::options::ChangesOptions};
+    /// # use unreql_deadpool::{Backoff, PoolWrapper};
+    /// # use futures::TryStreamExt;
+    /// # async fn example(pool: PoolWrapper) -> unreql::Result<()> {
+    /// let mut feed = pool.resilient_changes::<serde_json::Value>(
+    ///     r.table("games"),
+    ///     ChangesOptions::new().include_initial(true),
+    ///     Backoff::new(),
+    /// );
+    /// while let Some(change) = feed.try_next().await? {
+    ///     println!("{change}");
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn resilient_changes<T>(
+        &self,
+        query: Command,
+        opts: ChangesOptions,
+        backoff: Backoff,
+    ) -> impl Stream<Item = Result<T, Error>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        let pool = self.clone();
+        Box::pin(stream! {
+            let mut attempt = 0;
+            loop {
+                let mut feed = query.clone().changes(opts).run::<T>(pool.clone());
+                let err = loop {
+                    match feed.next().await {
+                        Some(Ok(val)) => {
+                            attempt = 0;
+                            yield Ok(val);
+                        }
+                        Some(Err(err)) => break Some(err),
+                        None => break None,
+                    }
+                };
+                // the feed was closed without an error
+                let Some(err) = err else { break };
+                drop(feed);
+                if !is_recoverable(&err) || backoff.max_retries.is_some_and(|x| attempt >= x) {
+                    yield Err(err);
+                    break;
+                }
+                Timer::after(backoff.delay(attempt)).await;
+                attempt += 1;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let backoff = Backoff::new()
+            .initial(Duration::from_millis(100))
+            .max(Duration::from_secs(1));
+        let delays: Vec<_> = (0..6).map(|x| backoff.delay(x).as_millis()).collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(backoff.delay(100), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn fatal_errors() {
+        assert!(is_recoverable(&Driver::ConnectionBroken.into()));
+        let timeout = Driver::ConnectTimeout(Duration::from_secs(20));
+        assert!(is_recoverable(&timeout.into()));
+        assert!(is_recoverable(&Driver::CircuitOpen.into()));
+        assert!(is_recoverable(&Driver::Reconnected.into()));
+        assert!(is_recoverable(
+            &unreql::Availability::OpFailed("table unavailable".into()).into()
+        ));
+        assert!(!is_recoverable(&Error::Compile("bad query".into())));
+        assert!(!is_recoverable(
+            &Runtime::Permission("denied".into()).into()
+        ));
+    }
+}
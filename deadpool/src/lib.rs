@@ -15,34 +15,155 @@
 //! let manager = SessionManager::new(cfg);
 //! let pool = Pool::builder(manager).max_size(20).build().unwrap().wrapper();
 //! # #[derive(serde::Deserialize)] struct User;
-//! let user: User = r.table("users").get("id").exec(&pool).await?;
+//! // `None` if there is no user with this id
+//! let user: Option<User> = r.table("users").get("id").exec(&pool).await?;
 //! # Ok(()) }
 //! ```
+//!
+//! A fuller example, creating, reading, updating and deleting users and
+//! handling the errors, is `examples/1-deadpool/pool_crud.rs` in the
+//! repository.
+
+mod affinity;
+mod changes;
+mod stats;
 
+use std::future::Future;
+use std::io;
 use std::ops::Deref;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use deadpool::managed::{self, Pool, PoolError};
 
 use unreql::{
-    cmd::{connect, run},
-    r, Connection, Error, Session,
+    cmd::{
+        connect::{self, TcpStream},
+        run,
+    },
+    pool::SessionFactory,
+    Connection, Error, Session,
 };
 
+pub use changes::Backoff;
+pub use stats::AcquireStats;
+pub use unreql::pool::{BreakerState, RecyclePolicy, RetryPolicy};
+
+use affinity::Affinity;
+use stats::AcquireHook;
+
+// How long `get_affine` keeps a key pinned to a session by default
+const AFFINITY_WINDOW: Duration = Duration::from_secs(5);
+
 #[derive(Debug)]
 pub struct SessionManager {
-    options: connect::Options,
+    factory: SessionFactory,
 }
 
 impl SessionManager {
     pub fn new(options: connect::Options) -> Self {
-        Self { options }
+        Self {
+            factory: SessionFactory::new(options),
+        }
+    }
+
+    /// Open the connections of new sessions with `connector`
+    ///
+    /// The closure returns a connected stream, the handshake is then done
+    /// over it with the options of the manager. `host` and `port` of the
+    /// options are not used.
+    ///
+    /// ## Example
+    /// Connect through a local SSH tunnel.
+    ///
+    /// ```rust
+    /// # use unreql::cmd::connect::{self, TcpStream};
+    /// # use unreql_deadpool::SessionManager;
+    /// let manager = SessionManager::new(connect::Options::default())
+    ///     .with_connector(|| TcpStream::connect("127.0.0.1:28016"));
+    /// ```
+    pub fn with_connector<F, Fut>(mut self, connector: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<TcpStream>> + Send + 'static,
+    {
+        self.factory = self.factory.with_connector(connector);
+        self
+    }
+
+    /// Stop connecting to an unavailable cluster for a while.
+    ///
+    /// After `threshold` consecutive failures to create or recycle a session
+    /// the breaker opens: for `cool_down` creating a session fails right away
+    /// with [Driver::CircuitOpen](unreql::Driver::CircuitOpen). Then one
+    /// session is created to probe the cluster, if it succeeds the pool works
+    /// as usual again, otherwise the breaker opens for another `cool_down`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use unreql::cmd::connect;
+    /// # use unreql_deadpool::SessionManager;
+    /// let manager = SessionManager::new(connect::Options::default())
+    ///     .with_circuit_breaker(5, Duration::from_secs(10));
+    /// ```
+    pub fn with_circuit_breaker(mut self, threshold: u32, cool_down: Duration) -> Self {
+        self.factory = self.factory.with_circuit_breaker(threshold, cool_down);
+        self
+    }
+
+    /// Retry to create a session while the server is unreachable, with
+    /// exponential backoff, instead of failing right away.
+    ///
+    /// See [RetryPolicy] for which errors are retried. With a circuit
+    /// breaker, all the attempts of one session count as one failure.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// # use unreql::cmd::connect;
+    /// # use unreql_deadpool::{RetryPolicy, SessionManager};
+    /// let manager = SessionManager::new(connect::Options::default()).with_retry(
+    ///     RetryPolicy::new()
+    ///         .max_attempts(5)
+    ///         .base_delay(Duration::from_millis(200))
+    ///         .max_delay(Duration::from_secs(3)),
+    /// );
+    /// ```
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.factory = self.factory.with_retry(policy);
+        self
+    }
+
+    /// The state of the circuit breaker, `None` if it is not enabled
+    pub fn breaker_state(&self) -> Option<BreakerState> {
+        self.factory.breaker_state()
+    }
+
+    /// Set how a session is checked before it is reused, by default
+    /// with a trivial query
+    ///
+    /// ## Example
+    /// Evict the sessions handed over to another server by a proxy.
+    ///
+    /// ```rust
+    /// # use unreql::cmd::connect;
+    /// # use unreql_deadpool::{RecyclePolicy, SessionManager};
+    /// let manager = SessionManager::new(connect::Options::default())
+    ///     .with_recycle_policy(RecyclePolicy::SameServer);
+    /// ```
+    pub fn with_recycle_policy(mut self, policy: RecyclePolicy) -> Self {
+        self.factory = self.factory.with_recycle_policy(policy);
+        self
     }
 
     /// Get a new session outside the pool.
     /// Use the new session to create a connection for changes
     pub async fn new_session(&self) -> Result<Session, Error> {
-        r.connect(self.options.clone()).await
+        self.factory.new_session().await
     }
 }
 
@@ -52,7 +173,7 @@ impl managed::Manager for SessionManager {
     type Error = Error;
 
     async fn create(&self) -> Result<Self::Type, Self::Error> {
-        self.new_session().await
+        self.factory.create().await
     }
 
     async fn recycle(
@@ -60,65 +181,123 @@ impl managed::Manager for SessionManager {
         conn: &mut Self::Type,
         _: &managed::Metrics,
     ) -> managed::RecycleResult<Error> {
-        let _: i64 = r.expr(200).exec(conn).await?;
+        self.factory.recycle(conn).await?;
         Ok(())
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct PoolWrapper(Pool<SessionManager>);
+pub struct PoolWrapper {
+    pool: Pool<SessionManager>,
+    on_acquire: Option<AcquireHook>,
+    affinity: Arc<Affinity<Session>>,
+}
 
 impl Deref for PoolWrapper {
     type Target = Pool<SessionManager>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.pool
     }
 }
 
-#[async_trait]
-impl run::Arg for &PoolWrapper {
-    async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, run::Options), Error> {
+impl PoolWrapper {
+    /// The state of the circuit breaker of the manager, `None` if it is
+    /// not enabled
+    ///
+    /// See [SessionManager::with_circuit_breaker]
+    pub fn breaker_state(&self) -> Option<BreakerState> {
+        self.manager().breaker_state()
+    }
+
+    /// Call `hook` every time a query takes a session from the pool
+    ///
+    /// Useful to tell pool contention from slow queries.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use unreql_deadpool::PoolWrapper;
+    /// # fn example(pool: PoolWrapper) {
+    /// let pool = pool.on_acquire(|stats| {
+    ///     println!("waited for a session {:?}", stats.pool_wait);
+    /// });
+    /// # }
+    /// ```
+    pub fn on_acquire(mut self, hook: impl Fn(&AcquireStats) + Send + Sync + 'static) -> Self {
+        self.on_acquire = Some(AcquireHook::new(hook));
+        self
+    }
+
+    /// Get a session of the pool, the same one for the same `key` while
+    /// it keeps being used
+    ///
+    /// Queries run with the sessions of the same key, e.g. the id of
+    /// a user session, see each other's writes right away. This is best
+    /// effort: a key is unpinned once it was not used for the
+    /// [affinity window](Self::affinity_window), 5 seconds by default,
+    /// or if its session is broken.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// # use unreql::{r, rjson, types::WriteStatus};
+    /// # use unreql_deadpool::PoolWrapper;
+    /// # async fn example(pool: PoolWrapper) -> unreql::Result<()> {
+    /// let session = pool.get_affine("user-42").await?;
+    /// let item = rjson!({ "user": "user-42", "item": 1 });
+    /// r.table("carts").insert(item).exec::<WriteStatus>(&session).await?;
+    ///
+    /// let session = pool.get_affine("user-42").await?;
+    /// let count: u64 = r.table("carts").count(()).exec(&session).await?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn get_affine(&self, key: impl Into<String>) -> Result<Session, Error> {
+        let key = key.into();
+        let usable = |session: &Session| !session.is_broken();
+        if let Some(session) = self.affinity.get(&key, Instant::now(), usable) {
+            return Ok(session);
+        }
+        let session = self.session(false).await?;
+        self.affinity.pin(key, session.clone(), Instant::now());
+        Ok(session)
+    }
+
+    /// Set how long [get_affine](Self::get_affine) keeps an unused key
+    /// pinned to its session
+    ///
+    /// The keys pinned so far are forgotten.
+    pub fn affinity_window(mut self, window: Duration) -> Self {
+        self.affinity = Arc::new(Affinity::new(window));
+        self
+    }
+
+    async fn session(&self, for_changes: bool) -> Result<Session, Error> {
         if for_changes {
             // for `changes` create a separate new connection to DB
-            let sess = self.manager().new_session().await?;
-            sess.into_run_opts(for_changes).await
-        } else {
-            // otherwise the available connection is used
-            let sess = match self.get().await {
-                Ok(v) => v,
-                Err(err) => {
-                    return match err {
-                        PoolError::Backend(err) => Err(err),
-                        _ => Err(Error::Driver(unreql::Driver::Other(err.to_string()))),
-                    }
-                }
-            };
-            sess.into_run_opts(for_changes).await
+            return self.manager().new_session().await;
+        }
+        // otherwise the available connection is used
+        match stats::acquire(&self.pool, self.on_acquire.as_ref()).await {
+            Ok(sess) => Ok(sess.clone()),
+            Err(PoolError::Backend(err)) => Err(err),
+            Err(err) => Err(Error::Driver(unreql::Driver::Other(err.to_string()))),
         }
     }
 }
 
+#[async_trait]
+impl run::Arg for &PoolWrapper {
+    async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, run::Options), Error> {
+        let sess = self.session(for_changes).await?;
+        sess.into_run_opts(for_changes).await
+    }
+}
+
 #[async_trait]
 impl run::Arg for PoolWrapper {
     async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, run::Options), Error> {
-        if for_changes {
-            // for `changes` create a separate new connection to DB
-            let sess = self.manager().new_session().await?;
-            sess.into_run_opts(for_changes).await
-        } else {
-            // otherwise the available connection is used
-            let sess = match self.get().await {
-                Ok(v) => v,
-                Err(err) => {
-                    return match err {
-                        PoolError::Backend(err) => Err(err),
-                        _ => Err(Error::Driver(unreql::Driver::Other(err.to_string()))),
-                    }
-                }
-            };
-            sess.into_run_opts(for_changes).await
-        }
+        (&self).into_run_opts(for_changes).await
     }
 }
 
@@ -134,6 +313,32 @@ impl IntoPoolWrapper for Pool<SessionManager> {
 
 impl From<Pool<SessionManager>> for PoolWrapper {
     fn from(pool: Pool<SessionManager>) -> Self {
-        Self(pool)
+        Self {
+            pool,
+            on_acquire: None,
+            affinity: Arc::new(Affinity::new(AFFINITY_WINDOW)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn connector_is_used() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let manager = SessionManager::new(connect::Options::default()).with_connector({
+            let calls = calls.clone();
+            move || {
+                calls.fetch_add(1, Ordering::SeqCst);
+                async { Err(io::Error::other("no tunnel")) }
+            }
+        });
+        let err = manager.new_session().await.unwrap_err();
+        assert_eq!(err.to_string(), "client error; no tunnel");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
     }
 }
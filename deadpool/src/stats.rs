@@ -0,0 +1,96 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use deadpool::managed::{Manager, Object, Pool, PoolError};
+
+/// Measurements of taking a session from the pool for one query
+///
+/// Reported to the hook set by [PoolWrapper::on_acquire](crate::PoolWrapper::on_acquire).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AcquireStats {
+    /// How long the query waited for a session, including creating
+    /// a new one if the pool had none available.
+    pub pool_wait: Duration,
+    /// Whether a session was obtained.
+    pub success: bool,
+}
+
+#[derive(Clone)]
+pub(crate) struct AcquireHook(Arc<dyn Fn(&AcquireStats) + Send + Sync>);
+
+impl AcquireHook {
+    pub(crate) fn new(hook: impl Fn(&AcquireStats) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(hook))
+    }
+}
+
+impl fmt::Debug for AcquireHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AcquireHook")
+    }
+}
+
+// Takes an object from the pool reporting the time spent waiting
+pub(crate) async fn acquire<M: Manager>(
+    pool: &Pool<M>,
+    hook: Option<&AcquireHook>,
+) -> Result<Object<M>, PoolError<M::Error>> {
+    let Some(hook) = hook else {
+        return pool.get().await;
+    };
+    let start = Instant::now();
+    let object = pool.get().await;
+    (hook.0)(&AcquireStats {
+        pool_wait: start.elapsed(),
+        success: object.is_ok(),
+    });
+    object
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use deadpool::managed::{Metrics, RecycleResult};
+    use std::sync::Mutex;
+
+    struct Dummy;
+
+    #[async_trait]
+    impl Manager for Dummy {
+        type Type = ();
+        type Error = ();
+
+        async fn create(&self) -> Result<(), ()> {
+            Ok(())
+        }
+
+        async fn recycle(&self, _: &mut (), _: &Metrics) -> RecycleResult<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn reports_wait_on_exhausted_pool() {
+        let pool = Pool::builder(Dummy).max_size(1).build().unwrap();
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let hook = {
+            let reported = reported.clone();
+            AcquireHook::new(move |stats| reported.lock().unwrap().push(*stats))
+        };
+
+        let held = acquire(&pool, Some(&hook)).await.unwrap();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(held);
+        });
+        let _second = acquire(&pool, Some(&hook)).await.unwrap();
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 2);
+        assert!(reported.iter().all(|x| x.success));
+        assert!(reported[1].pool_wait >= Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,18 @@
+// These only need to compile, the checks are never run.
+
+use futures::TryStreamExt;
+use serde_json::Value;
+use unreql::r;
+use unreql_deadpool::PoolWrapper;
+
+#[test]
+fn result_type_only_with_pool() {
+    async fn check(pool: &PoolWrapper) -> unreql::Result<()> {
+        r.table("test").run::<Value>(pool).try_next().await?;
+        r.table("test").get(1).exec::<Value>(pool).await?;
+        r.table("test").exec_to_vec::<Value>(pool.clone()).await?;
+        r.db_list().exec::<Vec<String>>(pool).await?;
+        Ok(())
+    }
+    let _ = check;
+}
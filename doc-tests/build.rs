@@ -0,0 +1,142 @@
+//! Lifts the doc examples of some command groups of unreql into functions
+//! building their queries, for the snapshot tests of `tests/doc_snapshots.rs`
+
+use std::fmt::Write;
+use std::path::Path;
+use std::{env, fs};
+
+// The groups whose examples are snapshotted
+const GROUPS: &[&str] = &["selecting", "writing"];
+
+const START: &str = "# unreql::example(|r, conn| {";
+const RUN: &str = ".run(conn)";
+// The markers of `create_cmd!` written before the name of the command
+const MARKERS: &[&str] = &["only_root", "only_command"];
+
+struct Example {
+    cmd: String,
+    name: String,
+    uses: Vec<String>,
+    body: Vec<String>,
+}
+
+fn main() {
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("doc_examples.rs");
+    let mut code = String::new();
+    for group in GROUPS {
+        let path = format!("../reql/src/cmd/groups/{group}.rs");
+        println!("cargo:rerun-if-changed={path}");
+        let source = fs::read_to_string(&path).unwrap();
+        write_group(&mut code, group, &examples(&source));
+    }
+    fs::write(out, code).unwrap();
+}
+
+// The lines of the doc comments, with `None` for the other lines
+fn doc_lines(source: &str) -> Vec<Option<&str>> {
+    source
+        .lines()
+        .map(|line| {
+            let line = line.trim_start().strip_prefix("///")?;
+            Some(line.strip_prefix(' ').unwrap_or(line))
+        })
+        .collect()
+}
+
+// The command documented by the comment going on after `from`
+fn command_name(lines: &[&str], from: usize) -> Option<String> {
+    lines[from..]
+        .iter()
+        .map(|line| line.trim_start())
+        .filter(|line| !line.starts_with("///") && !line.starts_with("#["))
+        .find_map(|line| {
+            let line = line.strip_prefix("pub fn ").unwrap_or(line);
+            let end = line.find(|c: char| !c.is_alphanumeric() && c != '_')?;
+            let (name, rest) = line.split_at(end);
+            // `name(args)`, `name<T>(args)` or `name,` of `create_cmd!`
+            let is_cmd =
+                rest.starts_with(['(', '<', ',']) && !name.is_empty() && !MARKERS.contains(&name);
+            is_cmd.then(|| name.to_owned())
+        })
+}
+
+fn examples(source: &str) -> Vec<Example> {
+    let lines: Vec<&str> = source.lines().collect();
+    let docs = doc_lines(source);
+    let mut examples: Vec<Example> = Vec::new();
+    let mut uses = Vec::new();
+    let mut i = 0;
+    while i < docs.len() {
+        let Some(line) = docs[i] else {
+            uses.clear();
+            i += 1;
+            continue;
+        };
+        if line.starts_with("```") {
+            uses.clear();
+        }
+        if let Some(stmt) = line.strip_prefix("# use ") {
+            uses.push(stmt.to_owned());
+        }
+        if line != START {
+            i += 1;
+            continue;
+        }
+
+        let mut body = Vec::new();
+        i += 1;
+        while let Some(line) = docs.get(i).copied().flatten() {
+            if line.starts_with("# })") {
+                break;
+            }
+            body.push(line.strip_prefix("# ").unwrap_or(line).to_owned());
+            i += 1;
+        }
+        // only the examples ending with the query to run
+        let Some(last) = body.last_mut() else {
+            continue;
+        };
+        let Some(query) = last.trim_end().strip_suffix(RUN) else {
+            continue;
+        };
+        *last = query.to_owned();
+
+        let Some(cmd) = command_name(&lines, i) else {
+            continue;
+        };
+        let n = examples.iter().filter(|x| x.cmd == cmd).count();
+        examples.push(Example {
+            name: format!("{cmd}_{}", n + 1),
+            cmd,
+            uses: uses.clone(),
+            body,
+        });
+    }
+    examples
+}
+
+fn write_group(code: &mut String, group: &str, examples: &[Example]) {
+    writeln!(code, "#[allow(unused_imports, clippy::all)]").unwrap();
+    writeln!(
+        code,
+        "pub fn {group}() -> Vec<(&'static str, unreql::Result<serde_json::Value>)> {{"
+    )
+    .unwrap();
+    writeln!(code, "    vec![").unwrap();
+    for example in examples {
+        writeln!(code, "        (\"{}\", {{", example.name).unwrap();
+        for stmt in &example.uses {
+            writeln!(code, "            use {stmt}").unwrap();
+        }
+        writeln!(code, "            let r = unreql::r;").unwrap();
+        writeln!(code, "            let query = {{").unwrap();
+        for line in &example.body {
+            writeln!(code, "                {line}").unwrap();
+        }
+        writeln!(code, "            }};").unwrap();
+        writeln!(code, "            query.to_query_json()").unwrap();
+        writeln!(code, "        }}),").unwrap();
+    }
+    writeln!(code, "    ]").unwrap();
+    writeln!(code, "}}").unwrap();
+}
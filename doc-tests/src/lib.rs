@@ -0,0 +1,4 @@
+//! Snapshot tests of the doc examples of unreql, kept out of the
+//! published crate so that it builds without a build script
+//!
+//! See `tests/doc_snapshots.rs`.
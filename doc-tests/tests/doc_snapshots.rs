@@ -0,0 +1,67 @@
+//! The term JSON of the doc examples of some command groups, compared to
+//! the snapshots of `tests/snapshots`
+//!
+//! The examples are lifted from the doc comments by the build script.
+//! Run with `UPDATE_SNAPSHOTS=1` to write the snapshots after changing
+//! an example or the serialization of a command.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::Value;
+
+mod examples {
+    include!(concat!(env!("OUT_DIR"), "/doc_examples.rs"));
+}
+
+fn check(group: &str, examples: Vec<(&'static str, unreql::Result<Value>)>) {
+    let queries: BTreeMap<_, _> = examples
+        .into_iter()
+        .map(|(name, query)| match query {
+            Ok(query) => (name.to_owned(), query),
+            Err(error) => panic!("example {name} of {group}: {error}"),
+        })
+        .collect();
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "snapshots"]
+        .iter()
+        .collect::<PathBuf>()
+        .join(format!("{group}.json"));
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        // one query per line, so a change shows as the lines of its examples
+        let lines: Vec<_> = queries
+            .iter()
+            .map(|(name, query)| format!("  {name:?}: {query}"))
+            .collect();
+        fs::write(&path, format!("{{\n{}\n}}\n", lines.join(",\n"))).unwrap();
+        return;
+    }
+
+    let snapshot = fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("no snapshot {path:?}, run with UPDATE_SNAPSHOTS=1"));
+    let snapshot: BTreeMap<String, Value> = serde_json::from_str(&snapshot).unwrap();
+    for (name, query) in &queries {
+        match snapshot.get(name) {
+            Some(expected) => assert_eq!(query, expected, "example {name} of {group}"),
+            None => panic!("example {name} of {group} has no snapshot"),
+        }
+    }
+    for name in snapshot.keys() {
+        assert!(
+            queries.contains_key(name),
+            "snapshot {name} of {group} has no example"
+        );
+    }
+}
+
+#[test]
+fn selecting() {
+    check("selecting", examples::selecting());
+}
+
+#[test]
+fn writing() {
+    check("writing", examples::writing());
+}
@@ -10,7 +10,7 @@ async fn main() {
     let opts = ChangesOptions::new()
         .include_initial(true)
         .include_states(true);
-    let mut q = r.table("test").changes(opts).run::<_, Change>(&sess);
+    let mut q = r.table("test").changes(opts).run::<Change>(&sess);
 
     while let Ok(Some(changed)) = q.try_next().await {
         dbg!(changed);
@@ -0,0 +1,28 @@
+//! Create, read, update and delete through a pool, see
+//! `unreql_examples::pool_crud`
+//!
+//! `cargo run --example pool_crud` uses the server of `RDB_HOST` and
+//! `RDB_PORT`, `cargo run --example pool_crud -- --simulate` a fake one.
+
+use deadpool::managed::Pool;
+use unreql_deadpool::{IntoPoolWrapper, SessionManager};
+use unreql_examples::{connect_opts, fake_server, pool_crud};
+
+#[tokio::main]
+async fn main() {
+    let opts = if std::env::args().any(|arg| arg == "--simulate") {
+        fake_server::start().await
+    } else {
+        connect_opts()
+    };
+    let manager = SessionManager::new(opts);
+    let pool = Pool::builder(manager)
+        .max_size(4)
+        .build()
+        .unwrap()
+        .wrapper();
+
+    for user in pool_crud::run(&pool).await.unwrap() {
+        println!("{:?}", user);
+    }
+}
@@ -0,0 +1,28 @@
+//! A realtime leaderboard, see `unreql_examples::leaderboard`
+//!
+//! `cargo run --example leaderboard` uses the server of `RDB_HOST` and
+//! `RDB_PORT`, `cargo run --example leaderboard -- --simulate` a fake one.
+
+use deadpool::managed::Pool;
+use unreql_deadpool::{IntoPoolWrapper, SessionManager};
+use unreql_examples::{connect_opts, fake_server, leaderboard};
+
+#[tokio::main]
+async fn main() {
+    let opts = if std::env::args().any(|arg| arg == "--simulate") {
+        fake_server::start().await
+    } else {
+        connect_opts()
+    };
+    let manager = SessionManager::new(opts);
+    let pool = Pool::builder(manager)
+        .max_size(4)
+        .build()
+        .unwrap()
+        .wrapper();
+
+    let board = leaderboard::run(&pool, leaderboard::DEMO_ROUNDS)
+        .await
+        .unwrap();
+    println!("winner: {}", board[0].id);
+}
@@ -0,0 +1,68 @@
+//! Latency and allocations of trivial queries, e.g. the health checks of
+//! a pool, against the fake server
+//!
+//! Run it with `cargo bench -p unreql_examples --bench trivial_queries`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use unreql::r;
+use unreql_examples::fake_server;
+
+const QUERIES: usize = 10_000;
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    sorted[(sorted.len() - 1) * p / 100]
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let session = r.connect(fake_server::start().await).await.unwrap();
+    // warm up
+    for _ in 0..100 {
+        r.expr(1).exec::<u8>(&session).await.unwrap();
+    }
+
+    let mut latencies = Vec::with_capacity(QUERIES);
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let started = Instant::now();
+    for _ in 0..QUERIES {
+        let query = Instant::now();
+        r.expr(1).exec::<u8>(&session).await.unwrap();
+        latencies.push(query.elapsed());
+    }
+    let total = started.elapsed();
+    // the server runs in the same process, its allocations are counted too
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    latencies.sort();
+    println!("{} sequential r.expr(1).exec() in {:?}", QUERIES, total);
+    println!(
+        "latency p50 {:?}, p99 {:?}",
+        percentile(&latencies, 50),
+        percentile(&latencies, 99)
+    );
+    println!(
+        "allocations per query {:.1}",
+        allocations as f64 / QUERIES as f64
+    );
+}
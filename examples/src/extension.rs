@@ -0,0 +1,48 @@
+//! Custom commands defined outside of `unreql`
+//!
+//! Our functions stored in the database follow one convention: the
+//! `udfs` table keeps a ReQL function in the `body` field of every document.
+//! `r.udf("name", args)` calls such a function with the given arguments.
+
+use serde::Serialize;
+use unreql::cmd::args::ManyArgs;
+use unreql::cmd::TermType;
+use unreql::{r, rjson, Command};
+
+/// Commands on `r`
+pub trait RExt {
+    /// Call the stored function `name` with `args`
+    fn udf(self, name: impl Serialize + 'static, args: impl ManyArgs<()>) -> Command;
+}
+
+impl RExt for r {
+    fn udf(self, name: impl Serialize + 'static, args: impl ManyArgs<()>) -> Command {
+        let body = r.table("udfs").get(name).g("body");
+        // FUNCALL takes the function first and then its arguments
+        let cmd = Command::new(TermType::Funcall).with_arg(body);
+        args.with_cmd(cmd)
+    }
+}
+
+/// Commands chained after other commands
+pub trait CommandExt {
+    /// Whether the string contains nothing but whitespace
+    fn blank(self) -> Command;
+
+    /// Set `updated_at` to the current time and `updated_by` to `user`
+    fn touch(self, user: impl Serialize + 'static) -> Command;
+}
+
+impl CommandExt for Command {
+    fn blank(self) -> Command {
+        // `split` without arguments splits on whitespace
+        Command::new(TermType::Split).with_parent(self).is_empty()
+    }
+
+    fn touch(self, user: impl Serialize + 'static) -> Command {
+        self.update(rjson!({
+            "updated_at": r.now(),
+            "updated_by": user,
+        }))
+    }
+}
@@ -0,0 +1,484 @@
+//! A fake RethinkDB server for the examples
+//!
+//! It knows just enough ReQL to run [leaderboard](crate::leaderboard) and
+//! [pool_crud](crate::pool_crud) without a real server, e.g. in CI: listing
+//! and creating tables and indexes, inserting, getting, updating and
+//! deleting documents by primary key and one `order_by` + `limit`
+//! changefeed ordered by `score`. Writes return their changes with
+//! `return_changes`. Like the real server, a query on a missing table
+//! fails with `OP_FAILED`. The tests of the `bb8` pool use
+//! it too.
+
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use scram::{hash_password, AuthenticationProvider, PasswordInfo, ScramServer};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use unreql::cmd::{connect, TermType};
+
+const ITERATIONS: u16 = 4096;
+
+const SUCCESS_ATOM: u8 = 1;
+const SUCCESS_PARTIAL: u8 = 3;
+const SUCCESS_SEQUENCE: u8 = 2;
+const WAIT_COMPLETE: u8 = 4;
+const RUNTIME_ERROR: u8 = 18;
+
+const OP_FAILED: u32 = 4100000;
+
+const START: u64 = 1;
+const CONTINUE: u64 = 2;
+const STOP: u64 = 3;
+const NOREPLY_WAIT: u64 = 4;
+
+/// Start the server and return the options to connect to it, as `admin`
+/// without password
+pub async fn start() -> connect::Options {
+    start_counting().await.0
+}
+
+/// Start the server like [start], also returning the number of
+/// connections made to it so far, e.g. to check the reuse of pooled
+/// sessions
+pub async fn start_counting() -> (connect::Options, Arc<AtomicUsize>) {
+    listen(None).await
+}
+
+/// Start the server like [start], except that it never answers the
+/// connection number `stalled`, counted from 1, e.g. to make connecting
+/// time out once
+pub async fn start_stalling(stalled: usize) -> connect::Options {
+    listen(Some(stalled)).await.0
+}
+
+async fn listen(stalled: Option<usize>) -> (connect::Options, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let state = Arc::new(Mutex::new(State::default()));
+    let connections = Arc::new(AtomicUsize::new(0));
+    tokio::spawn({
+        let connections = connections.clone();
+        async move {
+            // the stalled connection is kept open, but never read
+            let mut kept = None;
+            while let Ok((stream, _)) = listener.accept().await {
+                let n = connections.fetch_add(1, Ordering::SeqCst) + 1;
+                if Some(n) == stalled {
+                    kept = Some(stream);
+                    continue;
+                }
+                tokio::spawn(serve(stream, state.clone()));
+            }
+            drop(kept);
+        }
+    });
+    let options = connect::Options::new()
+        .host(addr.ip().to_string())
+        .port(addr.port());
+    (options, connections)
+}
+
+#[derive(Default)]
+struct State {
+    tables: Vec<String>,
+    indexes: Vec<String>,
+    // the documents of each table
+    docs: HashMap<String, Vec<Value>>,
+    feeds: Vec<Feed>,
+}
+
+// A subscribed `order_by` + `limit` changefeed
+struct Feed {
+    table: String,
+    limit: usize,
+    top: Vec<Value>,
+    tx: UnboundedSender<Vec<Value>>,
+}
+
+impl State {
+    fn get(&self, table: &str, id: &Value) -> Option<&Value> {
+        self.docs.get(table)?.iter().find(|doc| doc["id"] == *id)
+    }
+
+    // Inserts or replaces `doc`, an insert of an existing document fails
+    // unless `upsert`
+    fn write(&mut self, table: &str, doc: Value, upsert: bool) -> Result<&'static str, String> {
+        let docs = self.docs.entry(table.to_owned()).or_default();
+        let result = match docs.iter_mut().find(|old| old["id"] == doc["id"]) {
+            Some(old) if !upsert => {
+                return Err(format!("Duplicate primary key `id`:\n{:#}\n{:#}", old, doc))
+            }
+            Some(old) if *old == doc => return Ok("unchanged"),
+            Some(old) => {
+                *old = doc;
+                "replaced"
+            }
+            None => {
+                docs.push(doc);
+                "inserted"
+            }
+        };
+        self.notify();
+        Ok(result)
+    }
+
+    fn delete(&mut self, table: &str, id: &Value) -> &'static str {
+        let docs = self.docs.entry(table.to_owned()).or_default();
+        let Some(pos) = docs.iter().position(|doc| doc["id"] == *id) else {
+            return "skipped";
+        };
+        docs.remove(pos);
+        self.notify();
+        "deleted"
+    }
+
+    // Sends the changes of the top documents to the feeds
+    fn notify(&mut self) {
+        let docs = &self.docs;
+        self.feeds.retain_mut(|feed| {
+            let new = top(
+                docs.get(&feed.table).map(Vec::as_slice).unwrap_or_default(),
+                feed.limit,
+            );
+            let changes = diff(&feed.top, &new);
+            feed.top = new;
+            // forget the feeds whose client is gone
+            changes.is_empty() || feed.tx.send(changes).is_ok()
+        });
+    }
+}
+
+// The first `limit` documents by descending score
+fn top(docs: &[Value], limit: usize) -> Vec<Value> {
+    let mut docs = docs.to_vec();
+    docs.sort_by(|a, b| {
+        let score = |doc: &Value| doc["score"].as_u64();
+        score(b)
+            .cmp(&score(a))
+            .then(a["id"].as_str().cmp(&b["id"].as_str()))
+    });
+    docs.truncate(limit);
+    docs
+}
+
+// The changes turning `old` into `new`, applied in order
+fn diff(old: &[Value], new: &[Value]) -> Vec<Value> {
+    let mut changes = Vec::new();
+    let mut current = old.to_vec();
+    for doc in old {
+        if !new.iter().any(|new| new["id"] == doc["id"]) {
+            let offset = current.iter().position(|cur| cur == doc).unwrap();
+            current.remove(offset);
+            changes.push(json!({ "old_val": doc, "new_val": null, "old_offset": offset }));
+        }
+    }
+    for (offset, doc) in new.iter().enumerate() {
+        match current.iter().position(|cur| cur["id"] == doc["id"]) {
+            Some(old_offset) if current[old_offset] == *doc && old_offset == offset => {}
+            Some(old_offset) => {
+                let old_val = current.remove(old_offset);
+                current.insert(offset, doc.clone());
+                changes.push(json!({
+                    "old_val": old_val, "new_val": doc,
+                    "old_offset": old_offset, "new_offset": offset,
+                }));
+            }
+            None => {
+                current.insert(offset, doc.clone());
+                changes.push(json!({ "old_val": null, "new_val": doc, "new_offset": offset }));
+            }
+        }
+    }
+    changes
+}
+
+// The type and the arguments of a term
+fn term(value: &Value) -> Option<(i64, &[Value])> {
+    let items = value.as_array()?;
+    let typ = items.first()?.as_i64()?;
+    let args = items.get(1).and_then(Value::as_array);
+    Some((typ, args.map(Vec::as_slice).unwrap_or_default()))
+}
+
+fn is(typ: i64, term: TermType) -> bool {
+    typ == term as i64
+}
+
+// Finds a term of type `typ` in `value`
+fn find(value: &Value, typ: TermType) -> Option<&[Value]> {
+    let (found, args) = term(value)?;
+    if is(found, typ) {
+        return Some(args);
+    }
+    args.iter().find_map(|arg| find(arg, typ))
+}
+
+// The options of a term
+fn opt<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    value.as_array()?.get(2)?.get(name)
+}
+
+// The name of the table a query reads or writes
+fn table_name(value: &Value) -> Option<String> {
+    find(value, TermType::Table)?.last().map(string)
+}
+
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_owned()
+}
+
+fn response(typ: u8, results: Value) -> Value {
+    json!({ "t": typ, "r": results })
+}
+
+// The status of a write, with the `changes` of the written documents if
+// the write sets `return_changes`
+fn write_status(query: &Value, results: &[Result<&str, String>], changes: Vec<Value>) -> Value {
+    let count = |name| results.iter().filter(|x| **x == Ok(name)).count();
+    let errors: Vec<_> = results.iter().filter_map(|x| x.clone().err()).collect();
+    let mut status = json!({
+        "inserted": count("inserted"), "replaced": count("replaced"),
+        "unchanged": count("unchanged"), "skipped": count("skipped"),
+        "deleted": count("deleted"), "errors": errors.len(),
+    });
+    if let Some(error) = errors.first() {
+        status["first_error"] = json!(error);
+    }
+    if opt(query, "return_changes") == Some(&json!(true)) {
+        status["changes"] = json!(changes);
+    }
+    status
+}
+
+// The change of a write turning `old_val` into `new_val`, none if the
+// document was left alone
+fn change(
+    result: &Result<&str, String>,
+    old_val: Option<Value>,
+    new_val: Option<Value>,
+) -> Option<Value> {
+    match result {
+        Ok("inserted" | "replaced" | "deleted") => {
+            Some(json!({ "old_val": old_val, "new_val": new_val }))
+        }
+        _ => None,
+    }
+}
+
+// Answers a query that is not a changefeed
+fn answer(query: &Value, state: &Mutex<State>) -> Value {
+    let Some((typ, args)) = term(query) else {
+        // a datum, e.g. the health check of the pool
+        return response(SUCCESS_ATOM, json!([query]));
+    };
+    let mut state = state.lock().unwrap();
+    let table = table_name(query).unwrap_or_default();
+    if !table.is_empty() && !state.tables.contains(&table) {
+        let msg = format!("Table `test.{}` does not exist.", table);
+        return json!({ "t": RUNTIME_ERROR, "e": OP_FAILED, "r": [msg] });
+    }
+    if is(typ, TermType::TableList) {
+        response(SUCCESS_ATOM, json!([state.tables]))
+    } else if is(typ, TermType::TableCreate) {
+        state.tables.push(string(&args[0]));
+        response(SUCCESS_ATOM, json!([{ "tables_created": 1 }]))
+    } else if is(typ, TermType::IndexList) {
+        response(SUCCESS_ATOM, json!([state.indexes]))
+    } else if is(typ, TermType::IndexCreate) {
+        state.indexes.push(string(&args[1]));
+        response(SUCCESS_ATOM, json!([{ "created": 1 }]))
+    } else if is(typ, TermType::IndexWait) {
+        let ready: Vec<_> = (state.indexes.iter())
+            .map(|index| json!({ "index": index, "ready": true }))
+            .collect();
+        response(SUCCESS_SEQUENCE, json!(ready))
+    } else if is(typ, TermType::Insert) {
+        let docs = match term(&args[1]) {
+            Some((typ, docs)) if is(typ, TermType::MakeArray) => docs.to_vec(),
+            _ => vec![args[1].clone()],
+        };
+        let conflict = opt(query, "conflict").and_then(Value::as_str);
+        let upsert = matches!(conflict, Some("update" | "replace"));
+        let mut results = Vec::new();
+        let mut changes = Vec::new();
+        for doc in docs {
+            let old = state.get(&table, &doc["id"]).cloned();
+            let result = state.write(&table, doc.clone(), upsert);
+            changes.extend(change(&result, old, Some(doc)));
+            results.push(result);
+        }
+        response(
+            SUCCESS_ATOM,
+            json!([write_status(query, &results, changes)]),
+        )
+    } else if is(typ, TermType::Get) {
+        let doc = state.get(&table, &args[1]).cloned();
+        response(SUCCESS_ATOM, json!([doc]))
+    } else if let Some(id) = get_id(typ, args, TermType::Update) {
+        let old = state.get(&table, id).cloned();
+        let (result, new) = match old.clone() {
+            Some(mut doc) => {
+                if let (Some(doc), Some(fields)) = (doc.as_object_mut(), args[1].as_object()) {
+                    doc.extend(fields.clone());
+                }
+                (state.write(&table, doc.clone(), true), Some(doc))
+            }
+            None => (Ok("skipped"), None),
+        };
+        let changes = change(&result, old, new).into_iter().collect();
+        response(
+            SUCCESS_ATOM,
+            json!([write_status(query, &[result], changes)]),
+        )
+    } else if let Some(id) = get_id(typ, args, TermType::Delete) {
+        let old = state.get(&table, id).cloned();
+        let result = Ok(state.delete(&table, id));
+        let changes = change(&result, old, None).into_iter().collect();
+        response(
+            SUCCESS_ATOM,
+            json!([write_status(query, &[result], changes)]),
+        )
+    } else {
+        response(RUNTIME_ERROR, json!(["not supported by the fake server"]))
+    }
+}
+
+// The primary key of a `table.get(id).update(..)` or `.delete()`
+fn get_id(typ: i64, args: &[Value], write: TermType) -> Option<&Value> {
+    if !is(typ, write) {
+        return None;
+    }
+    match term(args.first()?)? {
+        (typ, args) if is(typ, TermType::Get) => args.get(1),
+        _ => None,
+    }
+}
+
+// Subscribes to the changes of the top documents, returns the first batch
+fn subscribe(query: &Value, state: &Mutex<State>) -> (Value, UnboundedReceiver<Vec<Value>>) {
+    let limit = find(query, TermType::Limit)
+        .and_then(|args| args.get(1)?.as_u64())
+        .unwrap_or(u64::MAX) as usize;
+    let table = table_name(query).unwrap_or_default();
+    let mut state = state.lock().unwrap();
+    let docs = state.docs.get(&table).map(Vec::as_slice);
+    let top = top(docs.unwrap_or_default(), limit);
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    let mut initial = vec![json!({ "state": "initializing" })];
+    for (offset, doc) in top.iter().enumerate() {
+        initial.push(json!({ "new_val": doc, "new_offset": offset }));
+    }
+    initial.push(json!({ "state": "ready" }));
+    state.feeds.push(Feed {
+        table,
+        limit,
+        top,
+        tx,
+    });
+    (response(SUCCESS_PARTIAL, json!(initial)), rx)
+}
+
+struct Admin;
+
+impl AuthenticationProvider for Admin {
+    fn get_password_for(&self, username: &str) -> Option<PasswordInfo> {
+        if username != "admin" {
+            return None;
+        }
+        let iterations = NonZeroU32::new(ITERATIONS.into()).unwrap();
+        let hashed = hash_password("", iterations, b"salt");
+        Some(PasswordInfo::new(
+            hashed.to_vec(),
+            ITERATIONS,
+            b"salt".to_vec(),
+        ))
+    }
+}
+
+async fn read_message(stream: &mut BufReader<TcpStream>) -> Value {
+    let mut msg = Vec::new();
+    stream.read_until(0, &mut msg).await.unwrap();
+    msg.pop();
+    serde_json::from_slice(&msg).unwrap()
+}
+
+async fn write_message(stream: &mut BufReader<TcpStream>, msg: Value) {
+    let mut msg = serde_json::to_vec(&msg).unwrap();
+    msg.push(0);
+    stream.get_mut().write_all(&msg).await.unwrap();
+}
+
+async fn handshake(stream: &mut BufReader<TcpStream>) {
+    let mut version = [0u8; 4];
+    stream.read_exact(&mut version).await.unwrap();
+    let client_first = read_message(stream).await;
+    let info = json!({
+        "success": true,
+        "min_protocol_version": 0,
+        "max_protocol_version": 0,
+        "server_version": "2.4.4",
+    });
+    write_message(stream, info).await;
+
+    let scram = ScramServer::new(Admin);
+    let auth = client_first["authentication"].as_str().unwrap();
+    let (scram, server_first) = scram.handle_client_first(auth).unwrap().server_first();
+    let server_first = json!({ "success": true, "authentication": server_first });
+    write_message(stream, server_first).await;
+    let client_final = read_message(stream).await;
+    let auth = client_final["authentication"].as_str().unwrap();
+    let (_, server_final) = scram.handle_client_final(auth).unwrap().server_final();
+    let server_final = json!({ "success": true, "authentication": server_final });
+    write_message(stream, server_final).await;
+}
+
+async fn serve(stream: TcpStream, state: Arc<Mutex<State>>) {
+    let mut stream = BufReader::new(stream);
+    handshake(&mut stream).await;
+
+    let mut feeds = HashMap::new();
+    let mut header = [0u8; 12];
+    while stream.read_exact(&mut header).await.is_ok() {
+        let token = &header[..8];
+        let len = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+        let mut query = vec![0u8; len];
+        stream.read_exact(&mut query).await.unwrap();
+        let query: Value = serde_json::from_slice(&query).unwrap();
+
+        let resp = match query[0].as_u64() {
+            Some(START) if find(&query[1], TermType::Changes).is_some() => {
+                let (resp, rx) = subscribe(&query[1], &state);
+                feeds.insert(token.to_vec(), rx);
+                resp
+            }
+            Some(START) => answer(&query[1], &state),
+            Some(CONTINUE) => match feeds.get_mut(token) {
+                Some(rx) => match rx.recv().await {
+                    Some(changes) => response(SUCCESS_PARTIAL, json!(changes)),
+                    None => response(SUCCESS_SEQUENCE, json!([])),
+                },
+                None => response(RUNTIME_ERROR, json!(["unknown token"])),
+            },
+            Some(STOP) => {
+                feeds.remove(token);
+                response(SUCCESS_SEQUENCE, json!([]))
+            }
+            Some(NOREPLY_WAIT) => response(WAIT_COMPLETE, json!([])),
+            _ => response(RUNTIME_ERROR, json!(["unknown query type"])),
+        };
+
+        let body = serde_json::to_vec(&resp).unwrap();
+        let mut frame = token.to_vec();
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&body);
+        if stream.get_mut().write_all(&frame).await.is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! A realtime leaderboard: the top players of a `scores` table, kept up
+//! to date with an `order_by` + `limit` changefeed
+//!
+//! Run it with `cargo run --example leaderboard`, against the server of
+//! `RDB_HOST` and `RDB_PORT`, or with `--simulate` against a fake server.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use unreql::cmd::options::{ChangesOptions, Conflict, InsertOptions};
+use unreql::types::{Change, WriteStatus};
+use unreql::{r, Driver, Error, Result};
+use unreql_deadpool::PoolWrapper;
+
+pub const TABLE: &str = "scores";
+pub const INDEX: &str = "score";
+/// How many players the leaderboard shows
+pub const TOP: usize = 3;
+
+/// The scores reached by the players, in order
+pub const DEMO_ROUNDS: &[(&str, u32)] = &[
+    ("alice", 10),
+    ("bob", 30),
+    ("carol", 20),
+    ("dave", 5),
+    ("dave", 40),
+    ("alice", 35),
+    ("bob", 31),
+];
+
+// How long to wait for the changes of a write
+const SETTLE: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Player {
+    pub id: String,
+    pub score: u32,
+}
+
+/// The top players, updated with the offsets of the changefeed
+#[derive(Debug, Default)]
+pub struct Board(Vec<Player>);
+
+impl Board {
+    pub fn apply(&mut self, change: Change<Player>) {
+        if let Some(offset) = change.old_offset {
+            if offset < self.0.len() {
+                self.0.remove(offset);
+            }
+        }
+        if let (Some(offset), Some(player)) = (change.new_offset, change.new_val.into_option()) {
+            self.0.insert(offset.min(self.0.len()), player);
+        }
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.0
+    }
+}
+
+/// Create the table unless it exists, returns whether it was created
+pub async fn ensure_table(pool: &PoolWrapper, table: &str) -> Result<bool> {
+    let tables: Vec<String> = r.table_list().exec_to_vec(pool).await?;
+    if tables.iter().any(|name| name == table) {
+        return Ok(false);
+    }
+    r.table_create(table.to_owned()).exec::<Value>(pool).await?;
+    Ok(true)
+}
+
+/// Create the index unless it exists and wait for it to be ready,
+/// returns whether it was created
+pub async fn ensure_index(pool: &PoolWrapper, table: &str, index: &str) -> Result<bool> {
+    let indexes: Vec<String> = r
+        .table(table.to_owned())
+        .index_list()
+        .exec_to_vec(pool)
+        .await?;
+    if indexes.iter().any(|name| name == index) {
+        return Ok(false);
+    }
+    let table = r.table(table.to_owned());
+    table
+        .clone()
+        .index_create(index.to_owned())
+        .exec::<Value>(pool)
+        .await?;
+    table
+        .index_wait(index.to_owned())
+        .exec_to_vec::<Value>(pool)
+        .await?;
+    Ok(true)
+}
+
+/// Save the score of a player
+pub async fn save_score(pool: &PoolWrapper, player: &Player) -> Result<WriteStatus> {
+    let opts = InsertOptions::new().conflict(Conflict::Update);
+    let status: WriteStatus = r
+        .table(TABLE)
+        .insert(r.with_opt(player.clone(), opts))
+        .exec(pool)
+        .await?;
+    if status.errors > 0 {
+        let error = status.first_error.unwrap_or_default();
+        return Err(Error::Driver(Driver::Other(error)));
+    }
+    Ok(status)
+}
+
+/// Play the rounds and return the final leaderboard
+pub async fn run(pool: &PoolWrapper, rounds: &[(&str, u32)]) -> Result<Vec<Player>> {
+    ensure_table(pool, TABLE).await?;
+    ensure_index(pool, TABLE, INDEX).await?;
+
+    let opts = ChangesOptions::new()
+        .include_initial(true)
+        .include_offsets(true)
+        .include_states(true);
+    let mut feed = r
+        .table(TABLE)
+        .order_by(r.index(r.desc(INDEX)))
+        .limit(TOP)
+        .changes(opts)
+        .cursor::<Change<Player>>(pool);
+
+    let mut board = Board::default();
+    while let Some(change) = feed.try_next_timeout(Duration::from_secs(5)).await? {
+        match change.state.as_deref() {
+            Some("ready") => break,
+            Some(_) => {}
+            None => board.apply(change),
+        }
+    }
+
+    let mut joined = HashSet::new();
+    for (id, score) in rounds {
+        let player = Player {
+            id: id.to_string(),
+            score: *score,
+        };
+        let status = save_score(pool, &player).await?;
+        let action = if joined.insert(*id) {
+            "joined"
+        } else {
+            "updated"
+        };
+        println!(
+            "{id} {action} with {score} points ({} inserted, {} replaced)",
+            status.inserted, status.replaced
+        );
+
+        while let Some(change) = feed.try_next_timeout(SETTLE).await? {
+            board.apply(change);
+        }
+        for (rank, player) in board.players().iter().enumerate() {
+            println!("  {}. {} {}", rank + 1, player.id, player.score);
+        }
+    }
+    Ok(board.0)
+}
@@ -1,15 +1,10 @@
-use std::env;
+pub mod extension;
+pub mod fake_server;
+pub mod leaderboard;
+pub mod pool_crud;
 
+/// The options to connect to the server of the `RDB_*` environment
+/// variables, see [Options::from_env](unreql::cmd::connect::Options::from_env)
 pub fn connect_opts() -> unreql::cmd::connect::Options {
-    let mut opts = unreql::cmd::connect::Options::default();
-    if let Ok(host) = env::var("RDB_HOST") {
-        opts = opts.host(host);
-    }
-    if let Ok(port) = env::var("RDB_PORT") {
-        opts = opts.port(port.parse().unwrap());
-    }
-    if let Ok(db) = env::var("RDB_DB") {
-        opts = opts.db(db);
-    }
-    opts
+    unreql::cmd::connect::Options::from_env().expect("invalid RDB_* environment variable")
 }
@@ -0,0 +1,120 @@
+//! Create, read, update and delete users through a deadpool pool
+//!
+//! The writes run with [exec_write](unreql::Command::exec_write), a
+//! failed one is told apart by its [Runtime] error kind.
+//!
+//! Run it with `cargo run --example pool_crud`, against the server of
+//! `RDB_HOST` and `RDB_PORT`, or with `--simulate` against a fake server.
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use unreql::{r, Availability, Error, Result, Runtime};
+use unreql_deadpool::PoolWrapper;
+
+use crate::leaderboard::ensure_table;
+
+pub const TABLE: &str = "users";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub id: String,
+    pub name: String,
+}
+
+impl User {
+    pub fn new(id: &str, name: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+}
+
+/// Insert a new user, returns `false` if one with the same id exists
+pub async fn create(pool: &PoolWrapper, user: &User) -> Result<bool> {
+    let insert = r.table(TABLE).insert(user.clone());
+    match insert.exec_write(pool, "id").await {
+        Ok(token) => Ok(token.is_some()),
+        Err(error) if is_duplicate(&error) => Ok(false),
+        Err(error) => Err(error),
+    }
+}
+
+/// Get a user, `None` if there is none with this id
+pub async fn read(pool: &PoolWrapper, id: &str) -> Result<Option<User>> {
+    r.table(TABLE).get(id.to_owned()).exec(pool).await
+}
+
+/// Rename a user, returns `false` if there is none with this id or it
+/// has this name already
+pub async fn rename(pool: &PoolWrapper, id: &str, name: &str) -> Result<bool> {
+    let update = r
+        .table(TABLE)
+        .get(id.to_owned())
+        .update(json!({ "name": name }));
+    Ok(update.exec_write(pool, "id").await?.is_some())
+}
+
+/// Delete a user, returns `false` if there is none with this id
+pub async fn delete(pool: &PoolWrapper, id: &str) -> Result<bool> {
+    let delete = r.table(TABLE).get(id.to_owned()).delete(());
+    Ok(delete.exec_write(pool, "id").await?.is_some())
+}
+
+/// Whether the error is the one of an insert of a document whose id is
+/// in the table already
+pub fn is_duplicate(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Runtime(Runtime::WriteFailed {
+            first_error: Some(msg),
+            ..
+        }) if msg.starts_with("Duplicate primary key")
+    )
+}
+
+/// Whether the error is the one of a query on a missing table
+pub fn is_missing_table(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::Runtime(Runtime::Availability(Availability::OpFailed(msg)))
+            if msg.contains("does not exist")
+    )
+}
+
+/// Read a user, creating the table if the read fails because it is
+/// missing
+pub async fn read_or_create_table(pool: &PoolWrapper, id: &str) -> Result<Option<User>> {
+    match read(pool, id).await {
+        Err(error) if is_missing_table(&error) => {
+            ensure_table(pool, TABLE).await?;
+            Ok(None)
+        }
+        result => result,
+    }
+}
+
+/// Run a create-read-update-delete cycle on a user and return what was
+/// read after each step
+///
+/// Panics if a write does not change the user as expected.
+pub async fn run(pool: &PoolWrapper) -> Result<Vec<Option<User>>> {
+    let mut reads = vec![read_or_create_table(pool, "ada").await?];
+
+    let ada = User::new("ada", "Ada");
+    assert!(create(pool, &ada).await?, "ada not created");
+    assert!(!create(pool, &ada).await?, "ada created twice");
+    reads.push(read(pool, "ada").await?);
+
+    assert!(
+        rename(pool, "ada", "Ada Lovelace").await?,
+        "ada not renamed"
+    );
+    reads.push(read(pool, "ada").await?);
+
+    assert!(delete(pool, "ada").await?, "ada not deleted");
+    assert!(!delete(pool, "ada").await?, "ada deleted twice");
+    reads.push(read(pool, "ada").await?);
+
+    Ok(reads)
+}
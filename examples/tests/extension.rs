@@ -0,0 +1,39 @@
+use serde_json::{json, to_value};
+use unreql::r;
+use unreql_examples::extension::{CommandExt, RExt};
+
+#[test]
+fn udf() {
+    let query = r.udf("score", r.args((1, "hard")));
+    assert_eq!(
+        json!([
+            64,
+            [[31, [[16, [[15, ["udfs"]], "score"]], "body"]], 1, "hard"]
+        ]),
+        to_value(&query).unwrap()
+    );
+}
+
+#[test]
+fn udf_without_args() {
+    let query = r.udf("now", ());
+    assert_eq!(
+        json!([64, [[31, [[16, [[15, ["udfs"]], "now"]], "body"]]]]),
+        to_value(&query).unwrap()
+    );
+}
+
+#[test]
+fn blank() {
+    let query = r.expr("  ").blank();
+    assert_eq!(json!([86, [[149, ["  "]]]]), to_value(&query).unwrap());
+}
+
+#[test]
+fn touch() {
+    let query = r.table("posts").get(1).touch("admin");
+    assert_eq!(
+        json!([53, [[16, [[15, ["posts"]], 1]], {"updated_at": [103], "updated_by": "admin"}]]),
+        to_value(&query).unwrap()
+    );
+}
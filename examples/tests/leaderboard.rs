@@ -0,0 +1,31 @@
+use deadpool::managed::Pool;
+use unreql_deadpool::{IntoPoolWrapper, SessionManager};
+use unreql_examples::{fake_server, leaderboard};
+
+// Runs the leaderboard example against the fake server, so the changefeed,
+// the pool and the helpers it uses keep working together.
+#[tokio::test]
+async fn simulated_leaderboard() {
+    let manager = SessionManager::new(fake_server::start().await);
+    let pool = Pool::builder(manager)
+        .max_size(2)
+        .build()
+        .unwrap()
+        .wrapper();
+
+    let board = leaderboard::run(&pool, leaderboard::DEMO_ROUNDS)
+        .await
+        .unwrap();
+    let board: Vec<_> = board
+        .iter()
+        .map(|player| (player.id.as_str(), player.score))
+        .collect();
+    assert_eq!(board, [("dave", 40), ("alice", 35), ("bob", 31)]);
+
+    // the table and the index now exist
+    assert!(!leaderboard::ensure_table(&pool, leaderboard::TABLE)
+        .await
+        .unwrap());
+    let index = leaderboard::ensure_index(&pool, leaderboard::TABLE, leaderboard::INDEX);
+    assert!(!index.await.unwrap());
+}
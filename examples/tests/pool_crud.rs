@@ -0,0 +1,73 @@
+use deadpool::managed::Pool;
+use unreql::types::WriteStatus;
+use unreql::{r, Driver, Error};
+use unreql_deadpool::{IntoPoolWrapper, PoolWrapper, SessionManager};
+use unreql_examples::fake_server;
+use unreql_examples::pool_crud::{self, User};
+
+async fn pool() -> PoolWrapper {
+    let manager = SessionManager::new(fake_server::start().await);
+    Pool::builder(manager)
+        .max_size(2)
+        .build()
+        .unwrap()
+        .wrapper()
+}
+
+// Runs the pool_crud example against the fake server
+#[tokio::test]
+async fn simulated_crud() {
+    let pool = pool().await;
+    let reads = pool_crud::run(&pool).await.unwrap();
+    assert_eq!(
+        reads,
+        [
+            None,
+            Some(User::new("ada", "Ada")),
+            Some(User::new("ada", "Ada Lovelace")),
+            None,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn missing_table() {
+    let pool = pool().await;
+    let error = pool_crud::read(&pool, "ada").await.unwrap_err();
+    assert!(pool_crud::is_missing_table(&error), "{:?}", error);
+    assert!(error.is_retryable());
+
+    assert_eq!(
+        pool_crud::read_or_create_table(&pool, "ada").await.unwrap(),
+        None
+    );
+    assert!(!pool_crud::rename(&pool, "ada", "Ada").await.unwrap());
+    assert!(!pool_crud::delete(&pool, "ada").await.unwrap());
+}
+
+#[tokio::test]
+async fn typed_reads_and_errors() {
+    let pool = pool().await;
+    pool_crud::read_or_create_table(&pool, "ada").await.unwrap();
+    let ada = User::new("ada", "Ada");
+    assert!(pool_crud::create(&pool, &ada).await.unwrap());
+
+    let error = r
+        .table(pool_crud::TABLE)
+        .insert(ada)
+        .exec::<WriteStatus>(&pool)
+        .await
+        .unwrap()
+        .check()
+        .unwrap_err();
+    assert!(pool_crud::is_duplicate(&error), "{:?}", error);
+
+    // a missing document is `null`, not a `User`
+    let missing = r.table(pool_crud::TABLE).get("bob");
+    let error = missing.exec::<User>(&pool).await.unwrap_err();
+    assert!(
+        matches!(error, Error::Driver(Driver::Json(..))),
+        "{:?}",
+        error
+    );
+}
@@ -0,0 +1,28 @@
+use std::time::Duration;
+
+use deadpool::managed::Pool;
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+use unreql::{cmd::options::ChangesOptions, r};
+use unreql_deadpool::{Backoff, IntoPoolWrapper, SessionManager};
+use unreql_examples::fake_server;
+
+// The pool takes the first connection, the feed times out connecting on
+// the second one and resubscribes on the third
+#[tokio::test]
+async fn resubscribes_after_connect_timeout() {
+    let options = fake_server::start_stalling(2).await;
+    let manager = SessionManager::new(options.timeout(Duration::from_millis(50)));
+    let pool = Pool::builder(manager)
+        .max_size(1)
+        .build()
+        .unwrap()
+        .wrapper();
+    r.table_create("games").exec::<Value>(&pool).await.unwrap();
+
+    let backoff = Backoff::new().initial(Duration::from_millis(10));
+    let mut feed =
+        pool.resilient_changes::<Value>(r.table("games"), ChangesOptions::new(), backoff);
+    let change = feed.try_next().await.unwrap();
+    assert_eq!(change, Some(json!({ "state": "initializing" })));
+}
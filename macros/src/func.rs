@@ -35,31 +35,12 @@ impl Func {
 
     pub(super) fn process(self) -> TokenStream {
         let Self { mv, args, body } = self;
-        let mut header = quote!(#mv |);
-        let mut params = TokenStream::new();
         let func_args = args.len();
-        for (i, arg) in args.into_iter().enumerate() {
-            let var = quote!(unreql::Command::var(*ids.get(#i).unwrap()));
-            if i == func_args - 1 {
-                header.extend(quote!(#arg: unreql::Command));
-                params.extend(quote!(#var));
-            } else {
-                header.extend(quote!(#arg: unreql::Command, ));
-                params.extend(quote!(#var, ));
-            }
-        }
-        header.extend(quote!(|));
-        let closure = quote!(#header #body);
-        quote!({
-            let closure = #closure;
-            let mut ids = Vec::with_capacity(#func_args);
-            for _ in 0..#func_args {
-                let id = unreql::var_counter();
-                ids.push(id);
-            }
-            let func = closure(#params);
-            unreql::Func::new(ids, func).into_cmd()
-        })
+        // the variables are created by a shared runtime helper, so every
+        // call site only expands to a closure
+        quote!(unreql::Func::build(
+            #mv |[#(#args),*]: [unreql::Command; #func_args]| #body
+        ))
     }
 }
 
@@ -15,7 +15,7 @@ pub fn func(input: TokenStream) -> TokenStream {
     Func::new(input.into()).process().into()
 }
 
-#[proc_macro_derive(OptionsBuilder)]
+#[proc_macro_derive(OptionsBuilder, attributes(builder))]
 pub fn options_builder(input: TokenStream) -> TokenStream {
     options_builder::parse(input)
 }
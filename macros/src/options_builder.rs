@@ -1,6 +1,8 @@
 use proc_macro2::TokenStream;
 use quote::{quote, ToTokens};
-use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericArgument, PathArguments, Type};
+use syn::{
+    parse_macro_input, Data, DeriveInput, Fields, GenericArgument, Ident, PathArguments, Type,
+};
 
 pub(super) fn parse(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
@@ -12,6 +14,13 @@ pub(super) fn parse(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
             Fields::Named(fields) => {
                 for field in fields.named {
                     let name = field.ident;
+                    // the setter of a field behind a feature is behind it too
+                    let cfgs = field.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+                    // `#[builder(into)]` makes the setter take `impl Into<T>`
+                    let into = field.attrs.iter().any(|attr| {
+                        attr.path.is_ident("builder")
+                            && attr.parse_args::<Ident>().is_ok_and(|arg| arg == "into")
+                    });
                     let mut generics = TokenStream::new();
                     let mut where_clause = TokenStream::new();
                     let mut value = quote!(#name);
@@ -28,13 +37,21 @@ pub(super) fn parse(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
                         value = quote!(Db(#value))
                     }
 
+                    if into {
+                        value = quote!(#value.into());
+                    }
+
                     if param.is_option {
                         value = quote!(Some(#value))
                     }
 
-                    let ty = param.ty;
+                    let mut ty = param.ty;
+                    if into {
+                        ty = quote!(impl Into<#ty>);
+                    }
 
                     methods.extend(quote! {
+                        #(#cfgs)*
                         pub fn #name #generics(mut self, #name: #ty) -> Self #where_clause {
                             self.#name = #value;
                             self
@@ -0,0 +1,202 @@
+//! A blocking API for scripts and command line tools
+//!
+//! Requires the `blocking` feature.
+//!
+//! Each call drives the async driver to completion on the current thread
+//! with [futures::executor::block_on], so no async runtime is needed.
+//! The socket is polled by the background thread of `async-io`.
+//!
+//! Do not use it from inside an async runtime: blocking a task of
+//! `tokio`, `async-std` or any other executor stalls its thread, and
+//! every other task scheduled on it, until the query completes. Use the
+//! async API there.
+//!
+//! ## Example
+//!
+//! ```
+//! use unreql::blocking::BlockingSession;
+//! use unreql::cmd::connect::Options;
+//! use unreql::r;
+//!
+//! # fn example() -> unreql::Result<()> {
+//! let session = BlockingSession::connect(Options::new().db("marvel"))?;
+//! let count: u64 = r.table("heroes").count(()).exec_blocking(&session)?;
+//! let names: Vec<String> = r.table("heroes").g("name").exec_to_vec_blocking(&session)?;
+//!
+//! for change in r.table("heroes").changes(()).run_blocking::<serde_json::Value>(&session) {
+//!     println!("{}", change?);
+//! }
+//! # Ok(()) }
+//! ```
+
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use futures::executor::{self, BlockingStream};
+use futures::stream::Stream;
+use serde::de::DeserializeOwned;
+
+use crate::cmd::{connect, run};
+use crate::{r, Command, Connection, Result, Session};
+
+/// A [Session] used from synchronous code
+#[derive(Debug, Clone)]
+pub struct BlockingSession {
+    session: Session,
+}
+
+impl BlockingSession {
+    /// Connect to a server, see [r.connect](crate::r::connect)
+    pub fn connect(options: impl connect::Arg) -> Result<Self> {
+        executor::block_on(r.connect(options)).map(Self::from)
+    }
+
+    /// The async session, e.g. to pass it to an async part of the program
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+}
+
+impl From<Session> for BlockingSession {
+    fn from(session: Session) -> Self {
+        Self { session }
+    }
+}
+
+#[async_trait]
+impl run::Arg for &BlockingSession {
+    async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, run::Options)> {
+        self.session.into_run_opts(for_changes).await
+    }
+}
+
+type BoxStream<'a, T> = Pin<Box<dyn Stream<Item = Result<T>> + 'a>>;
+
+/// The results of a query, read one by one while blocking the thread
+///
+/// Returned by [Command::run_blocking]. Changefeeds never end, the
+/// iterator blocks until the next change.
+pub struct BlockingCursor<'a, T> {
+    stream: BlockingStream<BoxStream<'a, T>>,
+}
+
+impl<T> Iterator for BlockingCursor<'_, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.stream.next()
+    }
+}
+
+impl<T> std::fmt::Debug for BlockingCursor<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockingCursor").finish_non_exhaustive()
+    }
+}
+
+impl Command {
+    /// Run a query and iterate over its results, blocking the thread,
+    /// see [run](Self::run) and the [blocking](crate::blocking) module
+    ///
+    /// Requires the `blocking` feature.
+    pub fn run_blocking<'a, T>(self, arg: impl run::Arg + 'a) -> BlockingCursor<'a, T>
+    where
+        T: Unpin + DeserializeOwned + 'a,
+    {
+        let stream: BoxStream<'a, T> = Box::pin(self.run(arg));
+        BlockingCursor {
+            stream: executor::block_on_stream(stream),
+        }
+    }
+
+    /// Run a query and return one result, blocking the thread, see
+    /// [exec](Self::exec) and the [blocking](crate::blocking) module
+    ///
+    /// Requires the `blocking` feature.
+    pub fn exec_blocking<T>(self, arg: impl run::Arg) -> Result<T>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        executor::block_on(self.exec(arg))
+    }
+
+    /// Run a query and collect all the results, blocking the thread, see
+    /// [exec_to_vec](Self::exec_to_vec) and the [blocking](crate::blocking)
+    /// module
+    ///
+    /// Requires the `blocking` feature.
+    pub fn exec_to_vec_blocking<T>(self, arg: impl run::Arg) -> Result<Vec<T>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        executor::block_on(self.exec_to_vec(arg))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_server;
+    use serde_json::{json, Value};
+    use tokio::runtime::Runtime;
+
+    // A session to a server on `runtime` answering the datums with
+    // themselves, arrays as sequences, and changefeeds with one change per
+    // batch
+    fn connect(runtime: &Runtime) -> BlockingSession {
+        let options = runtime.block_on(fake_server::rethinkdb(|_, mut stream| async move {
+            let mut change = 0;
+            while let Some((token, query)) = fake_server::next_query(&mut stream).await {
+                let resp = match (query[0].as_u64(), &query[1]) {
+                    // START of `changes`
+                    (Some(1), term) if term[0] == json!(152) => {
+                        json!({ "t": 3, "r": [{ "new_val": 0, "old_val": null }] })
+                    }
+                    // START of `r.expr` with an array
+                    (Some(1), term) if term[0] == json!(2) => json!({ "t": 2, "r": term[1] }),
+                    (Some(1), term) => json!({ "t": 1, "r": [term] }),
+                    // CONTINUE
+                    (Some(2), _) => {
+                        change += 1;
+                        json!({ "t": 3, "r": [{ "new_val": change, "old_val": change - 1 }] })
+                    }
+                    _ => json!({ "t": 2, "r": [] }),
+                };
+                fake_server::send(&mut stream, token, &resp.to_string()).await;
+            }
+        }));
+        BlockingSession::connect(options).unwrap()
+    }
+
+    #[test]
+    fn exec_blocking() {
+        let runtime = Runtime::new().unwrap();
+        let session = connect(&runtime);
+        let n: u32 = r.expr(7).exec_blocking(&session).unwrap();
+        assert_eq!(n, 7);
+        let name: String = r.expr("hulk").exec_blocking(&session).unwrap();
+        assert_eq!(name, "hulk");
+    }
+
+    #[test]
+    fn exec_to_vec_blocking() {
+        let runtime = Runtime::new().unwrap();
+        let session = connect(&runtime);
+        let items: Vec<u32> = r.expr([1, 2, 3]).exec_to_vec_blocking(&session).unwrap();
+        assert_eq!(items, [1, 2, 3]);
+    }
+
+    #[test]
+    fn changefeed_iterator() {
+        let runtime = Runtime::new().unwrap();
+        let session = connect(&runtime);
+        let changes: Vec<Value> = r
+            .table("heroes")
+            .changes(())
+            .run_blocking::<Value>(&session)
+            .take(3)
+            .map(|change| change.unwrap()["new_val"].clone())
+            .collect();
+        assert_eq!(changes, [json!(0), json!(1), json!(2)]);
+    }
+}
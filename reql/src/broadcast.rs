@@ -0,0 +1,383 @@
+//! Share one changefeed among many subscribers
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures::channel::mpsc;
+use futures::future::{self, Either};
+use futures::stream::{Stream, StreamExt};
+use futures::task::AtomicWaker;
+use serde::de::DeserializeOwned;
+
+use crate::cmd::args::Opt;
+use crate::cmd::options::ChangesOptions;
+use crate::cmd::run;
+use crate::{Command, Error, Result};
+
+/// An item received by a [Subscription]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FeedItem<T> {
+    /// A value from the changefeed
+    Item(T),
+    /// The subscriber was too slow, this many values were dropped for it
+    Lagged(u64),
+}
+
+struct Subscriber<T> {
+    tx: mpsc::Sender<Result<FeedItem<T>>>,
+    lagged: u64,
+}
+
+struct Shared<T> {
+    subscribers: Mutex<Vec<Subscriber<T>>>,
+    capacity: usize,
+    count: AtomicUsize,
+    subscribed: AtomicBool,
+    finished: AtomicBool,
+    // the error ending the feed, given to every subscriber once its buffer
+    // is drained, even a full one
+    error: Mutex<Option<Error>>,
+    waker: AtomicWaker,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Subscriber<T>>> {
+        self.subscribers.lock().unwrap_or_else(|x| x.into_inner())
+    }
+
+    fn is_abandoned(&self) -> bool {
+        self.subscribed.load(Ordering::SeqCst) && self.count.load(Ordering::SeqCst) == 0
+    }
+}
+
+/// One server changefeed delivered to any number of subscribers
+///
+/// [new](Self::new) returns the feed together with the future that runs
+/// the changefeed, spawn it on your runtime. The future completes when the
+/// changefeed ends or when the last subscriber is dropped, dropping the
+/// changefeed stream with it.
+///
+/// Every subscriber has a buffer of `capacity` values. If a subscriber does
+/// not keep up, values are dropped for it and it receives
+/// [FeedItem::Lagged] with their number instead. An error ends the feed
+/// and is delivered to every subscriber, after the values in its buffer.
+///
+/// ## Example
+///
+/// ```
+/// # use unreql::{r, Session};
+/// # use unreql::broadcast::SharedFeed;
+/// # use futures::TryStreamExt;
+/// # async fn example(session: Session) -> unreql::Result<()> {
+/// let (feed, driver) = SharedFeed::<serde_json::Value>::new(
+///     session.connection()?,
+///     r.table("games"),
+///     (),
+///     128,
+/// );
+/// tokio::spawn(driver);
+///
+/// let mut changes = feed.subscribe();
+/// while let Some(change) = changes.try_next().await? {
+///     println!("{:?}", change);
+/// }
+/// # Ok(()) }
+/// ```
+pub struct SharedFeed<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Clone for SharedFeed<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for SharedFeed<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedFeed")
+            .field("subscribers", &self.shared.count.load(Ordering::SeqCst))
+            .field("capacity", &self.shared.capacity)
+            .finish()
+    }
+}
+
+impl<T> SharedFeed<T>
+where
+    T: Clone + Unpin + DeserializeOwned + Send + 'static,
+{
+    /// Run `query.changes(opts)` once for all the subscribers
+    pub fn new(
+        arg: impl run::Arg + Send + 'static,
+        query: Command,
+        opts: impl Opt<ChangesOptions>,
+        capacity: usize,
+    ) -> (Self, impl Future<Output = ()> + Send) {
+        let changes = query.changes(opts).run::<T>(arg);
+        Self::from_stream(changes, capacity)
+    }
+}
+
+impl<T> SharedFeed<T>
+where
+    T: Clone + Send + 'static,
+{
+    pub(crate) fn from_stream<S>(
+        stream: S,
+        capacity: usize,
+    ) -> (Self, impl Future<Output = ()> + Send)
+    where
+        S: Stream<Item = Result<T>> + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            subscribers: Mutex::new(Vec::new()),
+            capacity: capacity.max(1),
+            count: AtomicUsize::new(0),
+            subscribed: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+            error: Mutex::new(None),
+            waker: AtomicWaker::new(),
+        });
+        let driver = drive(shared.clone(), stream);
+        (Self { shared }, driver)
+    }
+
+    /// Receive the values of the feed from now on
+    pub fn subscribe(&self) -> Subscription<T> {
+        // the channel has room for `buffer + 1` values
+        let (tx, rx) = mpsc::channel(self.shared.capacity - 1);
+        let mut subscribers = self.shared.lock();
+        // a subscription to a finished feed ends right away
+        let finished = self.shared.finished.load(Ordering::SeqCst);
+        if !finished {
+            subscribers.push(Subscriber { tx, lagged: 0 });
+        }
+        drop(subscribers);
+        self.shared.count.fetch_add(1, Ordering::SeqCst);
+        self.shared.subscribed.store(true, Ordering::SeqCst);
+        Subscription {
+            rx,
+            shared: self.shared.clone(),
+            ended: finished,
+        }
+    }
+
+    /// The number of live subscriptions
+    pub fn subscribers(&self) -> usize {
+        self.shared.count.load(Ordering::SeqCst)
+    }
+}
+
+async fn drive<T, S>(shared: Arc<Shared<T>>, stream: S)
+where
+    T: Clone,
+    S: Stream<Item = Result<T>>,
+{
+    let mut stream = Box::pin(stream);
+    let abandoned = future::poll_fn(|cx: &mut Context<'_>| {
+        shared.waker.register(cx.waker());
+        if shared.is_abandoned() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    });
+    let mut abandoned = Box::pin(abandoned);
+    loop {
+        let item = match future::select(stream.next(), abandoned.as_mut()).await {
+            Either::Left((Some(item), _)) => item,
+            Either::Left((None, _)) | Either::Right(_) => break,
+        };
+        let item = match item {
+            Ok(item) => item,
+            Err(error) => {
+                *shared.error.lock().unwrap_or_else(|x| x.into_inner()) = Some(error);
+                break;
+            }
+        };
+        let mut subscribers = shared.lock();
+        subscribers.retain(|x| !x.tx.is_closed());
+        for subscriber in subscribers.iter_mut() {
+            send(subscriber, &item);
+        }
+    }
+    // dropping the senders ends every subscription
+    let mut subscribers = shared.lock();
+    shared.finished.store(true, Ordering::SeqCst);
+    subscribers.clear();
+}
+
+fn send<T: Clone>(subscriber: &mut Subscriber<T>, item: &T) {
+    if subscriber.lagged > 0 {
+        let lagged = Ok(FeedItem::Lagged(subscriber.lagged));
+        if subscriber.tx.try_send(lagged).is_err() {
+            subscriber.lagged += 1;
+            return;
+        }
+        subscriber.lagged = 0;
+    }
+    if subscriber
+        .tx
+        .try_send(Ok(FeedItem::Item(item.clone())))
+        .is_err()
+    {
+        subscriber.lagged += 1;
+    }
+}
+
+/// The values of a [SharedFeed] for one subscriber
+pub struct Subscription<T> {
+    rx: mpsc::Receiver<Result<FeedItem<T>>>,
+    shared: Arc<Shared<T>>,
+    // whether the end of the feed, with its error, was returned
+    ended: bool,
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = Result<FeedItem<T>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.rx.poll_next_unpin(cx) {
+            Poll::Ready(None) if !self.ended => {
+                self.ended = true;
+                let error = self.shared.error.lock().unwrap_or_else(|x| x.into_inner());
+                Poll::Ready(error.clone().map(Err))
+            }
+            poll => poll,
+        }
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        self.rx.close();
+        if self.shared.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.shared.waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{SinkExt, TryStreamExt};
+
+    #[tokio::test]
+    async fn every_subscriber_gets_all_values() {
+        let (mut tx, rx) = mpsc::channel::<Result<u32>>(8);
+        let (feed, driver) = SharedFeed::from_stream(rx, 8);
+        let first = feed.subscribe();
+        let second = feed.subscribe();
+        let driver = tokio::spawn(driver);
+
+        for i in 0..3 {
+            tx.send(Ok(i)).await.unwrap();
+        }
+        drop(tx);
+        driver.await.unwrap();
+
+        let expected = [FeedItem::Item(0), FeedItem::Item(1), FeedItem::Item(2)];
+        assert_eq!(first.try_collect::<Vec<_>>().await.unwrap(), expected);
+        assert_eq!(second.try_collect::<Vec<_>>().await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn slow_subscriber_lags() {
+        let (tx, rx) = mpsc::unbounded::<Result<u32>>();
+        let (feed, driver) = SharedFeed::from_stream(rx, 2);
+        let slow = feed.subscribe();
+        for i in 0..5 {
+            tx.unbounded_send(Ok(i)).unwrap();
+        }
+        drop(tx);
+        driver.await;
+
+        // 0 and 1 fill the buffer, 2..5 are dropped and nothing follows
+        // them, so the lag itself is never reported
+        let items = slow.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(items, [FeedItem::Item(0), FeedItem::Item(1)]);
+    }
+
+    #[tokio::test]
+    async fn lag_is_reported_before_next_value() {
+        let (mut tx, rx) = mpsc::channel::<Result<u32>>(0);
+        let (feed, driver) = SharedFeed::from_stream(rx, 2);
+        let mut slow = feed.subscribe();
+        let driver = tokio::spawn(driver);
+
+        for i in 0..4 {
+            tx.send(Ok(i)).await.unwrap();
+        }
+        assert_eq!(slow.try_next().await.unwrap(), Some(FeedItem::Item(0)));
+        assert_eq!(slow.try_next().await.unwrap(), Some(FeedItem::Item(1)));
+        tx.send(Ok(4)).await.unwrap();
+        drop(tx);
+        driver.await.unwrap();
+
+        let rest = slow.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(rest, [FeedItem::Lagged(2), FeedItem::Item(4)]);
+    }
+
+    #[tokio::test]
+    async fn stops_after_last_subscriber_drops() {
+        let (_tx, rx) = mpsc::channel::<Result<u32>>(8);
+        let (feed, driver) = SharedFeed::from_stream(rx, 8);
+        let first = feed.subscribe();
+        let second = feed.subscribe();
+        let driver = tokio::spawn(driver);
+
+        drop(first);
+        tokio::task::yield_now().await;
+        assert!(!driver.is_finished());
+        assert_eq!(feed.subscribers(), 1);
+
+        drop(second);
+        driver.await.unwrap();
+        assert_eq!(feed.subscribers(), 0);
+
+        let late = feed.subscribe();
+        assert!(late.collect::<Vec<_>>().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn errors_reach_every_subscriber() {
+        let (tx, rx) = mpsc::unbounded::<Result<u32>>();
+        let (feed, driver) = SharedFeed::from_stream(rx, 8);
+        let first = feed.subscribe();
+        let second = feed.subscribe();
+        tx.unbounded_send(Err(crate::Driver::ConnectionBroken.into()))
+            .unwrap();
+        driver.await;
+
+        for sub in [first, second] {
+            let items: Vec<_> = sub.collect().await;
+            assert_eq!(items.len(), 1);
+            assert!(items[0].is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn error_reaches_a_full_subscriber() {
+        let (tx, rx) = mpsc::unbounded::<Result<u32>>();
+        let (feed, driver) = SharedFeed::from_stream(rx, 2);
+        let full = feed.subscribe();
+        for i in 0..3 {
+            tx.unbounded_send(Ok(i)).unwrap();
+        }
+        tx.unbounded_send(Err(crate::Driver::ConnectionBroken.into()))
+            .unwrap();
+        driver.await;
+
+        let mut items = full.collect::<Vec<_>>().await.into_iter();
+        assert_eq!(items.next().unwrap().unwrap(), FeedItem::Item(0));
+        assert_eq!(items.next().unwrap().unwrap(), FeedItem::Item(1));
+        let error = items.next().unwrap().unwrap_err();
+        assert!(error.is_unreachable(), "{:?}", error);
+        assert!(items.next().is_none());
+    }
+}
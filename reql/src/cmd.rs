@@ -1,8 +1,21 @@
 pub mod args;
+pub mod cancel;
 pub mod close;
 pub mod connect;
+pub mod cursor;
+pub mod feed;
+pub mod filter_audit;
 pub mod func;
 pub mod options;
+pub mod reshard;
 pub mod run;
+pub(crate) mod stream;
+#[cfg(feature = "tls")]
+pub mod tls;
+pub mod write_token;
 
 mod groups;
+pub(crate) mod validate;
+
+/// Term types of the ReQL protocol, used with [Command::new](crate::Command::new)
+pub use ql2::term::TermType;
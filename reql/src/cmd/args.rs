@@ -1,3 +1,52 @@
+//! Arguments accepted by the commands
+//!
+//! The traits in this module describe which values a command argument
+//! accepts. They are part of the public API and can be used to add
+//! custom commands in other crates.
+//!
+//! - [Arg] – a single argument, a value or a function
+//! - [ManyArgs] – zero or more arguments, spliced with [r.args](crate::r::args)
+//! - [Opt] – only command options
+//! - [OneAndSecondOptionalArg] – a required argument and an optional second one
+//! - [WithOpts] – options attached by [r.with_opt](crate::r::with_opt)
+//! - [DoArgs] – arguments of [do_](crate::Command::do_)
+//!
+//! The type parameter `P` of the traits is the type of the command options,
+//! use `()` if the command has none.
+//!
+//! ## Custom commands
+//!
+//! A command is built with [Command::new](crate::Command::new) and the
+//! argument traits, then attached to the previous command with
+//! [with_parent](crate::Command::with_parent). Downstream crates usually put
+//! such commands into an extension trait:
+//!
+//! ```
+//! use unreql::cmd::args::ManyArgs;
+//! use unreql::cmd::TermType;
+//! use unreql::{r, Command};
+//!
+//! pub trait Shout {
+//!     /// Upper-case a string and append the given suffixes to it
+//!     fn shout(self, suffix: impl ManyArgs<()>) -> Command;
+//! }
+//!
+//! impl Shout for Command {
+//!     fn shout(self, suffix: impl ManyArgs<()>) -> Command {
+//!         let upcase = Command::new(TermType::Upcase).with_parent(self);
+//!         suffix.with_cmd(Command::new(TermType::Add).with_parent(upcase))
+//!     }
+//! }
+//!
+//! let query = r.expr("hello").shout(r.args(["!", "!"]));
+//! assert_eq!(
+//!     serde_json::to_string(&query).unwrap(),
+//!     r#"[24,[[141,["hello"]],"!","!"]]"#,
+//! );
+//! ```
+//!
+//! See `examples/src/extension.rs` in the repository for a complete example.
+
 use crate::Command;
 
 mod arg;
@@ -8,16 +57,51 @@ mod opt;
 
 pub use arg::Arg;
 pub use do_args::DoArgs;
-pub use many::ManyArgs;
+pub use many::{ContainsArg, ManyArgs};
 pub use one_two_opt::OneAndSecondOptionalArg;
 pub use opt::Opt;
 
+/// Several arguments passed as one, see [r.args](crate::r::args)
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct Args<T>(pub(crate) T);
 
+/// Several arguments of different types, like [r.args](crate::r::args)
+/// with a tuple but without a limit on their count
+///
+/// Each argument is converted to a [Command], so values, commands and
+/// functions can be mixed.
+///
+/// ```
+/// # use unreql::{args_vec, r};
+/// # #[cfg(not(feature = "dates-times"))]
+/// # fn main() {}
+/// # #[cfg(feature = "dates-times")]
+/// # fn main() {
+/// let query = r.object(args_vec!["a", 1, "b", r.now(), "c", [1, 2], "d", true]);
+/// assert_eq!(
+///     serde_json::to_string(&query).unwrap(),
+///     r#"[143,["a",1,"b",[103],"c",[2,[1,2]],"d",true]]"#,
+/// );
+/// # }
+/// ```
+#[macro_export]
+macro_rules! args_vec {
+    ($($arg:expr),* $(,)?) => {
+        $crate::r.args({
+            let args: ::std::vec::Vec<$crate::Command> =
+                ::std::vec![$($crate::Command::from_json_2($arg)),*];
+            args
+        })
+    };
+}
+
+/// Arguments with command options, see [r.with_opt](crate::r::with_opt)
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct ArgsWithOpt<T, P>(pub(crate) T, pub(crate) P);
 
+/// Options that can be attached to a command
+///
+/// Implemented by all the option structs in [options](crate::cmd::options).
 pub trait WithOpts {
     fn with_opts(self, cmd: Command) -> Command;
 }
@@ -4,7 +4,13 @@ use crate::{cmd::options::Index, Command};
 
 use super::{ArgsWithOpt, WithOpts};
 
+/// A single argument of a command
+///
+/// Any serializable value, including a [Command](crate::Command) or
+/// a [func!](crate::func), optionally with command options given by
+/// [r.with_opt](crate::r::with_opt). `null` and `()` add no argument.
 pub trait Arg<P> {
+    /// Add the argument to `cmd`
     fn with_cmd(self, cmd: Command) -> Command;
 }
 
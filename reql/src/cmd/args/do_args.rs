@@ -6,7 +6,9 @@ use crate::Command;
 
 use super::Args;
 
+/// Arguments of [do_](crate::Command::do_), the function goes last
 pub trait DoArgs {
+    /// Build the `FUNCALL` term, `parent` becomes the first function argument
     fn build(self, parent: Option<Command>) -> Command;
 }
 
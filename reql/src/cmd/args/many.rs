@@ -1,11 +1,18 @@
 use ql2::term::TermType;
 use serde::Serialize;
+use serde_json::Value;
 
-use crate::{cmd::options::Index, r, Command};
+use crate::{cmd::options::Index, r, Command, Func};
 
 use super::{Args, ArgsWithOpt, WithOpts};
 
+/// Zero or more arguments of a command
+///
+/// A single serializable value or several values passed with
+/// [r.args](crate::r::args), optionally with command options given by
+/// [r.with_opt](crate::r::with_opt).
 pub trait ManyArgs<P> {
+    /// Add the arguments to `cmd`
     fn with_cmd(self, cmd: Command) -> Command;
 }
 
@@ -72,61 +79,46 @@ impl<P> ManyArgs<P> for Args<Command> {
     }
 }
 
-impl<T1, T2, P> ManyArgs<P> for Args<(T1, T2)>
-where
-    T1: Serialize + 'static,
-    T2: Serialize + 'static,
-{
-    fn with_cmd(self, cmd: Command) -> Command {
-        cmd.with_arg(Command::from_json_2(self.0 .0).wrap_by_func())
-            .with_arg(Command::from_json_2(self.0 .1).wrap_by_func())
-    }
-}
-
-impl<T1, T2, T3, P> ManyArgs<P> for Args<(T1, T2, T3)>
-where
-    T1: Serialize + 'static,
-    T2: Serialize + 'static,
-    T3: Serialize + 'static,
-{
-    fn with_cmd(self, cmd: Command) -> Command {
-        cmd.with_arg(Command::from_json_2(self.0 .0).wrap_by_func())
-            .with_arg(Command::from_json_2(self.0 .1).wrap_by_func())
-            .with_arg(Command::from_json_2(self.0 .2).wrap_by_func())
-    }
-}
+// Tuples of up to 12 values of different types, use
+// [args_vec!](crate::args_vec) for more
+macro_rules! tuple_args {
+    ($($name:ident)+) => {
+        impl<$($name,)+ P> ManyArgs<P> for Args<($($name,)+)>
+        where
+            $($name: Serialize + 'static,)+
+        {
+            #[allow(non_snake_case)]
+            fn with_cmd(self, cmd: Command) -> Command {
+                let ($($name,)+) = self.0;
+                cmd$(.with_arg(Command::from_json_2($name).wrap_by_func()))+
+            }
+        }
 
-impl<T1, T2, T3, T4, P> ManyArgs<P> for Args<(T1, T2, T3, T4)>
-where
-    T1: Serialize + 'static,
-    T2: Serialize + 'static,
-    T3: Serialize + 'static,
-    T4: Serialize + 'static,
-{
-    fn with_cmd(self, cmd: Command) -> Command {
-        cmd.with_arg(Command::from_json_2(self.0 .0).wrap_by_func())
-            .with_arg(Command::from_json_2(self.0 .1).wrap_by_func())
-            .with_arg(Command::from_json_2(self.0 .2).wrap_by_func())
-            .with_arg(Command::from_json_2(self.0 .3).wrap_by_func())
-    }
+        impl<$($name,)+ P> ManyArgs<P> for ArgsWithOpt<Args<($($name,)+)>, P>
+        where
+            $($name: Serialize + 'static,)+
+            P: WithOpts,
+        {
+            fn with_cmd(self, cmd: Command) -> Command {
+                let cmd = ManyArgs::<P>::with_cmd(self.0, cmd);
+                self.1.with_opts(cmd)
+            }
+        }
+    };
 }
 
-impl<T1, T2, T3, T4, T5, P> ManyArgs<P> for Args<(T1, T2, T3, T4, T5)>
-where
-    T1: Serialize + 'static,
-    T2: Serialize + 'static,
-    T3: Serialize + 'static,
-    T4: Serialize + 'static,
-    T5: Serialize + 'static,
-{
-    fn with_cmd(self, cmd: Command) -> Command {
-        cmd.with_arg(Command::from_json_2(self.0 .0).wrap_by_func())
-            .with_arg(Command::from_json_2(self.0 .1).wrap_by_func())
-            .with_arg(Command::from_json_2(self.0 .2).wrap_by_func())
-            .with_arg(Command::from_json_2(self.0 .3).wrap_by_func())
-            .with_arg(Command::from_json_2(self.0 .4).wrap_by_func())
-    }
-}
+tuple_args!(T1);
+tuple_args!(T1 T2);
+tuple_args!(T1 T2 T3);
+tuple_args!(T1 T2 T3 T4);
+tuple_args!(T1 T2 T3 T4 T5);
+tuple_args!(T1 T2 T3 T4 T5 T6);
+tuple_args!(T1 T2 T3 T4 T5 T6 T7);
+tuple_args!(T1 T2 T3 T4 T5 T6 T7 T8);
+tuple_args!(T1 T2 T3 T4 T5 T6 T7 T8 T9);
+tuple_args!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10);
+tuple_args!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11);
+tuple_args!(T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12);
 
 impl<T, P> ManyArgs<P> for ArgsWithOpt<T, P>
 where
@@ -190,83 +182,91 @@ where
     }
 }
 
-impl<T, P> ManyArgs<P> for ArgsWithOpt<Args<(T,)>, P>
+impl<P> ManyArgs<P> for ArgsWithOpt<Args<Command>, P>
 where
-    T: Serialize + 'static,
     P: WithOpts,
 {
     fn with_cmd(self, cmd: Command) -> Command {
-        let cmd = cmd.with_arg(Command::from_json_2(self.0 .0).wrap_by_func());
+        let cmd = cmd.with_arg(Command::new(TermType::Args).with_arg(self.0 .0.wrap_by_func()));
         self.1.with_opts(cmd)
     }
 }
 
-impl<T1, T2, P> ManyArgs<P> for ArgsWithOpt<Args<(T1, T2)>, P>
-where
-    T1: Serialize + 'static,
-    T2: Serialize + 'static,
-    P: WithOpts,
-{
+impl<P> ManyArgs<P> for Index {
     fn with_cmd(self, cmd: Command) -> Command {
-        let cmd = ManyArgs::<P>::with_cmd(self.0, cmd);
-        self.1.with_opts(cmd)
+        self.with_opts(cmd)
     }
 }
 
-impl<T1, T2, T3, P> ManyArgs<P> for ArgsWithOpt<Args<(T1, T2, T3)>, P>
-where
-    T1: Serialize + 'static,
-    T2: Serialize + 'static,
-    T3: Serialize + 'static,
-    P: WithOpts,
-{
-    fn with_cmd(self, cmd: Command) -> Command {
-        let cmd = ManyArgs::<P>::with_cmd(self.0, cmd);
-        self.1.with_opts(cmd)
+/// A value or a predicate, for building the arguments of
+/// [contains](crate::Command::contains),
+/// [has_fields](crate::Command::has_fields) and
+/// [with_fields](crate::Command::with_fields) at runtime
+///
+/// A `Vec<ContainsArg>` is passed with [r.args](crate::r::args):
+///
+/// ```
+/// # use unreql::cmd::args::ContainsArg;
+/// # use unreql::{func, r};
+/// # #[cfg(not(feature = "math-logic"))]
+/// # fn main() {}
+/// # #[cfg(feature = "math-logic")]
+/// # fn main() {
+/// let mut args: Vec<ContainsArg> = vec!["loki".into()];
+/// args.push(func!(|hero| hero.eq("hulk")).into());
+/// let query = r.table("marvel").g("heroes").contains(r.args(args));
+/// # }
+/// ```
+#[derive(Debug)]
+pub enum ContainsArg {
+    Value(Value),
+    /// A function, built with [func!](crate::func) or [r.row](crate::r::row)
+    Func(Func),
+}
+
+impl ContainsArg {
+    fn into_cmd(self) -> Command {
+        match self {
+            Self::Value(value) => Command::from(value),
+            Self::Func(func) => func.rebound(),
+        }
     }
 }
 
-impl<T1, T2, T3, T4, P> ManyArgs<P> for ArgsWithOpt<Args<(T1, T2, T3, T4)>, P>
-where
-    T1: Serialize + 'static,
-    T2: Serialize + 'static,
-    T3: Serialize + 'static,
-    T4: Serialize + 'static,
-    P: WithOpts,
-{
-    fn with_cmd(self, cmd: Command) -> Command {
-        let cmd = ManyArgs::<P>::with_cmd(self.0, cmd);
-        self.1.with_opts(cmd)
+impl From<Value> for ContainsArg {
+    fn from(value: Value) -> Self {
+        Self::Value(value)
     }
 }
 
-impl<T1, T2, T3, T4, T5, P> ManyArgs<P> for ArgsWithOpt<Args<(T1, T2, T3, T4, T5)>, P>
-where
-    T1: Serialize + 'static,
-    T2: Serialize + 'static,
-    T3: Serialize + 'static,
-    T4: Serialize + 'static,
-    T5: Serialize + 'static,
-    P: WithOpts,
-{
-    fn with_cmd(self, cmd: Command) -> Command {
-        let cmd = ManyArgs::<P>::with_cmd(self.0, cmd);
-        self.1.with_opts(cmd)
+impl From<&str> for ContainsArg {
+    fn from(value: &str) -> Self {
+        Self::Value(value.into())
     }
 }
 
-impl<P> ManyArgs<P> for ArgsWithOpt<Args<Command>, P>
-where
-    P: WithOpts,
-{
-    fn with_cmd(self, cmd: Command) -> Command {
-        let cmd = cmd.with_arg(Command::new(TermType::Args).with_arg(self.0 .0.wrap_by_func()));
-        self.1.with_opts(cmd)
+impl From<String> for ContainsArg {
+    fn from(value: String) -> Self {
+        Self::Value(value.into())
     }
 }
 
-impl<P> ManyArgs<P> for Index {
+impl From<Func> for ContainsArg {
+    fn from(func: Func) -> Self {
+        Self::Func(func)
+    }
+}
+
+impl From<Command> for ContainsArg {
+    fn from(cmd: Command) -> Self {
+        Self::Func(Func(cmd.wrap_by_func()))
+    }
+}
+
+impl<P> ManyArgs<P> for Args<Vec<ContainsArg>> {
     fn with_cmd(self, cmd: Command) -> Command {
-        self.with_opts(cmd)
+        self.0
+            .into_iter()
+            .fold(cmd, |cmd, arg| cmd.with_arg(arg.into_cmd()))
     }
 }
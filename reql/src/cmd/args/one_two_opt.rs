@@ -28,6 +28,7 @@ use super::{Args, ArgsWithOpt, WithOpts};
 /// r.example(r.with_opt(r.args((arg1, arg2)), opt));
 /// ```
 pub trait OneAndSecondOptionalArg<P> {
+    /// Add the arguments to `cmd`
     fn with_cmd(self, cmd: Command) -> Command;
 }
 
@@ -2,7 +2,12 @@ use crate::{cmd::options::Index, Command};
 
 use super::{ArgsWithOpt, WithOpts};
 
+/// Only the options of a command
+///
+/// Either `()` for no options, the options themselves or
+/// `r.with_opt((), opts)`.
 pub trait Opt<P> {
+    /// Add the options to `cmd`
     fn with_cmd(self, cmd: Command) -> Command;
 }
 
@@ -0,0 +1,230 @@
+//! Cancel running queries
+//!
+//! Pass a [CancelToken] to a query with [cancel](super::run::Options::cancel).
+//! Once the token is cancelled, the query stops waiting for the server,
+//! sends a `STOP` for its token so the server stops working on it, and
+//! fails with [Driver::Cancelled](crate::Driver::Cancelled).
+//!
+//! ## Example
+//!
+//! Give up on a slow query when the request it serves is aborted.
+//!
+//! ```
+//! use unreql::{r, cmd::{cancel::CancelToken, run::Options}};
+//! # async fn example(session: unreql::Session) -> unreql::Result<()> {
+//! let token = CancelToken::new();
+//! let opts = Options::new().cancel(token.clone());
+//! let count = r.table("logs").count(()).exec::<u64>(r.args((&session, opts)));
+//! // e.g. when the client of the request goes away
+//! token.cancel();
+//! assert!(matches!(count.await, Err(unreql::Error::Driver(unreql::Driver::Cancelled))));
+//! # Ok(()) }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::future::poll_fn;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// A token to cancel the queries it was given to
+///
+/// Clones share the same state: cancelling one cancels all of them.
+/// Tokens compare equal when they are clones of each other.
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Wakers>,
+}
+
+// The wakers of the pending `cancelled` futures, by the key of each future
+#[derive(Default)]
+struct Wakers {
+    next: u64,
+    waiting: HashMap<u64, Waker>,
+}
+
+// A pending `cancelled` future, its waker is removed when it is dropped
+struct Waiter<'a> {
+    inner: &'a Inner,
+    key: Option<u64>,
+}
+
+impl Waiter<'_> {
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.inner.cancelled.load(Ordering::SeqCst) {
+            return Poll::Ready(());
+        }
+        let mut wakers = self.inner.wakers.lock().unwrap();
+        let key = *self.key.get_or_insert_with(|| {
+            wakers.next += 1;
+            wakers.next
+        });
+        match wakers.waiting.get_mut(&key) {
+            Some(waker) if waker.will_wake(cx.waker()) => {}
+            Some(waker) => waker.clone_from(cx.waker()),
+            None => {
+                wakers.waiting.insert(key, cx.waker().clone());
+            }
+        }
+        drop(wakers);
+        // `cancel` may have run before the waker was registered
+        match self.inner.cancelled.load(Ordering::SeqCst) {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            self.inner.wakers.lock().unwrap().waiting.remove(&key);
+        }
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Cancel the queries of the token, now and in the future
+    pub fn cancel(&self) {
+        self.0.cancelled.store(true, Ordering::SeqCst);
+        let waiting = std::mem::take(&mut self.0.wakers.lock().unwrap().waiting);
+        for waker in waiting.into_values() {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the token is cancelled
+    ///
+    /// Dropping the future before forgets its waker, so a token that is
+    /// never cancelled does not grow with the queries waiting on it.
+    pub async fn cancelled(&self) {
+        let mut waiter = Waiter {
+            inner: &self.0,
+            key: None,
+        };
+        poll_fn(|cx| waiter.poll(cx)).await
+    }
+
+    /// A guard cancelling the token when it is dropped, e.g. with the
+    /// task that owns it
+    pub fn drop_guard(self) -> DropGuard {
+        DropGuard(Some(self))
+    }
+}
+
+impl fmt::Debug for CancelToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CancelToken")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for CancelToken {}
+
+impl PartialOrd for CancelToken {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for CancelToken {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        Arc::as_ptr(&self.0).cmp(&Arc::as_ptr(&other.0))
+    }
+}
+
+impl Hash for CancelToken {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Arc::as_ptr(&self.0).hash(state);
+    }
+}
+
+/// Cancels its [CancelToken] when dropped, see [CancelToken::drop_guard]
+#[derive(Debug)]
+pub struct DropGuard(Option<CancelToken>);
+
+impl DropGuard {
+    /// Drop the guard without cancelling the token
+    pub fn disarm(mut self) -> CancelToken {
+        self.0.take().unwrap()
+    }
+}
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        if let Some(token) = &self.0 {
+            token.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::FutureExt;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn cancel_wakes_the_waiters() {
+        let token = CancelToken::new();
+        let waiter = tokio::spawn({
+            let token = token.clone();
+            async move { token.cancelled().await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!token.is_cancelled());
+        token.cancel();
+        waiter.await.unwrap();
+        // and the later ones right away
+        token.cancelled().await;
+    }
+
+    #[test]
+    fn dropped_waiters_are_forgotten() {
+        let token = CancelToken::new();
+        for _ in 0..100 {
+            assert_eq!(token.cancelled().now_or_never(), None);
+        }
+        assert!(token.0.wakers.lock().unwrap().waiting.is_empty());
+
+        let mut waiter = Box::pin(token.cancelled());
+        assert_eq!((&mut waiter).now_or_never(), None);
+        assert_eq!((&mut waiter).now_or_never(), None);
+        assert_eq!(token.0.wakers.lock().unwrap().waiting.len(), 1);
+        token.cancel();
+        assert_eq!(waiter.now_or_never(), Some(()));
+    }
+
+    #[test]
+    fn drop_guard() {
+        let token = CancelToken::new();
+        drop(token.clone().drop_guard());
+        assert!(token.is_cancelled());
+
+        let token = CancelToken::new();
+        let guard = token.clone().drop_guard();
+        assert_eq!(guard.disarm(), token);
+        assert!(!token.is_cancelled());
+    }
+}
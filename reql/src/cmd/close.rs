@@ -1,9 +1,12 @@
-//! Close an open connection
+//! Close an open connection or session
 //!
-//! Closing a connection normally waits until all outstanding requests have
-//! finished and then frees any open resources associated with the
-//! connection. By passing `SkipNoreplyWait` as the argument, the connection
-//! will be closed immediately, possibly aborting any outstanding noreply
+//! Closing a connection stops the changefeed running on it, if any. The
+//! `STOP` query sent for that takes no argument. Closing a session shuts
+//! its socket down, freeing any open resources associated with it.
+//!
+//! Both normally wait for the outstanding `noreply` queries of the session
+//! to be processed first. By passing `SkipNoreplyWait` as the argument,
+//! they close immediately, possibly aborting any outstanding noreply
 //! writes.
 //!
 //! A noreply query is executed by passing the `noreply` option to the
@@ -12,6 +15,9 @@
 //! explicitly wait for a noreply query to complete by using the
 //! [noreply_wait](crate::Session::noreply_wait) command.
 //!
+//! To stop a changefeed and keep using its session, run it with
+//! [run_feed](crate::Command::run_feed) and call
+//! [FeedHandle::stop](crate::cmd::feed::FeedHandle::stop) instead.
 //!
 //! ## Example
 //!
@@ -38,6 +44,17 @@
 //! # }
 //! ```
 //!
+//! ## Example
+//!
+//! Close a session once it is no longer needed.
+//!
+//! ```
+//! # async fn example() -> unreql::Result<()> {
+//! # let session = unreql::r.connect(()).await?;
+//! session.close(()).await
+//! # }
+//! ```
+//!
 //! ## Related commands
 //!
 //! * [connect](crate::r::connect)
@@ -62,3 +79,123 @@ impl Arg for SkipNoreplyWait {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::SkipNoreplyWait;
+    use crate::{fake_server, r, Connection, Session};
+    use futures::channel::mpsc::{self, UnboundedReceiver};
+    use futures::{Stream, StreamExt, TryStreamExt};
+    use serde_json::{json, Value};
+
+    type Frames = UnboundedReceiver<(u64, Value)>;
+
+    // A session to a server passing on every query it reads with its
+    // token. It answers a changefeed with one change, `STOP` with the end
+    // of the feed, `NOREPLY_WAIT` with `WAIT_COMPLETE` and any other
+    // `START` with the value of its `r.expr`.
+    async fn session() -> (Session, Frames) {
+        let (tx, rx) = mpsc::unbounded();
+        let session = fake_server::scripted(move |mut stream| async move {
+            while let Some((token, query)) = fake_server::next_query(&mut stream).await {
+                let resp = match query[0].as_u64().unwrap() {
+                    // `CHANGES`
+                    1 if query[1][0] == 152 => {
+                        json!({"t": 3, "r": [{"new_val": 1}]})
+                    }
+                    1 => json!({"t": 1, "r": [query[1]]}),
+                    3 => json!({"t": 2, "r": []}),
+                    4 => json!({"t": 4, "r": []}),
+                    _ => unreachable!("{query}"),
+                };
+                tx.unbounded_send((u64::from_le_bytes(token), query))
+                    .unwrap();
+                fake_server::send(&mut stream, token, &resp.to_string()).await;
+            }
+        })
+        .await;
+        (session, rx)
+    }
+
+    // Runs `r.expr(7)` and returns the frames written before it
+    async fn frames_before_marker(session: &Session, frames: &mut Frames) -> Vec<(u64, Value)> {
+        assert_eq!(r.expr(7).exec::<u8>(session).await.unwrap(), 7);
+        let mut written = Vec::new();
+        while let Some((token, query)) = frames.next().await {
+            if query[1] == json!(7) {
+                return written;
+            }
+            written.push((token, query));
+        }
+        unreachable!("no marker");
+    }
+
+    // Starts a changefeed and reads its first change, the feed is only
+    // stopped by closing the connection
+    async fn feed(
+        session: &Session,
+        frames: &mut Frames,
+    ) -> (Connection, impl Stream<Item = crate::Result<Value>>) {
+        let conn = session.connection().unwrap();
+        let mut changes = r.table("t").changes(()).run::<Value>(conn.clone());
+        let change = changes.try_next().await.unwrap().unwrap();
+        assert_eq!(change, json!({"new_val": 1}));
+        let (token, _) = frames.next().await.unwrap();
+        assert_eq!(token, conn.token);
+        (conn, changes)
+    }
+
+    #[tokio::test]
+    async fn close_feed() {
+        let (session, mut frames) = session().await;
+        let (mut conn, _changes) = feed(&session, &mut frames).await;
+        conn.close(()).await.unwrap();
+        let written = frames_before_marker(&session, &mut frames).await;
+        assert_eq!(written.len(), 2);
+        assert_eq!(written[0], (conn.token, json!([3])));
+        assert_ne!(written[1].0, conn.token);
+        assert_eq!(written[1].1, json!([4]));
+    }
+
+    #[tokio::test]
+    async fn close_feed_skipping_noreply_wait() {
+        let (session, mut frames) = session().await;
+        let (mut conn, _changes) = feed(&session, &mut frames).await;
+        conn.close(SkipNoreplyWait).await.unwrap();
+        let written = frames_before_marker(&session, &mut frames).await;
+        assert_eq!(written, [(conn.token, json!([3]))]);
+    }
+
+    #[tokio::test]
+    async fn close_without_feed() {
+        let (session, mut frames) = session().await;
+        let mut conn = session.connection().unwrap();
+        conn.close(()).await.unwrap();
+        let written = frames_before_marker(&session, &mut frames).await;
+        assert_eq!(written.len(), 1);
+        assert_eq!(written[0].1, json!([4]));
+
+        conn.close(SkipNoreplyWait).await.unwrap();
+        let written = frames_before_marker(&session, &mut frames).await;
+        assert!(written.is_empty());
+    }
+
+    #[tokio::test]
+    async fn close_session() {
+        let (session, mut frames) = session().await;
+        session.close(()).await.unwrap();
+        assert_eq!(frames.next().await.unwrap().1, json!([4]));
+        // the server reads the end of the stream
+        assert!(frames.next().await.is_none());
+        assert!(session.is_broken());
+        assert!(r.expr(7).exec::<u8>(&session).await.is_err());
+        session.close(()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn close_session_skipping_noreply_wait() {
+        let (session, mut frames) = session().await;
+        session.close(SkipNoreplyWait).await.unwrap();
+        assert!(frames.next().await.is_none());
+    }
+}
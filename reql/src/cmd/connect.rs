@@ -1,28 +1,39 @@
 //! Create a new connection to the database server
 
 use super::args::Args;
+use super::stream::Stream;
+use crate::events::EventLog;
 use crate::tools::StaticString;
 use crate::{err, InnerSession, Result, Session};
-use async_net::{AsyncToSocketAddrs, TcpStream};
-use dashmap::DashMap;
-use futures::io::{AsyncReadExt, AsyncWriteExt};
-use futures::lock::Mutex;
+use async_io::Timer;
+use async_net::AsyncToSocketAddrs;
+use futures::future::{self, Either};
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use ql2::version_dummy::Version;
 use scram::client::{ScramClient, ServerFinal, ServerFirst};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::ffi::OsString;
+use std::fmt;
+use std::future::Future;
 use std::net::SocketAddr;
-use std::sync::atomic::{AtomicBool, AtomicU64};
+use std::pin::pin;
 use std::sync::Arc;
-use tracing::trace;
+use std::time::Duration;
+use tracing::{trace, warn};
 use unreql_macros::OptionsBuilder;
 
+pub use async_net::TcpStream;
+
 const BUF_SIZE: usize = 1024;
 const NULL_BYTE: u8 = b'\0';
-const PROTOCOL_VERSION: usize = 0;
+pub(crate) const PROTOCOL_VERSION: usize = 0;
 
 pub(crate) const DEFAULT_DB: &str = "test";
 
+/// The default [timeout](Options::timeout) of a connection
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(20);
+
 /// Options accepted by [crate::r::connect]
 #[derive(Debug, Clone, OptionsBuilder, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[non_exhaustive]
@@ -31,12 +42,82 @@ pub struct Options {
     pub host: Cow<'static, str>,
     /// The driver port, by default `28015`.
     pub port: u16,
+    /// The servers to fail over between, as `(host, port)` pairs, by
+    /// default empty.
+    ///
+    /// When set, `host` and `port` are not used. [connect](crate::r::connect)
+    /// tries the hosts in turn and moves to the next one when a host
+    /// cannot be reached, e.g. it refuses the connection or the
+    /// [timeout](Self::timeout), which applies to every host, is exceeded.
+    /// Other errors, such as wrong credentials, fail right away.
+    pub hosts: Vec<(String, u16)>,
     /// The database used if not explicitly specified in a query, by default `test`.
     pub db: Cow<'static, str>,
     /// The user account to connect as (default `admin`).
     pub user: Cow<'static, str>,
     /// The password for the user account to connect as (default `""`, empty).
     pub password: Cow<'static, str>,
+    /// How many of the most recent protocol events to keep for
+    /// [recent_events](crate::Session::recent_events), by default `0` (disabled).
+    ///
+    /// The errors of a broken connection then hold the events up to the
+    /// failure, shown by their `Debug` output. To hold them,
+    /// [ConnectionBroken](crate::Driver::ConnectionBroken) is returned as an
+    /// [Io](crate::Driver::Io) error of kind `ConnectionAborted`; match
+    /// both, or use [is_unreachable](crate::Error::is_unreachable).
+    pub event_log_size: usize,
+    /// Fail the queries that would use the `test` database because they
+    /// do not name one and `db` was left to its default, by default `false`.
+    ///
+    /// The `test` database can still be queried with `r.db("test")`.
+    pub default_db_required: bool,
+    /// Log a warning when [exec_to_vec](crate::Command::exec_to_vec)
+    /// collects a table scan without `order_by`, whose results come in no
+    /// particular order, by default `false`.
+    ///
+    /// Meant for tests, to find the assertions on that order, which pass
+    /// or fail depending on the server. See
+    /// [exec_to_sorted_vec](crate::Command::exec_to_sorted_vec).
+    pub warn_unordered: bool,
+    /// How strictly the handshake checks the server, by default
+    /// [Strict](CompatMode::Strict).
+    pub compatibility: CompatMode,
+    /// How long connecting may take, the TCP connection and the handshake
+    /// together, by default [DEFAULT_TIMEOUT] (20 seconds). When it is
+    /// exceeded the connection fails with
+    /// [ConnectTimeout](err::Driver::ConnectTimeout). Set the field to
+    /// `None` to wait as long as the OS does.
+    pub timeout: Option<Duration>,
+    /// Reconnect a broken session before its next query, by default
+    /// `false`, see [reconnect](crate::Session::reconnect).
+    ///
+    /// A query whose request cannot be sent is sent again once after
+    /// reconnecting. A query that was sent is never sent again, as it may
+    /// have run: it fails, and the session reconnects for the next one.
+    pub auto_reconnect: bool,
+    /// Connect over TLS, by default `None`. With the `tls` feature, see
+    /// [tls](super::tls).
+    #[cfg(feature = "tls")]
+    #[builder(into)]
+    pub tls: Option<super::tls::TlsConfig>,
+}
+
+/// How strictly [connect](crate::r::connect) checks the server
+///
+/// RethinkDB-compatible servers, e.g. forks of RethinkDB, speak the same
+/// protocol but may describe themselves differently in the handshake.
+/// The version string of the server is never checked.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum CompatMode {
+    /// Fail if the server does not send its range of protocol versions,
+    /// or if the range does not include the version of the driver
+    #[default]
+    Strict,
+    /// Only log a warning if the range of protocol versions is missing
+    /// or does not include the version of the driver, and try to
+    /// authenticate anyway
+    Relaxed,
 }
 
 impl Default for Options {
@@ -44,13 +125,95 @@ impl Default for Options {
         Self {
             host: "localhost".static_string(),
             port: 28015,
+            hosts: Vec::new(),
             db: DEFAULT_DB.static_string(),
             user: "admin".static_string(),
             password: "".static_string(),
+            event_log_size: 0,
+            default_db_required: false,
+            warn_unordered: false,
+            compatibility: CompatMode::Strict,
+            timeout: Some(DEFAULT_TIMEOUT),
+            auto_reconnect: false,
+            #[cfg(feature = "tls")]
+            tls: None,
+        }
+    }
+}
+
+impl Options {
+    /// Options read from the `RDB_*` environment variables
+    ///
+    /// | Variable       | Option                   |
+    /// |----------------|--------------------------|
+    /// | `RDB_HOST`     | [host](Self::host)       |
+    /// | `RDB_PORT`     | [port](Self::port)       |
+    /// | `RDB_DB`       | [db](Self::db)           |
+    /// | `RDB_USER`     | [user](Self::user)       |
+    /// | `RDB_PASSWORD` | [password](Self::password) |
+    ///
+    /// A variable that is not set keeps the default value. The builder
+    /// methods called on the result win over the environment. A value that
+    /// is not valid unicode, or a port that is not a number, is an
+    /// [InvalidEnv](err::Driver::InvalidEnv) error.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, cmd::connect::Options};
+    /// # async fn example() -> unreql::Result<()> {
+    /// // `RDB_DB` is ignored, the database is always `marvel`
+    /// let session = r.connect(Options::from_env()?.db("marvel")).await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var_os(name))
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<OsString>) -> Result<Self> {
+        let string = |name: &str| -> Result<Option<String>> {
+            match var(name).map(OsString::into_string) {
+                None => Ok(None),
+                Some(Ok(value)) => Ok(Some(value)),
+                Some(Err(value)) => Err(invalid_env(name, value.to_string_lossy())),
+            }
+        };
+        let mut opts = Self::default();
+        if let Some(host) = string("RDB_HOST")? {
+            opts.host = host.into();
+        }
+        if let Some(port) = string("RDB_PORT")? {
+            opts.port = port.parse().map_err(|_| invalid_env("RDB_PORT", port))?;
+        }
+        if let Some(db) = string("RDB_DB")? {
+            opts.db = db.into();
+        }
+        if let Some(user) = string("RDB_USER")? {
+            opts.user = user.into();
+        }
+        if let Some(password) = string("RDB_PASSWORD")? {
+            opts.password = password.into();
+        }
+        Ok(opts)
+    }
+
+    // The hosts to connect to, `hosts` or else `host` and `port`
+    fn host_list(&self) -> Vec<(String, u16)> {
+        match self.hosts.is_empty() {
+            true => vec![(self.host.to_string(), self.port)],
+            false => self.hosts.clone(),
         }
     }
 }
 
+fn invalid_env(name: &str, value: impl Into<String>) -> crate::Error {
+    err::Driver::InvalidEnv {
+        name: name.to_owned(),
+        value: value.into(),
+    }
+    .into()
+}
+
 /// The arguments accepted by [crate::r::connect]
 pub trait Arg {
     type ToAddrs: AsyncToSocketAddrs;
@@ -99,60 +262,174 @@ pub(crate) async fn new<T>((addr, options): (Option<T>, Options)) -> Result<Sess
 where
     T: AsyncToSocketAddrs,
 {
-    let stream = match addr {
-        Some(addr) => TcpStream::connect(addr).await?,
-        None => TcpStream::connect((options.host.as_ref(), options.port)).await?,
-    };
-    let inner = InnerSession {
-        stream: Mutex::new(handshake(stream, &options).await?),
-        db: Mutex::new(options.db),
-        channels: DashMap::new(),
-        token: AtomicU64::new(0),
-        broken: AtomicBool::new(false),
-        change_feed: AtomicBool::new(false),
-    };
+    match addr {
+        Some(addr) => {
+            with_timeout(options.timeout, async {
+                let stream = TcpStream::connect(addr).await?;
+                open(stream, options).await
+            })
+            .await
+        }
+        None => connect_hosts(options, 0).await,
+    }
+}
+
+// Connects to the hosts of `options` in turn, from the one at `start`
+// around to the one before it, until one of them can be reached
+pub(crate) async fn connect_hosts(options: Options, start: usize) -> Result<Session> {
+    let dialer = Dialer(options);
+    let (stream, index) = dialer.dial(start).await?;
+    let mut inner = session(stream, dialer.0.clone());
+    inner.host = index.into();
+    inner.dialer = Some(dialer);
     Ok(Session {
         inner: Arc::new(inner),
     })
 }
 
+// Connects to the server again with the options of a session, see
+// `Session::reconnect`. Its `Debug` leaves out the options, which hold
+// the password.
+pub(crate) struct Dialer(Options);
+
+impl Dialer {
+    // Returns the stream of the first host that can be reached, from the
+    // one at `start`, and its index
+    pub(crate) async fn dial(&self, start: usize) -> Result<(Stream, usize)> {
+        let hosts = self.0.host_list();
+        let mut attempts = hosts.len();
+        for index in (0..hosts.len()).cycle().skip(start % hosts.len()) {
+            let (host, port) = &hosts[index];
+            let mut options = self.0.clone();
+            // the name of the server over TLS
+            options.host = host.clone().into();
+            let result = with_timeout(options.timeout, async {
+                let stream = TcpStream::connect((host.as_str(), *port)).await?;
+                establish(stream, &options).await
+            })
+            .await;
+            attempts -= 1;
+            match result {
+                Ok(stream) => return Ok((stream, index)),
+                Err(err) if attempts > 0 && err.is_unreachable() => {
+                    warn!(
+                        "failed to connect to {}:{}, trying the next host; error: {}",
+                        host, port, err
+                    );
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        unreachable!("the host list is never empty")
+    }
+
+    pub(crate) fn auto_reconnect(&self) -> bool {
+        self.0.auto_reconnect
+    }
+}
+
+impl fmt::Debug for Dialer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dialer")
+            .field("hosts", &self.0.host_list())
+            .field("auto_reconnect", &self.0.auto_reconnect)
+            .finish()
+    }
+}
+
+// Fails with `ConnectTimeout` unless `connect` is done within `timeout`
+pub(crate) async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    connect: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(timeout) = timeout else {
+        return connect.await;
+    };
+    match future::select(pin!(connect), Timer::after(timeout)).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(err::Driver::ConnectTimeout(timeout).into()),
+    }
+}
+
+/// Create a session over an already connected stream
+///
+/// Use it when the driver should not open the connection itself, e.g. to
+/// go through an SSH tunnel. `host`, `port` and `hosts` of the options
+/// are ignored, except for the host being the default name of the server
+/// over TLS.
+/// The [timeout](Options::timeout) covers the handshake.
+///
+/// ## Example
+///
+/// ```
+/// use unreql::cmd::connect::{self, Options, TcpStream};
+///
+/// # async fn example() -> unreql::Result<()> {
+/// let stream = TcpStream::connect("127.0.0.1:28016").await?;
+/// let session = connect::with_stream(stream, Options::new().db("marvel")).await?;
+/// # Ok(()) }
+/// ```
+pub async fn with_stream(stream: TcpStream, options: Options) -> Result<Session> {
+    with_timeout(options.timeout, open(stream, options)).await
+}
+
+// Opens a session over a connected stream, without timeout
+pub(crate) async fn open(stream: TcpStream, options: Options) -> Result<Session> {
+    let stream = establish(stream, &options).await?;
+    Ok(Session {
+        inner: Arc::new(session(stream, options)),
+    })
+}
+
+// Does the handshakes over a connected stream, TLS first if enabled
+async fn establish(stream: TcpStream, options: &Options) -> Result<Stream> {
+    #[cfg(feature = "tls")]
+    if let Some(tls) = &options.tls {
+        trace!("starting the TLS handshake");
+        let stream = tls.connect(stream, &options.host).await?;
+        return Ok(handshake(stream, options).await?.into());
+    }
+    Ok(handshake(stream, options).await?.into())
+}
+
+fn session(stream: Stream, options: Options) -> InnerSession {
+    let mut inner = InnerSession::new(stream, options.db, EventLog::new(options.event_log_size));
+    inner.db_required = options.default_db_required;
+    inner.warn_unordered = options.warn_unordered;
+    inner
+}
+
 // Performs the actual handshake
 //
 // This method optimises message exchange as suggested in the RethinkDB
 // documentation by sending message 3 right after message 1, without waiting
 // for message 2 first.
-async fn handshake(mut stream: TcpStream, opts: &Options) -> Result<TcpStream> {
+async fn handshake<S>(mut stream: S, opts: &Options) -> Result<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     trace!("sending supported version to RethinkDB");
-    stream
-        .write_all(&(Version::V10 as i32).to_le_bytes())
-        .await?; // message 1
+    write_message(&mut stream, &(Version::V10 as i32).to_le_bytes()).await?; // message 1
 
     let scram = ScramClient::new(opts.user.as_ref(), opts.password.as_ref(), None);
     let (scram, msg) = client_first(scram)?;
     trace!("sending client first message");
-    stream.write_all(&msg).await?; // message 3
+    write_message(&mut stream, &msg).await?; // message 3
 
-    let mut buf = [0u8; BUF_SIZE];
+    let mut messages = Messages::default();
 
     trace!("receiving message(s) from RethinkDB");
-    let _ = stream.read(&mut buf).await?; // message 2
-    let (len, resp) = bytes(&buf, 0);
+    let resp = messages.next(&mut stream).await?; // message 2
     trace!(
         "received server info; info: {}",
-        crate::tools::bytes_to_string(resp)
+        crate::tools::bytes_to_string(&resp)
     );
-    ServerInfo::validate(resp)?;
-
-    let offset = len + 1;
-    let resp = if offset < BUF_SIZE && buf[offset] != NULL_BYTE {
-        bytes(&buf, offset).1
-    } else {
-        trace!("reading auth response");
-        let _ = stream.read(&mut buf).await?; // message 4
-        bytes(&buf, 0).1
-    };
+    ServerInfo::validate(&resp, opts.compatibility)?;
+
+    trace!("reading auth response");
+    let resp = messages.next(&mut stream).await?; // message 4
     trace!("received auth response");
-    let info = AuthResponse::from_slice(resp)?;
+    let info = AuthResponse::from_slice(&resp)?;
     let auth = match info.authentication {
         Some(auth) => auth,
         None => {
@@ -163,59 +440,89 @@ async fn handshake(mut stream: TcpStream, opts: &Options) -> Result<TcpStream> {
 
     let (scram, msg) = client_final(scram, &auth)?;
     trace!("sending client final message");
-    stream.write_all(&msg).await?; // message 5
+    write_message(&mut stream, &msg).await?; // message 5
 
     trace!("reading server final message");
-    let _ = stream.read(&mut buf).await?; // message 6
-    let resp = bytes(&buf, 0).1;
+    let resp = messages.next(&mut stream).await?; // message 6
     trace!("received server final message");
-    server_final(scram, resp)?;
+    server_final(scram, &resp)?;
 
     trace!("client connected successfully");
 
     Ok(stream)
 }
 
-fn bytes(buf: &[u8], offset: usize) -> (usize, &[u8]) {
-    let len = buf[offset..]
-        .iter()
-        .take_while(|x| **x != NULL_BYTE)
-        .count();
-    let max = offset + len;
-    (max, &buf[offset..max])
+async fn write_message(stream: &mut (impl AsyncWrite + Unpin), msg: &[u8]) -> Result<()> {
+    // `write_all` keeps writing until the whole message is written
+    stream.write_all(msg).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+// Null terminated handshake messages, which may arrive split across reads
+// or several in one read
+#[derive(Default)]
+struct Messages {
+    buf: Vec<u8>,
+}
+
+impl Messages {
+    async fn next(&mut self, stream: &mut (impl AsyncRead + Unpin)) -> Result<Vec<u8>> {
+        loop {
+            if let Some(end) = self.buf.iter().position(|x| *x == NULL_BYTE) {
+                let msg = self.buf[..end].to_vec();
+                self.buf.drain(..=end);
+                return Ok(msg);
+            }
+            let mut chunk = [0u8; BUF_SIZE];
+            let len = stream.read(&mut chunk).await?;
+            if len == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            self.buf.extend_from_slice(&chunk[..len]);
+        }
+    }
 }
 
-// We are going to use &str for `server_version` because it is safe to do so.
-// Unfortunately, the other fields that are using String, are doing so because
-// because they can potentially contain an escaped double quote which is not
-// supported by serde in &str.
+// The first message of the server. Its `server_version` is left out, a
+// RethinkDB-compatible server may send anything there.
 #[derive(Serialize, Deserialize, Debug)]
-struct ServerInfo<'a> {
+struct ServerInfo {
     success: bool,
-    min_protocol_version: usize,
-    max_protocol_version: usize,
-    server_version: &'a str,
+    #[serde(default)]
+    min_protocol_version: Option<usize>,
+    #[serde(default)]
+    max_protocol_version: Option<usize>,
 }
 
-impl ServerInfo<'_> {
-    fn validate(resp: &[u8]) -> Result<()> {
+impl ServerInfo {
+    fn validate(resp: &[u8], compat: CompatMode) -> Result<()> {
         let info = serde_json::from_slice::<ServerInfo>(resp)?;
         if !info.success {
             return Err(err::Runtime::Internal(crate::tools::bytes_to_string(resp)).into());
         }
+        let range = info.min_protocol_version.zip(info.max_protocol_version);
         #[allow(clippy::absurd_extreme_comparisons)]
-        if PROTOCOL_VERSION < info.min_protocol_version
-            || info.max_protocol_version < PROTOCOL_VERSION
-        {
-            let msg = format!(
-                "unsupported protocol version {version}, expected between {min} and {max}",
+        let supported =
+            range.is_some_and(|(min, max)| min <= PROTOCOL_VERSION && PROTOCOL_VERSION <= max);
+        if supported {
+            return Ok(());
+        }
+        if compat == CompatMode::Relaxed {
+            warn!(
+                ?range,
                 version = PROTOCOL_VERSION,
-                min = info.min_protocol_version,
-                max = info.max_protocol_version,
+                "the server may not support the protocol version of the driver"
             );
-            return Err(err::Driver::Other(msg).into());
+            return Ok(());
+        }
+        match range {
+            Some((min, max)) => Err(err::Driver::ProtocolMismatch { min, max }.into()),
+            None => {
+                let msg = String::from("server did not send its protocol versions");
+                Err(err::Driver::Other(msg).into())
+            }
         }
-        Ok(())
     }
 }
 
@@ -247,7 +554,7 @@ fn client_final(scram: ServerFirst<'_>, auth: &str) -> Result<(ServerFinal, Vec<
     let scram = scram
         .handle_server_first(auth)
         .map_err(|x| x.to_string())
-        .map_err(err::Driver::Other)?;
+        .map_err(err::Driver::Auth)?;
     let (scram, client_final) = scram.client_final();
     let conf = AuthConfirmation {
         authentication: client_final,
@@ -270,10 +577,9 @@ impl AuthResponse {
         let info = serde_json::from_slice::<AuthResponse>(resp)?;
         if !info.success {
             // If error code is between 10 and 20, this is an auth error
-            if let Some(10..=20) = info.error_code {
-                if let Some(msg) = info.error {
-                    return Err(err::Driver::Auth(msg).into());
-                }
+            if let Some(code @ 10..=20) = info.error_code {
+                let message = info.error.unwrap_or_default();
+                return Err(err::Driver::AuthFailed { code, message }.into());
             }
             return Err(err::Runtime::Internal(crate::tools::bytes_to_string(resp)).into());
         }
@@ -285,8 +591,325 @@ fn server_final(scram: ServerFinal, resp: &[u8]) -> Result<()> {
     let info = AuthResponse::from_slice(resp)?;
     if let Some(auth) = info.authentication {
         if let Err(error) = scram.handle_server_final(&auth) {
-            return Err(err::Driver::Other(error.to_string()).into());
+            return Err(err::Driver::Auth(error.to_string()).into());
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_server::{self, SERVER_INFO};
+    use crate::{r, Error};
+    use async_net::TcpListener;
+    use std::sync::atomic::Ordering;
+
+    // Connects to a server that sends `replies` byte by byte after reading
+    // the version and the client first message
+    async fn connect(replies: Vec<&'static str>) -> Result<Session> {
+        connect_with(Options::default(), replies).await
+    }
+
+    async fn connect_with(options: Options, replies: Vec<&'static str>) -> Result<Session> {
+        let stream = TcpStream::connect(server(replies).await).await.unwrap();
+        with_stream(stream, options).await
+    }
+
+    async fn server(replies: Vec<&'static str>) -> SocketAddr {
+        fake_server::serve(|mut stream| async move {
+            fake_server::reply(&mut stream, &replies).await;
+            // keep the connection open until the client is done
+            let _ = stream.read(&mut [0u8; 1]).await;
+        })
+        .await
+    }
+
+    fn vars(vars: &'static [(&str, &str)]) -> impl Fn(&str) -> Option<OsString> {
+        |name| {
+            let (_, value) = vars.iter().find(|(var, _)| *var == name)?;
+            Some(value.into())
+        }
+    }
+
+    #[test]
+    fn options_from_vars() {
+        assert_eq!(Options::from_vars(vars(&[])).unwrap(), Options::default());
+
+        let opts = Options::from_vars(vars(&[
+            ("RDB_HOST", "db.local"),
+            ("RDB_PORT", "28016"),
+            ("RDB_DB", "marvel"),
+            ("RDB_USER", "stark"),
+            ("RDB_PASSWORD", "jarvis"),
+        ]))
+        .unwrap();
+        let expected = Options::new()
+            .host("db.local")
+            .port(28016)
+            .db("marvel")
+            .user("stark")
+            .password("jarvis");
+        assert_eq!(opts, expected);
+
+        // explicit options win
+        let opts = Options::from_vars(vars(&[("RDB_DB", "marvel")])).unwrap();
+        assert_eq!(opts.db("dc").db, "dc");
+
+        match Options::from_vars(vars(&[("RDB_PORT", "http")])) {
+            Err(Error::Driver(err::Driver::InvalidEnv { name, value })) => {
+                assert_eq!(name, "RDB_PORT");
+                assert_eq!(value, "http");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn options_from_non_unicode_var() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let var = |_: &str| Some(OsString::from_vec(vec![b'd', 0xff]));
+        match Options::from_vars(var) {
+            Err(Error::Driver(err::Driver::InvalidEnv { name, .. })) => {
+                assert_eq!(name, "RDB_HOST");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn options_from_env() {
+        // the only test of the crate reading or changing these variables
+        std::env::set_var("RDB_DB", "marvel");
+        std::env::remove_var("RDB_PORT");
+        let opts = Options::from_env();
+        std::env::remove_var("RDB_DB");
+        let opts = opts.unwrap();
+        assert_eq!(opts.db, "marvel");
+        assert_eq!(opts.port, 28015);
+    }
+
+    #[tokio::test]
+    async fn wrong_password() {
+        let reject = r#"{"success":false,"error":"Wrong password","error_code":12}"#;
+        match connect(vec![SERVER_INFO, reject]).await {
+            Err(Error::Driver(err::Driver::AuthFailed { code, message })) => {
+                assert_eq!(code, 12);
+                assert_eq!(message, "Wrong password");
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn unsupported_protocol_version() {
+        let info = r#"{"success":true,"min_protocol_version":1,"max_protocol_version":2,"server_version":"9.0.0"}"#;
+        match connect(vec![info]).await {
+            Err(Error::Driver(err::Driver::ProtocolMismatch { min: 1, max: 2 })) => {}
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn other_rejection() {
+        let reject = r#"{"success":false,"error":"Invalid message","error_code":3}"#;
+        match connect(vec![SERVER_INFO, reject]).await {
+            Err(Error::Runtime(err::Runtime::Internal(msg))) => {
+                assert!(msg.contains("Invalid message"));
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    // Whether the handshake went past the server info, to the rejection
+    // of the password sent after it
+    async fn accepts_server_info(options: Options, info: &'static str) -> bool {
+        let reject = r#"{"success":false,"error":"Wrong password","error_code":12}"#;
+        match connect_with(options, vec![info, reject]).await {
+            Err(Error::Driver(err::Driver::AuthFailed { .. })) => true,
+            Err(_) => false,
+            Ok(_) => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn any_server_version() {
+        let info = r#"{"success":true,"min_protocol_version":0,"max_protocol_version":0,"server_version":{"fork":"rebirthdb","version":[2,5]},"build":"dev"}"#;
+        assert!(accepts_server_info(Options::default(), info).await);
+        let info = r#"{"success":true,"min_protocol_version":0,"max_protocol_version":0}"#;
+        assert!(accepts_server_info(Options::default(), info).await);
+    }
+
+    #[tokio::test]
+    async fn relaxed_protocol_versions() {
+        let relaxed = Options::new().compatibility(CompatMode::Relaxed);
+        let missing = r#"{"success":true,"server_version":"fork-1.0"}"#;
+        assert!(!accepts_server_info(Options::default(), missing).await);
+        assert!(accepts_server_info(relaxed.clone(), missing).await);
+
+        let unsupported = r#"{"success":true,"min_protocol_version":1,"max_protocol_version":2,"server_version":"fork-1.0"}"#;
+        assert!(accepts_server_info(relaxed.clone(), unsupported).await);
+
+        // a failure is still a failure
+        let reject = r#"{"success":false,"error":"Invalid message","error_code":3}"#;
+        assert!(!accepts_server_info(relaxed, reject).await);
+    }
+
+    fn timed_out(result: Result<Session>) -> bool {
+        matches!(result, Err(Error::Driver(err::Driver::ConnectTimeout(_))))
+    }
+
+    #[tokio::test]
+    async fn handshake_timeout() {
+        let options = Options::new().timeout(Duration::from_millis(50));
+        // the server never answers the client first message
+        assert!(timed_out(connect_with(options, vec![SERVER_INFO]).await));
+    }
+
+    #[tokio::test]
+    async fn connect_timeout() {
+        // the server accepts the connection and says nothing
+        let addr = fake_server::serve(|mut stream| async move {
+            let _ = stream.read(&mut [0u8; 1024]).await;
+            let _ = stream.read(&mut [0u8; 1024]).await;
+        })
+        .await;
+        let options = Options::new().timeout(Duration::from_millis(50));
+        assert!(timed_out(new((Some(addr), options)).await));
+    }
+
+    // A host refusing connections
+    async fn dead_host() -> (String, u16) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        ("127.0.0.1".into(), port)
+    }
+
+    // A host rejecting the password
+    async fn live_host() -> (String, u16) {
+        let reject = r#"{"success":false,"error":"Wrong password","error_code":12}"#;
+        let addr = server(vec![SERVER_INFO, reject]).await;
+        (addr.ip().to_string(), addr.port())
+    }
+
+    fn rejected(result: Result<Session>) -> bool {
+        matches!(result, Err(Error::Driver(err::Driver::AuthFailed { .. })))
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_the_next_host() {
+        let hosts = vec![dead_host().await, dead_host().await, live_host().await];
+        assert!(rejected(r.connect(Options::new().hosts(hosts)).await));
+
+        // from the host at `start`, around the list
+        let hosts = vec![live_host().await, dead_host().await];
+        assert!(rejected(
+            connect_hosts(Options::new().hosts(hosts), 1).await
+        ));
+    }
+
+    #[tokio::test]
+    async fn fails_with_the_error_of_the_last_host() {
+        let hosts = vec![dead_host().await, dead_host().await];
+        match r.connect(Options::new().hosts(hosts)).await {
+            Err(Error::Driver(err::Driver::Io(kind, _))) => {
+                assert_eq!(kind, std::io::ErrorKind::ConnectionRefused);
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn only_unreachable_hosts_are_skipped() {
+        // the dead host is not tried after the rejection
+        let hosts = vec![live_host().await, dead_host().await];
+        assert!(rejected(r.connect(Options::new().hosts(hosts)).await));
+    }
+
+    // A server answering the queries of its n-th connection with `n`,
+    // which drops the connection instead of answering the query after
+    // the first `queries`
+    async fn rethinkdb(queries: usize) -> Options {
+        fake_server::rethinkdb(move |n, mut stream| async move {
+            for _ in 0..queries {
+                let Some((token, _)) = fake_server::next_query(&mut stream).await else {
+                    return;
+                };
+                fake_server::answer(&mut stream, token, n as u32).await;
+            }
+            // drops the connection once the next query is read
+            fake_server::next_query(&mut stream).await;
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn reconnect() {
+        let session = r.connect(rethinkdb(1).await).await.unwrap();
+        assert_eq!(r.expr(0).exec::<u32>(&session).await.unwrap(), 1);
+        let before = session.connection().unwrap();
+        assert!(r.expr(0).exec::<u32>(&session).await.is_err());
+        assert!(session.is_broken());
+
+        session.reconnect().await.unwrap();
+        assert!(!session.is_broken());
+        assert_eq!(r.expr(0).exec::<u32>(&session).await.unwrap(), 2);
+        match r.expr(0).exec::<u32>(before).await {
+            Err(Error::Driver(err::Driver::Reconnected)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn auto_reconnect() {
+        let session = r
+            .connect(rethinkdb(1).await.auto_reconnect(true))
+            .await
+            .unwrap();
+        let mut conn = session.connection().unwrap();
+        assert_eq!(r.expr(0).exec::<u32>(&mut conn).await.unwrap(), 1);
+        // the query was sent, it is not sent again
+        assert!(r.expr(0).exec::<u32>(&mut conn).await.is_err());
+        assert_eq!(r.expr(0).exec::<u32>(&mut conn).await.unwrap(), 2);
+        assert_eq!(session.inner.epoch(), 1);
+
+        // not after `close`
+        session
+            .close(crate::cmd::close::SkipNoreplyWait)
+            .await
+            .unwrap();
+        match r.expr(0).exec::<u32>(&session).await {
+            Err(Error::Driver(err::Driver::ConnectionBroken)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnects_to_the_last_good_host() {
+        let options = rethinkdb(0).await;
+        let live = (options.host.to_string(), options.port);
+        let session = r
+            .connect(Options::new().hosts(vec![dead_host().await, live]))
+            .await
+            .unwrap();
+        assert_eq!(session.inner.host.load(Ordering::SeqCst), 1);
+        session.reconnect().await.unwrap();
+        assert_eq!(session.inner.host.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn session_over_a_stream_cannot_reconnect() {
+        let options = rethinkdb(0).await;
+        let stream = TcpStream::connect((options.host.as_ref(), options.port))
+            .await
+            .unwrap();
+        let session = with_stream(stream, options).await.unwrap();
+        match session.reconnect().await {
+            Err(Error::Driver(err::Driver::Other(msg))) => assert!(msg.contains("reconnect")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,436 @@
+//! Look ahead in query results
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_io::Timer;
+use futures::future::{self, Either};
+use futures::stream::{self, Fuse, FusedStream, Stream, StreamExt};
+
+use crate::types::Change;
+use crate::Result;
+
+/// A stream of query results that can look at the next one without
+/// consuming it
+///
+/// Returned by [Command::cursor](crate::Command::cursor), or wrap any
+/// result stream with [Cursor::new]. The cursor is itself a [Stream] and
+/// a [FusedStream], so the `futures` adapters and `select!` work on it.
+///
+/// ## Example
+///
+/// ```
+/// # use unreql::r;
+/// # use futures::TryStreamExt;
+/// # use serde_json::Value;
+/// # async fn example(conn: unreql::Session) -> unreql::Result<()> {
+/// let mut cursor = r.table("events").cursor::<Value>(&conn);
+/// while let Some(event) = cursor.peek().await? {
+///     if event["type"] == "batch" {
+///         // handle the batch header and the items following it
+///     }
+///     cursor.try_next().await?;
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct Cursor<S: Stream> {
+    stream: Fuse<S>,
+    peeked: Option<S::Item>,
+}
+
+impl<S, T> Cursor<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+{
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream: stream.fuse(),
+            peeked: None,
+        }
+    }
+
+    /// Return the next result without removing it from the cursor
+    ///
+    /// The result is buffered and returned again by the following `peek`
+    /// or `next`. An error is buffered too, so `next` returns it after
+    /// `peek` did.
+    pub async fn peek<'a>(&'a mut self) -> Result<Option<&'a T>>
+    where
+        T: 'a,
+    {
+        if self.peeked.is_none() {
+            self.peeked = self.stream.next().await;
+        }
+        match &self.peeked {
+            Some(Ok(item)) => Ok(Some(item)),
+            Some(Err(error)) => Err(error.clone()),
+            None => Ok(None),
+        }
+    }
+
+    /// Wait at most `timeout` for the next result
+    ///
+    /// Returns `Ok(None)` if no result arrived in time, e.g. a changefeed
+    /// was idle. The cursor keeps going and the result is returned by a
+    /// later call. `Ok(None)` is also returned once the cursor is
+    /// exhausted, tell them apart with [is_terminated](Self::is_terminated).
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use unreql::r;
+    /// # use serde_json::Value;
+    /// # async fn example(conn: unreql::Session) -> unreql::Result<()> {
+    /// let mut feed = r.table("games").changes(()).cursor::<Value>(&conn);
+    /// while !feed.is_terminated() {
+    ///     match feed.try_next_timeout(Duration::from_secs(30)).await? {
+    ///         Some(change) => println!("{change}"),
+    ///         None => println!("no changes for 30 seconds"),
+    ///     }
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub async fn try_next_timeout(&mut self, timeout: Duration) -> Result<Option<T>> {
+        if let Some(item) = self.peeked.take() {
+            return item.map(Some);
+        }
+        // dropping `next` keeps the stream where it is
+        match future::select(self.stream.next(), Timer::after(timeout)).await {
+            Either::Left((item, _)) => item.transpose(),
+            Either::Right(_) => Ok(None),
+        }
+    }
+
+    /// Whether all the results were returned
+    pub fn is_terminated(&self) -> bool {
+        self.peeked.is_none() && self.stream.is_done()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+}
+
+impl<S, O, N> Cursor<S>
+where
+    S: Stream<Item = Result<Change<O, N>>> + Unpin,
+{
+    /// Keep only the additions, changes and removals of a changefeed
+    /// that are included
+    ///
+    /// The kind of a change is its `type`, so run the feed with
+    /// `include_types`. Without it the kind is inferred from `old_val` and
+    /// `new_val`. Other items, such as states, initial values and errors,
+    /// are always kept.
+    ///
+    /// ```
+    /// # use unreql::r;
+    /// # use unreql::cmd::options::ChangesOptions;
+    /// # use unreql::types::Change;
+    /// # async fn example(conn: unreql::Session) {
+    /// let opts = ChangesOptions::new().include_types(true);
+    /// let deletions = r
+    ///     .table("users")
+    ///     .changes(opts)
+    ///     .cursor::<Change>(&conn)
+    ///     .changes_filtered(false, false, true);
+    /// # }
+    /// ```
+    pub fn changes_filtered(
+        self,
+        include_add: bool,
+        include_change: bool,
+        include_remove: bool,
+    ) -> Cursor<impl Stream<Item = Result<Change<O, N>>> + Unpin> {
+        let Self { stream, peeked } = self;
+        let changes = stream::iter(peeked).chain(stream).filter(move |item| {
+            let keep = match item {
+                Ok(change) => match change_kind(change) {
+                    Some(ChangeKind::Add) => include_add,
+                    Some(ChangeKind::Change) => include_change,
+                    Some(ChangeKind::Remove) => include_remove,
+                    None => true,
+                },
+                Err(_) => true,
+            };
+            future::ready(keep)
+        });
+        Cursor::new(Box::pin(changes))
+    }
+}
+
+impl<S, T> Cursor<S>
+where
+    S: Stream<Item = Result<Change<T>>> + Unpin,
+{
+    /// Skip the changes of a changefeed that leave the value of their key
+    /// as it was
+    ///
+    /// The latest value of every key, read from the documents by `key`,
+    /// is kept to compare the next changes with. A change is skipped if
+    /// its new value equals the latest one of its key, or if it removes a
+    /// key that is already removed. The server only squashes the changes
+    /// of a short time window, with the `squash` option, while this
+    /// remembers every key the feed sent, so it suits feeds of a bounded
+    /// set of documents. Other items, such as states and errors, are
+    /// always kept.
+    ///
+    /// ```
+    /// # use unreql::r;
+    /// # use unreql::types::Change;
+    /// # use serde::Deserialize;
+    /// #[derive(Debug, Clone, PartialEq, Deserialize)]
+    /// struct Player {
+    ///     id: u32,
+    ///     score: u32,
+    /// }
+    ///
+    /// # async fn example(conn: unreql::Session) {
+    /// let scores = r
+    ///     .table("players")
+    ///     .changes(())
+    ///     .cursor::<Change<Player>>(&conn)
+    ///     .changes_latest_by(|player| player.id);
+    /// # }
+    /// ```
+    pub fn changes_latest_by<K, F>(
+        self,
+        mut key: F,
+    ) -> Cursor<impl Stream<Item = Result<Change<T>>> + Unpin>
+    where
+        F: FnMut(&T) -> K,
+        K: Eq + Hash,
+        T: Clone + PartialEq,
+    {
+        // the latest value of each key, `None` once removed
+        let mut latest: HashMap<K, Option<T>> = HashMap::new();
+        let Self { stream, peeked } = self;
+        let changes = stream::iter(peeked).chain(stream).filter(move |item| {
+            let keep = match item {
+                Ok(change) => match (change.old_value(), change.new_value()) {
+                    (_, Some(new)) => match latest.insert(key(new), Some(new.clone())) {
+                        Some(Some(previous)) => previous != *new,
+                        _ => true,
+                    },
+                    (Some(old), None) => !matches!(latest.insert(key(old), None), Some(None)),
+                    (None, None) => true,
+                },
+                Err(_) => true,
+            };
+            future::ready(keep)
+        });
+        Cursor::new(Box::pin(changes))
+    }
+}
+
+enum ChangeKind {
+    Add,
+    Change,
+    Remove,
+}
+
+fn change_kind<O, N>(change: &Change<O, N>) -> Option<ChangeKind> {
+    match change.result_type.as_deref() {
+        Some("add") => Some(ChangeKind::Add),
+        Some("change") => Some(ChangeKind::Change),
+        Some("remove") => Some(ChangeKind::Remove),
+        Some(_) => None,
+        None if change.state.is_some() => None,
+        None => match (change.old_value(), change.new_value()) {
+            (None, Some(_)) => Some(ChangeKind::Add),
+            (Some(_), Some(_)) => Some(ChangeKind::Change),
+            (Some(_), None) => Some(ChangeKind::Remove),
+            (None, None) => None,
+        },
+    }
+}
+
+impl<S, T> Stream for Cursor<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    T: Unpin,
+{
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(item) = self.peeked.take() {
+            return Poll::Ready(Some(item));
+        }
+        self.stream.poll_next_unpin(cx)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let peeked = usize::from(self.peeked.is_some());
+        let (lower, upper) = self.stream.size_hint();
+        (
+            lower.saturating_add(peeked),
+            upper.and_then(|upper| upper.checked_add(peeked)),
+        )
+    }
+}
+
+impl<S, T> FusedStream for Cursor<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    T: Unpin,
+{
+    fn is_terminated(&self) -> bool {
+        Cursor::is_terminated(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+
+    #[tokio::test]
+    async fn peek_does_not_consume() {
+        let mut cursor = Cursor::new(stream::iter(vec![Ok(1), Ok(2)]));
+        assert_eq!(cursor.peek().await.unwrap(), Some(&1));
+        assert_eq!(cursor.peek().await.unwrap(), Some(&1));
+        assert_eq!(cursor.try_next().await.unwrap(), Some(1));
+        assert_eq!(cursor.peek().await.unwrap(), Some(&2));
+        assert_eq!(cursor.try_next().await.unwrap(), Some(2));
+        assert_eq!(cursor.peek().await.unwrap(), None);
+        assert_eq!(cursor.try_next().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn stream_adapters() {
+        let mut cursor = Cursor::new(stream::iter(vec![Ok(1), Ok(2), Ok(3)]));
+        assert_eq!(cursor.peek().await.unwrap(), Some(&1));
+        assert_eq!(cursor.size_hint(), (3, Some(3)));
+        let doubled: Vec<_> = (&mut cursor)
+            .take(2)
+            .map_ok(|n| n * 2)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(doubled, [2, 4]);
+        assert!(!FusedStream::is_terminated(&cursor));
+        while let Some(n) = cursor.next().await {
+            assert_eq!(n.unwrap(), 3);
+        }
+        assert!(FusedStream::is_terminated(&cursor));
+    }
+
+    fn change(value: serde_json::Value) -> Result<Change> {
+        Ok(serde_json::from_value(value).unwrap())
+    }
+
+    fn feed() -> Vec<Result<Change>> {
+        vec![
+            change(serde_json::json!({"state": "ready"})),
+            change(serde_json::json!({"new_val": {"id": 1}, "type": "add"})),
+            change(
+                serde_json::json!({"old_val": {"id": 1}, "new_val": {"id": 1, "a": 1}, "type": "change"}),
+            ),
+            change(serde_json::json!({"old_val": {"id": 1}, "type": "remove"})),
+            change(serde_json::json!({"new_val": {"id": 2}, "type": "initial"})),
+        ]
+    }
+
+    #[tokio::test]
+    async fn filters_by_type() {
+        let cursor = Cursor::new(stream::iter(feed())).changes_filtered(false, false, true);
+        let changes: Vec<_> = cursor.try_collect().await.unwrap();
+        let types: Vec<_> = changes.iter().map(|x| x.result_type.as_deref()).collect();
+        assert_eq!(types, [None, Some("remove"), Some("initial")]);
+    }
+
+    #[tokio::test]
+    async fn infers_kind_without_types() {
+        let feed = vec![
+            change(serde_json::json!({"new_val": {"id": 1}})),
+            change(serde_json::json!({"old_val": {"id": 1}, "new_val": {"id": 1}})),
+            change(serde_json::json!({"old_val": {"id": 1}, "new_val": null})),
+        ];
+        let cursor = Cursor::new(stream::iter(feed)).changes_filtered(true, false, false);
+        let changes: Vec<_> = cursor.try_collect().await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].old_val.is_absent());
+    }
+
+    #[tokio::test]
+    async fn keeps_peeked_change() {
+        let mut cursor = Cursor::new(stream::iter(feed()));
+        cursor.try_next().await.unwrap();
+        assert!(cursor.peek().await.unwrap().is_some());
+        let cursor = cursor.changes_filtered(true, true, false);
+        let changes: Vec<_> = cursor.try_collect().await.unwrap();
+        let types: Vec<_> = changes.iter().map(|x| x.result_type.as_deref()).collect();
+        assert_eq!(types, [Some("add"), Some("change"), Some("initial")]);
+    }
+
+    #[tokio::test]
+    async fn latest_by_key() {
+        let feed = vec![
+            change(serde_json::json!({"state": "ready"})),
+            change(serde_json::json!({"new_val": {"id": 1, "a": 1}})),
+            change(serde_json::json!({"new_val": {"id": 2, "a": 1}})),
+            // no-op update of 1
+            change(serde_json::json!({"old_val": {"id": 1, "a": 1}, "new_val": {"id": 1, "a": 1}})),
+            change(serde_json::json!({"old_val": {"id": 1, "a": 1}, "new_val": {"id": 1, "a": 2}})),
+            change(serde_json::json!({"old_val": {"id": 2, "a": 1}, "new_val": null})),
+            // 2 is already removed
+            change(serde_json::json!({"old_val": {"id": 2, "a": 1}, "new_val": null})),
+            change(serde_json::json!({"new_val": {"id": 2, "a": 1}})),
+            Err(crate::Driver::ConnectionBroken.into()),
+        ];
+        let mut cursor = Cursor::new(stream::iter(feed));
+        cursor.peek().await.unwrap();
+        let cursor = cursor.changes_latest_by(|doc| doc["id"].as_u64());
+        let kept: Vec<_> = cursor
+            .map(|item| {
+                let change = item.ok()?;
+                Some((change.old_val.into_option(), change.new_val.into_option()))
+            })
+            .collect()
+            .await;
+        let doc = |id: u32, a: u32| Some(serde_json::json!({"id": id, "a": a}));
+        assert_eq!(
+            kept,
+            [
+                Some((None, None)),
+                Some((None, doc(1, 1))),
+                Some((None, doc(2, 1))),
+                Some((doc(1, 1), doc(1, 2))),
+                Some((doc(2, 1), None)),
+                Some((None, doc(2, 1))),
+                None,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn next_timeout_keeps_the_stream() {
+        let (tx, rx) = futures::channel::mpsc::unbounded::<Result<u32>>();
+        let mut cursor = Cursor::new(rx);
+        let timeout = Duration::from_millis(10);
+        assert_eq!(cursor.try_next_timeout(timeout).await.unwrap(), None);
+        assert!(!cursor.is_terminated());
+
+        tx.unbounded_send(Ok(1)).unwrap();
+        assert_eq!(cursor.try_next_timeout(timeout).await.unwrap(), Some(1));
+        tx.unbounded_send(Ok(2)).unwrap();
+        assert_eq!(cursor.peek().await.unwrap(), Some(&2));
+        assert_eq!(cursor.try_next_timeout(timeout).await.unwrap(), Some(2));
+
+        drop(tx);
+        assert_eq!(cursor.try_next_timeout(timeout).await.unwrap(), None);
+        assert!(cursor.is_terminated());
+    }
+
+    #[tokio::test]
+    async fn peeked_error_is_returned_by_next() {
+        let items: Vec<Result<u32>> = vec![Err(crate::Driver::ConnectionBroken.into())];
+        let mut cursor = Cursor::new(stream::iter(items));
+        assert!(cursor.peek().await.is_err());
+        assert!(cursor.try_next().await.is_err());
+        assert_eq!(cursor.peek().await.unwrap(), None);
+    }
+}
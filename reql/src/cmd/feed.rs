@@ -0,0 +1,158 @@
+//! Stop a changefeed and keep using its session
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::stream::{Fuse, FusedStream, Stream, StreamExt};
+use ql2::query::QueryType;
+
+use crate::proto::Payload;
+use crate::{Connection, Result};
+
+/// A changefeed that can be stopped without closing its session
+///
+/// Returned by [Command::run_feed](crate::Command::run_feed). It is a
+/// [Stream] of the changes, and [stop](Self::stop) ends the changefeed on
+/// the server. Dropping the handle instead does not tell the server
+/// right away.
+///
+/// ## Example
+///
+/// ```
+/// # use unreql::r;
+/// # use futures::TryStreamExt;
+/// # use serde_json::Value;
+/// # async fn example(conn: unreql::Session) -> unreql::Result<()> {
+/// let mut feed = r.table("games").changes(()).run_feed::<Value>(&conn).await?;
+/// while let Some(change) = feed.try_next().await? {
+///     if change["new_val"]["finished"] == true {
+///         break;
+///     }
+/// }
+/// feed.stop().await?;
+/// let games: u64 = r.table("games").count(()).exec(&conn).await?;
+/// # Ok(()) }
+/// ```
+#[derive(Debug)]
+pub struct FeedHandle<S> {
+    stream: Fuse<S>,
+    conn: Connection,
+}
+
+impl<S, T> FeedHandle<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+{
+    pub(crate) fn new(stream: S, conn: Connection) -> Self {
+        Self {
+            stream: stream.fuse(),
+            conn,
+        }
+    }
+
+    /// Stop the changefeed
+    ///
+    /// Sends the `STOP` query of the protocol for the changefeed and
+    /// resolves once the server answered it. The changes received but not
+    /// read yet are dropped. The session can run other queries right
+    /// after, there is no need to [close](Connection::close) it.
+    pub async fn stop(self) -> Result<()> {
+        let Self { stream, mut conn } = self;
+        let done = stream.is_done();
+        // the response to a `CONTINUE` still waiting for changes is
+        // discarded when it arrives
+        drop(stream);
+        if !done {
+            let payload = Payload(QueryType::Stop, None, Default::default());
+            conn.request(&payload, false).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<S, T> Stream for FeedHandle<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    T: Unpin,
+{
+    type Item = Result<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.stream.poll_next_unpin(cx)
+    }
+}
+
+impl<S, T> FusedStream for FeedHandle<S>
+where
+    S: Stream<Item = Result<T>> + Unpin,
+    T: Unpin,
+{
+    fn is_terminated(&self) -> bool {
+        self.stream.is_done()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fake_server::{self, read_query, send};
+    use crate::{r, Session};
+    use futures::TryStreamExt;
+    use serde_json::{json, Value};
+    use std::time::Duration;
+
+    // A session to a server sending one change, answering the `CONTINUE`
+    // of the feed only once it is stopped, then the value of an `r.expr`
+    async fn session(continued: bool) -> Session {
+        fake_server::scripted(move |mut stream| async move {
+            let (feed, _) = read_query(&mut stream).await;
+            send(&mut stream, feed, r#"{"t":3,"r":[{"new_val":1}]}"#).await;
+            if continued {
+                let (_, query) = read_query(&mut stream).await;
+                assert_eq!(query, json!([2]));
+            }
+            let (token, query) = read_query(&mut stream).await;
+            assert_eq!((token, query), (feed, json!([3])));
+            if continued {
+                send(&mut stream, feed, r#"{"t":2,"r":[]}"#).await;
+            }
+            send(&mut stream, feed, r#"{"t":2,"r":[]}"#).await;
+            let (other, query) = read_query(&mut stream).await;
+            let body = json!({"t": 1, "r": [query[1]]}).to_string();
+            send(&mut stream, other, &body).await;
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn stop_between_batches() {
+        let session = session(false).await;
+        let mut feed = r
+            .table("t")
+            .changes(())
+            .run_feed::<Value>(&session)
+            .await
+            .unwrap();
+        let change = feed.try_next().await.unwrap().unwrap();
+        assert_eq!(change, json!({"new_val": 1}));
+        feed.stop().await.unwrap();
+        assert_eq!(r.expr(7).exec::<u8>(&session).await.unwrap(), 7);
+        assert!(session.inner.channels.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stop_while_waiting_for_changes() {
+        let session = session(true).await;
+        let mut feed = r
+            .table("t")
+            .changes(())
+            .run_feed::<Value>(&session)
+            .await
+            .unwrap();
+        feed.try_next().await.unwrap();
+        let next = tokio::time::timeout(Duration::from_millis(50), feed.try_next());
+        assert!(next.await.is_err());
+        feed.stop().await.unwrap();
+        assert_eq!(r.expr(7).exec::<u8>(&session).await.unwrap(), 7);
+        assert!(session.inner.channels.is_empty());
+    }
+}
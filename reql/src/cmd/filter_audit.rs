@@ -0,0 +1,107 @@
+//! Count the documents a filter skips because they lack fields
+//!
+//! See [Command::filter_audit].
+
+use futures::future;
+use ql2::term::TermType;
+use serde::de::DeserializeOwned;
+use tracing::warn;
+
+use crate::cmd::run;
+use crate::{r, Command, Result};
+
+/// A filter together with the count of documents missing the fields
+/// its predicate reads
+///
+/// Created by [Command::filter_audit].
+#[derive(Debug, Clone)]
+pub struct FilterAudit {
+    filtered: Command,
+    missing: Command,
+    fields: Vec<String>,
+}
+
+/// The results of [FilterAudit::exec_filtered_with_audit]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Audited<T> {
+    /// The documents matching the predicate
+    pub items: Vec<T>,
+    /// How many documents of the sequence lack at least one of the fields
+    pub missing: u64,
+}
+
+impl FilterAudit {
+    pub(crate) fn new(sequence: Command, predicate: Command, fields: Vec<String>) -> Self {
+        // not `.not()`, which is left out without the `math-logic` feature
+        let lacking =
+            Command::new(TermType::Not).with_parent(r.row().has_fields(r.args(fields.clone())));
+        let missing = sequence.clone().filter(lacking).count(());
+        Self {
+            filtered: sequence.filter(predicate),
+            missing,
+            fields,
+        }
+    }
+
+    /// The filter query
+    pub fn query(&self) -> Command {
+        self.filtered.clone()
+    }
+
+    /// The query counting the documents missing a field
+    pub fn missing_query(&self) -> Command {
+        self.missing.clone()
+    }
+
+    /// Run the filter and the count at the same time
+    ///
+    /// The two queries are not run in a transaction, writes made between
+    /// them are seen by only one of them. A warning is logged with
+    /// `tracing` if documents were skipped.
+    pub async fn exec_filtered_with_audit<T>(self, arg: impl run::Arg + Clone) -> Result<Audited<T>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        let Self {
+            filtered,
+            missing,
+            fields,
+        } = self;
+        let (items, missing) = future::try_join(
+            filtered.exec_to_vec::<T>(arg.clone()),
+            missing.exec::<u64>(arg),
+        )
+        .await?;
+        if missing > 0 {
+            warn!(missing, ?fields, "filter skipped documents missing fields");
+        }
+        Ok(Audited { items, missing })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_server::answering;
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn items_and_missing_count() {
+        let audit = r.table("users").filter_audit(r.row().g("adult"), ["adult"]);
+        let session = answering(vec![
+            (
+                audit.query(),
+                r#"{"t":2,"r":[{"id":1,"adult":true},{"id":2,"adult":true}]}"#,
+            ),
+            (audit.missing_query(), r#"{"t":1,"r":[3]}"#),
+        ])
+        .await;
+        let Audited { items, missing } = audit
+            .exec_filtered_with_audit::<Value>(&session)
+            .await
+            .unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(missing, 3);
+    }
+}
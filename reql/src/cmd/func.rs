@@ -1,7 +1,33 @@
-use crate::Command;
+use std::collections::HashMap;
+
+use crate::proto::Datum;
+use crate::{err, Command, Result};
 use ql2::term::TermType;
+use serde::{Serialize, Serializer};
+use serde_json::Number;
 
-#[derive(Debug)]
+/// An anonymous ReQL function
+///
+/// A function built once, e.g. a validation predicate, can be stored and
+/// used in many queries. Get one from [func!](crate::func) with
+/// [TryFrom], then pass clones of it as arguments:
+///
+/// ```
+/// # use unreql::{func, r, Func};
+/// # #[cfg(feature = "math-logic")]
+/// # fn example() -> unreql::Result<()> {
+/// let adult = Func::try_from(func!(|user| user.g("age").ge(18)))?;
+/// let users = r.table("users").filter(adult.clone());
+/// let admins = r.table("admins").filter(adult);
+/// # Ok(()) }
+/// ```
+///
+/// Each time a `Func` is passed as an argument its variables, and those
+/// of the functions nested in it, are renumbered. The same function can
+/// then be nested in itself, e.g. in a subquery, without its variables
+/// shadowing each other. Serializing a `Func` with serde directly does
+/// not renumber them.
+#[derive(Debug, Clone)]
 pub struct Func(pub(crate) Command);
 
 impl Func {
@@ -16,10 +42,52 @@ impl Func {
         )
     }
 
+    /// Build a function of `N` arguments from a closure over its variables
+    ///
+    /// This is what [func!](crate::func) expands to.
+    #[doc(hidden)]
+    pub fn build<const N: usize, T>(body: impl FnOnce([Command; N]) -> T) -> Command
+    where
+        T: Into<Command>,
+    {
+        // only calling the closure is specific to a `func!` call site, the
+        // variables are created by functions shared by all of them
+        let (ids, vars) = vars::<N>();
+        build_func(ids, body(vars).into())
+    }
+
     pub fn into_cmd(self) -> Command {
         self.0
     }
 
+    /// A function passing the result of this function to `other`
+    ///
+    /// `f.compose(g)` takes the arguments of `f` and returns `g(f(args))`,
+    /// so `other` must take one argument.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{func, r, Func};
+    /// # #[cfg(feature = "math-logic")]
+    /// # fn example() -> unreql::Result<()> {
+    /// let age = Func::try_from(func!(|user| user.g("age")))?;
+    /// let adult = Func::try_from(func!(|age| age.ge(18)))?;
+    /// let query = r.table("users").filter(age.compose(adult));
+    /// # Ok(()) }
+    /// ```
+    pub fn compose(self, other: Func) -> Func {
+        let ids = var_ids(self.arity());
+        let inner = ids.iter().fold(
+            Command::new(TermType::Funcall).with_arg(self.rebound()),
+            |cmd, id| cmd.with_arg(Command::var(*id)),
+        );
+        let outer = Command::new(TermType::Funcall)
+            .with_arg(other.rebound())
+            .with_arg(inner);
+        Self::new(ids, outer)
+    }
+
     #[allow(dead_code)]
     pub(crate) fn row<T>(body: T) -> Self
     where
@@ -27,4 +95,106 @@ impl Func {
     {
         Self::new(vec![0], body)
     }
+
+    // The function with new variables
+    pub(crate) fn rebound(&self) -> Command {
+        let mut cmd = self.0.clone();
+        rebind(&mut cmd, &mut HashMap::new());
+        cmd
+    }
+
+    fn arity(&self) -> usize {
+        match self.0.args().front().map(Command::datum) {
+            Some(Some(Ok(Datum::Array(params)))) => params.len(),
+            _ => 0,
+        }
+    }
+}
+
+impl TryFrom<Command> for Func {
+    type Error = err::Error;
+
+    /// Fails if `cmd` is not a function, expressions using
+    /// [r.row](crate::r::row) are turned into one.
+    fn try_from(cmd: Command) -> Result<Self> {
+        let cmd = cmd.wrap_by_func();
+        if cmd.typ() != TermType::Func {
+            return Err(err::Driver::Other("the command is not a function".into()).into());
+        }
+        Ok(Func(cmd))
+    }
+}
+
+impl Serialize for Func {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+fn var_ids(count: usize) -> Vec<u64> {
+    (0..count).map(|_| crate::var_counter()).collect()
+}
+
+// New variables for a function of `N` arguments, with their ids
+fn vars<const N: usize>() -> (Vec<u64>, [Command; N]) {
+    let ids = var_ids(N);
+    let vars: Vec<_> = ids.iter().map(|id| Command::var(*id)).collect();
+    match vars.try_into() {
+        Ok(vars) => (ids, vars),
+        Err(_) => unreachable!("one variable per argument"),
+    }
+}
+
+fn build_func(ids: Vec<u64>, body: Command) -> Command {
+    Func::new(ids, body).into_cmd()
+}
+
+// Gives new ids to the parameters of the functions in `cmd` and to the
+// variables referring to them
+fn rebind(cmd: &mut Command, ids: &mut HashMap<u64, u64>) {
+    let typ = cmd.typ();
+    if let (TermType::Func | TermType::Var, Some(first)) = (typ, cmd.mut_args().front_mut()) {
+        if let Some(Ok(datum)) = first.mut_datum() {
+            match (typ, datum) {
+                (TermType::Func, Datum::Array(params)) => {
+                    for param in params {
+                        if let Datum::Number(id) = param {
+                            let new = crate::var_counter();
+                            if let Some(old) = id.as_u64() {
+                                ids.insert(old, new);
+                            }
+                            *id = Number::from(new);
+                        }
+                    }
+                }
+                (TermType::Var, Datum::Number(id)) => {
+                    if let Some(new) = id.as_u64().and_then(|old| ids.get(&old)) {
+                        *id = Number::from(*new);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    for arg in cmd.mut_args() {
+        rebind(arg, ids);
+    }
+    if let Some(Ok(datum)) = cmd.mut_datum() {
+        rebind_datum(datum, ids);
+    }
+    if let Some(Ok(datum)) = cmd.mut_opts() {
+        rebind_datum(datum, ids);
+    }
+}
+
+fn rebind_datum(datum: &mut Datum, ids: &mut HashMap<u64, u64>) {
+    match datum {
+        Datum::Command(cmd) => rebind(cmd, ids),
+        Datum::Array(arr) => arr.iter_mut().for_each(|datum| rebind_datum(datum, ids)),
+        Datum::Object(obj) => obj.values_mut().for_each(|datum| rebind_datum(datum, ids)),
+        _ => {}
+    }
 }
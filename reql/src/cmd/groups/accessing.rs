@@ -1,26 +1,129 @@
+use std::sync::{Arc, Mutex};
+
 use futures::{stream::Stream, TryStreamExt};
 use ql2::term::TermType;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::warn;
 
 use crate::{
-    cmd::{args::Opt, options::ChangesOptions, run},
-    Command,
+    cmd::{
+        args::{Arg, Args, ManyArgs, Opt},
+        cursor::Cursor,
+        feed::FeedHandle,
+        options::{ChangesOptions, Index},
+        run, validate,
+    },
+    types::QueryStats,
+    Command, Error, Runtime,
 };
 
 impl Command {
     /// Run a query on a connection.
     ///
+    /// The connection argument may be a [Session](crate::Session), a
+    /// [Connection](crate::Connection) or anything else implementing
+    /// [run::Arg]. Its type is always inferred, so only the result type
+    /// is given explicitly, e.g. `run::<User>(&conn)`.
+    ///
+    /// A borrowed `&Connection` runs the query under a new token of its
+    /// session, so there is no need to clone the connection for every query.
+    ///
+    /// ## Example
+    /// Run a query against another database than the default one.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use unreql::cmd::run::Options;
+    /// # use serde_json::Value;
+    /// # use futures::TryStreamExt;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let opts = Options::new().db("tenant_42");
+    /// let users: Vec<Value> = r.table("users").run(r.args((conn, opts))).try_collect().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// ## Example
+    /// Read the results one by one. The stream is `Unpin`, so the
+    /// [StreamExt](futures::StreamExt) and [TryStreamExt] adapters can be
+    /// used on it directly.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use futures::{StreamExt, TryStreamExt};
+    /// # use serde_json::Value;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let mut heroes = r.table("marvel").run::<Value>(conn);
+    /// while let Some(hero) = heroes.next().await {
+    ///     println!("{}", hero?["name"]);
+    /// }
+    ///
+    /// let names: Vec<String> = r.table("marvel")
+    ///   .run::<Value>(conn)
+    ///   .take(10)
+    ///   .map_ok(|hero| hero["name"].to_string())
+    ///   .try_collect()
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
     /// # Related commands
     /// - [exec](Self::exec)
     /// - [exec_to_vec](Self::exec_to_vec)
-    pub fn run<A, T>(self, arg: A) -> impl Stream<Item = crate::Result<T>>
+    /// - [cursor](Self::cursor)
+    pub fn run<T>(self, arg: impl run::Arg) -> impl Stream<Item = crate::Result<T>> + Unpin
     where
-        A: run::Arg,
         T: Unpin + DeserializeOwned,
     {
         Box::pin(run::new(self, arg))
     }
 
+    /// Run a query like [run](Self::run), returning a [Cursor] that can
+    /// [peek](Cursor::peek) at the next result.
+    pub fn cursor<T>(
+        self,
+        arg: impl run::Arg,
+    ) -> Cursor<impl Stream<Item = crate::Result<T>> + Unpin>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        Cursor::new(Box::pin(run::new(self, arg)))
+    }
+
+    /// Run a changefeed like [run](Self::run), returning a [FeedHandle]
+    /// that can [stop](FeedHandle::stop) it and leave the session free
+    /// for other queries.
+    ///
+    /// ## Example
+    /// Wait for the next score, then go on with other queries.
+    ///
+    /// ```
+    /// # use unreql::r;
+    /// # use futures::TryStreamExt;
+    /// # use serde_json::Value;
+    /// # async fn example(conn: unreql::Session) -> unreql::Result<()> {
+    /// let mut feed = r.table("scores").changes(()).run_feed::<Value>(&conn).await?;
+    /// let change = feed.try_next().await?;
+    /// feed.stop().await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [run](Self::run)
+    /// - [changes](Self::changes)
+    pub async fn run_feed<T>(
+        self,
+        arg: impl run::Arg,
+    ) -> crate::Result<FeedHandle<impl Stream<Item = crate::Result<T>> + Unpin>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        let (conn, opts) = arg.into_run_opts(self.change_feed()).await?;
+        let stream = Box::pin(run::new(self, Args((conn.clone(), opts))));
+        Ok(FeedHandle::new(stream, conn))
+    }
+
     /// Run a query on a connection and return one result.
     ///
     /// ## Example
@@ -47,7 +150,7 @@ impl Command {
     /// # async fn example(conn: &mut Session) -> unreql::Result<()> {
     /// let doc = r.table("test")
     ///   .get("id")
-    ///   .run::<_, Value>(conn)
+    ///   .run::<Value>(conn)
     ///   .try_next()
     ///   .await?;
     ///   # Ok(())
@@ -57,9 +160,8 @@ impl Command {
     /// # Related commands
     /// - [run](Self::run)
     /// - [exec_to_vec](Self::exec_to_vec)
-    pub async fn exec<A, T>(self, arg: A) -> crate::Result<T>
+    pub async fn exec<T>(self, arg: impl run::Arg) -> crate::Result<T>
     where
-        A: run::Arg,
         T: Unpin + DeserializeOwned,
     {
         match self.run(arg).try_next().await? {
@@ -68,6 +170,74 @@ impl Command {
         }
     }
 
+    /// Run a query like [exec](Self::exec) and return its result with
+    /// the [QueryStats] of the query.
+    ///
+    /// The query is run with the `profile` option set, the server time
+    /// of the stats is read from the profile returned by the server.
+    ///
+    /// ## Example
+    /// Log how long counting the heroes took.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let (count, stats) = r.table("marvel").count(()).exec_with_stats::<u64>(conn).await?;
+    /// println!("{count} heroes, {}", serde_json::to_string(&stats)?);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [exec](Self::exec)
+    pub async fn exec_with_stats<T>(self, arg: impl run::Arg) -> crate::Result<(T, QueryStats)>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        let stats = Arc::new(Mutex::new(QueryStats::default()));
+        let result = Box::pin(run::profiled(self, arg, Some(stats.clone())))
+            .try_next()
+            .await?;
+        let stats = stats.lock().unwrap().clone();
+        match result {
+            Some(result) => Ok((result, stats)),
+            None => Err(crate::Driver::NotFound.into()),
+        }
+    }
+
+    /// Check the index names of the query, then [exec](Self::exec) it.
+    ///
+    /// The indexes read by `get_all`, `between`, `order_by`, `eq_join`,
+    /// `get_intersecting` and `get_nearest` on tables named literally are
+    /// looked up with `index_list` first, so a typo fails with
+    /// [Driver::UnknownIndex](crate::Driver::UnknownIndex) instead of
+    /// a runtime error on the server. The indexes of a table are cached
+    /// by the session for a minute, creating, dropping or renaming an
+    /// index through the session clears the cache.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use serde_json::Value;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let user: Value = r.table("users")
+    ///   .get_all(r.with_opt("bob@example.com", r.index("email")))
+    ///   .nth(0)
+    ///   .exec_validated(conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn exec_validated<T>(self, arg: impl run::Arg) -> crate::Result<T>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        let (conn, opts) = arg.into_run_opts(self.change_feed()).await?;
+        validate::check_indexes(&self, &conn.session, &opts).await?;
+        self.exec(Args((conn, opts))).await
+    }
+
     /// Run a query on a connection and collect all the results as `Vec`.
     ///
     /// ## Example
@@ -89,7 +259,7 @@ impl Command {
     /// # use serde_json::Value;
     /// # use futures::TryStreamExt;
     /// # async fn example(conn: &mut Session) {
-    /// let mut cur = r.table("test").run::<_, Value>(conn);
+    /// let mut cur = r.table("test").run::<Value>(conn);
     /// let mut docs = vec![];
     /// while let Ok(Some(doc)) = cur.try_next().await {
     ///   docs.push(doc);
@@ -97,15 +267,329 @@ impl Command {
     /// # }
     /// ```
     ///
+    /// The results of a table scan come in no particular order, use
+    /// [order_by](Self::order_by) or [exec_to_sorted_vec](Self::exec_to_sorted_vec)
+    /// to compare them with an expected list.
+    ///
     /// # Related commands
     /// - [run](Self::run)
     /// - [exec](Self::exec)
-    pub async fn exec_to_vec<A, T>(self, arg: A) -> crate::Result<Vec<T>>
+    /// - [exec_to_sorted_vec](Self::exec_to_sorted_vec)
+    pub async fn exec_to_vec<T>(self, arg: impl run::Arg) -> crate::Result<Vec<T>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        let (conn, opts) = arg.into_run_opts(self.change_feed()).await?;
+        if conn.session.inner.warn_unordered && self.is_unordered_scan() {
+            warn!("exec_to_vec collects a table scan without order_by, in no particular order");
+        }
+        self.run(Args((conn, opts))).try_collect().await
+    }
+
+    /// Collect all the results like [exec_to_vec](Self::exec_to_vec) and
+    /// sort them.
+    ///
+    /// Sorting on the client keeps the results of an unordered scan
+    /// comparable, e.g. in tests, without an index for
+    /// [order_by](Self::order_by).
+    ///
+    /// ## Example
+    /// Check the names of the heroes.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let names: Vec<String> = r.table("marvel").g("name").exec_to_sorted_vec(conn).await?;
+    /// assert_eq!(names, ["Black Widow", "Iron Man", "Thor"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [exec_to_vec](Self::exec_to_vec)
+    /// - [exec_to_vec_sorted_by_key](Self::exec_to_vec_sorted_by_key)
+    pub async fn exec_to_sorted_vec<T>(self, arg: impl run::Arg) -> crate::Result<Vec<T>>
+    where
+        T: Unpin + DeserializeOwned + Ord,
+    {
+        let mut results: Vec<T> = self.run(arg).try_collect().await?;
+        results.sort();
+        Ok(results)
+    }
+
+    /// Collect all the results like [exec_to_vec](Self::exec_to_vec) and
+    /// sort them by the key returned by `key`.
+    ///
+    /// ## Example
+    /// Check the heroes, by id.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use serde::Deserialize;
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// struct Hero {
+    ///     id: u32,
+    ///     name: String,
+    /// }
+    ///
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let heroes: Vec<Hero> = r.table("marvel")
+    ///   .exec_to_vec_sorted_by_key(|hero: &Hero| hero.id, conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [exec_to_vec](Self::exec_to_vec)
+    /// - [exec_to_sorted_vec](Self::exec_to_sorted_vec)
+    pub async fn exec_to_vec_sorted_by_key<T, K>(
+        self,
+        key: impl FnMut(&T) -> K,
+        arg: impl run::Arg,
+    ) -> crate::Result<Vec<T>>
+    where
+        T: Unpin + DeserializeOwned,
+        K: Ord,
+    {
+        let mut results: Vec<T> = self.run(arg).try_collect().await?;
+        results.sort_by_key(key);
+        Ok(results)
+    }
+
+    /// Coerce a selection or a stream to an array on the server and
+    /// collect it, the same as `.coerce_to("array")` followed by
+    /// [exec_to_vec](Self::exec_to_vec).
+    ///
+    /// ## Example
+    /// Get several heroes at once.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use serde_json::Value;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let heroes: Vec<Value> = r.table("marvel")
+    ///   .get_all(r.args(["Iron Man", "Thor"]))
+    ///   .exec_array(conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [exec_to_vec](Self::exec_to_vec)
+    /// - [coerce_to](Self::coerce_to)
+    pub async fn exec_array<T>(self, arg: impl run::Arg) -> crate::Result<Vec<T>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        self.coerce_to("array").exec_to_vec(arg).await
+    }
+
+    /// Run the [info](Self::info) command on a connection and return
+    /// the typed result.
+    ///
+    /// ## Example
+    /// Get the primary key of a table.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use unreql::types::TableInfo;
+    /// # async fn example(conn: &mut Session) -> unreql::Result<()> {
+    /// let info: TableInfo = r.table("marvel").exec_info(conn).await?;
+    /// println!("primary key: {}", info.primary_key);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Use [Info](crate::types::Info) if the kind of the value is not known in advance.
+    ///
+    /// # Related commands
+    /// - [info](Self::info)
+    /// - [exec](Self::exec)
+    pub async fn exec_info<T>(self, arg: impl run::Arg) -> crate::Result<T>
     where
-        A: run::Arg,
         T: Unpin + DeserializeOwned,
     {
-        self.run(arg).try_collect().await
+        self.info().exec(arg).await
+    }
+
+    /// Run the [count](Self::count) command on a connection and return
+    /// the number.
+    ///
+    /// `value` is the same as the argument of `count`: `()` to count all
+    /// the elements, a value to count the elements equal to it, or
+    /// a predicate to count the elements it matches.
+    ///
+    /// ## Example
+    /// Count the users over 18.
+    ///
+    /// ```
+    /// # use unreql::{func, r, Session};
+    /// # #[cfg(feature = "math-logic")]
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let adults = r.table("users")
+    ///   .exec_count(func!(|user| user.g("age").gt(18)), conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [count](Self::count)
+    /// - [exec](Self::exec)
+    pub async fn exec_count(
+        self,
+        value: impl ManyArgs<()>,
+        arg: impl run::Arg,
+    ) -> crate::Result<u64> {
+        self.count(value).exec(arg).await
+    }
+
+    /// Run a query returning a boolean on a connection and return it,
+    /// e.g. [contains](Self::contains), [is_empty](Self::is_empty) or
+    #[cfg_attr(feature = "math-logic", doc = " [eq](Self::eq).")]
+    #[cfg_attr(not(feature = "math-logic"), doc = " `eq`.")]
+    ///
+    /// A result that is not a boolean is a [Json](crate::Driver::Json)
+    /// error.
+    ///
+    /// ## Example
+    /// Check if a hero is in the table.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let known = r.table("marvel")
+    ///   .g("name")
+    ///   .contains("Iron Man")
+    ///   .exec_bool(conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [exec](Self::exec)
+    pub async fn exec_bool(self, arg: impl run::Arg) -> crate::Result<bool> {
+        self.exec(arg).await
+    }
+
+    /// Run the [sum](Self::sum) command on a connection and return the
+    /// number, `0` for an empty sequence.
+    ///
+    /// ## Example
+    /// How many points have been scored across all games?
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let points = r.table("games").exec_sum("points", conn).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [sum](Self::sum)
+    /// - [exec_avg](Self::exec_avg)
+    pub async fn exec_sum(
+        self,
+        field_or_function: impl Serialize + 'static,
+        arg: impl run::Arg,
+    ) -> crate::Result<f64> {
+        self.sum(field_or_function).exec(arg).await
+    }
+
+    /// Run the [avg](Self::avg) command on a connection and return the
+    /// number, `None` for an empty sequence.
+    ///
+    /// The server fails `avg` on an empty sequence with a non-existence
+    /// error, which is returned as `None` instead.
+    ///
+    /// ## Example
+    /// What’s the average number of points scored in a game?
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let avg = r.table("games").exec_avg("points", conn).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [avg](Self::avg)
+    /// - [exec_sum](Self::exec_sum)
+    pub async fn exec_avg(
+        self,
+        field_or_function: impl Serialize + 'static,
+        arg: impl run::Arg,
+    ) -> crate::Result<Option<f64>> {
+        empty_as_none(self.avg(field_or_function).exec(arg).await)
+    }
+
+    /// Run the [min](Self::min) command on a connection and return the
+    /// minimum element, `None` for an empty sequence.
+    ///
+    /// The server fails `min` on an empty sequence with a non-existence
+    /// error, which is returned as `None` instead.
+    ///
+    /// ## Example
+    /// Return the user who has scored the fewest points.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use serde_json::Value;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let user: Option<Value> = r.table("users").exec_min("points", conn).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [min](Self::min)
+    /// - [exec_max](Self::exec_max)
+    pub async fn exec_min<T>(
+        self,
+        field_or_function: impl Arg<Index>,
+        arg: impl run::Arg,
+    ) -> crate::Result<Option<T>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        empty_as_none(self.min(field_or_function).exec(arg).await)
+    }
+
+    /// Run the [max](Self::max) command on a connection and return the
+    /// maximum element, `None` for an empty sequence.
+    ///
+    /// The server fails `max` on an empty sequence with a non-existence
+    /// error, which is returned as `None` instead.
+    ///
+    /// ## Example
+    /// Return the user who has scored the most points.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use serde_json::Value;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let user: Option<Value> = r.table("users").exec_max("points", conn).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [max](Self::max)
+    /// - [exec_min](Self::exec_min)
+    pub async fn exec_max<T>(
+        self,
+        field_or_function: impl Arg<Index>,
+        arg: impl run::Arg,
+    ) -> crate::Result<Option<T>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        empty_as_none(self.max(field_or_function).exec(arg).await)
     }
 
     /// Turn a query into a changefeed, an infinite stream of objects
@@ -170,25 +654,27 @@ impl Command {
     /// Return all the changes that increase a player’s score.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("test")
     ///   .changes(())
     ///   .filter(r.row().g("new_val").g("score").gt(r.row().g("old_val").g("score")))
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
     /// Return all the changes to a specific player’s score that increase it past 10.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("test")
     ///   .get(1)
     ///   .filter(r.row().g("score").gt(10))
     ///   .changes(())
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -196,12 +682,13 @@ impl Command {
     ///
     /// ```
     /// # use unreql::rjson;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("test")
     ///   .changes(())
     ///   .filter(r.row().g("old_val").eq(rjson!(null)))
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -255,3 +742,217 @@ impl Command {
             .with_parent(self)
     }
 }
+
+// The errors of `avg`, `min` and `max` on an empty sequence
+const EMPTY_STREAM: &[&str] = &[
+    "Cannot take the average of an empty stream",
+    "Cannot take the min of an empty stream",
+    "Cannot take the max of an empty stream",
+];
+
+// The non-existence error of `avg`, `min` and `max` on an empty sequence
+// as `None`, other errors, e.g. of a missing field, are kept
+fn empty_as_none<T>(result: crate::Result<T>) -> crate::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::Runtime(Runtime::NonExistence(msg)))
+            if EMPTY_STREAM.iter().any(|empty| msg.starts_with(empty)) =>
+        {
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fake_server::{answering, read_query, scripted, send};
+    use crate::Session;
+    use crate::{r, rjson, Driver, Error, Runtime};
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+
+    const EMPTY_AVG: &str = r#"{"t":18,"e":3100000,"r":["Cannot take the average of an empty stream.  (If you passed `avg` a field name, it may be that no elements of your input sequence had that field.)"]}"#;
+    const EMPTY_MIN: &str =
+        r#"{"t":18,"e":3100000,"r":["Cannot take the min of an empty stream."]}"#;
+    const EMPTY_MAX: &str =
+        r#"{"t":18,"e":3100000,"r":["Cannot take the max of an empty stream."]}"#;
+
+    #[tokio::test]
+    async fn exec_sum() {
+        let session = answering(vec![(
+            r.table("games").sum("points"),
+            r#"{"t":1,"r":[42]}"#,
+        )])
+        .await;
+        let sum = r.table("games").exec_sum("points", &session).await.unwrap();
+        assert_eq!(sum, 42.0);
+
+        let session = answering(vec![(r.table("games").sum("points"), r#"{"t":1,"r":[0]}"#)]).await;
+        let sum = r.table("games").exec_sum("points", &session).await.unwrap();
+        assert_eq!(sum, 0.0);
+    }
+
+    #[tokio::test]
+    async fn exec_avg() {
+        let session = answering(vec![(
+            r.table("games").avg("points"),
+            r#"{"t":1,"r":[7.5]}"#,
+        )])
+        .await;
+        let avg = r.table("games").exec_avg("points", &session).await.unwrap();
+        assert_eq!(avg, Some(7.5));
+
+        let session = answering(vec![(r.table("games").avg("points"), EMPTY_AVG)]).await;
+        let avg = r.table("games").exec_avg("points", &session).await.unwrap();
+        assert_eq!(avg, None);
+    }
+
+    #[tokio::test]
+    async fn exec_min_and_max() {
+        let session = answering(vec![
+            (
+                r.table("users").min("points"),
+                r#"{"t":1,"r":[{"id":1,"points":3}]}"#,
+            ),
+            (
+                r.table("users").max("points"),
+                r#"{"t":1,"r":[{"id":2,"points":9}]}"#,
+            ),
+        ])
+        .await;
+        let user: Option<Value> = r.table("users").exec_min("points", &session).await.unwrap();
+        assert_eq!(user, Some(json!({"id": 1, "points": 3})));
+        let user: Option<Value> = r.table("users").exec_max("points", &session).await.unwrap();
+        assert_eq!(user, Some(json!({"id": 2, "points": 9})));
+
+        let session = answering(vec![
+            (r.table("users").min("points"), EMPTY_MIN),
+            (r.table("users").max("points"), EMPTY_MAX),
+        ])
+        .await;
+        let user: Option<Value> = r.table("users").exec_min("points", &session).await.unwrap();
+        assert_eq!(user, None);
+        let user: Option<Value> = r.table("users").exec_max("points", &session).await.unwrap();
+        assert_eq!(user, None);
+    }
+
+    #[tokio::test]
+    async fn other_errors_are_kept() {
+        let missing_table = r#"{"t":18,"e":4100000,"r":["Table `test.games` does not exist."]}"#;
+        let session = answering(vec![(r.table("games").avg("points"), missing_table)]).await;
+        let err = r
+            .table("games")
+            .exec_avg("points", &session)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+
+        let missing_field =
+            r#"{"t":18,"e":3100000,"r":["No attribute `points` in object:\n{\n\t\"id\":\t1\n}"]}"#;
+        let session = answering(vec![(r.table("users").max("points"), missing_field)]).await;
+        let err = r
+            .table("users")
+            .exec_max::<Value>("points", &session)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&err, Error::Runtime(Runtime::NonExistence(msg)) if msg.starts_with("No attribute")),
+            "{:?}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn exec_bool_contains() {
+        let query = || r.table("marvel").g("name").contains("Iron Man");
+        let session = answering(vec![(query(), r#"{"t":1,"r":[true]}"#)]).await;
+        assert!(query().exec_bool(&session).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn exec_bool_not_a_bool() {
+        let query = || r.table("marvel").count(());
+        let session = answering(vec![(query(), r#"{"t":1,"r":[3]}"#)]).await;
+        let err = query().exec_bool(&session).await.unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Driver(Driver::Json(_, Some(ref value))) if *value == json!(3)
+        ));
+    }
+
+    #[tokio::test]
+    async fn exec_bool_is_empty() {
+        let query = || {
+            r.table("marvel")
+                .filter(rjson!({"team": "Avengers"}))
+                .is_empty()
+        };
+        let session = answering(vec![(query(), r#"{"t":1,"r":[false]}"#)]).await;
+        assert!(!query().exec_bool(&session).await.unwrap());
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Hero {
+        id: u32,
+        name: String,
+    }
+
+    #[tokio::test]
+    async fn exec_to_sorted_vec() {
+        let query = || r.table("marvel").g("name");
+        let response = r#"{"t":2,"r":["Thor","Iron Man","Black Widow"]}"#;
+        let session = answering(vec![(query(), response)]).await;
+        let names: Vec<String> = query().exec_to_sorted_vec(&session).await.unwrap();
+        assert_eq!(names, ["Black Widow", "Iron Man", "Thor"]);
+    }
+
+    #[tokio::test]
+    async fn exec_to_vec_sorted_by_key() {
+        let response = r#"{"t":2,"r":[{"id":3,"name":"Thor"},{"id":1,"name":"Iron Man"}]}"#;
+        let session = answering(vec![(r.table("marvel"), response)]).await;
+        let heroes = r
+            .table("marvel")
+            .exec_to_vec_sorted_by_key(|hero: &Hero| hero.id, &session)
+            .await
+            .unwrap();
+        let ids: Vec<u32> = heroes.iter().map(|hero| hero.id).collect();
+        assert_eq!(ids, [1, 3]);
+        assert_eq!(heroes[0].name, "Iron Man");
+    }
+
+    // A session to a server answering a query run with `profile` with
+    // `response`
+    async fn profiled(response: &'static str) -> Session {
+        scripted(move |mut stream| async move {
+            let (token, query) = read_query(&mut stream).await;
+            assert_eq!(query[2], json!({"profile": true}));
+            send(&mut stream, token, response).await;
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn exec_with_stats_count() {
+        let response = r#"{"t":1,"r":[3],"p":[
+            {"description":"Evaluating count.","duration(ms)":1.5,"sub_tasks":[]}
+        ]}"#;
+        let session = profiled(response).await;
+        let query = r.table("marvel").count(());
+        let (count, stats) = query.exec_with_stats::<u64>(&session).await.unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(stats.server_ms, 1.5);
+        assert_eq!(stats.rows, 1);
+        assert_eq!(stats.batches, 1);
+        assert_eq!(stats.bytes, response.len());
+        assert!(stats.total_ms >= stats.wait_ms);
+    }
+
+    #[tokio::test]
+    async fn exec_with_stats_error() {
+        let session = profiled(r#"{"t":18,"e":3100000,"r":["No attribute `name`"]}"#).await;
+        let query = r.table("marvel").get(1).g("name");
+        let err = query.exec_with_stats::<String>(&session).await.unwrap_err();
+        assert!(matches!(err, Error::Runtime(Runtime::NonExistence(_))));
+    }
+}
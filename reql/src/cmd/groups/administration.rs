@@ -1,13 +1,14 @@
-use unreql_macros::create_cmd;
 use ql2::term::TermType;
 use serde::Serialize;
+use unreql_macros::create_cmd;
 
 use crate::{
     cmd::{
         args::{ManyArgs, Opt},
-        options::{GrantOptions, ReconfigureOptions, WaitOptions},
+        options::{ChangesOptions, GrantOptions, ReconfigureOptions, WaitOptions},
+        reshard::Reshard,
     },
-    Command,
+    r, Command,
 };
 
 create_cmd!(
@@ -43,6 +44,64 @@ create_cmd!(
     reconfigure(args: ManyArgs<ReconfigureOptions>)
 );
 
+impl Command {
+    /// Reconfigure a table to `shards` shards with `replicas` replicas
+    /// each, checking the proposed configuration with a dry run first.
+    ///
+    /// A wrapper around [reconfigure](Self::reconfigure) for scripts:
+    /// [apply](crate::cmd::reshard::DryRunReport::apply) runs the
+    /// reconfiguration only if the dry run assigned every shard as
+    /// requested and the closure confirms it.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let report = r.table("users").reshard(2, 3).dry_run().exec_reconfigure(conn).await?;
+    /// let applied = report
+    ///     .apply(conn, |result| {
+    ///         println!("{:?}", result.config_changes);
+    ///         true
+    ///     })
+    ///     .await?;
+    /// # Ok(()) }
+    /// ```
+    pub fn reshard(self, shards: u8, replicas: u8) -> Reshard {
+        Reshard::new(self, shards, replicas)
+    }
+
+    /// Subscribe to the status of a table, e.g. to follow the progress
+    /// of [reconfigure](Self::reconfigure) or [rebalance](Self::rebalance).
+    ///
+    /// This is a changefeed on the document of the table in the
+    /// `rethinkdb.table_status` system table. Every change carries the
+    /// status of the shards and replicas in `new_val`, the table is ready
+    /// when `new_val.status.all_replicas_ready` is `true`.
+    ///
+    /// ## Example
+    /// Follow the rebalancing of a table after changing the number of shards.
+    ///
+    /// ```
+    /// # use unreql::cmd::options::ChangesOptions;
+    /// # unreql::example(|r, conn| {
+    /// r.table("superheroes")
+    ///   .status_changes(ChangesOptions::new().include_initial(true))
+    ///   .run(conn)
+    /// # })
+    /// ```
+    ///
+    /// # Related commands
+    /// - [status](Self::status)
+    /// - [changes](Self::changes)
+    pub fn status_changes(self, opt: impl Opt<ChangesOptions>) -> Command {
+        r.db("rethinkdb")
+            .table("table_status")
+            .get(self.config().g("id"))
+            .changes(opt)
+    }
+}
+
 create_cmd!(
     /// Return the status of a table.
     ///
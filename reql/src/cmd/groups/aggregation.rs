@@ -194,12 +194,13 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "dates-times")]
     /// # unreql::example(|r, conn| {
     /// r.table("matches")
     ///   .group([r.row().g("date").year(), r.row().g("date").month()])
     ///   .count(())
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// Result:
@@ -265,6 +266,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("games")
     ///   .filter(r.row().g("type").eq("free"))
@@ -272,7 +274,7 @@ create_cmd!(
     ///   .max("points")
     ///   .g("points")
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// Result:
@@ -295,6 +297,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("games")
     ///   .group(r.args((
@@ -304,7 +307,7 @@ create_cmd!(
     ///   .max("points")
     ///   .g("points")
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// Result:
@@ -516,13 +519,14 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts")
     ///   .map(func!(|doc| r.expr(1)))
     ///   .reduce(func!(|left, right| left.add(right)))
     ///   .default(0)
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// A shorter way to execute this query is to use count.
@@ -533,13 +537,14 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts")
     ///   .map(func!(|doc| doc.g("comments").count(())))
     ///   .reduce(func!(|left, right| left.add(right)))
     ///   .default(0)
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -548,13 +553,14 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts")
     ///   .map(func!(|doc| doc.g("comments").count(())))
     ///   .reduce(func!(|left, right| left.add(right)))
     ///   .default(0)
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -563,6 +569,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts")
     ///   .map(func!(|doc| doc.g("comments").count(())))
@@ -575,7 +582,7 @@ create_cmd!(
     ///   ))
     ///   .default(0)
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// A shorter way to execute this query is to use max.
@@ -648,6 +655,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("words")
     ///   .order_by("id")
@@ -655,7 +663,7 @@ create_cmd!(
     ///     acc.clone().add(r.branch(acc.eq(""), "", ", ")).add(word)
     ///   }), ())
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// (This example could be implemented with `reduce`, but `fold` will
@@ -668,6 +676,7 @@ create_cmd!(
     /// ```
     /// # use unreql::{func, rjson};
     /// # use unreql::cmd::options::FoldOptions;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("even_things")
     ///   .order_by("id")
@@ -681,7 +690,7 @@ create_cmd!(
     ///     }))
     ///   )
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// The first function increments the accumulator each time it’s called,
@@ -696,6 +705,7 @@ create_cmd!(
     /// ```
     /// # use unreql::{func, rjson};
     /// # use unreql::cmd::options::FoldOptions;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("tracker")
     ///   .filter(rjson!({"name": "bob"}))
@@ -711,7 +721,7 @@ create_cmd!(
     ///     }))
     ///   )
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -755,16 +765,18 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").g("age").count(func!(|age| age.gt(18))).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").count(func!(|user| user.g("age").gt(18))).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -787,6 +799,11 @@ create_cmd!(
     /// # })
     /// ```
     ///
+    /// Use [exec_count](crate::Command::exec_count) to get the number directly.
+    /// To check whether there is any element at all, use
+    /// [is_empty](crate::Command::is_empty) instead of comparing the count
+    /// with `0`: it stops at the first element instead of counting them all.
+    ///
     /// # Related commands
     /// - [map](Self::map)
     /// - [reduce](Self::reduce)
@@ -795,6 +812,7 @@ create_cmd!(
     /// - [min](Self::min)
     /// - [max](Self::max)
     /// - [group](Self::group)
+    /// - [exec_count](crate::Command::exec_count)
     count(value: ManyArgs<()>)
 );
 
@@ -832,11 +850,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("games").sum(func!(|game| {
     ///   game.clone().g("points").add(game.g("bonus_points"))
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -895,11 +914,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("games").avg(func!(|game| {
     ///   game.clone().g("points").add(game.g("bonus_points"))
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -975,11 +995,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").min(func!(|user| {
     ///   user.clone().g("points").add(user.g("bonusPoints"))
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -1076,11 +1097,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").max(func!(|user| {
     ///   user.clone().g("points").add(user.g("bonusPoints"))
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -1200,11 +1222,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("marvel").get("ironman").g("battles").contains(func!(|battle| {
     ///   battle.clone().g("winner").eq("ironman").and(battle.g("loser").eq("superman"))
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -1,6 +1,6 @@
-use unreql_macros::create_cmd;
 use ql2::term::TermType;
 use serde::Serialize;
+use unreql_macros::create_cmd;
 
 use crate::Command;
 
@@ -2,9 +2,11 @@ use ql2::term::TermType;
 use serde::Serialize;
 use unreql_macros::create_cmd;
 
+use serde_json::Value;
+
 use crate::{
     cmd::{args::Opt, options::DuringOptions},
-    Command,
+    Command, Datum, Driver,
 };
 
 create_cmd!(
@@ -47,6 +49,10 @@ create_cmd!(
     ///
     /// To use `hour`, `minutes` and `seconds` see [time_ext](Self::time_ext) command.
     ///
+    /// Arguments given as literal values are checked by the driver, the query
+    /// fails to serialize with a [Driver](crate::Driver) error if one is out
+    /// of range. Arguments given as ReQL expressions are checked by the server.
+    ///
     /// ## Example
     /// Update the birthdate of the user “John” to November 3rd, 1986 UTC.
     ///
@@ -64,7 +70,14 @@ create_cmd!(
     /// - [epoch_time](Self::epoch_time)
     /// - [iso_8601](Self::iso_8601)
     only_root,
-    time(year: Serialize, month: Serialize, day: Serialize, timezone: Serialize)
+    time(year: Serialize, month: Serialize, day: Serialize, timezone: Serialize) {
+        let cmd = Command::new(TermType::Time)
+            .with_arg(Command::from_json_2(year))
+            .with_arg(Command::from_json_2(month))
+            .with_arg(Command::from_json_2(day))
+            .with_arg(Command::from_json_2(timezone));
+        check_time(cmd)
+    }
 );
 
 create_cmd!(
@@ -80,14 +93,115 @@ create_cmd!(
     /// - `seconds` is a double. Its value will be rounded to three decimal places (millisecond-precision).
     /// - `timezone` can be `'Z'` (for UTC) or a string with the format `±[hh]:[mm]`.
     ///
+    /// Arguments given as literal values are checked by the driver, as in
+    /// [time](Self::time). `hour` must be between 0 and 23, `minutes`
+    /// between 0 and 59 and `seconds` between 0 and 60 (excluded).
+    ///
     /// # Related commands
     /// - [now](Self::now)
     /// - [epoch_time](Self::epoch_time)
     /// - [iso_8601](Self::iso_8601)
     only_root,
-    time_ext:Time(year: Serialize, month: Serialize, day: Serialize, hour: Serialize, minutes: Serialize, seconds: Serialize, timezone: Serialize)
+    time_ext:Time(year: Serialize, month: Serialize, day: Serialize, hour: Serialize, minutes: Serialize, seconds: Serialize, timezone: Serialize) {
+        let cmd = Command::new(TermType::Time)
+            .with_arg(Command::from_json_2(year))
+            .with_arg(Command::from_json_2(month))
+            .with_arg(Command::from_json_2(day))
+            .with_arg(Command::from_json_2(hour))
+            .with_arg(Command::from_json_2(minutes))
+            .with_arg(Command::from_json_2(seconds))
+            .with_arg(Command::from_json_2(timezone));
+        check_time(cmd)
+    }
 );
 
+// The name and the integer range of the numeric arguments of `r.time`
+const DATE_PARTS: [(&str, i64, i64); 3] = [("year", 1400, 9999), ("month", 1, 12), ("day", 1, 31)];
+const TIME_PARTS: [(&str, i64, i64); 2] = [("hour", 0, 23), ("minutes", 0, 59)];
+
+// Fails the command if a literal argument of `r.time` is out of range,
+// expressions are left to the server.
+fn check_time(cmd: Command) -> Command {
+    match time_error(&cmd) {
+        Some(error) => {
+            let error = Driver::Other(format!("invalid r.time argument: {error}"));
+            (Err(error.into()) as crate::Result<Datum>).into()
+        }
+        None => cmd,
+    }
+}
+
+fn time_error(cmd: &Command) -> Option<String> {
+    let args: Vec<_> = cmd.args().iter().map(literal).collect();
+    let (timezone, numbers) = args.split_last()?;
+    let (date, time) = numbers.split_at(DATE_PARTS.len().min(numbers.len()));
+    let integers = DATE_PARTS
+        .iter()
+        .zip(date)
+        .chain(TIME_PARTS.iter().zip(time));
+    for ((name, min, max), value) in integers {
+        match value {
+            Some(Value::Number(num))
+                if num.as_i64().is_some_and(|n| (*min..=*max).contains(&n)) => {}
+            Some(value) => {
+                return Some(format!(
+                    "{name} must be an integer between {min} and {max}, got {value}"
+                ))
+            }
+            None => {}
+        }
+    }
+    match time.get(TIME_PARTS.len()) {
+        Some(Some(Value::Number(num)))
+            if num.as_f64().is_some_and(|s| (0.0..60.0).contains(&s)) => {}
+        Some(Some(value)) => {
+            return Some(format!(
+                "seconds must be a number between 0 and 60 (excluded), got {value}"
+            ))
+        }
+        _ => {}
+    }
+    match timezone {
+        Some(Value::String(tz)) if is_timezone(tz) => None,
+        Some(value) => Some(format!(
+            "timezone must be 'Z' or have the format ±[hh]:[mm], got {value}"
+        )),
+        None => None,
+    }
+}
+
+// `Z` or `[+-]HH:MM`, with hours up to 23 and minutes up to 59
+fn is_timezone(tz: &str) -> bool {
+    if tz == "Z" {
+        return true;
+    }
+    let bytes = tz.as_bytes();
+    let digits = |range: std::ops::Range<usize>, max: u8| {
+        let part = &tz[range];
+        part.bytes().all(|b| b.is_ascii_digit()) && part.parse::<u8>().is_ok_and(|n| n <= max)
+    };
+    bytes.len() == 6
+        && (bytes[0] == b'+' || bytes[0] == b'-')
+        && bytes[3] == b':'
+        && digits(1..3, 23)
+        && digits(4..6, 59)
+}
+
+// The value of a literal argument, `None` for an expression
+fn literal(cmd: &Command) -> Option<Value> {
+    if cmd.typ() != TermType::Datum {
+        return None;
+    }
+    match cmd.datum() {
+        Some(Ok(Datum::Value(value))) => Some(value.clone()),
+        Some(Ok(Datum::Number(num))) => Some(Value::Number(num.clone())),
+        Some(Ok(Datum::String(string))) => Some(Value::String(string.clone())),
+        Some(Ok(Datum::Bool(b))) => Some(Value::Bool(*b)),
+        Some(Ok(Datum::Null)) => Some(Value::Null),
+        _ => None,
+    }
+}
+
 create_cmd!(
     /// Create a time object based on seconds since epoch.
     ///
@@ -172,9 +286,10 @@ create_cmd!(
     /// Return all the users in the “-07:00” timezone.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(r.row().g("subscriptionDate").timezone().eq("-07:00")).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -238,11 +353,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(func!(|user| {
     ///   user.g("birthdate").date().eq(r.now().date())
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// *Note* that the `now` command always returns UTC time, so the comparison
@@ -251,11 +367,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(func!(|user| {
     ///   user.g("birthdate").date().eq(r.now().in_timezone("-08:00").date())
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -275,11 +392,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///   r.row().g("date").time_of_day().le(12*60*60)
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -297,11 +415,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///   r.row().g("birthdate").year().eq(1986)
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -320,11 +439,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///   r.row().g("birthdate").month().eq(11)
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -332,11 +452,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///   r.row().g("birthdate").month().eq(r.november())
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -353,11 +474,12 @@ create_cmd!(
     /// Return the users born on the 24th of any month.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///   r.row().g("birthdate").day().eq(24)
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -385,11 +507,12 @@ create_cmd!(
     /// Retrieve all the users who were born on a Tuesday.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///   r.row().g("birthdate").day_of_week().eq(r.tuesday())
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -408,11 +531,12 @@ create_cmd!(
     /// Retrieve all the users who were born the first day of a year.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///   r.row().g("birthdate").day_of_year().eq(1)
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -429,11 +553,12 @@ create_cmd!(
     /// Return all the posts submitted after midnight and before 4am.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts").filter(
     ///   r.row().g("date").hours().lt(4)
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -450,11 +575,12 @@ create_cmd!(
     /// Return all the posts submitted during the first 10 minutes of every hour.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts").filter(
     ///   r.row().g("date").minutes().lt(10)
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -471,11 +597,12 @@ create_cmd!(
     /// Return the post submitted during the first 30 seconds of every minute.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts").filter(
     ///   r.row().g("date").seconds().lt(30)
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
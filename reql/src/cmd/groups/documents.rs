@@ -2,9 +2,11 @@ use ql2::term::TermType;
 use serde::Serialize;
 use unreql_macros::create_cmd;
 
+use serde_json::{json, Value};
+
 use crate::{
     cmd::args::{ManyArgs, OneAndSecondOptionalArg},
-    Command,
+    r, Command, Datum, Driver,
 };
 
 create_cmd!(
@@ -12,34 +14,38 @@ create_cmd!(
     ///
     /// *Note* that `row` does not work within subqueries to access nested documents;
     /// you should use anonymous functions to access those documents instead.
-    /// (See the last example.)
+    /// (See the last example.) The driver fails such queries with a
+    /// [Compile](crate::Error::Compile) error before sending them.
     ///
     /// ## Example
     /// Get all users whose age is greater than 5.
     ///
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(r.row().g("age").gt(5)).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
     /// Access the attribute ‘child’ of an embedded document.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(r.row().g("embedded_doc").g("child").gt(5)).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
     /// Add 1 to every element of an array.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.expr([1, 2, 3]).map(r.row().add(1)).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -47,11 +53,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(func!(|doc| {
     ///   doc.g("name").eq(r.table("prizes").get("winner"))
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     only_root,
     row:ImplicitVar
@@ -505,9 +512,10 @@ create_cmd!(
     /// with not, wrapped with `filter`.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("players").filter(r.row().has_fields("games_won").not()).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -566,6 +574,123 @@ create_cmd!(
     has_fields(selector: ManyArgs<()>)
 );
 
+impl Command {
+    /// [has_fields](Self::has_fields) for a nested field, given by the
+    /// path of field names leading to it
+    ///
+    /// The path is turned into the nested selector object of `has_fields`,
+    /// `["games_won", "championships"]` into
+    /// `{"games_won": {"championships": true}}`.
+    ///
+    /// ## Example
+    /// Return the players who have the “championships” field.
+    ///
+    /// ```
+    /// # unreql::example(|r, conn| {
+    /// r.table("players").has_path(&["games_won", "championships"]).run(conn)
+    /// # })
+    /// ```
+    ///
+    /// # Related commands
+    /// - [has_any_path](Self::has_any_path)
+    /// - [filter_has_path](Self::filter_has_path)
+    pub fn has_path(self, path: &[&str]) -> Command {
+        match path_selector(path) {
+            Ok(selector) => self.has_fields(selector),
+            Err(error) => (Err(error) as crate::Result<Datum>).into(),
+        }
+    }
+
+    /// Test if an object has at least one of the nested fields given by
+    /// their paths, see [has_path](Self::has_path)
+    ///
+    /// The paths are tested with `has_fields` one by one and the results
+    #[cfg_attr(
+        feature = "math-logic",
+        doc = " combined with [or](Self::or), so the object is repeated in the"
+    )]
+    #[cfg_attr(
+        not(feature = "math-logic"),
+        doc = " combined with `or`, so the object is repeated in the"
+    )]
+    /// query for every path: use it on `r.row()` or a function argument.
+    /// An empty list of paths gives `false`.
+    ///
+    /// ## Example
+    /// Return the players who have won the playoffs or a championship.
+    ///
+    /// ```
+    /// # use unreql::func;
+    /// # unreql::example(|r, conn| {
+    /// r.table("players")
+    ///   .filter(func!(|player| player.has_any_path(&[
+    ///     &["games_won", "playoffs"],
+    ///     &["games_won", "championships"],
+    ///   ])))
+    ///   .run(conn)
+    /// # })
+    /// ```
+    ///
+    /// # Related commands
+    /// - [has_path](Self::has_path)
+    pub fn has_any_path(self, paths: &[&[&str]]) -> Command {
+        let mut checks = paths.iter().map(|path| self.clone().has_path(path));
+        let Some(first) = checks.next() else {
+            return r.expr(false);
+        };
+        let rest: Vec<Command> = checks.collect();
+        if rest.is_empty() {
+            return first;
+        }
+        // the checks are added as they are, `r.row()` in them must not be
+        // wrapped into functions like the arguments of `or`
+        let any = Command::new(TermType::Or).with_arg(first);
+        rest.into_iter().fold(any, |any, check| any.with_arg(check))
+    }
+
+    /// [filter](Self::filter) a sequence by the existence of a nested
+    /// field, see [has_path](Self::has_path)
+    ///
+    /// Unlike `has_path` on a sequence, the result is a `filter` term, so
+    /// it can be combined with other filters and still reads from the
+    /// table lazily.
+    ///
+    /// ## Example
+    /// Return the players who have won a championship and are active.
+    ///
+    /// ```
+    /// # use unreql::rjson;
+    /// # unreql::example(|r, conn| {
+    /// r.table("players")
+    ///   .filter_has_path(&["games_won", "championships"])
+    ///   .filter(rjson!({"active": true}))
+    ///   .run(conn)
+    /// # })
+    /// ```
+    ///
+    /// # Related commands
+    /// - [has_path](Self::has_path)
+    /// - [filter](Self::filter)
+    pub fn filter_has_path(self, path: &[&str]) -> Command {
+        match path_selector(path) {
+            Ok(selector) => self.filter(r.row().has_fields(selector)),
+            Err(error) => (Err(error) as crate::Result<Datum>).into(),
+        }
+    }
+}
+
+// The `has_fields` selector of a nested field, `{"a": {"b": true}}`
+fn path_selector(path: &[&str]) -> crate::Result<Value> {
+    let Some((last, parents)) = path.split_last() else {
+        return Err(Driver::Other("the path of a field cannot be empty".into()).into());
+    };
+    let selector = parents.iter().rev().fold(
+        json!({ *last: true }),
+        |selector, field| json!({ *field: selector }),
+    );
+    Ok(selector)
+}
+
 create_cmd!(
     /// Insert a value in to an array at a given index. Returns the modified array.
     ///
@@ -835,6 +960,26 @@ create_cmd!(
     /// ```
     ///
     /// ## Example
+    /// Any serializable value can be used as the replacement, e.g. a typed struct.
+    ///
+    /// ```
+    /// # use unreql::rjson;
+    /// #[derive(serde::Serialize)]
+    /// struct Data {
+    ///     age: u8,
+    ///     job: String,
+    /// }
+    ///
+    /// # unreql::example(|r, conn| {
+    /// let data = Data { age: 19, job: "Engineer".into() };
+    /// r.table("users")
+    ///   .get(1)
+    ///   .update(rjson!({ "data": r.literal(data) }))
+    ///   .run(conn)
+    /// # })
+    /// ```
+    ///
+    /// ## Example
     /// Use literal to remove a field from a document.
     ///
     /// ```
@@ -895,3 +1040,51 @@ create_cmd!(
     only_root,
     object(key_value: ManyArgs<()>)
 );
+
+impl r {
+    /// Creates an object from key-value pairs, where the values may be
+    /// commands.
+    ///
+    /// Unlike [object](Self::object), the pairs may be built at runtime and
+    /// every value becomes a separate argument of `OBJECT`, so commands are
+    /// evaluated by the server.
+    ///
+    /// ## Example
+    /// Insert a document with a generated id and the current time.
+    ///
+    /// ```
+    /// # #[cfg(feature = "dates-times")]
+    /// # unreql::example(|r, conn| {
+    /// let doc = r.object_pairs([("id", r.uuid(())), ("at", r.now())]);
+    /// r.table("events").insert(doc).run(conn)
+    /// # });
+    /// ```
+    ///
+    /// ## Example
+    /// Count the documents of the tables of a `Vec`.
+    ///
+    /// ```
+    /// # unreql::example(|r, conn| {
+    /// let tables = vec!["posts", "users"];
+    /// let counts = tables.iter().map(|&name| (name, r.table(name).count(())));
+    /// r.object_pairs(counts).run(conn)
+    /// // Result: {"posts": 12, "users": 3}
+    /// # })
+    /// ```
+    ///
+    /// # Related commands
+    /// - [object](Self::object)
+    /// - [array](Self::array)
+    pub fn object_pairs<K, V>(self, pairs: impl IntoIterator<Item = (K, V)>) -> Command
+    where
+        K: Into<String>,
+        V: Serialize + 'static,
+    {
+        pairs
+            .into_iter()
+            .fold(Command::new(TermType::Object), |cmd, (key, value)| {
+                cmd.with_arg(Command::from_json(key.into()))
+                    .with_arg(Command::from_json_2(value))
+            })
+    }
+}
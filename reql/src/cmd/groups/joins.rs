@@ -26,6 +26,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("marvel").inner_join(
     ///     r.table("dc"),
@@ -33,7 +34,7 @@ create_cmd!(
     ///         marvelRow.g("strength").lt(dcRow.g("strength"))
     ///     })
     /// ).zip().run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// (Compare this to an [outer_join](Self::outer_join) with the same inputs and predicate, which would
@@ -64,6 +65,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("marvel").outer_join(
     ///     r.table("dc"),
@@ -71,7 +73,7 @@ create_cmd!(
     ///         marvelRow.g("strength").lt(dcRow.g("strength"))
     ///     })
     /// ).zip().run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// (Compare this to an [inner_join](Self::inner_join) with the same inputs and predicate,
@@ -1,6 +1,6 @@
-use unreql_macros::create_cmd;
 use ql2::term::TermType;
 use serde::Serialize;
+use unreql_macros::create_cmd;
 
 use crate::{
     cmd::{args::ManyArgs, options::RandomOptions},
@@ -48,9 +48,10 @@ create_cmd!(
     /// Create a date one year from now.
     ///
     /// ```
+    /// # #[cfg(feature = "dates-times")]
     /// # unreql::example(|r, conn| {
     /// r.now().add(365 * 24 * 60 * 60).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -89,9 +90,10 @@ create_cmd!(
     /// Create a date one year ago today.
     ///
     /// ```
+    /// # #[cfg(feature = "dates-times")]
     /// # unreql::example(|r, conn| {
     /// r.now().sub(365*24*60*60).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -100,10 +102,11 @@ create_cmd!(
     /// ```
     /// # use unreql::DateTime;
     /// # use time::OffsetDateTime;
+    /// # #[cfg(feature = "dates-times")]
     /// # unreql::example(|r, conn| {
     /// let date = DateTime::from(OffsetDateTime::now_utc());
     /// r.now().sub(date).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -2,15 +2,20 @@ mod accessing;
 mod administration;
 mod aggregation;
 mod databases;
+#[cfg(feature = "dates-times")]
 mod dates_times;
 mod documents;
+#[cfg(feature = "geospatial")]
 mod geospatial;
+#[cfg(feature = "joins")]
 mod joins;
+#[cfg(feature = "math-logic")]
 mod math_logic;
 mod other;
 mod selecting;
+#[cfg(feature = "strings")]
 mod strings;
 mod structures;
 mod tables;
 mod transformations;
-mod writing;
+pub(crate) mod writing;
@@ -10,13 +10,44 @@ create_cmd!(maxval);
 create_cmd!(asc(key: Serialize));
 create_cmd!(desc(key: Serialize));
 
-create_cmd!(
-    /// Array
-    array:MakeArray,
-    Serialize
-);
-
 impl r {
+    /// Construct an array from the given values, which may be commands.
+    ///
+    /// Unlike [expr](Self::expr), every item becomes a separate argument
+    /// of `MAKE_ARRAY`, so commands in the array are evaluated by the server.
+    ///
+    /// ## Example
+    /// Duplicate every element of the array doubled.
+    ///
+    /// ```
+    /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
+    /// # unreql::example(|r, conn| {
+    /// r.expr([1, 2, 3]).concat_map(func!(|x| r.array([x.clone(), x.mul(2)]))).run(conn)
+    /// // Result: [1, 2, 2, 4, 3, 6]
+    /// # });
+    /// ```
+    ///
+    /// ## Example
+    /// Build an array from a `Vec` of commands.
+    ///
+    /// ```
+    /// # unreql::example(|r, conn| {
+    /// let counts = vec![r.table("posts").count(()), r.table("users").count(())];
+    /// r.array(counts).run(conn)
+    /// # })
+    /// ```
+    pub fn array<T>(self, items: impl IntoIterator<Item = T>) -> Command
+    where
+        T: Serialize + 'static,
+    {
+        items
+            .into_iter()
+            .fold(Command::new(TermType::MakeArray), |cmd, item| {
+                cmd.with_arg(Command::from_json_2(item))
+            })
+    }
+
     pub fn index(self, arg: impl Serialize + 'static) -> Index {
         let obj = rjson!({
             "index": arg
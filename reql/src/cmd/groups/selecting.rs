@@ -1,13 +1,20 @@
+use std::collections::HashMap;
+
 use ql2::term::TermType;
-use serde::Serialize;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
 use unreql_macros::create_cmd;
 
 use crate::{
     cmd::{
         args::{Arg, ManyArgs, Opt},
-        options::{BetweenOptions, FilterOptions, Index, TableOptions},
+        filter_audit::FilterAudit,
+        options::{BetweenOptions, CompoundBound, FilterOptions, Index, TableOptions},
+        run,
     },
-    Command,
+    r,
+    types::{IndexStatus, TableInfo},
+    Command, Datum,
 };
 
 create_cmd!(
@@ -114,6 +121,46 @@ create_cmd!(
     get(key: Serialize)
 );
 
+create_cmd!(
+    /// Get a document by primary key or fall back to another value
+    /// if the document does not exist.
+    ///
+    /// This is a shortcut for `get(key).default(fallback)`: the fallback
+    /// can be a value, another query or a function, and it is evaluated
+    /// on the server only when `get` returns `null`.
+    ///
+    /// ## Example
+    /// Return a placeholder user if the user is missing.
+    ///
+    /// ```
+    /// # use unreql::rjson;
+    /// # unreql::example(|r, conn| {
+    /// r.table("users")
+    ///   .get_or_else(10, rjson!({ "id": 10, "name": "anonymous" }))
+    ///   .run(conn)
+    /// # })
+    /// ```
+    ///
+    /// ## Example
+    /// Look for the document in an archive table if it is not found.
+    ///
+    /// ```
+    /// # unreql::example(|r, conn| {
+    /// r.table("posts")
+    ///   .get_or_else(1, r.table("posts_archive").get(1))
+    ///   .run(conn)
+    /// # })
+    /// ```
+    ///
+    /// # Related commands
+    /// - [get](Self::get)
+    /// - [default](Self::default)
+    only_command,
+    get_or_else(key: Serialize, fallback: Serialize) {
+        self.get(key).default(fallback)
+    }
+);
+
 create_cmd!(
     /// Get all documents where the given value matches the value of the requested index.
     ///
@@ -144,6 +191,33 @@ create_cmd!(
     /// # })
     /// ```
     ///
+    /// ## Example
+    /// Collect the heroes of a team by the secondary index `team` into typed
+    /// documents. Pass several keys with [r.args](crate::r::args).
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize)]
+    /// struct Hero {
+    ///     name: String,
+    ///     team: String,
+    /// }
+    ///
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let avengers: Vec<Hero> = r.table("marvel")
+    ///   .get_all(r.with_opt("avengers", r.index("team")))
+    ///   .exec_to_vec(conn)
+    ///   .await?;
+    /// let teams = r.args(["avengers", "x-men"]);
+    /// let heroes: Vec<Hero> = r.table("marvel")
+    ///   .get_all(r.with_opt(teams, r.index("team")))
+    ///   .exec_to_vec(conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
     /// *Note*: getAll does not perform any de-duplication. If you pass the same key more
     /// than once, the same document will be returned multiple times.
     ///
@@ -337,13 +411,15 @@ create_cmd!(
     ///
     /// While the `{field: value}` style of predicate is useful for exact matches, a more
     /// general way to write a predicate is to use the [row](Self::row) command with a comparison
-    /// operator such as [eq](Self::eq) or [gt](Self::gt), or to use an anonymous function that returns `true`
+    #[cfg_attr(feature = "math-logic", doc = " operator such as [eq](Self::eq) or [gt](Self::gt), or to use an anonymous function that returns `true`")]
+    #[cfg_attr(not(feature = "math-logic"), doc = " operator such as `eq` or `gt`, or to use an anonymous function that returns `true`")]
     /// or `false`.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(r.row().g("age").eq(30)).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// In this case, the predicate `r.row().g("age").eq(30)` returns `true` if the field
@@ -351,11 +427,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(func!(|user| {
     ///   user.g("age").eq(30)
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// Predicates to `filter` are evaluated on the server, and must use ReQL expressions.
@@ -369,31 +446,34 @@ create_cmd!(
     /// Get all users who are more than 18 years old.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(r.row().g("age").eq(18)).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
     /// Get all users who are less than 18 years old and more than 13 years old.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///   r.row().g("age").lt(18).and(r.row().g("age").gt(13))
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
     /// Get all users who are more than 18 years old or have their parental consent.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///   r.row().g("age").ge(18).and(r.row().g("hasParentalConsent"))
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # More complex predicates
@@ -404,6 +484,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "dates-times")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(func!(|user| {
     ///   user.g("subscriptionDate").during(
@@ -412,7 +493,7 @@ create_cmd!(
     ///     ()
     ///   )
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -420,11 +501,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "strings")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(func!(|user| {
     ///   user.g("email").match_("@gmail.com$")
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -503,6 +585,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::{func, rjson};
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(func!(|user| {
     ///   let left = user.clone().g("name").g("first").eq("William");
@@ -513,6 +596,7 @@ create_cmd!(
     ///
     /// // or
     ///
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(func!(|user| {
     ///   user.g("name").eq(rjson!({
@@ -520,7 +604,7 @@ create_cmd!(
     ///     "last": "Adama"
     ///   }))
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Handling missing fields
@@ -534,12 +618,13 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::cmd::options::FilterOptions;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(r.with_opt(
     ///   r.row().g("age").lt(18),
     ///   FilterOptions { default: Some(true) }
     /// )).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -573,13 +658,14 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(func!(|user| {
     ///   let editor = user.clone().g("role").eq("editor").default(false);
     ///   let admin = user.g("role").eq("admin").default(false);
     ///   editor.or(admin)
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// Instead of using the `default` optional argument to `filter`, we have to use
@@ -598,3 +684,406 @@ create_cmd!(
     only_command,
     filter(predicate: Arg<FilterOptions>)
 );
+
+impl Command {
+    /// Rewrite `filter` calls that can be served by an index of the table.
+    ///
+    /// A `filter` directly on the table described by `info` whose predicate
+    /// is a literal object with a single field compared against a literal
+    /// value is turned into `get_all` on the primary key, or on a secondary
+    /// index of `indexes` returning that field. Any other query is returned
+    /// unchanged.
+    ///
+    /// Only ready secondary indexes whose function is a plain field access
+    /// are used: a multi, geo or computed index would give other results
+    /// than the `filter`. `indexes` is the result of
+    /// [index_status](Self::index_status) on the table.
+    ///
+    /// A table named without its database is the table of `default_db`, the
+    /// database the query runs on (see [Options::db](crate::cmd::run::Options::db)
+    /// and [Session::use_](crate::Session::use_)). It is only rewritten if
+    /// `default_db` is the database of `info`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, rjson, Session};
+    /// # use unreql::types::{IndexStatus, TableInfo};
+    /// # use serde_json::Value;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let info: TableInfo = r.table("users").exec_info(conn).await?;
+    /// let indexes: Vec<IndexStatus> = r.table("users").index_status(()).exec(conn).await?;
+    /// // becomes r.table("users").get_all("bob@example.com", {"index": "email"})
+    /// // if `email` is an index on the `email` field
+    /// let users: Vec<Value> = r.table("users")
+    ///   .filter(rjson!({"email": "bob@example.com"}))
+    ///   .optimize_with_indexes(&info, &indexes, "test")
+    ///   .exec_to_vec(conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [filter](Self::filter)
+    /// - [get_all](Self::get_all)
+    /// - [index_status](Self::index_status)
+    pub fn optimize_with_indexes(
+        mut self,
+        info: &TableInfo,
+        indexes: &[IndexStatus],
+        default_db: &str,
+    ) -> Command {
+        if let Some(cmd) = filter_to_get_all(&self, info, indexes, default_db) {
+            return cmd;
+        }
+        for arg in self.mut_args().iter_mut() {
+            let cmd = std::mem::replace(arg, Command::new(TermType::Datum));
+            *arg = cmd.optimize_with_indexes(info, indexes, default_db);
+        }
+        self
+    }
+}
+
+impl Command {
+    /// Get all documents between two bounds of a compound index.
+    ///
+    /// A [CompoundBound::prefix] bound is padded with `r.minval()` or
+    /// `r.maxval()`, so it covers every key starting with the prefix, which
+    /// is easy to get wrong with a plain [between](Self::between).
+    ///
+    /// ## Example
+    /// Get all the documents whose `[year, month]` key is from March 2020
+    /// to the end of 2021.
+    ///
+    /// ```
+    /// # use unreql::cmd::options::CompoundBound;
+    /// # unreql::example(|r, conn| {
+    /// r.table("posts")
+    ///   .between_compound(
+    ///     CompoundBound::exact((2020, 3)),
+    ///     CompoundBound::prefix((2021,)),
+    ///     "year_month",
+    ///   )
+    ///   .run(conn)
+    /// # })
+    /// ```
+    ///
+    /// # Related commands
+    /// - [between](Self::between)
+    pub fn between_compound(
+        self,
+        lower: CompoundBound,
+        upper: CompoundBound,
+        index: impl Into<String>,
+    ) -> Command {
+        let (lower, left_bound) = lower.into_key(false);
+        let (upper, right_bound) = upper.into_key(true);
+        let opts = BetweenOptions {
+            index: Some(index.into()),
+            left_bound,
+            right_bound,
+        };
+        self.between(lower, upper, opts)
+    }
+}
+
+impl Command {
+    /// [filter](Self::filter) that also counts the documents lacking the
+    /// fields read by the predicate
+    ///
+    /// A document missing a field used by the predicate is silently
+    /// skipped by `filter`, and the server does not report it. The audit
+    /// runs a second query counting the documents of the sequence that
+    /// lack any of `fields`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use serde_json::Value;
+    /// # #[cfg(feature = "math-logic")]
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let audited = r.table("users")
+    ///   .filter_audit(r.row().g("age").gt(18), ["age"])
+    ///   .exec_filtered_with_audit::<Value>(conn)
+    ///   .await?;
+    /// println!("{} users, {} without age", audited.items.len(), audited.missing);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [filter](Self::filter)
+    /// - [has_fields](Self::has_fields)
+    pub fn filter_audit<I, S>(self, predicate: Command, fields: I) -> FilterAudit
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let fields = fields.into_iter().map(Into::into).collect();
+        FilterAudit::new(self, predicate, fields)
+    }
+}
+
+// Maximum number of keys sent in a single `get_all` by `get_many`
+const GET_MANY_CHUNK_SIZE: usize = 1000;
+
+impl Command {
+    /// Get documents by primary keys preserving the order of the keys.
+    ///
+    /// The documents are fetched with [get_all](Self::get_all) (in chunks
+    /// for large key lists) and then sorted on the client side so that the
+    /// `n`-th element of the result belongs to the `n`-th key. Missing
+    /// documents are returned as `None`, and duplicated keys map to the
+    /// same document.
+    ///
+    /// `primary_key` is the name of the primary key field of the table
+    /// (see [TableInfo::primary_key]).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # #[derive(serde::Deserialize)]
+    /// # struct User;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let users: Vec<Option<User>> = r.table("users")
+    ///   .get_many(&[3, 1, 2], "id", conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [get](Self::get)
+    /// - [get_all](Self::get_all)
+    pub async fn get_many<T>(
+        self,
+        keys: &[impl Serialize],
+        primary_key: &str,
+        arg: impl run::Arg + Clone,
+    ) -> crate::Result<Vec<Option<T>>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        let keys = keys
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut docs = Vec::with_capacity(keys.len());
+        for chunk in keys.chunks(GET_MANY_CHUNK_SIZE) {
+            let query = self.clone().get_all(r.args(chunk.to_vec()));
+            docs.extend(query.exec_to_vec::<Value>(arg.clone()).await?);
+        }
+        order_by_keys(docs, &keys, primary_key)
+            .into_iter()
+            .map(|doc| doc.map(serde_json::from_value).transpose())
+            .collect::<Result<_, _>>()
+            .map_err(Into::into)
+    }
+}
+
+fn order_by_keys(docs: Vec<Value>, keys: &[Value], primary_key: &str) -> Vec<Option<Value>> {
+    let by_key: HashMap<String, Value> = docs
+        .into_iter()
+        .filter_map(|doc| Some((key_string(doc.get(primary_key)?), doc)))
+        .collect();
+    keys.iter()
+        .map(|key| by_key.get(&key_string(key)).cloned())
+        .collect()
+}
+
+// The key as a string to match on, with the numbers as floats: the server
+// has only one number type, and sends `1.0` back as `1`
+fn key_string(key: &Value) -> String {
+    fn normalize(value: &Value) -> Value {
+        match value {
+            Value::Number(number) => number
+                .as_f64()
+                .and_then(serde_json::Number::from_f64)
+                .map_or_else(|| value.clone(), Value::Number),
+            Value::Array(items) => items.iter().map(normalize).collect(),
+            value => value.clone(),
+        }
+    }
+    normalize(key).to_string()
+}
+
+fn filter_to_get_all(
+    cmd: &Command,
+    info: &TableInfo,
+    indexes: &[IndexStatus],
+    default_db: &str,
+) -> Option<Command> {
+    if cmd.typ() != TermType::Filter || cmd.opts().is_some() {
+        return None;
+    }
+    let args: Vec<_> = cmd.args().iter().collect();
+    let [table, predicate] = args.as_slice() else {
+        return None;
+    };
+    if !is_info_table(table, info, default_db) {
+        return None;
+    }
+    let Some(Ok(Datum::Object(fields))) = literal_datum(predicate) else {
+        return None;
+    };
+    if fields.len() != 1 {
+        return None;
+    }
+    let (field, value) = fields.iter().next()?;
+    let value = Command::from(scalar_datum(value)?.clone());
+    let table = (*table).clone();
+    if *field == info.primary_key {
+        return Some(table.get_all(value));
+    }
+    let index = indexes.iter().find(|status| {
+        status.ready && !status.multi && !status.geo && field_index(status).as_ref() == Some(field)
+    })?;
+    Some(table.get_all(r.with_opt(value, r.index(index.index.clone()))))
+}
+
+fn is_info_table(cmd: &Command, info: &TableInfo, default_db: &str) -> bool {
+    if cmd.typ() != TermType::Table {
+        return false;
+    }
+    match cmd.args().iter().collect::<Vec<_>>().as_slice() {
+        [name] => default_db == info.db.name && literal_str(name) == Some(info.name.as_str()),
+        [db, name] => {
+            db.typ() == TermType::Db
+                && db.args().len() == 1
+                && literal_str(&db.args()[0]) == Some(info.db.name.as_str())
+                && literal_str(name) == Some(info.name.as_str())
+        }
+        _ => false,
+    }
+}
+
+// The field returned by the index function if it only reads a field, the
+// server prints it as `indexCreate('name', function(var1) { return var1("field"); })`
+fn field_index(status: &IndexStatus) -> Option<String> {
+    let (_, function) = status.query.as_deref()?.split_once("function(")?;
+    let (var, body) = function.split_once(')')?;
+    let body = body.trim_start().strip_prefix('{')?.trim_start();
+    let body = body.strip_prefix("return")?.trim_start();
+    let body = body.strip_prefix(var.trim())?.strip_prefix('(')?;
+    let mut fields = serde_json::Deserializer::from_str(body).into_iter::<String>();
+    let field = fields.next()?.ok()?;
+    let rest = body[fields.byte_offset()..]
+        .trim_start()
+        .strip_prefix(')')?;
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix(';').unwrap_or(rest).trim_start();
+    let rest = rest.strip_prefix('}')?.trim_start().strip_prefix(')')?;
+    rest.trim().is_empty().then_some(field)
+}
+
+fn literal_datum(cmd: &Command) -> &Option<crate::Result<Datum>> {
+    if cmd.typ() != TermType::Datum {
+        return &None;
+    }
+    cmd.datum()
+}
+
+fn literal_str(cmd: &Command) -> Option<&str> {
+    match literal_datum(cmd) {
+        Some(Ok(Datum::String(string))) => Some(string),
+        Some(Ok(Datum::Value(Value::String(string)))) => Some(string),
+        _ => None,
+    }
+}
+
+// Only scalars are safe to rewrite: arrays would change meaning
+// on compound and multi indexes.
+fn scalar_datum(datum: &Datum) -> Option<&Datum> {
+    match datum {
+        Datum::Bool(_) | Datum::Number(_) | Datum::String(_) => Some(datum),
+        Datum::Value(value) if value.is_boolean() || value.is_number() || value.is_string() => {
+            Some(datum)
+        }
+        Datum::Command(cmd) => match literal_datum(cmd) {
+            Some(Ok(datum)) => scalar_datum(datum),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fake_server::answering;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn get_many() {
+        let users = r.table("users");
+        let session = answering(vec![(
+            users.clone().get_all(r.args([3, 1, 2])),
+            r#"{"t":2,"r":[{"id":1},{"id":3}]}"#,
+        )])
+        .await;
+        let found = users
+            .get_many::<Value>(&[3, 1, 2], "id", &session)
+            .await
+            .unwrap();
+        assert_eq!(
+            found,
+            [Some(json!({"id": 3})), Some(json!({"id": 1})), None]
+        );
+    }
+
+    #[test]
+    fn order_by_keys_shuffled() {
+        let docs = vec![json!({"id": 3}), json!({"id": 1}), json!({"id": 2})];
+        let keys = [json!(1), json!(2), json!(3)];
+        let ordered = order_by_keys(docs, &keys, "id");
+        assert_eq!(
+            ordered,
+            [
+                Some(json!({"id": 1})),
+                Some(json!({"id": 2})),
+                Some(json!({"id": 3}))
+            ]
+        );
+    }
+
+    #[test]
+    fn order_by_keys_missing_and_duplicates() {
+        let docs = vec![json!({"email": "b", "n": 2}), json!({"email": "a", "n": 1})];
+        let keys = [json!("a"), json!("x"), json!("b"), json!("a")];
+        let ordered = order_by_keys(docs, &keys, "email");
+        assert_eq!(
+            ordered,
+            [
+                Some(json!({"email": "a", "n": 1})),
+                None,
+                Some(json!({"email": "b", "n": 2})),
+                Some(json!({"email": "a", "n": 1})),
+            ]
+        );
+    }
+
+    #[test]
+    fn order_by_keys_numbers() {
+        let docs = vec![json!({"id": 1}), json!({"id": [2, 0.5]})];
+        let keys = [json!(1.0), json!([2.0, 0.5]), json!("1")];
+        let ordered = order_by_keys(docs, &keys, "id");
+        assert_eq!(
+            ordered,
+            [Some(json!({"id": 1})), Some(json!({"id": [2, 0.5]})), None]
+        );
+    }
+
+    #[test]
+    fn order_by_keys_compound() {
+        let docs = vec![json!({"id": [2, "b"]}), json!({"id": [1, "a"]})];
+        let keys = [json!([1, "a"]), json!([2, "b"])];
+        let ordered = order_by_keys(docs, &keys, "id");
+        assert_eq!(
+            ordered,
+            [Some(json!({"id": [1, "a"]})), Some(json!({"id": [2, "b"]}))]
+        );
+    }
+}
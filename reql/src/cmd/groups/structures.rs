@@ -2,12 +2,14 @@ use ql2::term::TermType;
 use serde::Serialize;
 use unreql_macros::create_cmd;
 
+#[cfg(feature = "http")]
+use crate::cmd::options::HttpOptions;
+#[cfg(feature = "js")]
+use crate::cmd::options::JsOptions;
 use crate::{
-    cmd::{
-        args::{Arg, DoArgs, ManyArgs},
-        options::{HttpOptions, JsOptions},
-    },
-    Command,
+    cmd::args::{Arg, DoArgs, ManyArgs},
+    proto::{to_json, Datum},
+    r, Command,
 };
 
 create_cmd!(
@@ -53,11 +55,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("players").get(3).do_(func!(|player| {
     ///   player.clone().g("gross_score").sub(player.g("course_handicap"))
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -65,6 +68,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.do_(
     ///   r.args((
@@ -79,7 +83,7 @@ create_cmd!(
     ///     })
     ///   ))
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// *Note* that `branch`, the ReQL conditional command, must be used
@@ -90,6 +94,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::{func, rjson};
+    /// # #[cfg(all(feature = "dates-times", feature = "math-logic"))]
     /// # unreql::example(|r, conn| {
     /// let new_data = rjson!({
     ///   "id": 100,
@@ -104,7 +109,7 @@ create_cmd!(
     ///     r.table("log").insert(rjson!({"time": r.now(), "response": doc, "result": "error"})),
     ///   )
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -130,20 +135,22 @@ create_cmd!(
     /// Test the value of x.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// let x = 10;
     /// r.branch(r.expr(x).gt(5), "big", "small").run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
     /// Test the value of x.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// let x = 10;
     /// r.expr(x).gt(5).branch("big", "small").run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -161,6 +168,7 @@ create_cmd!(
     /// Categorize heroes by victory counts.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("marvel").map(
     ///   r.branch_ext(r.args([
@@ -171,7 +179,7 @@ create_cmd!(
     ///     r.row().g("name").add(" is a very nice")
     ///   ]))
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// To use for simple if-then-else see [branch](Self::branch).
@@ -264,6 +272,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("marvel").get("IronMan").do_(func!(|ironman| {
     ///     r.branch(
@@ -272,13 +281,50 @@ create_cmd!(
     ///         ironman
     ///     )
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
+    /// # Related commands
+    /// - [error_json](Self::error_json)
     only_root,
     error(message: Serialize)
 );
 
+impl r {
+    /// Throw a [user error](crate::Runtime::User) whose message is
+    /// `payload` serialized as compact JSON.
+    ///
+    /// Read the payload back from the error with
+    /// [Error::user_payload](crate::Error::user_payload), e.g. to return
+    /// typed failures from the guards of a `branch` or a `replace`.
+    ///
+    /// ## Example
+    /// Refuse to withdraw more than the balance of an account.
+    ///
+    /// ```
+    /// # use unreql::{func, rjson};
+    /// # #[cfg(feature = "math-logic")]
+    /// # unreql::example(|r, conn| {
+    /// r.table("accounts").get(1).update(func!(|account| {
+    ///     r.branch(
+    ///         account.clone().g("balance").ge(100),
+    ///         rjson!({ "balance": account.g("balance").sub(100) }),
+    ///         r.error_json(rjson!({ "code": "INSUFFICIENT_FUNDS", "missing": 100 })),
+    ///     )
+    /// })).run(conn)
+    /// # });
+    /// ```
+    ///
+    /// # Related commands
+    /// - [error](Self::error)
+    pub fn error_json(self, payload: impl Serialize) -> Command {
+        match to_json(&payload) {
+            Ok(message) => self.error(message.to_string()),
+            Err(error) => (Err(error) as crate::Result<Datum>).into(),
+        }
+    }
+}
+
 create_cmd!(
     /// Provide a default value in case of non-existence errors.
     ///
@@ -332,32 +378,35 @@ create_cmd!(
     /// (i.e., the field `age` is missing or equals `null`).
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///     r.row().g("age").lt(18).default(true)
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// One more way to write the previous query is to set the age
     /// to be `-1` when the field is missing.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///     r.row().g("age").default(-1).lt(18)
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// This can be accomplished with `has_fields` rather than `default`.
     ///
     /// ```
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(
     ///     r.row().has_fields("age").not().or(r.row().g("age").lt(18))
     /// ).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// The body of every `filter` is wrapped in an implicit `.default(false)`.
@@ -365,12 +414,13 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::cmd::options::FilterOptions;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("users").filter(r.with_opt(
     ///     r.row().g("age").lt(18),
     ///     FilterOptions::new().default(true)
     /// )).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -396,6 +446,7 @@ create_cmd!(
     default(value_or_function: Serialize)
 );
 
+#[cfg(feature = "js")]
 create_cmd!(
     /// Create a javascript expression.
     ///
@@ -595,6 +646,7 @@ create_cmd!(
     to_json:ToJsonString
 );
 
+#[cfg(feature = "http")]
 create_cmd!(
     /// Retrieve data from the specified URL over HTTP.
     ///
@@ -1,13 +1,14 @@
-use unreql_macros::create_cmd;
 use ql2::term::TermType;
 use serde::Serialize;
+use unreql_macros::create_cmd;
 
 use crate::{
     cmd::{
         args::{Arg, ManyArgs, Opt},
-        options,
+        options, run,
     },
-    Command,
+    types::IndexStatus,
+    Command, Driver, Func,
 };
 
 create_cmd!(
@@ -25,9 +26,37 @@ create_cmd!(
     /// # Related commands
     /// - [table_create](Self::table_create)
     /// - [table_drop](Self::table_drop)
+    /// - [table_exists](Self::table_exists)
     table_list,
 );
 
+create_cmd!(
+    /// Check whether a table exists in a database. The result is a boolean.
+    ///
+    /// This is a shortcut for `table_list().contains(table_name)`, so the
+    /// list of tables is checked on the server instead of being fetched.
+    ///
+    /// ## Example
+    /// Create the table ‘dc_universe’ unless it exists.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let exists: bool = r.db("heroes").table_exists("dc_universe").exec(conn).await?;
+    /// if !exists {
+    ///     r.db("heroes").table_create("dc_universe").exec::<serde_json::Value>(conn).await?;
+    /// }
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [table_list](Self::table_list)
+    /// - [table_create](Self::table_create)
+    table_exists(table_name: Serialize) {
+        self.table_list().contains(table_name)
+    }
+);
+
 create_cmd!(
     /// Create a table. A RethinkDB table is a collection of JSON documents.
     ///
@@ -196,7 +225,8 @@ create_cmd!(
     /// ```
     ///
     /// A geospatial index field should contain only geometry objects.
-    /// It will work with geometry ReQL terms ([get_intersecting](Command::get_intersecting) and [get_nearest](Command::get_nearest))
+    #[cfg_attr(feature = "geospatial", doc = " It will work with geometry ReQL terms ([get_intersecting](Command::get_intersecting) and [get_nearest](Command::get_nearest))")]
+    #[cfg_attr(not(feature = "geospatial"), doc = " It will work with geometry ReQL terms (`get_intersecting` and `get_nearest`)")]
     /// as well as index-specific terms ([index_status](Command::index_status), [index_wait](Command::index_wait), [index_drop](Command::index_drop) and [index_list](Command::index_list)).
     /// Using terms that rely on non-geometric ordering such as getAll, orderBy and between will result in an error.
     ///
@@ -294,6 +324,51 @@ create_cmd!(
     index_create(index: ManyArgs<options::IndexCreateOptions>)
 );
 
+impl Command {
+    /// Create a new secondary index and wait for it to be ready
+    ///
+    /// Takes the same arguments as [index_create](Self::index_create) and
+    /// returns once the index can be used by queries, with its status.
+    /// Both are run as one query.
+    ///
+    /// ## Example
+    /// Create an index on the `author` field and query it right away.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let status = r.table("comments").index_create_and_wait("author", conn).await?;
+    /// assert!(status.ready);
+    /// let comments: Vec<serde_json::Value> = r
+    ///   .table("comments")
+    ///   .get_all(r.with_opt("alice", r.index("author")))
+    ///   .exec_to_vec(conn)
+    ///   .await?;
+    /// # Ok(()) }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [index_create](Self::index_create)
+    /// - [index_wait](Self::index_wait)
+    pub async fn index_create_and_wait(
+        self,
+        index: impl ManyArgs<options::IndexCreateOptions>,
+        arg: impl run::Arg,
+    ) -> crate::Result<IndexStatus> {
+        let create = self.clone().index_create(index);
+        // the first argument after the table is the name of the index
+        let Some(name) = create.args().get(1).cloned() else {
+            let msg = "index_create_and_wait needs the name of the index";
+            return Err(Driver::Other(msg.into()).into());
+        };
+        let wait = self.index_wait(name).nth(0);
+        create
+            .do_(Func::new(vec![], wait).into_cmd())
+            .exec(arg)
+            .await
+    }
+}
+
 create_cmd!(
     /// Delete a previously created secondary index of this table.
     ///
@@ -449,3 +524,80 @@ create_cmd!(
     only_command,
     index_wait(index: ManyArgs<()>)
 );
+
+create_cmd!(
+    /// Set a write hook on a table, a function run on every write to it
+    /// (RethinkDB 2.3+).
+    ///
+    /// The function receives three arguments: the context of the write
+    /// (with the `primary_key` of the document and the `timestamp` of the
+    /// write), the old value of the document and its new value. It returns
+    /// the value to write instead of the new one, or `null` to delete the
+    /// document. The hook is not run for writes with the `ignore_write_hook`
+    /// option, which needs the `config` permission.
+    ///
+    /// Pass `null` instead of a function to remove the hook of the table.
+    ///
+    /// The command returns an object with a `created`, `replaced` or
+    /// `deleted` field set to `1`.
+    ///
+    /// ## Example
+    /// Keep the time of the last modification of the documents.
+    ///
+    /// ```
+    /// # use unreql::{func, rjson};
+    /// # unreql::example(|r, conn| {
+    /// r.table("comments")
+    ///   .set_write_hook(func!(|context, _old_val, new_val| {
+    ///     new_val.merge(rjson!({ "modified_at": context.g("timestamp") }))
+    ///   }))
+    ///   .run(conn)
+    /// # })
+    /// ```
+    ///
+    /// ## Example
+    /// Remove the write hook of the table.
+    ///
+    /// ```
+    /// # use serde_json::Value;
+    /// # unreql::example(|r, conn| {
+    /// r.table("comments").set_write_hook(Value::Null).run(conn)
+    /// # })
+    /// ```
+    ///
+    /// # Related commands
+    /// - [get_write_hook](Self::get_write_hook)
+    only_command,
+    set_write_hook(function: Serialize)
+);
+
+create_cmd!(
+    /// Get the write hook of a table.
+    ///
+    /// The result is `null` if the table has no write hook, otherwise an
+    /// object that can be read as a [WriteHook](crate::types::WriteHook):
+    ///
+    /// ```text
+    /// {
+    ///     function: <binary>,
+    ///     query: "setWriteHook(function(_var1, _var2, _var3) { ... })"
+    /// }
+    /// ```
+    ///
+    /// ## Example
+    /// Get the write hook of the comments table.
+    ///
+    /// ```
+    /// # use unreql::types::WriteHook;
+    /// # async fn example(conn: &unreql::Session) -> unreql::Result<()> {
+    /// # use unreql::r;
+    /// let hook: Option<WriteHook> = r.table("comments").get_write_hook().exec(conn).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [set_write_hook](Self::set_write_hook)
+    only_command,
+    get_write_hook,
+);
@@ -1,13 +1,17 @@
 use ql2::term::TermType;
-use serde::Serialize;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
 use unreql_macros::create_cmd;
 
 use crate::{
     cmd::{
         args::{ManyArgs, OneAndSecondOptionalArg},
-        options::{Index, SliceOptions, UnionOptions},
+        options::{BetweenOptions, Index, SliceOptions, Status, UnionOptions},
+        run,
     },
-    Command,
+    r, tools,
+    types::KeysetPage,
+    Command, Datum, Driver,
 };
 
 create_cmd!(
@@ -25,12 +29,13 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.expr([1, 2, 3, 4, 5])
     ///   .map(func!(|val| val.clone().mul(val)))
     ///   .run(conn)
     /// // Result: [1, 4, 9, 16, 25]
-    /// # })
+    /// # });
     /// ```
     ///
     /// Example
@@ -38,6 +43,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// let sequence1 = [100, 200, 300, 400];
     /// let sequence2 = [10, 20, 30, 40];
@@ -47,7 +53,7 @@ create_cmd!(
     ///   }))))
     ///   .run(conn)
     /// // Result: [1, 4, 9, 16, 25]
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -151,6 +157,61 @@ create_cmd!(
     with_fields(selector: ManyArgs<()>)
 );
 
+impl Command {
+    /// [with_fields](Self::with_fields) on the fields of the struct `T`
+    ///
+    /// The serialized names of the fields are used, so `#[serde(rename)]`
+    /// is taken into account. Documents missing any of the fields are
+    /// filtered out, even the fields with `#[serde(default)]`.
+    ///
+    /// ## Example
+    /// Get a list of users and their posts, excluding any users who have
+    /// not made any posts.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// #[derive(serde::Deserialize)]
+    /// struct UserPosts {
+    ///     id: u32,
+    ///     user: String,
+    ///     posts: Vec<u32>,
+    /// }
+    ///
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let users: Vec<UserPosts> = r.table("users").exec_with_fields(conn).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [with_fields](Self::with_fields)
+    /// - [exec_with_fields](Self::exec_with_fields)
+    pub fn with_fields_of<T>(self) -> Command
+    where
+        T: DeserializeOwned,
+    {
+        match tools::struct_fields::<T>() {
+            Some(fields) => self.with_fields(r.args(fields.to_vec())),
+            None => {
+                let msg = format!(
+                    "`{}` is not a struct with named fields",
+                    std::any::type_name::<T>()
+                );
+                let error: crate::Result<Value> = Err(Driver::Other(msg).into());
+                self.with_fields(Command::from(error))
+            }
+        }
+    }
+
+    /// Run [with_fields_of](Self::with_fields_of) and collect the results
+    pub async fn exec_with_fields<T>(self, arg: impl run::Arg) -> crate::Result<Vec<T>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        self.with_fields_of::<T>().exec_to_vec(arg).await
+    }
+}
+
 create_cmd!(
     /// Concatenate one or more elements into a single sequence using a mapping function.
     ///
@@ -161,22 +222,24 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.expr([1, 2, 3]).map(func!(|x| r.array([x.clone(), x.mul(2)]))).run(conn)
     /// // Result
     /// // [[1, 2], [2, 4], [3, 6]]
-    /// # })
+    /// # });
     /// ```
     ///
     /// Whereas `concat_map` with the same mapping function would merge those sequences into one:
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.expr([1, 2, 3]).concat_map(func!(|x| r.array([x.clone(), x.mul(2)]))).run(conn)
     /// // Result
     /// // [1, 2, 2, 4, 3, 6]
-    /// # })
+    /// # });
     /// ```
     ///
     /// The return value, array or stream, will be the same type as the input.
@@ -193,7 +256,8 @@ create_cmd!(
     /// ```
     ///
     /// ## Example
-    /// Simulate an [eq_join](Self::eq_join) using `concat_map`.
+    #[cfg_attr(feature = "joins", doc = " Simulate an [eq_join](Self::eq_join) using `concat_map`.")]
+    #[cfg_attr(not(feature = "joins"), doc = " Simulate an `eq_join` using `concat_map`.")]
     /// (This is how ReQL joins are implemented internally.)
     ///
     /// ```
@@ -234,7 +298,8 @@ create_cmd!(
     /// and the primary key (e.g., `r.index('id')`).
     ///
     /// Sorting functions passed to `order_by` must be deterministic. You cannot,
-    /// for instance, order rows using the [random](Self::random) command.
+    #[cfg_attr(feature = "math-logic", doc = " for instance, order rows using the [random](Self::random) command.")]
+    #[cfg_attr(not(feature = "math-logic"), doc = " for instance, order rows using the `random` command.")]
     /// Using a non-deterministic function with `order_by` will raise
     /// a `ReqlQueryLogicError`.
     ///
@@ -322,6 +387,8 @@ create_cmd!(
     ///
     /// *Note*: You cannot specify multiple orders in a compound index.
     /// See [issue #2306](https://github.com/rethinkdb/rethinkdb/issues/2306) to track progress.
+    /// Passing several orderings to `r.index` (e.g. `r.index([r.asc("date"), r.desc("title")])`)
+    /// is rejected by the driver and the query fails to serialize.
     ///
     /// ## Example: If you have a sequence with fewer documents than
     /// the `arrayLimit`, you can order it by multiple fields without an index.
@@ -371,13 +438,14 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts")
     ///   .index_create(r.args(("votes", func!(|post| {
     ///     post.clone().g("upvotes").sub(post.g("downvotes"))
     ///   }))))
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -386,26 +454,28 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("small_table")
     ///   .order_by(func!(|doc| {
     ///     doc.clone().g("upvotes").sub(doc.g("downvotes"))
     ///   }))
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// You can also select a descending ordering:
     ///
     /// ```
     /// # use unreql::func;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("small_table")
     ///   .order_by(r.desc(func!(|doc| {
     ///     doc.clone().g("upvotes").sub(doc.g("downvotes"))
     ///   })))
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -414,6 +484,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::cmd::options::BetweenOptions;
+    /// # #[cfg(feature = "dates-times")]
     /// # unreql::example(|r, conn| {
     /// let opts = BetweenOptions {
     ///   index: Some("date".into()),
@@ -423,7 +494,7 @@ create_cmd!(
     ///   .between(r.time(2013, 1, 1, "+00:00"), r.time(2013, 1, 1, "+00:00"), opts)
     ///   .order_by(r.index("date"))
     ///   .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// # Related commands
@@ -431,9 +502,40 @@ create_cmd!(
     /// - [limit](Self::limit)
     /// - [slice](Self::slice)
     only_command,
-    order_by(key_or_function: ManyArgs<Index>)
+    order_by(key_or_function: ManyArgs<Index>) {
+        let cmd = key_or_function.with_cmd(Command::new(TermType::OrderBy));
+        reject_compound_orders(cmd).with_parent(self)
+    }
 );
 
+const COMPOUND_ORDERS_ERROR: &str = "cannot specify multiple orders in a compound index; \
+    use `r.index(\"name\")` or `r.index(r.desc(\"name\"))` with a single ordering";
+
+// RethinkDB only accepts a single index name (optionally wrapped
+// by `r.asc`/`r.desc`) in the `index` option of `order_by`.
+fn reject_compound_orders(mut cmd: Command) -> Command {
+    let index = match cmd.opts() {
+        Some(Ok(Datum::Object(opts))) => opts.get("index"),
+        _ => None,
+    };
+    if index.map(is_array_datum).unwrap_or_default() {
+        cmd.set_opts(Err(Driver::Other(COMPOUND_ORDERS_ERROR.into()).into()));
+    }
+    cmd
+}
+
+fn is_array_datum(datum: &Datum) -> bool {
+    match datum {
+        Datum::Array(_) | Datum::Value(Value::Array(_)) => true,
+        Datum::Command(cmd) => match cmd.typ() {
+            TermType::MakeArray => true,
+            TermType::Datum => matches!(cmd.datum(), Some(Ok(datum)) if is_array_datum(datum)),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
 create_cmd!(
     /// Skip a number of elements from the head of the sequence.
     ///
@@ -580,6 +682,130 @@ create_cmd!(
     slice(start_end_offset: OneAndSecondOptionalArg<SliceOptions>)
 );
 
+impl Command {
+    /// Get a page of `page_size` documents ordered by `index`, the pages
+    /// counting from zero
+    ///
+    /// This runs `order_by(index).slice(start, end)` with the bounds of
+    /// the page and collects the results.
+    ///
+    /// Offset pagination is simple but the server still reads and skips
+    /// all the documents before the page, so the deeper the page, the
+    /// slower the query. Documents inserted or deleted between two
+    /// requests also shift the pages, so a document can be seen twice or
+    /// missed. Prefer ranges of the index, with
+    /// [between](Self::between), to walk through a large table.
+    ///
+    /// ## Example
+    /// The third page of the scoreboard, best players first.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use serde_json::Value;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let players: Vec<Value> = r.table("players")
+    ///   .page(r.index(r.desc("score")), 2, 20, conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [order_by](Self::order_by)
+    /// - [slice](Self::slice)
+    pub async fn page<T>(
+        self,
+        index: Index,
+        page_number: usize,
+        page_size: usize,
+        arg: impl run::Arg,
+    ) -> crate::Result<Vec<T>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        let start = page_number.saturating_mul(page_size);
+        let end = start.saturating_add(page_size);
+        self.order_by(index)
+            .slice(r.args((start, end)))
+            .exec_to_vec(arg)
+            .await
+    }
+
+    /// Get the `page_size` documents coming after `last_key` in the
+    /// order of `index`
+    ///
+    /// This runs `between(last_key, r.maxval(), index).order_by(index).limit(page_size)`
+    /// with the left bound open. Pass `r.minval()` as `last_key` for the
+    /// first page, then the [next](KeysetPage::next) key of each page.
+    ///
+    /// Unlike [page](Self::page), the server reads only the documents of
+    /// the page however far it is, and writes between two requests
+    /// don't shift the pages. The next key is read from the field named
+    /// like the index in the last document, so `index` must be the primary
+    /// key or a simple secondary index on a field of the same name, with
+    /// unique values.
+    ///
+    /// ## Example
+    /// Walk through all the users, 100 at a time.
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # use serde_json::Value;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let mut page = r.table("users")
+    ///   .paginate_after::<Value>("id", r.minval(), 100, conn)
+    ///   .await?;
+    /// while let Some(last_key) = page.next {
+    ///     // ...
+    ///     page = r.table("users")
+    ///       .paginate_after("id", last_key, 100, conn)
+    ///       .await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [between](Self::between)
+    /// - [order_by](Self::order_by)
+    /// - [limit](Self::limit)
+    pub async fn paginate_after<T>(
+        self,
+        index: &str,
+        last_key: impl Serialize + 'static,
+        page_size: usize,
+        arg: impl run::Arg,
+    ) -> crate::Result<KeysetPage<T>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        let opts = BetweenOptions::new()
+            .index(index.to_owned())
+            .left_bound(Status::Open);
+        let docs: Vec<Value> = self
+            .between(last_key, r.maxval(), opts)
+            .order_by(r.index(index.to_owned()))
+            .limit(page_size)
+            .exec_to_vec(arg)
+            .await?;
+        let next = match docs.last() {
+            Some(doc) if docs.len() >= page_size => match doc.get(index) {
+                Some(key) => Some(key.clone()),
+                None => {
+                    let msg = format!("no `{index}` field to resume after in {doc}");
+                    return Err(Driver::Other(msg).into());
+                }
+            },
+            _ => None,
+        };
+        let items = docs
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()?;
+        Ok(KeysetPage { items, next })
+    }
+}
+
 create_cmd!(
     /// Get the `nth` element of a sequence, counting from zero. If the argument
     /// is negative, count from the last element.
@@ -656,6 +882,11 @@ create_cmd!(
 create_cmd!(
     /// Test if a sequence is empty.
     ///
+    /// Prefer it to `count(()).eq(0)` to check whether a sequence has any
+    /// element: the server stops reading at the first one instead of
+    /// counting them all. Use [exec_bool](Self::exec_bool) to get the
+    /// result directly.
+    ///
     /// ## Example
     /// Are there any documents in the marvel table?
     ///
@@ -665,8 +896,25 @@ create_cmd!(
     /// # })
     /// ```
     ///
+    /// ## Example
+    /// Is there any hero with more than 100 victories?
+    ///
+    /// ```
+    /// # use unreql::{r, Session};
+    /// # #[cfg(feature = "math-logic")]
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let none = r.table("marvel")
+    ///   .filter(r.row().g("victories").gt(100))
+    ///   .is_empty()
+    ///   .exec_bool(conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
     /// # Related commands
     /// - [offsets_of](Self::offsets_of)
+    /// - [exec_bool](Self::exec_bool)
     only_command,
     is_empty,
 );
@@ -763,3 +1011,103 @@ create_cmd!(
     only_command,
     sample(number: Serialize)
 );
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fake_server::answering;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Player {
+        id: u32,
+        score: u32,
+    }
+
+    const PAGE: &str = r#"{"t":2,"r":[{"id":5,"score":50},{"id":6,"score":40}]}"#;
+
+    #[tokio::test]
+    async fn page() {
+        let expected = r
+            .table("players")
+            .order_by(r.index(r.desc("score")))
+            .slice(r.args((4, 6)));
+        let session = answering(vec![(expected, PAGE)]).await;
+        let players: Vec<Player> = r
+            .table("players")
+            .page(r.index(r.desc("score")), 2, 2, &session)
+            .await
+            .unwrap();
+        assert_eq!(
+            players,
+            [Player { id: 5, score: 50 }, Player { id: 6, score: 40 }]
+        );
+    }
+
+    #[tokio::test]
+    async fn first_page() {
+        let expected = r
+            .table("players")
+            .order_by(r.index("id"))
+            .slice(r.args((0, 10)));
+        let session = answering(vec![(expected, r#"{"t":2,"r":[]}"#)]).await;
+        let players: Vec<Player> = r
+            .table("players")
+            .page(r.index("id"), 0, 10, &session)
+            .await
+            .unwrap();
+        assert!(players.is_empty());
+    }
+
+    // The query of `paginate_after("id", last_key, 2)`
+    fn after(last_key: impl Serialize + 'static) -> Command {
+        let opts = BetweenOptions::new()
+            .index("id".to_owned())
+            .left_bound(Status::Open);
+        r.table("players")
+            .between(last_key, r.maxval(), opts)
+            .order_by(r.index("id"))
+            .limit(2)
+    }
+
+    #[tokio::test]
+    async fn paginate_after() {
+        let session = answering(vec![(after(4), PAGE)]).await;
+        let page = r
+            .table("players")
+            .paginate_after::<Player>("id", 4, 2, &session)
+            .await
+            .unwrap();
+        assert_eq!(
+            page.items,
+            [Player { id: 5, score: 50 }, Player { id: 6, score: 40 }]
+        );
+        assert_eq!(page.next, Some(json!(6)));
+    }
+
+    #[tokio::test]
+    async fn paginate_after_last_page() {
+        let response = r#"{"t":2,"r":[{"id":1,"score":10}]}"#;
+        let session = answering(vec![(after(r.minval()), response)]).await;
+        let page = r
+            .table("players")
+            .paginate_after::<Player>("id", r.minval(), 2, &session)
+            .await
+            .unwrap();
+        assert_eq!(page.items, [Player { id: 1, score: 10 }]);
+        assert_eq!(page.next, None);
+    }
+
+    #[tokio::test]
+    async fn paginate_after_missing_key() {
+        let response = r#"{"t":2,"r":[{"score":50},{"score":40}]}"#;
+        let session = answering(vec![(after(4), response)]).await;
+        let error = r
+            .table("players")
+            .paginate_after::<Player>("id", 4, 2, &session)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("no `id` field"), "{error}");
+    }
+}
@@ -1,11 +1,16 @@
 use ql2::term::TermType;
+use serde::{de::DeserializeOwned, Serialize};
+use tracing::warn;
 use unreql_macros::create_cmd;
 
 use crate::{
     cmd::{
         args::{Arg, ManyArgs, Opt},
-        options,
+        options, run,
     },
+    proto::Datum,
+    r,
+    types::WriteStatus,
     Command,
 };
 
@@ -265,11 +270,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::rjson;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts").get(1).update(rjson!({
     ///     "views": r.row().g("views").add(1),
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -277,11 +283,12 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::rjson;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts").get(1).update(rjson!({
     ///     "views": r.row().g("views").add(1).default(0),
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -290,6 +297,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::{func, rjson};
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts").get(1).update(func!(|post| {
     ///     r.branch(
@@ -298,7 +306,7 @@ create_cmd!(
     ///         rjson!({ "type": "normal" }),
     ///     )
     /// })).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -322,6 +330,11 @@ create_cmd!(
     /// ReqlRuntimeError: Could not prove function deterministic.  Maybe you want to use the non_atomic flag?
     /// ```
     ///
+    /// [update_non_atomic](Self::update_non_atomic) sets the flag for you.
+    /// Debug builds log a warning before running an update or a replace
+    /// whose value reads a table, or calls `r.js`, `r.http` or `r.random`,
+    /// without the flag.
+    ///
     /// ## Example
     /// Update the field `numComments` with a random value between 0 and 100.
     /// This update cannot be proven deterministic because of `r.js` (and in fact is not),
@@ -330,12 +343,13 @@ create_cmd!(
     /// ```
     /// # use unreql::cmd::options::UpdateOptions;
     /// # use unreql::rjson;
+    /// # #[cfg(feature = "js")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts").get(1).update(r.with_opt(
     ///     rjson!({ "numComments": r.js("Math.floor(Math.random()*100)") }),
     ///     UpdateOptions { non_atomic: Some(true), ..Default::default() },
     /// )).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -359,12 +373,13 @@ create_cmd!(
     /// ```
     /// # use unreql::cmd::options::UpdateOptions;
     /// # use unreql::rjson;
+    /// # #[cfg(feature = "math-logic")]
     /// # unreql::example(|r, conn| {
     /// r.table("posts").get(1).update(r.with_opt(
     ///     rjson!({ "views": r.row().g("views").add(1) }),
     ///     UpdateOptions { return_changes: Some(true.into()), ..Default::default() },
     /// )).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// The result will now include a changes field:
@@ -456,6 +471,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::rjson;
+    /// # #[cfg(feature = "dates-times")]
     /// # unreql::example(|r, conn| {
     /// let new_note = rjson!({
     ///     "date": r.now(),
@@ -465,7 +481,7 @@ create_cmd!(
     /// r.table("posts").get(1).update(rjson!(
     ///     { "notes": r.row().g("notes").append(new_note) }
     /// )).run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// This will fail if the `notes` field does not exist in the document.
@@ -486,6 +502,7 @@ create_cmd!(
     ///
     /// ```
     /// # use unreql::rjson;
+    /// # #[cfg(feature = "dates-times")]
     /// # unreql::example(|r, conn| {
     /// let icq_note = rjson!({
     ///    "date": r.now(),
@@ -496,7 +513,7 @@ create_cmd!(
     ///    .filter(r.row().has_fields(rjson!({ "contact": { "im": "icq" }})))
     ///    .update(rjson!({ "notes": r.row().g("notes").append(icq_note) }))
     ///    .run(conn)
-    /// # })
+    /// # });
     /// ```
     ///
     /// ## Example
@@ -517,10 +534,78 @@ create_cmd!(
     /// - [insert](Self::insert)
     /// - [replace](Self::replace)
     /// - [delete](Self::delete)
+    /// - [update_non_atomic](Self::update_non_atomic)
     only_command,
     update(object: Arg<options::UpdateOptions>)
 );
 
+impl Command {
+    /// [Update](Self::update) documents with the `non_atomic` flag set
+    ///
+    /// Needed when the new value reads other documents or is not
+    /// deterministic, e.g. calls `r.js`. The update is then not atomic:
+    /// the document may change between the read and the write.
+    ///
+    /// ## Example
+    /// Update the field `numComments` with the result of a sub-query.
+    ///
+    /// ```
+    /// # use unreql::rjson;
+    /// # unreql::example(|r, conn| {
+    /// r.table("posts").get(1).update_non_atomic(rjson!({
+    ///     "numComments": r.table("comments").filter(rjson!({ "idPost": 1 })).count(()),
+    /// })).run(conn)
+    /// # })
+    /// ```
+    ///
+    /// # Related commands
+    /// - [update](Self::update)
+    pub fn update_non_atomic(self, object: impl Serialize + 'static) -> Self {
+        let opts = options::UpdateOptions::new().non_atomic(true);
+        self.update(r.with_opt(object, opts))
+    }
+}
+
+// Terms the server cannot prove deterministic in the value of an update
+// or a replace
+const NON_ATOMIC_TERMS: &[TermType] = &[
+    TermType::Table,
+    TermType::Javascript,
+    TermType::Http,
+    TermType::Random,
+];
+
+/// Logs a warning for each update or replace of the query whose value
+/// needs the `non_atomic` flag but does not set it, the server would
+/// reject the query
+pub(crate) fn warn_non_atomic(query: &Command) {
+    for typ in missing_non_atomic(query) {
+        warn!(
+            term = ?typ,
+            "the value reads a subquery or is not deterministic, set the `non_atomic` flag"
+        );
+    }
+}
+
+fn missing_non_atomic(cmd: &Command) -> Vec<TermType> {
+    let mut found: Vec<_> = cmd.args().iter().flat_map(missing_non_atomic).collect();
+    let typ = cmd.typ();
+    if matches!(typ, TermType::Update | TermType::Replace)
+        && !non_atomic(cmd.opts())
+        && matches!(cmd.args().get(1), Some(value) if value.has_term(NON_ATOMIC_TERMS))
+    {
+        found.push(typ);
+    }
+    found
+}
+
+fn non_atomic(opts: &Option<crate::Result<Datum>>) -> bool {
+    match opts {
+        Some(Ok(Datum::Object(obj))) => matches!(obj.get("non_atomic"), Some(Datum::Bool(true))),
+        _ => false,
+    }
+}
+
 create_cmd!(
     /// Replace documents in a table.
     ///
@@ -769,6 +854,53 @@ create_cmd!(
     delete(opt: Opt<options::DeleteOptions>)
 );
 
+impl Command {
+    /// [Delete](Self::delete) documents and return the deleted ones
+    ///
+    /// The delete is run with `return_changes` set to `true`, whatever
+    /// `opts` sets it to, and the `old_val` of each change is returned.
+    /// The query fails with [WriteFailed](crate::Runtime::WriteFailed) if a
+    /// document could not be deleted.
+    ///
+    /// ## Example
+    /// Delete the comments of a post and keep them.
+    ///
+    /// ```
+    /// # use unreql::{r, rjson, Session};
+    /// # use unreql::cmd::options::DeleteOptions;
+    /// # use serde_json::Value;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let deleted: Vec<Value> = r.table("comments")
+    ///   .filter(rjson!({ "idPost": 3 }))
+    ///   .delete_returning(DeleteOptions::new(), conn)
+    ///   .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [delete](Self::delete)
+    pub async fn delete_returning<T>(
+        self,
+        opts: options::DeleteOptions,
+        arg: impl run::Arg,
+    ) -> crate::Result<Vec<T>>
+    where
+        T: Unpin + DeserializeOwned,
+    {
+        let opts = options::DeleteOptions {
+            return_changes: Some(true.into()),
+            ..opts
+        };
+        let status: WriteStatus<T> = self.delete(opts).exec(arg).await?;
+        let changes = status.check()?.changes.unwrap_or_default();
+        Ok(changes
+            .into_iter()
+            .filter_map(|change| change.old_val.into_option())
+            .collect())
+    }
+}
+
 create_cmd!(
     /// `sync` ensures that writes on a given table are written to permanent storage.
     ///
@@ -792,3 +924,27 @@ create_cmd!(
     only_command,
     sync,
 );
+
+#[cfg(all(test, feature = "js"))]
+mod test {
+    use super::*;
+    use crate::cmd::options::ReplaceOptions;
+    use serde_json::json;
+
+    #[test]
+    fn subquery_without_non_atomic() {
+        let posts = r.table("posts").get(1);
+        let count = r.table("comments").count(());
+        let query = posts.clone().update(json!({ "views": 1 }));
+        assert!(missing_non_atomic(&query).is_empty());
+        let query = posts.clone().update(count.clone());
+        assert_eq!(missing_non_atomic(&query), [TermType::Update]);
+        let query = posts.clone().update_non_atomic(count);
+        assert!(missing_non_atomic(&query).is_empty());
+        let query = posts.clone().replace(r.js("({id: 1})"));
+        assert_eq!(missing_non_atomic(&query), [TermType::Replace]);
+        let opts = ReplaceOptions::new().non_atomic(true);
+        let query = posts.replace(r.with_opt(r.js("({id: 1})"), opts));
+        assert!(missing_non_atomic(&query).is_empty());
+    }
+}
@@ -1,10 +1,10 @@
 use std::{borrow::Cow, collections::HashMap};
 
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_with::skip_serializing_none;
 use unreql_macros::{OptionsBuilder, WithOpts};
 
-use crate::Command;
+use crate::{r, Command};
 
 use super::args;
 
@@ -17,7 +17,7 @@ impl args::WithOpts for Index {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Durability {
     Hard,
@@ -32,6 +32,7 @@ pub enum ReadMode {
     Outdated,
 }
 
+#[cfg(feature = "math-logic")]
 #[skip_serializing_none]
 #[derive(Debug, Clone, Copy, Default, Serialize, WithOpts, OptionsBuilder)]
 pub struct RandomOptions {
@@ -153,11 +154,70 @@ pub enum Replicas {
     },
 }
 
+impl TableCreateOptions {
+    /// Use `replicas` replicas for every shard
+    pub fn replicas_int(self, replicas: u8) -> Self {
+        Self {
+            replicas: Some(Replicas::Int(replicas)),
+            ..self
+        }
+    }
+
+    /// Use the given number of replicas for each server tag, with the
+    /// primary replicas on the servers tagged `primary_replica_tag`
+    pub fn replicas_tagged<K>(
+        self,
+        replicas: impl IntoIterator<Item = (K, u8)>,
+        primary_replica_tag: impl Into<Cow<'static, str>>,
+    ) -> Self
+    where
+        K: Into<Cow<'static, str>>,
+    {
+        let replicas = replicas.into_iter().map(|(k, v)| (k.into(), v)).collect();
+        Self {
+            replicas: Some(Replicas::Map {
+                replicas,
+                primary_replica_tag: primary_replica_tag.into(),
+            }),
+            ..self
+        }
+    }
+
+    /// Check the replica settings the server would reject
+    ///
+    /// Called when the options are serialized, so `table_create` fails
+    /// with this error before the query is sent.
+    pub fn validate(&self) -> crate::Result<()> {
+        let error = |msg: String| Err(crate::Driver::Other(msg).into());
+        match &self.replicas {
+            Some(Replicas::Int(0)) => error("`replicas` must be at least 1".into()),
+            Some(Replicas::Map {
+                replicas,
+                primary_replica_tag,
+            }) => match replicas.get(primary_replica_tag) {
+                _ if replicas.is_empty() => error("`replicas` map must not be empty".into()),
+                None => error(format!(
+                    "`primary_replica_tag` `{}` is not a tag of the `replicas` map",
+                    primary_replica_tag
+                )),
+                Some(0) => error(format!(
+                    "`primary_replica_tag` `{}` must have at least 1 replica",
+                    primary_replica_tag
+                )),
+                Some(_) => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
 impl Serialize for TableCreateOptions {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
+        self.validate().map_err(serde::ser::Error::custom)?;
+
         #[derive(Serialize)]
         struct InnerOptions<'a> {
             #[serde(skip_serializing_if = "Option::is_none")]
@@ -257,6 +317,8 @@ pub enum Conflict {
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum ReturnChanges {
     Bool(bool),
+    /// Also return the changes of the documents that were not written,
+    /// with their [error](crate::types::Change::error)
     Always,
 }
 
@@ -368,6 +430,68 @@ pub enum Status {
     Closed,
 }
 
+/// A bound of [between_compound](Command::between_compound) on a compound index
+///
+/// A bound is included in the range unless it is made [open](Self::open).
+#[derive(Debug, Clone)]
+pub struct CompoundBound {
+    values: Vec<Command>,
+    prefix: bool,
+    status: Status,
+}
+
+impl CompoundBound {
+    /// The keys starting with `values`, e.g. `prefix((1,))` for all the
+    /// keys whose first item is 1
+    ///
+    /// `values` is a tuple, an array or a `Vec`.
+    pub fn prefix(values: impl Serialize) -> Self {
+        Self::new(values, true)
+    }
+
+    /// The key equal to `values`, e.g. `exact((1, "c"))`
+    pub fn exact(values: impl Serialize) -> Self {
+        Self::new(values, false)
+    }
+
+    fn new(values: impl Serialize, prefix: bool) -> Self {
+        let values = match crate::proto::to_json(&values) {
+            Ok(serde_json::Value::Array(values)) => {
+                values.into_iter().map(Command::from_json).collect()
+            }
+            value => vec![value.into()],
+        };
+        Self {
+            values,
+            prefix,
+            status: Status::Closed,
+        }
+    }
+
+    /// Exclude the bound from the range
+    pub fn open(mut self) -> Self {
+        self.status = Status::Open;
+        self
+    }
+
+    // A prefix is padded with one `MINVAL` or `MAXVAL`, which sorts before
+    // or after every key with this prefix however many items it has
+    pub(crate) fn into_key(self, upper: bool) -> (Command, Option<Status>) {
+        let Self {
+            mut values,
+            prefix,
+            status,
+        } = self;
+        if !prefix {
+            return (r.array(values), Some(status));
+        }
+        let after = upper == (status == Status::Closed);
+        values.push(if after { r.maxval() } else { r.minval() });
+        (r.array(values), None)
+    }
+}
+
+#[cfg(feature = "dates-times")]
 #[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Default, PartialEq, PartialOrd, WithOpts, OptionsBuilder)]
 pub struct DuringOptions {
@@ -445,12 +569,14 @@ pub struct FoldOptions {
     pub final_emit: Option<Command>,
 }
 
+#[cfg(feature = "js")]
 #[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Default, WithOpts, OptionsBuilder)]
 pub struct JsOptions {
     pub timeout: Option<f64>,
 }
 
+#[cfg(feature = "http")]
 #[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Default, WithOpts, OptionsBuilder)]
 pub struct HttpOptions {
@@ -485,6 +611,7 @@ pub struct HttpOptions {
     pub page_limit: Option<i64>,
 }
 
+#[cfg(feature = "http")]
 #[derive(Debug, Clone, Copy, Serialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum ResultFormat {
@@ -504,6 +631,7 @@ pub enum ResultFormat {
     Auto,
 }
 
+#[cfg(feature = "http")]
 #[derive(Debug, Clone, Copy, Serialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum HttpMethod {
@@ -515,6 +643,7 @@ pub enum HttpMethod {
     Head,
 }
 
+#[cfg(feature = "http")]
 #[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Default, WithOpts, OptionsBuilder)]
 pub struct HttpAuth {
@@ -527,6 +656,7 @@ pub struct HttpAuth {
     pub pass: Option<String>,
 }
 
+#[cfg(feature = "geospatial")]
 #[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Default, WithOpts, OptionsBuilder)]
 pub struct CircleOptions {
@@ -540,6 +670,7 @@ pub struct CircleOptions {
     pub fill: Option<bool>,
 }
 
+#[cfg(feature = "geospatial")]
 #[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Default, WithOpts, OptionsBuilder)]
 pub struct DistanceOptions {
@@ -547,6 +678,7 @@ pub struct DistanceOptions {
     pub unit: Option<String>,
 }
 
+#[cfg(feature = "geospatial")]
 #[skip_serializing_none]
 #[derive(Debug, Clone, Serialize, Default, WithOpts, OptionsBuilder)]
 pub struct GetNearestOptions {
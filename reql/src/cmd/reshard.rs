@@ -0,0 +1,289 @@
+//! Change the sharding and replication of a table after a dry run
+//!
+//! See [Command::reshard].
+
+use serde_json::Value;
+
+use crate::cmd::options::ReconfigureOptions;
+use crate::cmd::run;
+use crate::types::ReconfigureResult;
+use crate::{r, Command, Driver, Result};
+
+/// Reconfiguration of a table to a number of shards and replicas
+///
+/// Created by [Command::reshard].
+#[derive(Debug, Clone)]
+pub struct Reshard {
+    table: Command,
+    shards: u8,
+    replicas: u8,
+}
+
+impl Reshard {
+    pub(crate) fn new(table: Command, shards: u8, replicas: u8) -> Self {
+        Self {
+            table,
+            shards,
+            replicas,
+        }
+    }
+
+    /// Only report the configuration the table would get
+    pub fn dry_run(self) -> DryRun {
+        DryRun(self)
+    }
+
+    /// The `reconfigure` query
+    pub fn query(&self) -> Command {
+        self.table
+            .clone()
+            .reconfigure(r.with_opt((), self.options()))
+    }
+
+    /// Reconfigure the table right away
+    pub async fn exec_reconfigure(self, arg: impl run::Arg) -> Result<ReconfigureResult> {
+        self.query().exec(arg).await
+    }
+
+    fn options(&self) -> ReconfigureOptions {
+        ReconfigureOptions::new()
+            .shards(self.shards as i64)
+            .replicas(self.replicas as i64)
+    }
+
+    // Shard assignments of the proposed configuration that do not match
+    // the requested ones
+    fn errors(&self, result: &ReconfigureResult) -> Vec<String> {
+        let mut errors = Vec::new();
+        if result.config_changes.is_empty() {
+            errors.push("no configuration was proposed".to_owned());
+        }
+        for config in result.config_changes.iter() {
+            let shards = config
+                .new_value()
+                .and_then(|x| x.get("shards"))
+                .and_then(Value::as_array);
+            let Some(shards) = shards else {
+                errors.push("the proposed configuration has no shards".to_owned());
+                continue;
+            };
+            if shards.len() != self.shards as usize {
+                errors.push(format!(
+                    "{} shards proposed instead of {}",
+                    shards.len(),
+                    self.shards
+                ));
+            }
+            for (i, shard) in shards.iter().enumerate() {
+                if shard.get("primary_replica").is_none_or(Value::is_null) {
+                    errors.push(format!("shard {} has no primary replica", i));
+                }
+                let replicas = shard
+                    .get("replicas")
+                    .and_then(Value::as_array)
+                    .map_or(0, Vec::len);
+                if replicas != self.replicas as usize {
+                    errors.push(format!(
+                        "shard {} has {} replicas instead of {}",
+                        i, replicas, self.replicas
+                    ));
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// A [Reshard] that only reports the proposed configuration
+#[derive(Debug, Clone)]
+pub struct DryRun(Reshard);
+
+impl DryRun {
+    /// The `reconfigure` query with `dry_run`
+    pub fn query(&self) -> Command {
+        let opts = self.0.options().dry_run(true);
+        self.0.table.clone().reconfigure(r.with_opt((), opts))
+    }
+
+    pub async fn exec_reconfigure(self, arg: impl run::Arg) -> Result<DryRunReport> {
+        let result = self.query().exec(arg).await?;
+        Ok(DryRunReport {
+            reshard: self.0,
+            result,
+        })
+    }
+}
+
+/// The configuration proposed by a [DryRun]
+#[derive(Debug)]
+pub struct DryRunReport {
+    reshard: Reshard,
+    result: ReconfigureResult,
+}
+
+impl DryRunReport {
+    pub fn result(&self) -> &ReconfigureResult {
+        &self.result
+    }
+
+    /// Problems with the proposed shard assignment, e.g. shards without
+    /// a primary replica or with fewer replicas than requested
+    pub fn errors(&self) -> Vec<String> {
+        self.reshard.errors(&self.result)
+    }
+
+    /// Reconfigure the table if the dry run has no [errors](Self::errors)
+    /// and `confirm` accepts the proposed configuration
+    ///
+    /// Returns `None` if `confirm` declined it.
+    pub async fn apply<F>(self, arg: impl run::Arg, confirm: F) -> Result<Option<ReconfigureResult>>
+    where
+        F: FnOnce(&ReconfigureResult) -> bool,
+    {
+        let errors = self.errors();
+        if !errors.is_empty() {
+            let msg = format!("dry run failed; {}", errors.join("; "));
+            return Err(Driver::Other(msg).into());
+        }
+        if !confirm(&self.result) {
+            return Ok(None);
+        }
+        self.reshard.exec_reconfigure(arg).await.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_server::answering;
+    use serde_json::json;
+
+    fn report(config: Value) -> DryRunReport {
+        let result = json!({
+            "reconfigured": 0,
+            "config_changes": [{"old_val": {"shards": []}, "new_val": config}],
+        });
+        DryRunReport {
+            reshard: r.table("users").reshard(2, 2),
+            result: serde_json::from_value(result).unwrap(),
+        }
+    }
+
+    #[test]
+    fn valid_assignment() {
+        let report = report(json!({"shards": [
+            {"primary_replica": "a", "replicas": ["a", "b"], "nonvoting_replicas": []},
+            {"primary_replica": "b", "replicas": ["b", "a"], "nonvoting_replicas": []},
+        ]}));
+        assert!(report.errors().is_empty());
+    }
+
+    #[test]
+    fn failing_assignment() {
+        let report = report(json!({"shards": [
+            {"primary_replica": "a", "replicas": ["a"], "nonvoting_replicas": []},
+            {"primary_replica": null, "replicas": [], "nonvoting_replicas": []},
+            {"primary_replica": "b", "replicas": ["b", "a"], "nonvoting_replicas": []},
+        ]}));
+        assert_eq!(
+            report.errors(),
+            [
+                "3 shards proposed instead of 2",
+                "shard 0 has 1 replicas instead of 2",
+                "shard 1 has no primary replica",
+                "shard 1 has 0 replicas instead of 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_config() {
+        let mut report = report(json!(null));
+        assert_eq!(
+            report.errors(),
+            ["the proposed configuration has no shards"]
+        );
+        report.result.config_changes.clear();
+        assert_eq!(report.errors(), ["no configuration was proposed"]);
+    }
+
+    fn shard(primary: Value, replicas: Value) -> Value {
+        json!({"primary_replica": primary, "replicas": replicas, "nonvoting_replicas": []})
+    }
+
+    fn response(reconfigured: u32, shards: Vec<Value>) -> String {
+        let result = json!({
+            "reconfigured": reconfigured,
+            "config_changes": [{
+                "old_val": {"name": "users", "shards": [shard(json!("a"), json!(["a"]))]},
+                "new_val": {"name": "users", "shards": shards},
+            }],
+            "status_changes": [],
+        });
+        json!({"t": 1, "r": [result]}).to_string()
+    }
+
+    fn good_shards() -> Vec<Value> {
+        vec![
+            shard(json!("a"), json!(["a", "b"])),
+            shard(json!("b"), json!(["b", "a"])),
+        ]
+    }
+
+    // A session to a server answering the dry run with `dry_run` and the
+    // reconfiguration with `applied`
+    async fn server(dry_run: String, applied: Option<String>) -> crate::Session {
+        let reshard = r.table("users").reshard(2, 2);
+        let mut answers = vec![(reshard.clone().dry_run().query(), dry_run)];
+        answers.extend(applied.map(|x| (reshard.query(), x)));
+        answering(answers).await
+    }
+
+    #[tokio::test]
+    async fn applies_after_confirmation() {
+        let good = || response(0, good_shards());
+        let server = server(good(), Some(response(1, good_shards()))).await;
+        let reshard = r.table("users").reshard(2, 2).dry_run();
+        let report = reshard.exec_reconfigure(&server).await.unwrap();
+        assert_eq!(report.result().reconfigured, 0);
+        assert!(report.errors().is_empty());
+
+        let applied = report.apply(&server, |_| true).await.unwrap();
+        assert_eq!(applied.unwrap().reconfigured, 1);
+    }
+
+    #[tokio::test]
+    async fn declined() {
+        let server = server(response(0, good_shards()), None).await;
+        let reshard = r.table("users").reshard(2, 2).dry_run();
+        let report = reshard.exec_reconfigure(&server).await.unwrap();
+        let mut seen = false;
+        let applied = report
+            .apply(&server, |result| {
+                seen = result.config_changes.len() == 1;
+                false
+            })
+            .await
+            .unwrap();
+        assert!(seen);
+        assert!(applied.is_none());
+    }
+
+    #[tokio::test]
+    async fn failing_dry_run_is_not_applied() {
+        let shards = vec![
+            shard(json!("a"), json!(["a", "b"])),
+            shard(Value::Null, json!(["b"])),
+        ];
+        let server = server(response(0, shards), None).await;
+        let reshard = r.table("users").reshard(2, 2).dry_run();
+        let report = reshard.exec_reconfigure(&server).await.unwrap();
+        let error = report.apply(&server, |_| panic!("not confirmed")).await;
+        match error {
+            Err(crate::Error::Driver(Driver::Other(msg))) => {
+                assert!(msg.starts_with("dry run failed"))
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
@@ -1,25 +1,36 @@
 use super::args::Args;
+use super::cancel::CancelToken;
+use super::stream;
 use crate::cmd::options::{Durability, ReadMode};
-use crate::proto::{Command, Payload};
-use crate::{err, Connection, Result, Session};
+use crate::proto::{Command, Datum, Payload};
+use crate::types::QueryStats;
+use crate::{err, Connection, Direction, Event, Result, Session};
+use async_io::Timer;
+use async_net::TcpStream;
 use async_stream::try_stream;
 use async_trait::async_trait;
-use futures::io::{AsyncReadExt, AsyncWriteExt};
+use futures::channel::mpsc::TryRecvError;
+use futures::future::{self, Either};
+use futures::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
 use futures::stream::{Stream, StreamExt};
 use ql2::query::QueryType;
 use ql2::response::{ErrorType, ResponseType};
+use ql2::term::TermType;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::borrow::Cow;
+use std::pin::pin;
 use std::str;
 use std::sync::atomic::Ordering;
-use tracing::trace;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{trace, trace_span, Instrument};
 use unreql_macros::OptionsBuilder;
 
 const DATA_SIZE: usize = 4;
-const TOKEN_SIZE: usize = 8;
-const HEADER_SIZE: usize = DATA_SIZE + TOKEN_SIZE;
+pub(crate) const TOKEN_SIZE: usize = 8;
+pub(crate) const HEADER_SIZE: usize = DATA_SIZE + TOKEN_SIZE;
 
 #[derive(Deserialize, Debug)]
 #[allow(dead_code)]
@@ -30,6 +41,9 @@ pub(crate) struct Response {
     b: Option<Value>,
     p: Option<Value>,
     n: Option<Value>,
+    // the size of the body
+    #[serde(skip)]
+    size: usize,
 }
 
 impl Response {
@@ -41,6 +55,7 @@ impl Response {
             b: None,
             p: None,
             n: None,
+            size: 0,
         }
     }
 }
@@ -60,10 +75,43 @@ pub struct Options {
     pub durability: Option<Durability>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub group_format: Option<Format>,
+    /// What format to return binary data in. With `Raw` the server keeps
+    /// the `{"$reql_type$": "BINARY", "data": <base64>}` pseudotype as is.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub binary_format: Option<Format>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub noreply: Option<bool>,
+    /// Database to run the query against instead of the default one of
+    /// the session, for tables that are not prefixed with `r.db()`
     #[serde(skip_serializing_if = "Option::is_none")]
     pub db: Option<Db>,
+    /// Correlation id recorded in the driver's tracing spans for this query,
+    /// e.g. an application request id. It is never sent to the server.
+    #[serde(skip)]
+    pub tag: Option<Cow<'static, str>>,
+    /// How many times to run a read-only query again if the server fails it
+    /// with [OpFailed](crate::Availability::OpFailed) before returning any
+    /// results. Queries that write are never retried. Disabled by default.
+    #[serde(skip)]
+    pub retry_op_failed: Option<u32>,
+    /// How long to wait after each batch of a cursor before fetching the
+    /// next one, so background scans of large tables do not hog the
+    /// server. The pause starts once the batch is consumed. Never sent to
+    /// the server.
+    #[serde(skip)]
+    pub pace: Option<Duration>,
+    /// Stop the query when the token is cancelled, see
+    /// [cancel](super::cancel). Never sent to the server.
+    #[serde(skip)]
+    pub cancel: Option<CancelToken>,
+    /// How long the server may take to answer each request of the query,
+    /// the `START` and, for a cursor, the fetch of every further batch.
+    /// Past it, the query is stopped on the server and fails with
+    /// [QueryTimeout](crate::Driver::QueryTimeout). Not set by default,
+    /// e.g. a changefeed waits for changes as long as needed. Never sent
+    /// to the server.
+    #[serde(skip)]
+    pub timeout: Option<Duration>,
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -80,7 +128,7 @@ pub struct Db(pub Cow<'static, str>);
 pub(crate) const DEFAULT_DB: &str = "test";
 
 impl Options {
-    async fn default_db(self, session: &Session) -> Options {
+    pub(crate) async fn default_db(self, session: &Session) -> Options {
         let session_db = session.inner.db.lock().await;
         if self.db.is_none() && *session_db != DEFAULT_DB {
             return Self {
@@ -92,6 +140,18 @@ impl Options {
     }
 }
 
+/// One response of the server, read with the low-level query API of
+/// [Connection], e.g. [Connection::start_query]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Batch {
+    /// The results of the response, a single one for an atom
+    pub results: Vec<Value>,
+    /// Whether the server keeps the cursor open for more results, fetched
+    /// with [Connection::continue_query]
+    pub more: bool,
+}
+
 #[async_trait]
 pub trait Arg {
     async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, Options)>;
@@ -112,6 +172,22 @@ impl Arg for Connection {
     }
 }
 
+/// Runs the query under a new token of the same session, so the
+/// connection is not cloned and stays usable afterwards
+#[async_trait]
+impl Arg for &Connection {
+    async fn into_run_opts(self, _for_changes: bool) -> Result<(Connection, Options)> {
+        Ok((self.sibling()?, Default::default()))
+    }
+}
+
+#[async_trait]
+impl Arg for &mut Connection {
+    async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, Options)> {
+        (&*self).into_run_opts(for_changes).await
+    }
+}
+
 #[async_trait]
 impl Arg for Args<(&Session, Options)> {
     async fn into_run_opts(self, _for_changes: bool) -> Result<(Connection, Options)> {
@@ -129,6 +205,14 @@ impl Arg for Args<(Connection, Options)> {
     }
 }
 
+#[async_trait]
+impl Arg for Args<(&Connection, Options)> {
+    async fn into_run_opts(self, _for_changes: bool) -> Result<(Connection, Options)> {
+        let Args((conn, options)) = self;
+        Ok((conn.sibling()?, options))
+    }
+}
+
 #[async_trait]
 impl Arg for &mut Session {
     async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, Options)> {
@@ -137,21 +221,99 @@ impl Arg for &mut Session {
 }
 
 pub(crate) fn new<A, T>(query: Command, arg: A) -> impl Stream<Item = Result<T>>
+where
+    A: Arg,
+    T: Unpin + DeserializeOwned,
+{
+    profiled(query, arg, None)
+}
+
+// Runs the query like `new`, with `profile` set when `stats` is given and
+// the measures of the responses added to it
+pub(crate) fn profiled<A, T>(
+    query: Command,
+    arg: A,
+    stats: Option<Arc<Mutex<QueryStats>>>,
+) -> impl Stream<Item = Result<T>>
 where
     A: Arg,
     T: Unpin + DeserializeOwned,
 {
     try_stream! {
+        let started = Instant::now();
         let (mut conn, mut opts) = arg.into_run_opts(query.change_feed()).await?;
+        if let Some(stats) = &stats {
+            stats.lock().unwrap().wait_ms = millis(started.elapsed());
+            opts.profile = Some(true);
+        }
         opts = opts.default_db(&conn.session).await;
+        if opts.db.is_none() && conn.session.inner.db_required && query.uses_default_db() {
+            Err(err::Driver::NoDefaultDb)?;
+        }
+        if query.nests_implicit_var(0) {
+            Err(err::Error::Compile(NESTED_ROW.into()))?;
+        }
+        #[cfg(debug_assertions)]
+        super::groups::writing::warn_non_atomic(&query);
         let change_feed = query.change_feed();
         if change_feed {
             conn.session.inner.mark_change_feed();
         }
         let noreply = opts.noreply.unwrap_or_default();
+        let span = trace_span!("query", token = conn.token, tag = opts.tag.as_deref());
+        let mut retries = match opts.retry_op_failed {
+            Some(retries) if !query.is_write() => retries,
+            _ => 0,
+        };
+        let changes_indexes = query.has_term(super::validate::INDEX_WRITE_TERMS);
+        let pace = opts.pace;
+        let cancel = opts.cancel.clone();
+        let timeout = opts.timeout;
         let mut payload = Payload(QueryType::Start, Some(&query), opts);
         loop {
-            let (response_type, resp) = conn.request(&payload, noreply).await?;
+            if cancel.as_ref().is_some_and(CancelToken::is_cancelled) {
+                if payload.0 == QueryType::Continue {
+                    // the cursor is still open on the server
+                    conn.stop_abandoned().await;
+                }
+                Err(err::Driver::Cancelled)?;
+            }
+            let request = conn.request(&payload, noreply).instrument(span.clone());
+            let response = match (&cancel, timeout) {
+                (None, None) => Ok(request.await),
+                (cancel, timeout) => {
+                    let interrupted = interrupted(cancel.as_ref(), timeout);
+                    match future::select(pin!(request), pin!(interrupted)).await {
+                        Either::Left((response, _)) => Ok(response),
+                        Either::Right((error, _)) => Err(error),
+                    }
+                }
+            };
+            let response = match response {
+                Ok(response) => response,
+                Err(error) => {
+                    conn.stop_abandoned().await;
+                    Err(error)?
+                }
+            };
+            let (response_type, resp) = match response {
+                // nothing has been yielded yet while the query is starting
+                Err(error) if retries > 0 && error.is_retryable() && payload.0 == QueryType::Start => {
+                    retries -= 1;
+                    trace!("retrying query; token: {}, error: {}", conn.token, error);
+                    continue;
+                }
+                result => result?,
+            };
+            if let Some(stats) = &stats {
+                let mut stats = stats.lock().unwrap();
+                stats.add_batch(&resp.r, resp.p.as_ref(), resp.size);
+                stats.total_ms = millis(started.elapsed());
+            }
+            if changes_indexes && payload.0 == QueryType::Start {
+                // the indexes cached for `exec_validated` may be outdated
+                conn.session.inner.indexes.clear();
+            }
             trace!("yielding response; token: {}", conn.token);
             match response_type {
                 ResponseType::SuccessAtom => {
@@ -169,13 +331,13 @@ where
                     } else {
                         resp.r
                     };
-                    for val in serde_json::from_value::<Vec<T>>(atom_val)? {
+                    for val in results::<T>(atom_val)? {
                         yield val;
                     }
                     break;
                 },
                 ResponseType::SuccessSequence | ResponseType::ServerInfo => {
-                    for val in serde_json::from_value::<Vec<T>>(resp.r)? {
+                    for val in results::<T>(resp.r)? {
                         yield val;
                     }
                     break;
@@ -188,9 +350,13 @@ where
                         break;
                     }
                     payload = Payload(QueryType::Continue, None, Default::default());
-                    for val in serde_json::from_value::<Vec<T>>(resp.r)? {
+                    for val in results::<T>(resp.r)? {
                         yield val;
                     }
+                    if let Some(pace) = pace {
+                        trace!("pacing the cursor; token: {}, pace: {:?}", conn.token, pace);
+                        Timer::after(pace).await;
+                    }
                     continue;
                 }
                 ResponseType::WaitComplete => { break; }
@@ -207,6 +373,136 @@ where
     }
 }
 
+// Terms that change data or the cluster
+const WRITE_TERMS: &[TermType] = &[
+    TermType::Insert,
+    TermType::Update,
+    TermType::Replace,
+    TermType::Delete,
+    TermType::Sync,
+    TermType::DbCreate,
+    TermType::DbDrop,
+    TermType::TableCreate,
+    TermType::TableDrop,
+    TermType::IndexCreate,
+    TermType::IndexDrop,
+    TermType::IndexRename,
+    TermType::Reconfigure,
+    TermType::Rebalance,
+    TermType::Grant,
+    TermType::SetWriteHook,
+];
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+// Terms reading the default database when they are not given one, with
+// the number of arguments they take in that case
+const DEFAULT_DB_TERMS: &[(TermType, usize)] = &[
+    (TermType::Table, 1),
+    (TermType::TableCreate, 1),
+    (TermType::TableDrop, 1),
+    (TermType::TableList, 0),
+];
+
+// Terms returning the results of their first argument in the same order
+const ORDER_KEEPING_TERMS: &[TermType] = &[
+    TermType::Filter,
+    TermType::Map,
+    TermType::ConcatMap,
+    TermType::Pluck,
+    TermType::Without,
+    TermType::Merge,
+    TermType::WithFields,
+    TermType::HasFields,
+    TermType::Limit,
+    TermType::Skip,
+    TermType::Slice,
+    TermType::GetAll,
+    TermType::Between,
+    TermType::CoerceTo,
+];
+
+// The server resolves `r.row()` only inside a single function
+const NESTED_ROW: &str = "use func!(|doc| ...) instead of r.row() in subqueries";
+
+impl Command {
+    // Whether an `r.row()` is nested in more than one function, `funcs`
+    // being the number of functions around this term
+    fn nests_implicit_var(&self, funcs: usize) -> bool {
+        let funcs = match self.typ() {
+            TermType::ImplicitVar => return funcs > 1,
+            TermType::Func => funcs + 1,
+            _ => funcs,
+        };
+        matches!(self.datum(), Some(Ok(datum)) if datum.nests_implicit_var(funcs))
+            || matches!(self.opts(), Some(Ok(datum)) if datum.nests_implicit_var(funcs))
+            || self.args().iter().any(|arg| arg.nests_implicit_var(funcs))
+    }
+
+    pub(crate) fn has_term(&self, terms: &[TermType]) -> bool {
+        terms.contains(&self.typ())
+            || matches!(self.datum(), Some(Ok(datum)) if datum.has_term(terms))
+            || matches!(self.opts(), Some(Ok(datum)) if datum.has_term(terms))
+            || self.args().iter().any(|arg| arg.has_term(terms))
+    }
+
+    fn uses_default_db(&self) -> bool {
+        DEFAULT_DB_TERMS
+            .iter()
+            .any(|(typ, args)| *typ == self.typ() && self.args().len() <= *args)
+            || matches!(self.datum(), Some(Ok(datum)) if datum.uses_default_db())
+            || matches!(self.opts(), Some(Ok(datum)) if datum.uses_default_db())
+            || self.args().iter().any(Command::uses_default_db)
+    }
+
+    fn is_write(&self) -> bool {
+        self.has_term(WRITE_TERMS)
+    }
+
+    // Whether the results are read from a table in no particular order,
+    // with no `order_by` on the way
+    pub(crate) fn is_unordered_scan(&self) -> bool {
+        match self.typ() {
+            TermType::Table => true,
+            typ if ORDER_KEEPING_TERMS.contains(&typ) => {
+                matches!(self.args().front(), Some(arg) if arg.is_unordered_scan())
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Datum {
+    fn nests_implicit_var(&self, funcs: usize) -> bool {
+        match self {
+            Datum::Command(cmd) => cmd.nests_implicit_var(funcs),
+            Datum::Array(arr) => arr.iter().any(|datum| datum.nests_implicit_var(funcs)),
+            Datum::Object(obj) => obj.values().any(|datum| datum.nests_implicit_var(funcs)),
+            _ => false,
+        }
+    }
+
+    fn has_term(&self, terms: &[TermType]) -> bool {
+        match self {
+            Datum::Command(cmd) => cmd.has_term(terms),
+            Datum::Array(arr) => arr.iter().any(|datum| datum.has_term(terms)),
+            Datum::Object(obj) => obj.values().any(|datum| datum.has_term(terms)),
+            _ => false,
+        }
+    }
+
+    fn uses_default_db(&self) -> bool {
+        match self {
+            Datum::Command(cmd) => cmd.uses_default_db(),
+            Datum::Array(arr) => arr.iter().any(Datum::uses_default_db),
+            Datum::Object(obj) => obj.values().any(Datum::uses_default_db),
+            _ => false,
+        }
+    }
+}
+
 impl Payload<'_> {
     fn encode(&self, token: u64) -> Result<Vec<u8>> {
         let bytes = self.to_bytes()?;
@@ -219,92 +515,371 @@ impl Payload<'_> {
     }
 }
 
-impl Connection {
-    fn send_response(&self, db_token: u64, resp: Result<(ResponseType, Response)>) {
-        if let Some(tx) = self.session.inner.channels.get(&db_token) {
-            if let Err(error) = tx.unbounded_send(resp) {
-                if error.is_disconnected() {
-                    self.session.inner.channels.remove(&db_token);
+// The reader and the writer of the stream of a session
+pub(crate) fn split(stream: stream::Stream) -> (Reader, Writer) {
+    let tcp = stream.tcp().clone();
+    let (reader, writer) = stream.split();
+    (Reader::new(reader), Writer::new(writer, tcp))
+}
+
+/// Reads the responses of all the queries of a session
+///
+/// Whoever waits for a response reads the next frame from the stream and
+/// routes it to the channel of its token. Bytes are buffered as soon as
+/// they are read, so a caller that stops waiting never leaves a frame
+/// half read.
+#[derive(Debug)]
+pub(crate) struct Reader {
+    stream: ReadHalf<stream::Stream>,
+    buf: Vec<u8>,
+}
+
+impl Reader {
+    pub(crate) fn new(stream: ReadHalf<stream::Stream>) -> Self {
+        Self {
+            stream,
+            buf: Vec::new(),
+        }
+    }
+
+    async fn next_frame(&mut self, max_token: u64) -> Result<(u64, Vec<u8>)> {
+        loop {
+            if self.buf.len() >= HEADER_SIZE {
+                let token = u64::from_le_bytes(self.buf[..TOKEN_SIZE].try_into().unwrap());
+                trace!("db_token: {}", token);
+                if token > max_token {
+                    return Err(err::Driver::ConnectionBroken.into());
                 }
+                let len = u32::from_le_bytes(self.buf[TOKEN_SIZE..HEADER_SIZE].try_into().unwrap());
+                let end = HEADER_SIZE + len as usize;
+                if self.buf.len() >= end {
+                    let body = self.buf[HEADER_SIZE..end].to_vec();
+                    self.buf.drain(..end);
+                    trace!(
+                        "body read; db_token: {}, body: {}",
+                        token,
+                        crate::tools::bytes_to_string(&body),
+                    );
+                    return Ok((token, body));
+                }
+            }
+            let mut chunk = [0u8; 8192];
+            let len = self.stream.read(&mut chunk).await?;
+            if len == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
             }
+            self.buf.extend_from_slice(&chunk[..len]);
         }
     }
+}
 
-    pub(crate) async fn request<'a>(
-        &mut self,
-        query: &'a Payload<'a>,
-        noreply: bool,
-    ) -> Result<(ResponseType, Response)> {
-        self.submit(query, noreply).await;
-        match self.rx.lock().await.next().await {
-            Some(resp) => resp,
-            None => Ok((ResponseType::SuccessAtom, Response::new())),
+/// Writes the queries of a session
+///
+/// A query is queued whole before anything is written, and the bytes a
+/// dropped caller did not write are written by the next one, so frames
+/// are never cut.
+#[derive(Debug)]
+pub(crate) struct Writer {
+    stream: WriteHalf<stream::Stream>,
+    // the TCP stream under `stream`, to shut it down
+    tcp: TcpStream,
+    pending: Vec<u8>,
+}
+
+impl Writer {
+    pub(crate) fn new(stream: WriteHalf<stream::Stream>, tcp: TcpStream) -> Self {
+        Self {
+            stream,
+            tcp,
+            pending: Vec::new(),
+        }
+    }
+
+    pub(crate) fn tcp(&self) -> &TcpStream {
+        &self.tcp
+    }
+
+    pub(crate) fn shutdown(&self) -> std::io::Result<()> {
+        self.tcp.shutdown(std::net::Shutdown::Both)
+    }
+
+    fn queue(&mut self, frame: &[u8]) {
+        self.pending.extend_from_slice(frame);
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        while !self.pending.is_empty() {
+            let len = self.stream.write(&self.pending).await?;
+            if len == 0 {
+                return Err(std::io::ErrorKind::WriteZero.into());
+            }
+            self.pending.drain(..len);
         }
+        // a TLS stream buffers the records it writes
+        self.stream.flush().await
     }
+}
 
-    async fn submit<'a>(&self, query: &'a Payload<'a>, noreply: bool) {
-        let mut db_token = self.token;
-        let result = self.exec(query, noreply, &mut db_token).await;
-        self.send_response(db_token, result);
+// Counts the response of a query whose caller stopped waiting for it as
+// stale, so it is discarded instead of being taken for the response of
+// the next query with the same token
+struct Pending<'a> {
+    session: &'a Session,
+    token: u64,
+    epoch: u64,
+    armed: bool,
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        // after a reconnect the response will never come
+        if self.armed && self.epoch == self.session.inner.epoch() {
+            trace!("query dropped before its response; token: {}", self.token);
+            *self.session.inner.stale.entry(self.token).or_default() += 1;
+        }
+    }
+}
+
+impl Session {
+    // Sends a response to the connection waiting for it
+    async fn route(&self, db_token: u64, body: Vec<u8>) {
+        if self.take_stale(db_token) {
+            trace!("discarding a stale response; db_token: {}", db_token);
+            return;
+        }
+        let partial = is_partial(&body);
+        let delivered = match self.inner.channels.get(&db_token) {
+            Some(tx) => match tx.unbounded_send(Ok(body)) {
+                Err(error) if error.is_disconnected() => {
+                    drop(tx);
+                    self.inner.channels.remove(&db_token);
+                    false
+                }
+                _ => true,
+            },
+            None => false,
+        };
+        if !delivered && partial {
+            // the cursor is gone but the server keeps it open until stopped
+            self.stop_orphan(db_token).await;
+        }
+    }
+
+    // Stops the query of a token nobody reads anymore, without waiting
+    // for the response, which is discarded by `route`
+    async fn stop_orphan(&self, db_token: u64) {
+        trace!("stopping an orphaned cursor; db_token: {}", db_token);
+        let payload = Payload(QueryType::Stop, None, Default::default());
+        let buf = match payload.encode(db_token) {
+            Ok(buf) => buf,
+            Err(error) => {
+                trace!(
+                    "cannot encode STOP; db_token: {}, error: {}",
+                    db_token,
+                    error
+                );
+                return;
+            }
+        };
+        let mut writer = self.inner.writer.lock().await;
+        writer.queue(&buf);
+        if let Err(error) = writer.flush().await {
+            drop(writer);
+            trace!("cannot send STOP; db_token: {}, error: {}", db_token, error);
+            self.inner.mark_broken();
+        }
+    }
+
+    fn take_stale(&self, token: u64) -> bool {
+        let Some(mut stale) = self.inner.stale.get_mut(&token) else {
+            return false;
+        };
+        *stale -= 1;
+        drop(stale);
+        self.inner.stale.remove_if(&token, |_, stale| *stale == 0);
+        true
+    }
+}
+
+impl Connection {
+    // Stops the query of a cancelled or timed out request without
+    // waiting for the server. The responses of the request and of the
+    // `STOP` are discarded, the request counts as stale once it was sent.
+    async fn stop_abandoned(&self) {
+        if self.epoch != self.session.inner.epoch() {
+            return;
+        }
+        trace!("stopping an abandoned query; token: {}", self.token);
+        *self.session.inner.stale.entry(self.token).or_default() += 1;
+        self.session.stop_orphan(self.token).await;
+    }
+
+    // Reconnects a broken session with `auto_reconnect` and moves the
+    // connection to the new stream, returns whether it reconnected
+    async fn revive(&mut self, query: &Payload<'_>) -> Result<bool> {
+        let inner = &self.session.inner;
+        let mut reconnected = false;
+        if inner.auto_reconnect() {
+            let epoch = inner.epoch();
+            if inner.broken.load(Ordering::SeqCst) {
+                self.session.redial(epoch).await?;
+                reconnected = true;
+            }
+            // the cursor of the token was left on the old stream
+            let cursor = matches!(query.0, QueryType::Continue | QueryType::Stop);
+            if self.epoch != self.session.inner.epoch() && !cursor {
+                trace!("moving to the new stream; token: {}", self.token);
+                *self = self.sibling()?;
+            }
+        }
+        if self.epoch != self.session.inner.epoch() {
+            return Err(err::Driver::Reconnected.into());
+        }
+        Ok(reconnected)
     }
 
-    async fn exec<'a>(
-        &self,
+    pub(crate) async fn request<'a>(
+        &mut self,
         query: &'a Payload<'a>,
         noreply: bool,
-        db_token: &mut u64,
     ) -> Result<(ResponseType, Response)> {
-        let buf = query.encode(self.token)?;
-
-        let guard = self.session.inner.stream.lock().await;
-        let mut stream = guard.clone();
+        let mut reconnected = self.revive(query).await?;
+        // `buf` is recorded with the `record` feature
+        #[cfg_attr(not(feature = "record"), allow(unused_variables))]
+        let (buf, mut pending) = loop {
+            let buf = query.encode(self.token)?;
+            let mut pending = Pending {
+                session: &self.session,
+                token: self.token,
+                epoch: self.epoch,
+                armed: false,
+            };
 
-        trace!("sending query; token: {}, payload: {}", self.token, query);
-        stream.write_all(&buf).await?;
+            trace!("sending query; token: {}, payload: {}", self.token, query);
+            let mut writer = self.session.inner.writer.lock().await;
+            writer.queue(&buf);
+            pending.armed = !noreply;
+            let sent = writer.flush().await;
+            drop(writer);
+            self.session.inner.record(|| {
+                let event = Event::new(self.token, Direction::Sent, buf.len());
+                match &sent {
+                    Ok(_) => event,
+                    Err(error) => event.error(error),
+                }
+            });
+            let Err(error) = sent else {
+                break (buf, pending);
+            };
+            pending.armed = false;
+            self.session.inner.mark_broken();
+            drop(pending);
+            // the server did not get the query, it can be sent again
+            if reconnected || !self.session.inner.auto_reconnect() {
+                return Err(self.session.inner.with_events(error.into()));
+            }
+            trace!(
+                "sending the query again; token: {}, error: {}",
+                self.token,
+                error
+            );
+            reconnected = self.revive(query).await?;
+        };
         trace!("query sent; token: {}", self.token);
 
         if noreply {
             return Ok((ResponseType::SuccessAtom, Response::new()));
         }
 
-        trace!("reading header; token: {}", self.token);
-        let mut header = [0u8; HEADER_SIZE];
-        stream.read_exact(&mut header).await?;
-
-        let mut buf = [0u8; TOKEN_SIZE];
-        buf.copy_from_slice(&header[..TOKEN_SIZE]);
-        *db_token = {
-            let token = u64::from_le_bytes(buf);
-            trace!("db_token: {}", token);
-            if token > self.session.inner.token.load(Ordering::SeqCst) {
-                self.session.inner.mark_broken();
-                return Err(err::Driver::ConnectionBroken.into());
+        let body = self.receive().await;
+        pending.armed = false;
+        let body = match body {
+            Ok(Some(body)) => body,
+            Ok(None) => return Ok((ResponseType::SuccessAtom, Response::new())),
+            Err(error) => {
+                self.session
+                    .inner
+                    .record(|| Event::new(self.token, Direction::Received, 0).error(&error));
+                return Err(self.session.inner.with_events(error));
             }
-            token
         };
+        #[cfg(feature = "record")]
+        if let Some(recording) = &self.recording {
+            recording.record(self.token, &buf[HEADER_SIZE..], &body);
+        }
+        let result = self.parse_response(&body);
+        self.session.inner.record(|| {
+            let event = Event::new(self.token, Direction::Received, HEADER_SIZE + body.len());
+            match &result {
+                Ok((typ, _)) => event.response_type(typ),
+                Err(error) => event.error(error),
+            }
+        });
+        result
+    }
 
-        let mut buf = [0u8; DATA_SIZE];
-        buf.copy_from_slice(&header[TOKEN_SIZE..]);
-        let len = u32::from_le_bytes(buf) as usize;
-        trace!(
-            "header read; token: {}, db_token: {}, response_len: {}",
-            self.token,
-            db_token,
-            len
-        );
-
-        trace!("reading body; token: {}", self.token);
-        let mut buf = vec![0u8; len];
-        stream.read_exact(&mut buf).await?;
+    // Sends a query of the low-level API and returns the response as is
+    pub(crate) async fn batch(&mut self, query: &Payload<'_>, noreply: bool) -> Result<Batch> {
+        let (response_type, resp) = self.request(query, noreply).await?;
+        let more = match response_type {
+            ResponseType::SuccessPartial => true,
+            ResponseType::SuccessAtom
+            | ResponseType::SuccessSequence
+            | ResponseType::ServerInfo
+            | ResponseType::WaitComplete => false,
+            typ => {
+                let msg = error_message(resp.r)?;
+                return Err(response_error(typ, resp.e, msg));
+            }
+        };
+        let results = match resp.r {
+            Value::Array(results) => results,
+            result => vec![result],
+        };
+        Ok(Batch { results, more })
+    }
 
-        trace!(
-            "body read; token: {}, db_token: {}, body: {}",
-            self.token,
-            db_token,
-            crate::tools::bytes_to_string(&buf),
-        );
+    // Returns the body of the response, `None` if the connection was
+    // dropped
+    async fn receive(&self) -> Result<Option<Vec<u8>>> {
+        let mut rx = self.rx.lock().await;
+        loop {
+            let reader = self.session.inner.reader.lock();
+            let mut reader = match future::select(rx.next(), reader).await {
+                Either::Left((item, _)) => match item {
+                    Some(_) if self.session.take_stale(self.token) => continue,
+                    Some(body) => return body.map(Some),
+                    None => return Ok(None),
+                },
+                Either::Right((reader, next)) => {
+                    drop(next);
+                    reader
+                }
+            };
+            // the response may have been routed while waiting for the reader
+            match rx.try_recv() {
+                Ok(_) if self.session.take_stale(self.token) => continue,
+                Ok(body) => return body.map(Some),
+                Err(TryRecvError::Closed) => return Ok(None),
+                Err(TryRecvError::Empty) => {}
+            }
+            self.session.inner.broken()?;
+            trace!("reading a response; token: {}", self.token);
+            let max_token = self.session.inner.token.load(Ordering::SeqCst);
+            match reader.next_frame(max_token).await {
+                Ok((db_token, body)) => self.session.route(db_token, body).await,
+                Err(error) => {
+                    // the stream is lost, or out of sync
+                    self.session.inner.mark_broken();
+                    return Err(error);
+                }
+            }
+        }
+    }
 
-        let resp = serde_json::from_slice::<Response>(&buf)?;
+    fn parse_response(&self, buf: &[u8]) -> Result<(ResponseType, Response)> {
+        let mut resp = serde_json::from_slice::<Response>(buf)?;
+        resp.size = buf.len();
         trace!("response successfully parsed; token: {}", self.token,);
 
         let response_type = ResponseType::from_i32(resp.t)
@@ -319,6 +894,54 @@ impl Connection {
     }
 }
 
+// Waits until a request is abandoned, because `cancel` is cancelled or
+// `timeout` is exceeded, and returns why
+async fn interrupted(cancel: Option<&CancelToken>, timeout: Option<Duration>) -> err::Driver {
+    let cancelled = async {
+        match cancel {
+            Some(cancel) => cancel.cancelled().await,
+            None => future::pending().await,
+        }
+    };
+    let timer = match timeout {
+        Some(timeout) => Timer::after(timeout),
+        None => Timer::never(),
+    };
+    match future::select(pin!(cancelled), timer).await {
+        Either::Left(_) => err::Driver::Cancelled,
+        Either::Right(_) => err::Driver::QueryTimeout(timeout.unwrap_or_default()),
+    }
+}
+
+// Whether the body is a partial response, whose cursor is still open
+fn is_partial(body: &[u8]) -> bool {
+    #[derive(Deserialize)]
+    struct Head {
+        t: i32,
+    }
+    matches!(
+        serde_json::from_slice::<Head>(body),
+        Ok(Head { t }) if t == ResponseType::SuccessPartial as i32
+    )
+}
+
+// Deserializes the results of a response, an error keeps the result
+// that did not match `T`
+fn results<T: DeserializeOwned>(results: Value) -> Result<Vec<T>> {
+    match results {
+        Value::Array(results) => results
+            .iter()
+            .map(|result| {
+                T::deserialize(result).map_err(|error| err::Driver::json(error, result).into())
+            })
+            .collect(),
+        results => {
+            Ok(Vec::<T>::deserialize(&results)
+                .map_err(|error| err::Driver::json(error, &results))?)
+        }
+    }
+}
+
 fn error_message(response: Value) -> Result<String> {
     let messages = serde_json::from_value::<Vec<String>>(response)?;
     Ok(messages.join(" "))
@@ -346,3 +969,561 @@ fn response_error(response_type: ResponseType, error_type: Option<i32>, msg: Str
         _ => err::Driver::Other(format!("unexpected response: {}", msg)).into(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::options::{DeleteOptions, ReturnChanges};
+    use crate::fake_server::{answer, answering, next_query, read_query, scripted, send, session};
+    use crate::{r, rjson, Func};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn borrowed_connection_keeps_its_channel() {
+        let session = session(r#"{"t":1,"r":[1]}"#).await;
+        let mut conn = session.connection().unwrap();
+        assert_eq!(r.expr(1).exec::<u8>(&conn).await.unwrap(), 1);
+        assert_eq!(r.expr(1).exec::<u8>(&mut conn).await.unwrap(), 1);
+        let opts = Options::default().db("other");
+        let arg = crate::cmd::args::Args((&conn, opts));
+        assert_eq!(r.expr(1).exec::<u8>(arg).await.unwrap(), 1);
+
+        // only the channel of the borrowed connection is left
+        assert_eq!(session.inner.channels.len(), 1);
+        assert!(session.inner.channels.contains_key(&conn.token));
+
+        assert_eq!(r.expr(1).exec::<u8>(conn).await.unwrap(), 1);
+        assert!(session.inner.channels.is_empty());
+    }
+
+    #[tokio::test]
+    async fn server_info() {
+        let session = session(r#"{"t":5,"r":[{"id":"a","proxy":false,"name":"one"}]}"#).await;
+        assert_eq!(session.server().await.unwrap().id, "a");
+    }
+
+    #[tokio::test]
+    async fn server_info_unsupported() {
+        for body in [
+            r#"{"t":16,"r":["Unrecognized QueryType: 5."]}"#,
+            r#"{"t":5,"r":[{"version":"fork-1.0"}]}"#,
+            r#"{"t":5,"r":[]}"#,
+        ] {
+            let session = session(body).await;
+            match session.server().await {
+                Err(err::Error::Driver(err::Driver::Unsupported { query, .. })) => {
+                    assert_eq!(query, "SERVER_INFO");
+                }
+                other => panic!("unexpected result for {body}: {other:?}"),
+            }
+        }
+    }
+
+    #[cfg(feature = "math-logic")]
+    #[test]
+    fn write_queries() {
+        assert!(!r.table("users").get(1).is_write());
+        assert!(!r.table("users").filter(r.row().g("age").gt(18)).is_write());
+        assert!(r.table("users").get(1).delete(()).is_write());
+        assert!(r
+            .expr([1, 2])
+            .map(r.table("t").get(r.row()).delete(()))
+            .is_write());
+        assert!(r.table_create("users").is_write());
+        assert!(r.table("users").set_write_hook(Value::Null).is_write());
+        assert!(rjson!({ "result": r.table("t").insert(rjson!({})) }).is_write());
+    }
+
+    #[test]
+    fn default_db_queries() {
+        assert!(r.table("users").get(1).uses_default_db());
+        assert!(r.table_list().uses_default_db());
+        assert!(r.expr([1]).map(r.table("t").get(r.row())).uses_default_db());
+        assert!(!r.db("app").table("users").get(1).uses_default_db());
+        assert!(!r.db("app").table_list().uses_default_db());
+        assert!(!r.db_list().uses_default_db());
+        assert!(!r.expr(1).uses_default_db());
+    }
+
+    #[tokio::test]
+    async fn default_db_required() {
+        let mut session = session(r#"{"t":1,"r":[1]}"#).await;
+        Arc::get_mut(&mut session.inner).unwrap().db_required = true;
+        let err = r
+            .table("t")
+            .count(())
+            .exec::<u8>(&session)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, err::Error::Driver(err::Driver::NoDefaultDb)));
+
+        let count = r.db("app").table("t").count(()).exec::<u8>(&session);
+        assert_eq!(count.await.unwrap(), 1);
+        assert_eq!(r.expr(1).exec::<u8>(&session).await.unwrap(), 1);
+        let opts = Options::default().db("app");
+        let count = r
+            .table("t")
+            .count(())
+            .exec::<u8>(crate::cmd::args::Args((&session, opts)));
+        assert_eq!(count.await.unwrap(), 1);
+
+        session.use_("app").await;
+        let count = r.table("t").count(()).exec::<u8>(&session);
+        assert_eq!(count.await.unwrap(), 1);
+    }
+
+    #[cfg(feature = "math-logic")]
+    #[test]
+    fn nested_implicit_vars() {
+        assert!(!r
+            .table("users")
+            .filter(r.row().g("age").gt(18))
+            .nests_implicit_var(0));
+        let nested = r.expr([[1, 2]]).map(r.row().map(r.row().add(1)));
+        assert!(nested.nests_implicit_var(0));
+        let object = r
+            .table("users")
+            .map(rjson!({ "ids": r.row().g("posts").map(r.row().g("id")) }));
+        assert!(object.nests_implicit_var(0));
+    }
+
+    #[test]
+    fn unordered_scans() {
+        let users = || r.table("users");
+        assert!(users().is_unordered_scan());
+        assert!(users()
+            .filter(rjson!({ "age": 18 }))
+            .limit(5)
+            .is_unordered_scan());
+        assert!(!users().order_by("name").limit(5).is_unordered_scan());
+        assert!(!users()
+            .filter(rjson!({ "age": 18 }))
+            .count(())
+            .is_unordered_scan());
+        assert!(!r.expr([3, 1, 2]).is_unordered_scan());
+    }
+
+    #[cfg(feature = "math-logic")]
+    #[tokio::test]
+    async fn nested_row_is_rejected() {
+        let session = session(r#"{"t":1,"r":[1]}"#).await;
+        let err = r
+            .expr([[1, 2]])
+            .map(r.row().map(r.row().add(1)))
+            .exec::<Value>(&session)
+            .await
+            .unwrap_err();
+        assert!(matches!(&err, err::Error::Compile(msg) if msg == NESTED_ROW));
+        assert_eq!(r.expr(1).exec::<u8>(&session).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn row_in_function_subquery() {
+        let session = session(r#"{"t":1,"r":[null]}"#).await;
+        let is_nested_row =
+            |err: &err::Error| matches!(err, err::Error::Compile(msg) if msg == NESTED_ROW);
+
+        let query = r.table("users").filter(Func::build(|[user]: [Command; 1]| {
+            r.table("posts")
+                .filter(r.row().g("author").eq(user.g("id")))
+                .is_empty()
+        }));
+        let err = query.exec::<Value>(&session).await.unwrap_err();
+        assert!(is_nested_row(&err));
+
+        let query = r.table("users").filter(r.row().g("adult"));
+        query.exec::<Value>(&session).await.unwrap();
+        let query = r.table("users").filter(Func::build(|[user]: [Command; 1]| {
+            r.table("posts")
+                .filter(Func::build(|[post]: [Command; 1]| {
+                    post.g("author").eq(user.g("id"))
+                }))
+                .is_empty()
+        }));
+        query.exec::<Value>(&session).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn partial_response_streams_the_rest() {
+        // Answers START with a partial batch and CONTINUE with the last one
+        let session = scripted(|mut stream| async move {
+            for body in [r#"{"t":3,"r":[1,2]}"#, r#"{"t":2,"r":[3]}"#] {
+                let (token, _) = read_query(&mut stream).await;
+                send(&mut stream, token, body).await;
+            }
+        })
+        .await;
+
+        let items: Vec<u8> = r.table("t").exec_to_vec(&session).await.unwrap();
+        assert_eq!(items, [1, 2, 3]);
+        assert!(session.inner.channels.is_empty());
+    }
+
+    #[tokio::test]
+    async fn exec_array_collects_atom() {
+        let full = session(r#"{"t":1,"r":[[1,2,3]]}"#).await;
+        let items: Vec<u8> = r.table("t").get_all(1).exec_array(&full).await.unwrap();
+        assert_eq!(items, [1, 2, 3]);
+
+        let empty = session(r#"{"t":1,"r":[[]]}"#).await;
+        let items: Vec<u8> = r.table("t").get_all(1).exec_array(&empty).await.unwrap();
+        assert!(items.is_empty());
+    }
+
+    #[tokio::test]
+    async fn exec_count_with_predicate() {
+        let adults = || Func::build(|[user]: [Command; 1]| user.g("adult"));
+        let query = r.table("users").count(adults());
+        let session = answering(vec![(query, r#"{"t":1,"r":[2]}"#)]).await;
+        let count = r.table("users").exec_count(adults(), &session).await;
+        assert_eq!(count.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn delete_returning_old_docs() {
+        let comments = || r.table("comments").filter(rjson!({ "idPost": 3 }));
+        let returning = DeleteOptions::new().return_changes(ReturnChanges::Bool(true));
+        let deleting = answering(vec![(
+            comments().delete(returning),
+            r#"{"t":1,"r":[{"deleted":2,"errors":0,"inserted":0,"replaced":0,"skipped":0,"unchanged":0,
+            "changes":[{"new_val":null,"old_val":{"id":1}},{"new_val":null,"old_val":{"id":2}}]}]}"#,
+        )])
+        .await;
+        // `return_changes` is set whatever the options say
+        let opts = DeleteOptions::new().return_changes(ReturnChanges::Bool(false));
+        let deleted: Vec<Value> = comments().delete_returning(opts, &deleting).await.unwrap();
+        assert_eq!(
+            deleted,
+            [serde_json::json!({"id": 1}), serde_json::json!({"id": 2})]
+        );
+
+        let failing = session(
+            r#"{"t":1,"r":[{"deleted":0,"errors":1,"inserted":0,"replaced":0,"skipped":0,"unchanged":0,
+            "first_error":"write hook failed","changes":[]}]}"#,
+        )
+        .await;
+        let err = comments()
+            .delete_returning::<Value>(DeleteOptions::new(), &failing)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("write hook failed"));
+    }
+
+    #[tokio::test]
+    async fn index_create_and_wait() {
+        let comments = || r.table("comments");
+        let wait = comments().index_wait("author").nth(0);
+        let query = comments()
+            .index_create("author")
+            .do_(Func::new(vec![], wait).into_cmd());
+        let session = answering(vec![(
+            query,
+            r#"{"t":1,"r":[{"index":"author","ready":true,"multi":false,"geo":false,"outdated":false,
+            "query":"indexCreate('author', function(_var1) { return r.row('author'); })"}]}"#,
+        )])
+        .await;
+        let status = comments()
+            .index_create_and_wait("author", &session)
+            .await
+            .unwrap();
+        assert_eq!(status.index, "author");
+        assert!(status.ready);
+        assert!(!status.multi);
+    }
+
+    #[tokio::test]
+    async fn get_all_by_index_exec_to_vec() {
+        let avengers = || {
+            r.table("marvel")
+                .get_all(r.with_opt("avengers", r.index("team")))
+        };
+        let session = answering(vec![(
+            avengers(),
+            r#"{"t":2,"r":[{"id":1,"name":"Iron Man","team":"avengers"},{"id":2,"name":"Thor","team":"avengers"}]}"#,
+        )])
+        .await;
+        let heroes: Vec<Value> = avengers().exec_to_vec(&session).await.unwrap();
+        let names: Vec<_> = heroes.iter().map(|hero| &hero["name"]).collect();
+        assert_eq!(names, ["Iron Man", "Thor"]);
+    }
+
+    #[tokio::test]
+    async fn table_exists() {
+        let query = || r.db("heroes").table_exists("marvel");
+        let session = answering(vec![(query(), r#"{"t":1,"r":[true]}"#)]).await;
+        assert!(query().exec::<bool>(&session).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn exec_with_fields() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct UserPosts {
+            id: u32,
+            posts: Vec<u32>,
+        }
+
+        let session = answering(vec![(
+            r.table("users").with_fields(r.args(["id", "posts"])),
+            r#"{"t":2,"r":[{"id":1,"posts":[1,4]},{"id":3,"posts":[2]}]}"#,
+        )])
+        .await;
+        let users: Vec<UserPosts> = r.table("users").exec_with_fields(&session).await.unwrap();
+        let expected = [
+            UserPosts {
+                id: 1,
+                posts: vec![1, 4],
+            },
+            UserPosts {
+                id: 3,
+                posts: vec![2],
+            },
+        ];
+        assert_eq!(users, expected);
+    }
+
+    #[tokio::test]
+    async fn late_response_after_timeout_is_discarded() {
+        // Answers a query only once the client has given up on it and sent
+        // the next one, numbering the answers
+        let session = scripted(|mut stream| async move {
+            for n in (1..).step_by(2) {
+                let (late, _) = read_query(&mut stream).await;
+                let (next, _) = read_query(&mut stream).await;
+                answer(&mut stream, late, n).await;
+                answer(&mut stream, next, n + 1).await;
+            }
+        })
+        .await;
+        let timeout = Duration::from_millis(50);
+
+        // a new token for every query
+        let query = r.expr(0).exec::<u32>(&session);
+        assert!(tokio::time::timeout(timeout, query).await.is_err());
+        assert_eq!(r.expr(0).exec::<u32>(&session).await.unwrap(), 2);
+
+        // the same token for both queries
+        let mut conn = session.connection().unwrap();
+        let query = r.expr(0);
+        let payload = Payload(QueryType::Start, Some(&query), Options::default());
+        let request = conn.request(&payload, false);
+        assert!(tokio::time::timeout(timeout, request).await.is_err());
+        assert_eq!(session.inner.stale.len(), 1);
+        let (_, resp) = conn.request(&payload, false).await.unwrap();
+        assert_eq!(resp.r, serde_json::json!([4]));
+        assert!(session.inner.stale.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unread_response_of_a_dropped_query_is_discarded() {
+        let session = scripted(|mut stream| async move {
+            let (other, _) = read_query(&mut stream).await;
+            let (dropped, _) = read_query(&mut stream).await;
+            answer(&mut stream, dropped, 1).await;
+            answer(&mut stream, other, 2).await;
+            let (next, _) = read_query(&mut stream).await;
+            answer(&mut stream, next, 3).await;
+        })
+        .await;
+        let conn = session.connection().unwrap();
+
+        // `other` reads the responses, the one of `dropped` is routed to
+        // the channel of `conn` but never read
+        let mut other = Box::pin(r.expr(0).exec::<u32>(&session));
+        assert!(futures::poll!(&mut other).is_pending());
+        let mut dropped = Box::pin(r.expr(0).exec::<u32>(conn.clone()));
+        assert!(futures::poll!(&mut dropped).is_pending());
+        assert_eq!(other.await.unwrap(), 2);
+        drop(dropped);
+
+        let next = r.expr(0).exec::<u32>(conn);
+        let next = tokio::time::timeout(Duration::from_secs(1), next).await;
+        assert_eq!(next.unwrap().unwrap(), 3);
+        assert!(session.inner.stale.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancelled_query_is_stopped() {
+        use crate::cmd::cancel::CancelToken;
+
+        // Answers the cancelled query and its STOP once the STOP is read
+        let session = scripted(|mut stream| async move {
+            let (cancelled, _) = read_query(&mut stream).await;
+            let (token, stop) = read_query(&mut stream).await;
+            assert_eq!(token, cancelled);
+            assert_eq!(stop, serde_json::json!([3]));
+            answer(&mut stream, cancelled, 1).await;
+            answer(&mut stream, cancelled, 2).await;
+            let (next, _) = read_query(&mut stream).await;
+            answer(&mut stream, next, 3).await;
+        })
+        .await;
+        let conn = session.connection().unwrap();
+
+        let token = CancelToken::new();
+        let opts = Options::new().cancel(token.clone());
+        let query = r.expr(0).exec::<u32>(Args((conn.clone(), opts)));
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            token.cancel();
+        };
+        let (result, _) = futures::join!(query, cancel);
+        assert!(matches!(
+            result,
+            Err(err::Error::Driver(err::Driver::Cancelled))
+        ));
+
+        // both responses are discarded, the connection can be used again
+        assert_eq!(r.expr(0).exec::<u32>(conn).await.unwrap(), 3);
+        assert!(session.inner.stale.is_empty());
+    }
+
+    #[tokio::test]
+    async fn timed_out_query_is_stopped() {
+        // Answers the query and its STOP once the STOP is read
+        let session = scripted(|mut stream| async move {
+            let (slow, _) = read_query(&mut stream).await;
+            let (token, stop) = read_query(&mut stream).await;
+            assert_eq!(token, slow);
+            assert_eq!(stop, serde_json::json!([3]));
+            answer(&mut stream, slow, 1).await;
+            answer(&mut stream, slow, 2).await;
+            let (next, _) = read_query(&mut stream).await;
+            answer(&mut stream, next, 3).await;
+        })
+        .await;
+
+        let opts = Options::new().timeout(Duration::from_millis(20));
+        match r.expr(0).exec::<u32>(Args((&session, opts))).await {
+            Err(err::Error::Driver(err::Driver::QueryTimeout(timeout))) => {
+                assert_eq!(timeout, Duration::from_millis(20));
+            }
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        assert!(session.inner.channels.is_empty());
+
+        // the late responses are not taken for the ones of the next query
+        assert_eq!(r.expr(0).exec::<u32>(&session).await.unwrap(), 3);
+        assert!(session.inner.channels.is_empty());
+        assert!(session.inner.stale.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancelled_token_sends_nothing() {
+        use crate::cmd::cancel::CancelToken;
+
+        let session = session(r#"{"t":1,"r":[1]}"#).await;
+        let token = CancelToken::new();
+        token.cancel();
+        let opts = Options::new().cancel(token);
+        let result = r.expr(1).exec::<u8>(Args((&session, opts))).await;
+        assert!(matches!(
+            result,
+            Err(err::Error::Driver(err::Driver::Cancelled))
+        ));
+        assert!(session.inner.stale.is_empty());
+        assert_eq!(r.expr(1).exec::<u8>(&session).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn paced_cursor_waits_between_batches() {
+        use futures::TryStreamExt;
+        use std::time::Instant;
+
+        let session = session(r#"{"t":3,"r":[1]}"#).await;
+        let pace = Duration::from_millis(50);
+        let opts = Options::default().pace(pace);
+        let started = Instant::now();
+        let items: Vec<u8> = r
+            .table("t")
+            .run::<u8>(crate::cmd::args::Args((&session, opts)))
+            .take(3)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(items, [1, 1, 1]);
+        // two pauses before the second and the third batch
+        assert!(started.elapsed() >= pace * 2);
+    }
+
+    #[tokio::test]
+    async fn dropped_cursor_is_stopped() {
+        use futures::channel::oneshot;
+
+        let (stopped_tx, stopped) = oneshot::channel();
+        let session = scripted(|mut stream| async move {
+            let (cursor, _) = read_query(&mut stream).await;
+            send(&mut stream, cursor, r#"{"t":3,"r":[1]}"#).await;
+            // the CONTINUE is answered only after the cursor is dropped
+            let (_, query) = read_query(&mut stream).await;
+            assert_eq!(query, serde_json::json!([2]));
+            let (other, _) = read_query(&mut stream).await;
+            send(&mut stream, cursor, r#"{"t":3,"r":[2]}"#).await;
+            answer(&mut stream, other, 3).await;
+            let (token, query) = read_query(&mut stream).await;
+            stopped_tx.send((token == cursor, query)).unwrap();
+        })
+        .await;
+
+        let mut cursor = r.table("t").run::<u8>(&session);
+        assert_eq!(cursor.next().await.unwrap().unwrap(), 1);
+        let next = tokio::time::timeout(Duration::from_millis(50), cursor.next());
+        assert!(next.await.is_err());
+        drop(cursor);
+        assert!(session.inner.channels.is_empty());
+
+        // reading the response of another query routes the late batch
+        assert_eq!(r.expr(3).exec::<u8>(&session).await.unwrap(), 3);
+        let (same_token, query) = stopped.await.unwrap();
+        assert!(same_token);
+        assert_eq!(query, serde_json::json!([3]));
+        assert!(session.inner.channels.is_empty());
+    }
+
+    #[tokio::test]
+    async fn manual_cursor_driving() {
+        // Answers a batch to START and CONTINUE and ends the cursor on STOP
+        let session = scripted(|mut stream| async move {
+            let mut batch = 0;
+            while let Some((token, query)) = next_query(&mut stream).await {
+                let body = match query[0].as_u64() {
+                    Some(3) => r#"{"t":2,"r":[]}"#.to_owned(),
+                    _ => {
+                        batch += 1;
+                        format!(r#"{{"t":3,"r":[{}]}}"#, batch)
+                    }
+                };
+                send(&mut stream, token, &body).await;
+            }
+        })
+        .await;
+
+        let mut conn = session.connection().unwrap();
+        let query = r.table("t");
+        let batch = conn.start_query(&query, Options::default()).await.unwrap();
+        assert_eq!(batch.results, [serde_json::json!(1)]);
+        assert!(batch.more);
+        let batch = conn.continue_query().await.unwrap();
+        assert_eq!(batch.results, [serde_json::json!(2)]);
+        assert!(batch.more);
+        conn.stop_query().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn server_time_is_reused() {
+        let session = session(
+            r#"{"t":1,"r":[{"$reql_type$":"TIME","epoch_time":1700000000.123,"timezone":"+02:00"}]}"#,
+        )
+        .await;
+        let now = session.server_time().await.unwrap();
+        assert_eq!(now.unix_timestamp(), 1_700_000_000);
+        assert_eq!(now.millisecond(), 123);
+
+        let expected = serde_json::json!({
+            "$reql_type$": "TIME",
+            "epoch_time": 1700000000.123,
+            "timezone": "+02:00",
+        });
+        assert_eq!(serde_json::to_value(r.expr(&now)).unwrap(), expected);
+    }
+}
@@ -0,0 +1,480 @@
+//! TLS connections, with the `tls` feature
+//!
+//! Set [tls](super::connect::Options::tls) to connect to a server, or a
+//! proxy in front of it, over TLS. The TLS handshake is done before the
+//! RethinkDB handshake, the rest of the session is the same.
+//!
+//! ## Example
+//!
+//! Connect with the Mozilla root certificates.
+//!
+//! ```
+//! # use unreql::cmd::connect::Options;
+//! # use unreql::cmd::tls::TlsConfig;
+//! # async fn example() -> unreql::Result<()> {
+//! let options = Options::new()
+//!     .host("db.example.com")
+//!     .tls(TlsConfig::new()?);
+//! let session = unreql::r.connect(options).await?;
+//! # Ok(()) }
+//! ```
+//!
+//! ## Example
+//!
+//! Trust the CA of a private cluster, which also authenticates the
+//! client with a certificate.
+//!
+//! ```
+//! # use unreql::cmd::connect::Options;
+//! # use unreql::cmd::tls::{CertificateDer, PrivateKeyDer, TlsConfig};
+//! # async fn example(
+//! #     ca: CertificateDer<'static>,
+//! #     chain: Vec<CertificateDer<'static>>,
+//! #     key: PrivateKeyDer<'static>,
+//! # ) -> unreql::Result<()> {
+//! let tls = TlsConfig::with_root_certs([ca])?
+//!     .client_auth(chain, key)?
+//!     .server_name("rethinkdb.internal");
+//! let session = unreql::r.connect(Options::new().tls(tls)).await?;
+//! # Ok(()) }
+//! ```
+//!
+//! ## Example
+//!
+//! Connect to a development server with a self-signed certificate.
+//!
+//! ```
+//! # use unreql::cmd::connect::Options;
+//! # use unreql::cmd::tls::TlsConfig;
+//! # async fn example() -> unreql::Result<()> {
+//! let tls = TlsConfig::dangerous_skip_verification()?;
+//! let session = unreql::r.connect(Options::new().tls(tls)).await?;
+//! # Ok(()) }
+//! ```
+
+use crate::{err, Result};
+use async_net::TcpStream;
+use futures_rustls::client::TlsStream;
+use futures_rustls::pki_types::UnixTime;
+use futures_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use futures_rustls::rustls::client::{WantsClientCert, WebPkiServerVerifier};
+use futures_rustls::rustls::crypto::{self, ring, CryptoProvider};
+use futures_rustls::rustls::{
+    ClientConfig, ConfigBuilder, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use futures_rustls::TlsConnector;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+pub use futures_rustls::pki_types::pem::PemObject;
+pub use futures_rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+pub use futures_rustls::rustls;
+
+/// The TLS settings of a connection
+///
+/// The `rustls` configuration is built once and shared by the clones, so
+/// cloning the connect [Options](super::connect::Options), as pools do
+/// for every new session, stays cheap.
+///
+/// A `rustls` [ClientConfig] converts into a `TlsConfig`, for the
+/// settings it has and `TlsConfig` does not.
+///
+/// Two configs are equal if one is a clone of the other, with the same
+/// server name.
+#[derive(Clone)]
+pub struct TlsConfig {
+    server_name: Option<Cow<'static, str>>,
+    // `None` for a config given as a `ClientConfig`
+    verifier: Option<Arc<dyn ServerCertVerifier>>,
+    config: Arc<ClientConfig>,
+}
+
+impl TlsConfig {
+    /// Trust the Mozilla root certificates, of `webpki-roots`
+    pub fn new() -> Result<Self> {
+        let mut roots = RootCertStore::empty();
+        roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        Self::with_roots(roots)
+    }
+
+    /// Only trust the given root certificates, e.g. the CA of a private
+    /// cluster
+    pub fn with_root_certs(
+        certs: impl IntoIterator<Item = CertificateDer<'static>>,
+    ) -> Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for cert in certs {
+            roots.add(cert).map_err(tls_error)?;
+        }
+        Self::with_roots(roots)
+    }
+
+    fn with_roots(roots: RootCertStore) -> Result<Self> {
+        let verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider())
+            .build()
+            .map_err(tls_error)?;
+        Self::with_verifier(verifier)
+    }
+
+    /// Accept any certificate of the server, e.g. the self-signed
+    /// certificate of a development server
+    ///
+    /// The connection is still encrypted, but anyone between the client
+    /// and the server can read and change it. Never use it in production.
+    pub fn dangerous_skip_verification() -> Result<Self> {
+        Self::with_verifier(Arc::new(SkipVerification(provider())))
+    }
+
+    fn with_verifier(verifier: Arc<dyn ServerCertVerifier>) -> Result<Self> {
+        let config = builder(verifier.clone())?.with_no_client_auth();
+        Ok(Self {
+            server_name: None,
+            verifier: Some(verifier),
+            config: Arc::new(config),
+        })
+    }
+
+    /// Authenticate the client with a certificate, for mutual TLS
+    ///
+    /// Fails for a config converted from a `ClientConfig`, whose client
+    /// certificate is set with `rustls`.
+    pub fn client_auth(
+        mut self,
+        cert_chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Result<Self> {
+        let Some(verifier) = self.verifier.clone() else {
+            let msg = "the client certificate of a rustls ClientConfig is set with rustls";
+            return Err(tls_error(msg));
+        };
+        let config = builder(verifier)?
+            .with_client_auth_cert(cert_chain, key)
+            .map_err(tls_error)?;
+        self.config = Arc::new(config);
+        Ok(self)
+    }
+
+    /// The name checked against the certificate of the server and sent
+    /// for SNI, by default the [host](super::connect::Options::host)
+    pub fn server_name<T>(mut self, server_name: T) -> Self
+    where
+        T: crate::StaticString,
+    {
+        self.server_name = Some(server_name.static_string());
+        self
+    }
+
+    pub(crate) async fn connect(
+        &self,
+        stream: TcpStream,
+        host: &str,
+    ) -> Result<TlsStream<TcpStream>> {
+        let name = self.server_name.as_deref().unwrap_or(host);
+        let name = ServerName::try_from(name.to_owned()).map_err(tls_error)?;
+        let connector = TlsConnector::from(self.config.clone());
+        connector.connect(name, stream).await.map_err(tls_error)
+    }
+
+    // What the configs are compared and hashed by
+    fn key(&self) -> (Option<&str>, *const ClientConfig) {
+        (self.server_name.as_deref(), Arc::as_ptr(&self.config))
+    }
+}
+
+impl From<Arc<ClientConfig>> for TlsConfig {
+    fn from(config: Arc<ClientConfig>) -> Self {
+        Self {
+            server_name: None,
+            verifier: None,
+            config,
+        }
+    }
+}
+
+impl From<ClientConfig> for TlsConfig {
+    fn from(config: ClientConfig) -> Self {
+        Arc::new(config).into()
+    }
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+fn builder(
+    verifier: Arc<dyn ServerCertVerifier>,
+) -> Result<ConfigBuilder<ClientConfig, WantsClientCert>> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()
+        .map_err(tls_error)?;
+    Ok(builder
+        .dangerous()
+        .with_custom_certificate_verifier(verifier))
+}
+
+// Accepts any certificate, but still checks that the server owns it
+#[derive(Debug)]
+struct SkipVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for SkipVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn tls_error(error: impl fmt::Display) -> crate::Error {
+    err::Driver::Tls(error.to_string()).into()
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("server_name", &self.server_name)
+            .field("verifier", &self.verifier)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for TlsConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for TlsConfig {}
+
+impl PartialOrd for TlsConfig {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TlsConfig {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+impl Hash for TlsConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::connect::{self, Options};
+    use crate::Error;
+    use async_net::TcpListener;
+    use futures::io::{AsyncReadExt, AsyncWriteExt};
+    use futures_rustls::rustls::server::WebPkiClientVerifier;
+    use futures_rustls::rustls::ServerConfig;
+    use futures_rustls::TlsAcceptor;
+
+    const CA: &str = include_str!("../../tests/tls/ca.pem");
+    const SERVER: (&str, &str) = (
+        include_str!("../../tests/tls/server.pem"),
+        include_str!("../../tests/tls/server.key"),
+    );
+    const CLIENT: (&str, &str) = (
+        include_str!("../../tests/tls/client.pem"),
+        include_str!("../../tests/tls/client.key"),
+    );
+
+    fn cert(pem: &str) -> CertificateDer<'static> {
+        CertificateDer::from_pem_slice(pem.as_bytes()).unwrap()
+    }
+
+    fn key(pem: &str) -> PrivateKeyDer<'static> {
+        PrivateKeyDer::from_pem_slice(pem.as_bytes()).unwrap()
+    }
+
+    fn test_ca() -> TlsConfig {
+        TlsConfig::with_root_certs([cert(CA)]).unwrap()
+    }
+
+    // A TLS server for `rethinkdb.test`, requiring a client certificate
+    // signed by the test CA if `client_auth`, which reads the version and
+    // the client first message then rejects the password. Returns the
+    // version it read.
+    async fn connect(tls: TlsConfig, client_auth: bool) -> (Result<crate::Session>, Option<u32>) {
+        let provider = Arc::new(ring::default_provider());
+        let config = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .unwrap();
+        let config = if client_auth {
+            let mut roots = RootCertStore::empty();
+            roots.add(cert(CA)).unwrap();
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .unwrap();
+            config.with_client_cert_verifier(verifier)
+        } else {
+            config.with_no_client_auth()
+        };
+        let config = config
+            .with_single_cert(vec![cert(SERVER.0)], key(SERVER.1))
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = TlsAcceptor::from(Arc::new(config))
+                .accept(stream)
+                .await
+                .ok()?;
+            let mut version = [0u8; 4];
+            stream.read_exact(&mut version).await.ok()?;
+            let mut byte = [0u8];
+            while byte != [0] {
+                stream.read_exact(&mut byte).await.ok()?;
+            }
+            let info = r#"{"success":true,"min_protocol_version":0,"max_protocol_version":0,"server_version":"2.4.4"}"#;
+            let reject = r#"{"success":false,"error":"Wrong password","error_code":12}"#;
+            let replies = format!("{info}\0{reject}\0");
+            stream.write_all(replies.as_bytes()).await.ok()?;
+            stream.flush().await.ok()?;
+            let _ = stream.read(&mut byte).await;
+            Some(u32::from_le_bytes(version))
+        });
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let options = Options::new().host("rethinkdb.test").tls(tls);
+        let session = connect::with_stream(stream, options).await;
+        (session, server.await.unwrap())
+    }
+
+    fn is_auth_failed(session: &Result<crate::Session>) -> bool {
+        matches!(
+            session,
+            Err(Error::Driver(err::Driver::AuthFailed { code: 12, .. }))
+        )
+    }
+
+    #[tokio::test]
+    async fn handshake_over_tls() {
+        let (session, version) = connect(test_ca(), false).await;
+        assert!(is_auth_failed(&session), "{:?}", session.map(|_| ()));
+        assert_eq!(version, Some(0x34c2bdc3));
+    }
+
+    #[tokio::test]
+    async fn unknown_issuer() {
+        let (session, version) = connect(TlsConfig::new().unwrap(), false).await;
+        match session {
+            Err(Error::Driver(err::Driver::Tls(msg))) => assert!(msg.contains("UnknownIssuer")),
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+        assert_eq!(version, None);
+    }
+
+    #[tokio::test]
+    async fn wrong_server_name() {
+        let tls = test_ca().server_name("other.test");
+        let (session, _) = connect(tls, false).await;
+        match session {
+            Err(Error::Driver(err::Driver::Tls(msg))) => {
+                assert!(msg.contains("not valid for name"), "{msg}")
+            }
+            other => panic!("unexpected result: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn client_auth() {
+        let tls = test_ca()
+            .client_auth(vec![cert(CLIENT.0)], key(CLIENT.1))
+            .unwrap();
+        let (session, _) = connect(tls, true).await;
+        assert!(is_auth_failed(&session), "{:?}", session.map(|_| ()));
+
+        let (session, version) = connect(test_ca(), true).await;
+        assert!(!is_auth_failed(&session));
+        assert_eq!(version, None);
+    }
+
+    #[tokio::test]
+    async fn skip_verification() {
+        let tls = TlsConfig::dangerous_skip_verification().unwrap();
+        let (session, _) = connect(tls.server_name("other.test"), false).await;
+        assert!(is_auth_failed(&session), "{:?}", session.map(|_| ()));
+    }
+
+    #[tokio::test]
+    async fn from_client_config() {
+        let mut roots = RootCertStore::empty();
+        roots.add(cert(CA)).unwrap();
+        let config = ClientConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let tls = TlsConfig::from(config);
+        let (session, _) = connect(tls.clone(), false).await;
+        assert!(is_auth_failed(&session), "{:?}", session.map(|_| ()));
+
+        let client_auth = tls.client_auth(vec![cert(CLIENT.0)], key(CLIENT.1));
+        assert!(matches!(
+            client_auth,
+            Err(Error::Driver(err::Driver::Tls(_)))
+        ));
+    }
+
+    #[test]
+    fn cheap_clones() {
+        let tls = test_ca();
+        let options = Options::new().tls(tls.clone());
+        let clone = options.clone();
+        assert_eq!(options, clone);
+        let config = |options: &Options| Arc::as_ptr(&options.tls.as_ref().unwrap().config);
+        assert_eq!(config(&options), config(&clone));
+
+        assert_ne!(options, Options::new().tls(test_ca()));
+        assert_ne!(options, Options::new().tls(tls.server_name("other.test")));
+    }
+}
@@ -0,0 +1,294 @@
+//! Checks the index names of a query before running it
+//!
+//! See [Command::exec_validated].
+
+use std::time::{Duration, Instant};
+
+use ql2::term::TermType;
+use serde_json::Value;
+
+use crate::cmd::run::Options;
+use crate::proto::Datum;
+use crate::{err, r, Command, Result, Session};
+
+// How long the indexes of a table are cached by a session
+const INDEX_CACHE_TTL: Duration = Duration::from_secs(60);
+
+// Terms reading an index of the table in their first argument
+const INDEX_TERMS: &[TermType] = &[
+    TermType::GetAll,
+    TermType::Between,
+    TermType::OrderBy,
+    TermType::GetIntersecting,
+    TermType::GetNearest,
+];
+
+// Terms changing the indexes of a table, the cache is cleared after them
+pub(crate) const INDEX_WRITE_TERMS: &[TermType] = &[
+    TermType::IndexCreate,
+    TermType::IndexDrop,
+    TermType::IndexRename,
+    TermType::TableDrop,
+    TermType::DbDrop,
+];
+
+/// The index names cached by a session, by database and table
+pub(crate) type IndexCache = dashmap::DashMap<(String, String), (Vec<String>, Instant)>;
+
+// An index read by a query
+#[derive(Debug, PartialEq, Eq)]
+struct IndexUse {
+    db: Option<String>,
+    table: String,
+    index: String,
+}
+
+/// Fails if the query reads an index its table does not have
+pub(crate) async fn check_indexes(
+    query: &Command,
+    session: &Session,
+    opts: &Options,
+) -> Result<()> {
+    let mut uses = Vec::new();
+    collect(query, &mut uses);
+    if uses.is_empty() {
+        return Ok(());
+    }
+    let default_db = match &opts.db {
+        Some(db) => db.0.to_string(),
+        None => session.inner.db.lock().await.to_string(),
+    };
+    for IndexUse { db, table, index } in uses {
+        let db = db.unwrap_or_else(|| default_db.clone());
+        let known = indexes(session, &db, &table).await?;
+        if !known.contains(&index) {
+            return Err(err::Driver::UnknownIndex {
+                index,
+                table,
+                known,
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
+// The secondary indexes and the primary key of a table, cached
+async fn indexes(session: &Session, db: &str, table: &str) -> Result<Vec<String>> {
+    let key = (db.to_owned(), table.to_owned());
+    if let Some(cached) = session.inner.indexes.get(&key) {
+        let (known, fetched) = &*cached;
+        if fetched.elapsed() < INDEX_CACHE_TTL {
+            return Ok(known.clone());
+        }
+    }
+    let table_cmd = r.db(db.to_owned()).table(table.to_owned());
+    let known = table_cmd
+        .clone()
+        .index_list()
+        .append(table_cmd.info().g("primary_key"))
+        .exec_to_vec::<String>(session)
+        .await?;
+    session
+        .inner
+        .indexes
+        .insert(key, (known.clone(), Instant::now()));
+    Ok(known)
+}
+
+fn collect(cmd: &Command, uses: &mut Vec<IndexUse>) {
+    let table = match cmd.typ() {
+        typ if INDEX_TERMS.contains(&typ) => cmd.args().front(),
+        TermType::EqJoin => cmd.args().get(2),
+        _ => None,
+    };
+    if let (Some(table), Some(Ok(opts))) = (table.and_then(table_name), cmd.opts()) {
+        if let Some(index) = index_name(opts) {
+            let (db, table) = table;
+            uses.push(IndexUse { db, table, index });
+        }
+    }
+    for arg in cmd.args() {
+        collect(arg, uses);
+    }
+    if let Some(Ok(datum)) = cmd.datum() {
+        collect_datum(datum, uses);
+    }
+}
+
+fn collect_datum(datum: &Datum, uses: &mut Vec<IndexUse>) {
+    match datum {
+        Datum::Command(cmd) => collect(cmd, uses),
+        Datum::Array(arr) => arr.iter().for_each(|datum| collect_datum(datum, uses)),
+        Datum::Object(obj) => obj.values().for_each(|datum| collect_datum(datum, uses)),
+        _ => {}
+    }
+}
+
+// The database and the name of a `table` term with literal names
+fn table_name(cmd: &Command) -> Option<(Option<String>, String)> {
+    if cmd.typ() != TermType::Table {
+        return None;
+    }
+    match cmd.args().iter().collect::<Vec<_>>()[..] {
+        [name] => Some((None, string(name)?)),
+        [db, name] if db.typ() == TermType::Db => {
+            let db = string(db.args().front()?)?;
+            Some((Some(db), string(name)?))
+        }
+        _ => None,
+    }
+}
+
+// The `index` option, also inside `r.asc` and `r.desc`
+fn index_name(opts: &Datum) -> Option<String> {
+    let index = match opts {
+        Datum::Object(obj) => obj.get("index")?,
+        _ => return None,
+    };
+    match index {
+        Datum::String(name) => Some(name.clone()),
+        Datum::Command(cmd) => match cmd.typ() {
+            TermType::Asc | TermType::Desc => string(cmd.args().front()?),
+            _ => string(cmd),
+        },
+        _ => None,
+    }
+}
+
+fn string(cmd: &Command) -> Option<String> {
+    match cmd.datum() {
+        Some(Ok(Datum::String(string))) => Some(string.clone()),
+        Some(Ok(Datum::Value(Value::String(string)))) => Some(string.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fake_server, InnerSession};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn has_index_list(term: &Value) -> bool {
+        let Value::Array(items) = term else {
+            return false;
+        };
+        items.first() == Some(&Value::from(TermType::IndexList as i32))
+            || items.iter().any(has_index_list)
+    }
+
+    // A session to a server knowing the indexes `email` and `created_at`
+    // of every table, counting the `index_list` queries
+    async fn session() -> (Session, Arc<AtomicUsize>) {
+        let lists = Arc::new(AtomicUsize::new(0));
+        let stream = fake_server::connect({
+            let lists = lists.clone();
+            |mut stream| async move {
+                while let Some((token, query)) = fake_server::next_query(&mut stream).await {
+                    let body = if has_index_list(&query) {
+                        lists.fetch_add(1, Ordering::SeqCst);
+                        r#"{"t":1,"r":[["email","created_at","id"]]}"#
+                    } else {
+                        r#"{"t":1,"r":[1]}"#
+                    };
+                    fake_server::send(&mut stream, token, body).await;
+                }
+            }
+        });
+        let inner = InnerSession::new(stream.await, "app".into(), None);
+        let session = Session {
+            inner: Arc::new(inner),
+        };
+        (session, lists)
+    }
+
+    fn uses(query: Command) -> Vec<IndexUse> {
+        let mut uses = Vec::new();
+        collect(&query, &mut uses);
+        uses
+    }
+
+    fn index_use(db: Option<&str>, table: &str, index: &str) -> IndexUse {
+        IndexUse {
+            db: db.map(Into::into),
+            table: table.into(),
+            index: index.into(),
+        }
+    }
+
+    #[test]
+    fn collects_index_names() {
+        let query = r
+            .db("app")
+            .table("users")
+            .get_all(r.with_opt("bob", r.index("email")));
+        assert_eq!(uses(query), [index_use(Some("app"), "users", "email")]);
+
+        let query = r.table("posts").order_by(r.index(r.desc("created_at")));
+        assert_eq!(uses(query), [index_use(None, "posts", "created_at")]);
+
+        // the index of a sequence that is not a table is not checked
+        let query = r.expr([1]).order_by(r.index("id"));
+        assert!(uses(query).is_empty());
+        assert!(uses(r.table("users").get(1)).is_empty());
+    }
+
+    #[cfg(feature = "joins")]
+    #[test]
+    fn collects_eq_join_index_names() {
+        let query = r
+            .table("posts")
+            .order_by(r.index(r.desc("created_at")))
+            .eq_join("author", r.table("users"), r.index("emial"));
+        assert_eq!(
+            uses(query),
+            [
+                index_use(None, "users", "emial"),
+                index_use(None, "posts", "created_at"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_index_fails_before_running() {
+        let (session, lists) = session().await;
+        let query = r
+            .table("users")
+            .get_all(r.with_opt("bob", r.index("emial")));
+        let err = query.exec_validated::<u8>(&session).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "client error; unknown index 'emial' on table users (known: email, created_at, id)"
+        );
+
+        let query = r
+            .table("users")
+            .get_all(r.with_opt("bob", r.index("email")));
+        assert_eq!(query.exec_validated::<u8>(&session).await.unwrap(), 1);
+        // the indexes are cached by the session
+        assert_eq!(lists.load(Ordering::SeqCst), 1);
+        assert!(session
+            .inner
+            .indexes
+            .contains_key(&("app".into(), "users".into())));
+    }
+
+    #[tokio::test]
+    async fn index_create_clears_cache() {
+        let (session, lists) = session().await;
+        let query = r.table("users").order_by(r.index("email"));
+        assert_eq!(
+            query.clone().exec_validated::<u8>(&session).await.unwrap(),
+            1
+        );
+
+        let create = r.table("users").index_create("name");
+        assert_eq!(create.exec::<u8>(&session).await.unwrap(), 1);
+        assert!(session.inner.indexes.is_empty());
+
+        assert_eq!(query.exec_validated::<u8>(&session).await.unwrap(), 1);
+        assert_eq!(lists.load(Ordering::SeqCst), 2);
+    }
+}
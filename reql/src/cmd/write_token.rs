@@ -0,0 +1,365 @@
+//! Tell when a changefeed reflects a write of this process
+//!
+//! See [Command::exec_write].
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ql2::term::TermType;
+use serde_json::Value;
+
+use crate::cmd::run;
+use crate::types::{Change, WriteStatus};
+use crate::{Command, Datum, Driver, Result};
+
+// The terms `exec_write` runs, the ones taking `return_changes`
+const WRITE_TERMS: &[TermType] = &[
+    TermType::Insert,
+    TermType::Update,
+    TermType::Replace,
+    TermType::Delete,
+];
+
+// The sequence of the writes of the process
+static WRITE_SEQ: AtomicU64 = AtomicU64::new(1);
+
+/// A write run by [exec_write](Command::exec_write)
+///
+/// A feed-driven cache holds the written document once it has seen a
+/// change [reflecting](Self::is_reflected_by) the write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WriteToken {
+    /// The primary key of the written document
+    pub id: Value,
+    /// Increases with every write run by `exec_write` in the process, so a
+    /// later write of the same document has a greater `seq`
+    ///
+    /// [is_reflected_by](Self::is_reflected_by) does not read it: a cache
+    /// keeping the token of the last write of each document compares the
+    /// `seq` of a new token to drop the older one.
+    pub seq: u64,
+    primary_key: String,
+    // the content hash of the new value, `None` for a delete
+    hash: Option<u64>,
+}
+
+impl WriteToken {
+    fn new(change: &Change, primary_key: &str) -> Result<Self> {
+        let Some(id) = change_id(change, primary_key) else {
+            let msg = format!("the written document has no primary key {}", primary_key);
+            return Err(Driver::Other(msg).into());
+        };
+        Ok(Self {
+            id: id.clone(),
+            seq: WRITE_SEQ.fetch_add(1, Ordering::SeqCst),
+            primary_key: primary_key.to_owned(),
+            hash: change.new_value().map(content_hash),
+        })
+    }
+
+    /// Whether `change`, a document of a changefeed on the table, leaves
+    /// the document as this write left it
+    ///
+    /// The change must be of the same document with the same `new_val`,
+    /// `null` for a delete. The change of another write giving the
+    /// document the same content also matches. The token does not tell
+    /// whether the change came at or after the write, ordering the writes
+    /// of a document is left to the caller, see [seq](Self::seq).
+    ///
+    /// The feed may send the change before `exec_write` returns, so a
+    /// cache should also check the changes it has already applied.
+    pub fn is_reflected_by(&self, change: &Change) -> bool {
+        change_id(change, &self.primary_key) == Some(&self.id)
+            && change.new_value().map(content_hash) == self.hash
+    }
+}
+
+impl Command {
+    /// Run a write of a single document and return a token telling when a
+    /// changefeed reflects it
+    ///
+    /// The query must be an `insert`, `update`, `replace` or `delete`. It
+    /// is run with `return_changes` set to `true`, and must change at most
+    /// one document: a write changing more fails with
+    /// [ManyChanges](Driver::ManyChanges) once the documents are written.
+    /// A document the server could not write fails with
+    /// [WriteFailed](crate::Runtime::WriteFailed). `None` is returned if
+    /// the document is left unchanged, no changefeed sends a change for it
+    /// then.
+    ///
+    /// `primary_key` is the name of the primary key field of the table
+    /// (see [TableInfo::primary_key](crate::types::TableInfo::primary_key)).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, rjson, Session};
+    /// # use unreql::types::Change;
+    /// # use futures::TryStreamExt;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let mut feed = r.table("users").changes(()).run_feed::<Change>(conn).await?;
+    /// let token = r.table("users")
+    ///   .insert(rjson!({"id": 1, "name": "Ann"}))
+    ///   .exec_write(conn, "id")
+    ///   .await?;
+    /// if let Some(token) = token {
+    ///     while let Some(change) = feed.try_next().await? {
+    ///         if token.is_reflected_by(&change) {
+    ///             break;
+    ///         }
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Related commands
+    /// - [insert](Self::insert)
+    /// - [update](Self::update)
+    /// - [changes](Self::changes)
+    pub async fn exec_write(
+        mut self,
+        arg: impl run::Arg,
+        primary_key: &str,
+    ) -> Result<Option<WriteToken>> {
+        if !WRITE_TERMS.contains(&self.typ()) {
+            let msg = "exec_write runs an insert, update, replace or delete";
+            return Err(Driver::Other(msg.into()).into());
+        }
+        return_changes(&mut self);
+        let status: WriteStatus = self.exec(arg).await?;
+        match status.check()?.changes.unwrap_or_default().as_slice() {
+            [] => Ok(None),
+            [change] => WriteToken::new(change, primary_key).map(Some),
+            changes => Err(Driver::ManyChanges(changes.len()).into()),
+        }
+    }
+}
+
+// Sets `return_changes` in the options of the write. Options computed by
+// a query are left alone, the write then returns no change.
+fn return_changes(query: &mut Command) {
+    if query.opts().is_none() {
+        let opts = HashMap::from([("return_changes".to_owned(), Datum::Bool(true))]);
+        query.set_opts(Ok(Datum::Object(opts)));
+        return;
+    }
+    match query.mut_opts() {
+        Some(Ok(Datum::Object(opts))) => {
+            opts.insert("return_changes".to_owned(), Datum::Bool(true));
+        }
+        Some(Ok(Datum::Value(Value::Object(opts)))) => {
+            opts.insert("return_changes".to_owned(), Value::Bool(true));
+        }
+        _ => {}
+    }
+}
+
+// The primary key of the document of a change
+fn change_id<'a>(change: &'a Change, primary_key: &str) -> Option<&'a Value> {
+    change.new_value().or(change.old_value())?.get(primary_key)
+}
+
+// A hash of a document that does not depend on the order of the fields
+fn content_hash(value: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hash_value(value, &mut hasher);
+    hasher.finish()
+}
+
+fn hash_value(value: &Value, state: &mut impl Hasher) {
+    match value {
+        Value::Null => 0u8.hash(state),
+        Value::Bool(bool) => (1u8, bool).hash(state),
+        // `1` and `1.0` are the same number for the server
+        Value::Number(number) => (2u8, number.as_f64().map(f64::to_bits)).hash(state),
+        Value::String(string) => (3u8, string).hash(state),
+        Value::Array(items) => {
+            (4u8, items.len()).hash(state);
+            items.iter().for_each(|item| hash_value(item, state));
+        }
+        Value::Object(fields) => {
+            (5u8, fields.len()).hash(state);
+            let mut fields: Vec<_> = fields.iter().collect();
+            fields.sort_unstable_by_key(|(key, _)| *key);
+            for (key, value) in fields {
+                key.hash(state);
+                hash_value(value, state);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::options::{DeleteOptions, Durability};
+    use crate::fake_server::{self, read_query, send};
+    use crate::{r, rjson, Error, Runtime};
+    use futures::TryStreamExt;
+    use serde_json::json;
+
+    const INSERTED: &str = r#"{"t":1,"r":[{"inserted":1,"replaced":0,"unchanged":0,"skipped":0,"deleted":0,"errors":0,
+        "changes":[{"old_val":null,"new_val":{"id":1,"name":"Bob"}}]}]}"#;
+
+    fn change(value: Value) -> Change {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn hash_ignores_field_order() {
+        let a: Value = serde_json::from_str(r#"{"id":1,"tags":["a"],"age":1.0}"#).unwrap();
+        let b: Value = serde_json::from_str(r#"{"age":1,"tags":["a"],"id":1}"#).unwrap();
+        assert_eq!(content_hash(&a), content_hash(&b));
+        assert_ne!(
+            content_hash(&a),
+            content_hash(&json!({"id": 1, "tags": ["b"], "age": 1}))
+        );
+    }
+
+    #[test]
+    fn return_changes_in_options() {
+        let mut query = r.table("users").insert(rjson!({"id": 1}));
+        return_changes(&mut query);
+        assert_eq!(
+            query.to_query_json().unwrap()[2],
+            json!({"return_changes": true})
+        );
+
+        let mut query = r
+            .table("users")
+            .get(1)
+            .delete(DeleteOptions::new().durability(Durability::Soft));
+        return_changes(&mut query);
+        let opts = json!({"durability": "soft", "return_changes": true});
+        assert_eq!(query.to_query_json().unwrap()[2], opts);
+    }
+
+    #[tokio::test]
+    async fn reflected_by_feed() {
+        // a feed locks its connection, the writes go through another one
+        let feed_session = fake_server::scripted(|mut stream| async move {
+            let (feed, _) = read_query(&mut stream).await;
+            let batch = r#"{"t":3,"r":[{"old_val":null,"new_val":{"id":1,"name":"Ann"}}]}"#;
+            send(&mut stream, feed, batch).await;
+            let (token, query) = read_query(&mut stream).await;
+            assert_eq!((token, query), (feed, json!([2])));
+            let batch = r#"{"t":3,"r":[
+                {"old_val":null,"new_val":{"name":"Bob","id":2}},
+                {"old_val":{"id":1,"name":"Ann"},"new_val":{"name":"Bob","id":1}}]}"#;
+            send(&mut stream, feed, batch).await;
+        })
+        .await;
+        let session = fake_server::scripted(|mut stream| async move {
+            let (write, query) = read_query(&mut stream).await;
+            assert_eq!(query[1][2], json!({"return_changes": true}));
+            send(&mut stream, write, INSERTED).await;
+        })
+        .await;
+
+        let mut feed = r
+            .table("users")
+            .changes(())
+            .run_feed::<Change>(&feed_session)
+            .await
+            .unwrap();
+        let earlier = feed.try_next().await.unwrap().unwrap();
+        let token = r
+            .table("users")
+            .insert(rjson!({"id": 1, "name": "Bob"}))
+            .exec_write(&session, "id")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(token.id, json!(1));
+        assert!(!token.is_reflected_by(&earlier));
+        let other = feed.try_next().await.unwrap().unwrap();
+        assert!(!token.is_reflected_by(&other));
+        let ours = feed.try_next().await.unwrap().unwrap();
+        assert!(token.is_reflected_by(&ours));
+    }
+
+    #[tokio::test]
+    async fn sequence_increases() {
+        let session = fake_server::session(INSERTED).await;
+        let write = || r.table("users").insert(rjson!({"id": 1, "name": "Bob"}));
+        let first = write().exec_write(&session, "id").await.unwrap().unwrap();
+        let second = write().exec_write(&session, "id").await.unwrap().unwrap();
+        assert!(second.seq > first.seq);
+    }
+
+    #[tokio::test]
+    async fn delete_reflected_by_null() {
+        let session = fake_server::session(
+            r#"{"t":1,"r":[{"inserted":0,"replaced":0,"unchanged":0,"skipped":0,"deleted":1,"errors":0,
+            "changes":[{"old_val":{"id":"a","name":"Ann"},"new_val":null}]}]}"#,
+        )
+        .await;
+        let token = r.table("users").get("a").delete(());
+        let token = token.exec_write(&session, "id").await.unwrap().unwrap();
+        assert_eq!(token.id, json!("a"));
+        assert!(token.is_reflected_by(&change(json!({"old_val": {"id": "a"}, "new_val": null}))));
+        assert!(!token.is_reflected_by(&change(json!({"old_val": null, "new_val": {"id": "a"}}))));
+    }
+
+    #[tokio::test]
+    async fn unchanged_and_many() {
+        let session = fake_server::session(
+            r#"{"t":1,"r":[{"inserted":0,"replaced":0,"unchanged":1,"skipped":0,"deleted":0,"errors":0}]}"#,
+        )
+        .await;
+        let update = r.table("users").get(1).update(rjson!({"name": "Bob"}));
+        assert_eq!(update.exec_write(&session, "id").await.unwrap(), None);
+
+        let session = fake_server::session(
+            r#"{"t":1,"r":[{"inserted":0,"replaced":2,"unchanged":0,"skipped":0,"deleted":0,"errors":0,
+            "changes":[{"old_val":{"id":1},"new_val":{"id":1,"a":1}},{"old_val":{"id":2},"new_val":{"id":2,"a":1}}]}]}"#,
+        )
+        .await;
+        let update = r.table("users").update(rjson!({"a": 1}));
+        let err = update.exec_write(&session, "id").await.unwrap_err();
+        assert!(matches!(err, Error::Driver(Driver::ManyChanges(2))));
+
+        let err = r
+            .table("users")
+            .exec_write(&session, "id")
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("insert, update, replace or delete"));
+    }
+
+    #[tokio::test]
+    async fn write_failed() {
+        let session = fake_server::session(
+            r#"{"t":1,"r":[{"inserted":0,"replaced":0,"unchanged":0,"skipped":0,"deleted":0,"errors":1,
+            "first_error":"Duplicate primary key `id`"}]}"#,
+        )
+        .await;
+        let insert = r.table("users").insert(rjson!({"id": 1}));
+        let err = insert.exec_write(&session, "id").await.unwrap_err();
+        let Error::Runtime(Runtime::WriteFailed {
+            errors: 1,
+            first_error: Some(msg),
+        }) = err
+        else {
+            panic!("unexpected error {:?}", err);
+        };
+        assert_eq!(msg, "Duplicate primary key `id`");
+
+        let session = fake_server::session(
+            r#"{"t":1,"r":[{"inserted":0,"replaced":0,"unchanged":0,"skipped":0,"deleted":0,"errors":2}]}"#,
+        )
+        .await;
+        let insert = r.table("users").insert(rjson!([{"id": 1}, {"id": 2}]));
+        let err = insert.exec_write(&session, "id").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "runtime error; write error; 2 documents not written"
+        );
+    }
+}
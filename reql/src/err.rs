@@ -1,4 +1,7 @@
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{error, fmt, io};
 
 /// The most generic error message in ReQL
@@ -9,7 +12,16 @@ pub enum Error {
     Driver(Driver),
 }
 
-impl error::Error for Error {}
+// The message of an error already holds the messages of its `Runtime` or
+// `Driver`, so the source is the error those wrap, if any
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Driver(error) => error.source(),
+            Self::Compile(_) | Self::Runtime(_) => None,
+        }
+    }
+}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -25,6 +37,60 @@ impl Error {
     pub fn is_not_found(&self) -> bool {
         matches!(self, Self::Driver(Driver::NotFound))
     }
+
+    /// The payload of a [user error](Runtime::User) thrown with
+    /// [r.error_json](crate::r::error_json)
+    ///
+    /// Returns `None` for the other errors, and an error if the message
+    /// is not the JSON of a `T`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize)]
+    /// struct Refused {
+    ///     code: String,
+    /// }
+    ///
+    /// # fn example(error: unreql::Error) -> unreql::Result<()> {
+    /// match error.user_payload::<Refused>() {
+    ///     Some(refused) => println!("refused: {}", refused?.code),
+    ///     None => return Err(error),
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn user_payload<T: DeserializeOwned>(&self) -> Option<Result<T, Error>> {
+        match self {
+            Self::Runtime(Runtime::User(msg)) => {
+                Some(serde_json::from_str(msg).map_err(Error::from))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the query may be safely run again
+    ///
+    /// Only [Availability::OpFailed] is retryable: the operation has not been
+    /// applied. After [Availability::OpIndeterminate] a write may or may not
+    /// have happened, so running it again risks applying it twice.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::Runtime(Runtime::Availability(Availability::OpFailed(_)))
+        )
+    }
+
+    /// Whether the server could not be reached: IO errors, broken
+    /// connections and [connect timeouts](crate::cmd::connect::Options::timeout)
+    ///
+    /// The server may be reachable again soon, or through another host.
+    pub fn is_unreachable(&self) -> bool {
+        matches!(
+            self,
+            Self::Driver(Driver::Io(..) | Driver::ConnectionBroken | Driver::ConnectTimeout(_))
+        )
+    }
 }
 
 /// The parent class of all runtime errors
@@ -41,6 +107,14 @@ pub enum Runtime {
     Internal(String),
     Availability(Availability),
     Permission(String),
+    /// Some documents of a write could not be written, e.g. inserted with
+    /// a primary key already in the table. `errors` is how many, and
+    /// `first_error` the error of the first one, as reported in the
+    /// [WriteStatus](crate::types::WriteStatus) of the write.
+    WriteFailed {
+        errors: u32,
+        first_error: Option<String>,
+    },
 }
 
 impl From<Runtime> for Error {
@@ -49,6 +123,8 @@ impl From<Runtime> for Error {
     }
 }
 
+impl error::Error for Runtime {}
+
 impl fmt::Display for Runtime {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -59,6 +135,18 @@ impl fmt::Display for Runtime {
             Self::Internal(msg) => write!(f, "internal error; {}", msg),
             Self::Availability(msg) => write!(f, "availability error; {}", msg),
             Self::Permission(msg) => write!(f, "permission error; {}", msg),
+            Self::WriteFailed {
+                errors,
+                first_error: Some(msg),
+            } => write!(
+                f,
+                "write error; {} documents not written, first: {}",
+                errors, msg
+            ),
+            Self::WriteFailed {
+                errors,
+                first_error: None,
+            } => write!(f, "write error; {} documents not written", errors),
         }
     }
 }
@@ -80,6 +168,8 @@ impl From<Availability> for Error {
     }
 }
 
+impl error::Error for Availability {}
+
 impl fmt::Display for Availability {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -96,11 +186,91 @@ impl fmt::Display for Availability {
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub enum Driver {
+    /// The authentication exchange failed on the client side, e.g. the
+    /// server signature did not match.
     Auth(String),
+    /// The TLS configuration or handshake failed, see
+    /// [TlsConfig](crate::cmd::tls::TlsConfig) with the `tls` feature.
+    Tls(String),
+    /// The server rejected the credentials. `code` is the error code sent
+    /// by the server, between 10 and 20.
+    AuthFailed {
+        code: usize,
+        message: String,
+    },
+    /// The server does not support the protocol version of the driver.
+    ProtocolMismatch {
+        min: usize,
+        max: usize,
+    },
     ConnectionBroken,
     ConnectionLocked,
-    Io(io::ErrorKind, String),
-    Json(Arc<serde_json::Error>),
+    /// The query was cancelled with its
+    /// [CancelToken](crate::cmd::cancel::CancelToken).
+    Cancelled,
+    /// Connecting to the server took longer than the
+    /// [timeout](crate::cmd::connect::Options::timeout), given here.
+    ConnectTimeout(Duration),
+    /// The server did not answer a request of the query within its
+    /// [timeout](crate::cmd::run::Options::timeout), given here. The query
+    /// was stopped.
+    QueryTimeout(Duration),
+    /// The session [reconnected](crate::Session::reconnect) while the
+    /// query was waiting for its response, which may or may not have run,
+    /// or the connection of the query was opened before.
+    Reconnected,
+    /// Too many connections to the server failed recently, the connection
+    /// was not attempted.
+    CircuitOpen,
+    /// A pooled session is now answered by another server than the one
+    /// it was first seen on, e.g. after a failover behind a proxy, see
+    /// [RecyclePolicy::SameServer](crate::pool::RecyclePolicy::SameServer).
+    ServerChanged {
+        expected: String,
+        found: String,
+    },
+    /// The query would use the `test` database, see
+    /// [default_db_required](crate::cmd::connect::Options::default_db_required).
+    NoDefaultDb,
+    /// A write run by [exec_write](crate::Command::exec_write) changed more
+    /// than one document, given here. The documents are written.
+    ManyChanges(usize),
+    /// The query reads an index its table does not have, see
+    /// [exec_validated](crate::Command::exec_validated). `known` lists the
+    /// indexes of the table, primary key included.
+    UnknownIndex {
+        index: String,
+        table: String,
+        known: Vec<String>,
+    },
+    /// An environment variable read by
+    /// [Options::from_env](crate::cmd::connect::Options::from_env) has an
+    /// invalid value.
+    InvalidEnv {
+        name: String,
+        value: String,
+    },
+    /// A float of the query is NaN or infinite, which JSON cannot
+    /// represent. `path` is the JSON pointer of the float in the value
+    /// it was found in, empty when the value is the float itself. Wrap
+    /// the value in [Lossy](crate::types::Lossy) to send them as `null`.
+    NonFiniteFloat {
+        float: f64,
+        path: String,
+    },
+    /// The server does not support a query of the driver, e.g. the
+    /// `SERVER_INFO` query of [server](crate::Session::server) on some
+    /// RethinkDB-compatible servers. `message` is the error of the server,
+    /// or why its response could not be read.
+    Unsupported {
+        query: String,
+        message: String,
+    },
+    Io(io::ErrorKind, Arc<io::Error>),
+    /// A value could not be read from or written to JSON. The value is
+    /// given for the results that do not deserialize to the expected
+    /// type, truncated so that large documents stay readable.
+    Json(Arc<serde_json::Error>, Option<Value>),
     Other(String),
     NotFound,
 }
@@ -111,17 +281,85 @@ impl From<Driver> for Error {
     }
 }
 
+impl error::Error for Driver {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(_, error) => Some(&**error),
+            Self::Json(error, _) => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for Driver {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Auth(msg) => write!(f, "auth error; {}", msg),
+            Self::Tls(msg) => write!(f, "TLS error; {}", msg),
+            Self::AuthFailed { code, message } => {
+                write!(f, "authentication failed; {} (code {})", message, code)
+            }
+            Self::ProtocolMismatch { min, max } => write!(
+                f,
+                "unsupported protocol version {}, expected between {} and {}",
+                crate::cmd::connect::PROTOCOL_VERSION,
+                min,
+                max,
+            ),
             Self::ConnectionBroken => write!(f, "connection broken"),
+            Self::Cancelled => write!(f, "query cancelled"),
+            Self::ConnectTimeout(timeout) => {
+                write!(f, "connection timed out after {:?}", timeout)
+            }
+            Self::QueryTimeout(timeout) => write!(f, "query timed out after {:?}", timeout),
+            Self::Reconnected => write!(f, "the session reconnected, the query was lost"),
             Self::ConnectionLocked => write!(
                 f,
                 "another query is running a changefeed on this connection"
             ),
+            Self::CircuitOpen => write!(f, "circuit breaker is open"),
+            Self::ServerChanged { expected, found } => write!(
+                f,
+                "the session was answered by server {}, now by server {}",
+                expected, found
+            ),
+            Self::NoDefaultDb => write!(
+                f,
+                "the query does not name a database and no default database is configured"
+            ),
+            Self::ManyChanges(changes) => {
+                write!(f, "the write changed {} documents instead of one", changes)
+            }
+            Self::UnknownIndex {
+                index,
+                table,
+                known,
+            } => write!(
+                f,
+                "unknown index '{}' on table {} (known: {})",
+                index,
+                table,
+                known.join(", ")
+            ),
+            Self::InvalidEnv { name, value } => {
+                write!(f, "invalid value of {}: {:?}", name, value)
+            }
+            Self::NonFiniteFloat { float, path } if path.is_empty() => write!(
+                f,
+                "cannot send {} to the server, JSON has no NaN or infinite numbers",
+                float
+            ),
+            Self::NonFiniteFloat { float, path } => write!(
+                f,
+                "cannot send {} at {} to the server, JSON has no NaN or infinite numbers",
+                float, path
+            ),
+            Self::Unsupported { query, message } => {
+                write!(f, "the server does not support {}; {}", query, message)
+            }
             Self::Io(_, error) => write!(f, "{}", error),
-            Self::Json(error) => write!(f, "{}", error),
+            Self::Json(error, None) => write!(f, "{}", error),
+            Self::Json(error, Some(value)) => write!(f, "{} in {}", error, value),
             Self::Other(msg) => write!(f, "{}", msg),
             Self::NotFound => write!(f, "not found"),
         }
@@ -130,12 +368,150 @@ impl fmt::Display for Driver {
 
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
-        Driver::Io(err.kind(), err.to_string()).into()
+        Driver::Io(err.kind(), Arc::new(err)).into()
     }
 }
 
 impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Error {
-        Driver::Json(Arc::new(err)).into()
+        Driver::Json(Arc::new(err), None).into()
+    }
+}
+
+impl Driver {
+    // The error of a result not deserializing from `value`
+    pub(crate) fn json(error: serde_json::Error, value: &Value) -> Self {
+        Self::Json(Arc::new(error), Some(truncate(value, 0)))
+    }
+}
+
+// How much of a value is kept in a `Json` error
+const MAX_DEPTH: usize = 3;
+const MAX_ITEMS: usize = 8;
+const MAX_CHARS: usize = 64;
+const ELLIPSIS: &str = "...";
+
+fn truncate(value: &Value, depth: usize) -> Value {
+    match value {
+        Value::String(string) if string.chars().count() > MAX_CHARS => {
+            let prefix: String = string.chars().take(MAX_CHARS).collect();
+            Value::String(prefix + ELLIPSIS)
+        }
+        Value::Array(_) | Value::Object(_) if depth == MAX_DEPTH => ELLIPSIS.into(),
+        Value::Array(items) => {
+            let mut truncated: Vec<_> = (items.iter().take(MAX_ITEMS))
+                .map(|item| truncate(item, depth + 1))
+                .collect();
+            if items.len() > MAX_ITEMS {
+                truncated.push(ELLIPSIS.into());
+            }
+            Value::Array(truncated)
+        }
+        Value::Object(fields) => {
+            let mut truncated: serde_json::Map<_, _> = (fields.iter().take(MAX_ITEMS))
+                .map(|(key, item)| (key.clone(), truncate(item, depth + 1)))
+                .collect();
+            if fields.len() > MAX_ITEMS {
+                truncated.insert(ELLIPSIS.into(), ELLIPSIS.into());
+            }
+            Value::Object(truncated)
+        }
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn retryable() {
+        assert!(Error::from(Availability::OpFailed("primary replica lost".into())).is_retryable());
+        assert!(!Error::from(Availability::OpIndeterminate("timeout".into())).is_retryable());
+        assert!(!Error::from(Runtime::QueryLogic("bad type".into())).is_retryable());
+        assert!(!Error::from(Driver::ConnectionBroken).is_retryable());
+    }
+
+    fn chain(error: &dyn error::Error) -> Vec<String> {
+        let mut chain = vec![error.to_string()];
+        let mut source = error.source();
+        while let Some(error) = source {
+            chain.push(error.to_string());
+            source = error.source();
+        }
+        chain
+    }
+
+    #[test]
+    fn source_chain() {
+        let error = Error::from(io::Error::new(io::ErrorKind::UnexpectedEof, "eof"));
+        assert_eq!(chain(&error), ["client error; eof", "eof"]);
+        let source = error.source().unwrap();
+        let source = source.downcast_ref::<io::Error>().unwrap();
+        assert_eq!(source.kind(), io::ErrorKind::UnexpectedEof);
+
+        let json = serde_json::from_str::<u8>("x").unwrap_err();
+        let error = Error::from(json);
+        assert_eq!(chain(&error).len(), 2);
+        assert!(error.source().unwrap().is::<serde_json::Error>());
+
+        let error = Error::from(Availability::OpFailed("lost".into()));
+        assert_eq!(
+            chain(&error),
+            ["runtime error; availability error; operation failed; lost"]
+        );
+
+        assert!(Error::Compile("bad".into()).source().is_none());
+        assert!(Error::from(Driver::ConnectionBroken).source().is_none());
+    }
+
+    #[test]
+    fn json_error_value() {
+        let value = serde_json::json!({"id": 51, "tags": (0..10).collect::<Vec<_>>()});
+        let error = serde_json::from_value::<String>(value.clone()).unwrap_err();
+        let error = Driver::json(error, &value);
+        let Driver::Json(_, Some(truncated)) = &error else {
+            panic!("no value in {:?}", error);
+        };
+        assert_eq!(
+            truncated,
+            &serde_json::json!({"id": 51, "tags": [0, 1, 2, 3, 4, 5, 6, 7, "..."]})
+        );
+        assert!(error
+            .to_string()
+            .ends_with(r#" in {"id":51,"tags":[0,1,2,3,4,5,6,7,"..."]}"#));
+    }
+
+    #[test]
+    fn truncated_values() {
+        let long = "x".repeat(100);
+        assert_eq!(
+            truncate(&Value::from(long), 0),
+            format!("{}...", "x".repeat(64))
+        );
+        let nested = serde_json::json!([[[[[1]]]]]);
+        assert_eq!(truncate(&nested, 0), serde_json::json!([[["..."]]]));
+        let fields: serde_json::Map<_, _> = (0..10).map(|n| (format!("f{n}"), n.into())).collect();
+        let truncated = truncate(&Value::Object(fields), 0);
+        assert_eq!(truncated.as_object().unwrap().len(), MAX_ITEMS + 1);
+        assert_eq!(truncated[ELLIPSIS], ELLIPSIS);
+    }
+
+    #[tokio::test]
+    async fn user_payload_from_server() {
+        #[derive(serde::Deserialize)]
+        struct Refused {
+            code: String,
+            missing: u32,
+        }
+
+        let query = || crate::r.error_json(serde_json::json!({ "code": "NO_FUNDS", "missing": 5 }));
+        let response = r#"{"t":18,"e":5000000,"r":["{\"code\":\"NO_FUNDS\",\"missing\":5}"]}"#;
+        let session = crate::fake_server::answering(vec![(query(), response)]).await;
+        let error = query().exec::<Value>(&session).await.unwrap_err();
+        let refused = error.user_payload::<Refused>().unwrap().unwrap();
+        assert_eq!(refused.code, "NO_FUNDS");
+        assert_eq!(refused.missing, 5);
     }
 }
@@ -0,0 +1,228 @@
+//! In-memory log of the most recent protocol events of a session
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use std::{error, fmt, io};
+
+use crate::{Driver, Error};
+
+/// Whether the event was written to or read from the server
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// One protocol event recorded by [Session::recent_events](crate::Session::recent_events)
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Event {
+    /// When the event happened.
+    pub at: SystemTime,
+    /// The token of the query the event belongs to.
+    pub token: u64,
+    pub direction: Direction,
+    /// The type of the response, e.g. `SuccessAtom`. Only set for received events.
+    pub response_type: Option<String>,
+    /// The error if writing or reading failed.
+    pub error: Option<String>,
+    /// The number of bytes written or read, including the header.
+    pub size: usize,
+}
+
+impl Event {
+    pub(crate) fn new(token: u64, direction: Direction, size: usize) -> Self {
+        Self {
+            at: SystemTime::now(),
+            token,
+            direction,
+            response_type: None,
+            error: None,
+            size,
+        }
+    }
+
+    pub(crate) fn response_type(mut self, typ: impl std::fmt::Debug) -> Self {
+        self.response_type = Some(format!("{:?}", typ));
+        self
+    }
+
+    pub(crate) fn error(mut self, error: impl std::fmt::Display) -> Self {
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+// Fixed size ring buffer, the oldest event is dropped when it is full
+#[derive(Debug)]
+pub(crate) struct EventLog {
+    capacity: usize,
+    events: Mutex<VecDeque<Event>>,
+}
+
+impl EventLog {
+    pub(crate) fn new(capacity: usize) -> Option<Self> {
+        if capacity == 0 {
+            return None;
+        }
+        Some(Self {
+            capacity,
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+        })
+    }
+
+    pub(crate) fn push(&self, event: Event) {
+        let mut events = self.events.lock().unwrap_or_else(|x| x.into_inner());
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    pub(crate) fn events(&self) -> Vec<Event> {
+        let events = self.events.lock().unwrap_or_else(|x| x.into_inner());
+        events.iter().cloned().collect()
+    }
+}
+
+// An IO error with the most recent events of its session, which only its
+// `Debug` shows
+struct IoError {
+    error: Arc<io::Error>,
+    events: Vec<Event>,
+}
+
+impl fmt::Debug for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}, recent events: {:?}", self.error, self.events)
+    }
+}
+
+impl fmt::Display for IoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl error::Error for IoError {
+    // `error` itself is shown by `Display`
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+// `error` with the events of `log` if it comes from a broken connection,
+// the other errors are returned unchanged. A `ConnectionBroken` error
+// becomes an IO error of kind `ConnectionAborted` to hold them.
+pub(crate) fn attach(log: &EventLog, error: Error) -> Error {
+    let (kind, error) = match error {
+        Error::Driver(Driver::ConnectionBroken) => {
+            let kind = io::ErrorKind::ConnectionAborted;
+            let error = io::Error::new(kind, Driver::ConnectionBroken.to_string());
+            (kind, Arc::new(error))
+        }
+        Error::Driver(Driver::Io(kind, error)) => (kind, error),
+        error => return error,
+    };
+    let events = log.events();
+    let error = io::Error::new(kind, IoError { error, events });
+    Driver::Io(kind, Arc::new(error)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fake_server, r, InnerSession, Session};
+    use std::sync::Arc;
+
+    #[test]
+    fn disabled_when_empty() {
+        assert!(EventLog::new(0).is_none());
+    }
+
+    #[test]
+    fn wraps_around() {
+        let log = EventLog::new(3).unwrap();
+        for token in 0..5 {
+            log.push(Event::new(token, Direction::Sent, 10));
+        }
+        let tokens: Vec<_> = log.events().iter().map(|x| x.token).collect();
+        assert_eq!(tokens, [2, 3, 4]);
+    }
+
+    // A session to a server answering the first query with an unknown
+    // token, which breaks the connection
+    async fn broken_session(events: Option<EventLog>) -> Session {
+        let stream = fake_server::connect(|mut stream| async move {
+            fake_server::read_query(&mut stream).await;
+            fake_server::send(&mut stream, 999u64.to_le_bytes(), "").await;
+        });
+        Session {
+            inner: Arc::new(InnerSession::new(stream.await, "test".into(), events)),
+        }
+    }
+
+    #[tokio::test]
+    async fn records_broken_connection() {
+        let session = broken_session(EventLog::new(10)).await;
+        let result = r.expr(1).exec::<u8>(&session).await;
+
+        // the error carries the events up to the failure
+        let Err(Error::Driver(Driver::Io(kind, _))) = &result else {
+            panic!("expected a broken connection, got {:?}", result);
+        };
+        assert_eq!(*kind, io::ErrorKind::ConnectionAborted);
+
+        let events = session.recent_events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].direction, Direction::Sent);
+        assert_eq!(events[0].token, 0);
+        assert!(events[0].error.is_none());
+        assert_eq!(events[1].direction, Direction::Received);
+        assert_eq!(
+            events[1].error.as_deref(),
+            Some("client error; connection broken")
+        );
+        assert!(session.is_broken());
+    }
+
+    #[tokio::test]
+    async fn broken_connection_debug() {
+        let session = broken_session(EventLog::new(10)).await;
+        let error = r.expr(1).exec::<u8>(&session).await.unwrap_err();
+        // pasted as is, the error has the events
+        let debug = format!("{:?}", error);
+        assert!(debug.contains("recent events: [Event {"), "{}", debug);
+        assert!(debug.contains("direction: Sent"), "{}", debug);
+        assert!(debug.contains("direction: Received"), "{}", debug);
+        assert_eq!(error.to_string(), "client error; connection broken");
+        assert!(error.is_unreachable());
+    }
+
+    #[test]
+    fn io_error_debug() {
+        let log = EventLog::new(10).unwrap();
+        log.push(Event::new(7, Direction::Sent, 20).error("broken pipe"));
+        let error = io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe");
+        let error = attach(&log, error.into());
+        let Error::Driver(Driver::Io(kind, _)) = &error else {
+            panic!("expected an IO error, got {:?}", error);
+        };
+        assert_eq!(*kind, io::ErrorKind::BrokenPipe);
+        assert_eq!(error.to_string(), "client error; broken pipe");
+        let debug = format!("{:?}", error);
+        assert!(debug.contains("recent events: [Event {"), "{}", debug);
+        assert!(debug.contains("token: 7"), "{}", debug);
+    }
+
+    #[tokio::test]
+    async fn broken_without_log() {
+        let session = broken_session(None).await;
+        let result = r.expr(1).exec::<u8>(&session).await;
+        assert!(matches!(
+            result,
+            Err(crate::Error::Driver(crate::Driver::ConnectionBroken))
+        ));
+    }
+}
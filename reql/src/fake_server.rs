@@ -0,0 +1,225 @@
+//! A fake server for the tests of the driver
+//!
+//! It listens on a local port and runs a script of the test on the
+//! connection, which reads the queries and writes the responses with the
+//! frame helpers below. [rethinkdb] does the handshake first, for `admin`
+//! without password, so that the driver can connect to it.
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::num::NonZeroU32;
+use std::sync::Arc;
+
+use async_net::{TcpListener, TcpStream};
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use scram::{AuthenticationProvider, PasswordInfo, ScramServer};
+use serde_json::{json, Value};
+
+use crate::cmd::connect;
+use crate::cmd::run::{DEFAULT_DB, HEADER_SIZE, TOKEN_SIZE};
+use crate::tools::renumber_vars;
+use crate::{Command, InnerSession, Session};
+
+pub(crate) type Token = [u8; TOKEN_SIZE];
+
+pub(crate) const SERVER_INFO: &str = r#"{"success":true,"min_protocol_version":0,"max_protocol_version":0,"server_version":"2.4.4"}"#;
+
+// The address of a server running `script` on its first connection
+pub(crate) async fn serve<F, Fut>(script: F) -> SocketAddr
+where
+    F: FnOnce(TcpStream) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        script(stream).await;
+    });
+    addr
+}
+
+// The options to connect to a server running `script` after the
+// handshake of each of its connections, numbered from 1
+pub(crate) async fn rethinkdb<F, Fut>(script: F) -> connect::Options
+where
+    F: Fn(usize, TcpStream) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        for n in 1.. {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let script = script(n, stream.clone());
+            tokio::spawn(async move {
+                accept(&mut stream).await;
+                script.await;
+            });
+        }
+    });
+    connect::Options::new()
+        .host(addr.ip().to_string())
+        .port(addr.port())
+}
+
+// A stream to a server running `script` on the connection
+pub(crate) async fn connect<F, Fut>(script: F) -> TcpStream
+where
+    F: FnOnce(TcpStream) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    TcpStream::connect(serve(script).await).await.unwrap()
+}
+
+// A session to a server running `script` on the connection
+pub(crate) async fn scripted<F, Fut>(script: F) -> Session
+where
+    F: FnOnce(TcpStream) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let stream = connect(script).await;
+    Session {
+        inner: Arc::new(InnerSession::new(stream, DEFAULT_DB.into(), None)),
+    }
+}
+
+// A session to a server answering every query with `body`
+pub(crate) async fn session(body: &'static str) -> Session {
+    scripted(move |mut stream| async move {
+        while let Some((token, _)) = next_query(&mut stream).await {
+            send(&mut stream, token, body).await;
+        }
+    })
+    .await
+}
+
+// A session to a server answering the queries of `answers` with their
+// responses, each once and in any order. The server stops at a query it
+// does not expect.
+pub(crate) async fn answering(answers: Vec<(Command, impl Into<String>)>) -> Session {
+    let mut answers: Vec<(Value, String)> = answers
+        .into_iter()
+        .map(|(query, body)| (renumber_vars(&json!([1, query, {}])), body.into()))
+        .collect();
+    scripted(move |mut stream| async move {
+        while !answers.is_empty() {
+            let (token, query) = read_query(&mut stream).await;
+            let query = renumber_vars(&query);
+            let Some(i) = answers.iter().position(|(expected, _)| *expected == query) else {
+                panic!("unexpected query {}", query);
+            };
+            let (_, body) = answers.remove(i);
+            send(&mut stream, token, &body).await;
+        }
+    })
+    .await
+}
+
+struct Admin;
+
+impl AuthenticationProvider for Admin {
+    fn get_password_for(&self, username: &str) -> Option<PasswordInfo> {
+        if username != "admin" {
+            return None;
+        }
+        let iterations = NonZeroU32::new(4096).unwrap();
+        let hashed = scram::hash_password("", iterations, b"salt");
+        Some(PasswordInfo::new(hashed.to_vec(), 4096, b"salt".to_vec()))
+    }
+}
+
+// The server side of the handshake, for `admin` without password
+pub(crate) async fn accept(stream: &mut TcpStream) {
+    let mut version = [0u8; 4];
+    stream.read_exact(&mut version).await.unwrap();
+    let client_first = read_message(stream).await;
+    let scram = ScramServer::new(Admin);
+    let auth = client_first["authentication"].as_str().unwrap();
+    let (scram, server_first) = scram.handle_client_first(auth).unwrap().server_first();
+    let server_first = json!({"success": true, "authentication": server_first});
+    let msg = format!("{}\0{}\0", SERVER_INFO, server_first);
+    stream.write_all(msg.as_bytes()).await.unwrap();
+    let client_final = read_message(stream).await;
+    let auth = client_final["authentication"].as_str().unwrap();
+    let (_, server_final) = scram.handle_client_final(auth).unwrap().server_final();
+    let server_final = json!({"success": true, "authentication": server_final});
+    stream
+        .write_all(format!("{}\0", server_final).as_bytes())
+        .await
+        .unwrap();
+}
+
+// Reads a null terminated message of the handshake
+async fn read_message(stream: &mut TcpStream) -> Value {
+    let mut msg = Vec::new();
+    let mut byte = [0u8];
+    loop {
+        stream.read_exact(&mut byte).await.unwrap();
+        if byte == [0] {
+            return serde_json::from_slice(&msg).unwrap();
+        }
+        msg.push(byte[0]);
+    }
+}
+
+// Reads the protocol version and the client first message of the
+// handshake, then writes `replies` one byte at a time, each followed by a
+// null byte. Returns the version.
+pub(crate) async fn reply<S>(stream: &mut S, replies: &[&str]) -> u32
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut version = [0u8; 4];
+    stream.read_exact(&mut version).await.unwrap();
+    let mut byte = [1u8];
+    while byte != [0] {
+        stream.read_exact(&mut byte).await.unwrap();
+    }
+    for reply in replies {
+        for byte in reply.bytes().chain([0]) {
+            stream.write_all(&[byte]).await.unwrap();
+            stream.flush().await.unwrap();
+        }
+    }
+    u32::from_le_bytes(version)
+}
+
+// Reads the next query with its token, `None` once the client is gone
+pub(crate) async fn next_query(stream: &mut TcpStream) -> Option<(Token, Value)> {
+    let mut header = [0u8; HEADER_SIZE];
+    stream.read_exact(&mut header).await.ok()?;
+    let len = u32::from_le_bytes(header[TOKEN_SIZE..].try_into().unwrap()) as usize;
+    let mut query = vec![0u8; len];
+    stream.read_exact(&mut query).await.unwrap();
+    let token = header[..TOKEN_SIZE].try_into().unwrap();
+    Some((token, serde_json::from_slice(&query).unwrap()))
+}
+
+// Reads a query and returns it with its token
+pub(crate) async fn read_query(stream: &mut TcpStream) -> (Token, Value) {
+    next_query(stream)
+        .await
+        .expect("the client closed the connection")
+}
+
+// Writes the response `body` to the query of `token`
+pub(crate) async fn send(stream: &mut TcpStream, token: Token, body: &str) {
+    let mut resp = token.to_vec();
+    resp.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    resp.extend_from_slice(body.as_bytes());
+    stream.write_all(&resp).await.unwrap();
+}
+
+// Answers the query of `token` with the number `n`, in two writes to
+// exercise partial reads
+pub(crate) async fn answer(stream: &mut TcpStream, token: Token, n: u32) {
+    let body = format!(r#"{{"t":1,"r":[{}]}}"#, n);
+    let mut resp = token.to_vec();
+    resp.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    resp.extend_from_slice(body.as_bytes());
+    let (head, tail) = resp.split_at(HEADER_SIZE + 3);
+    stream.write_all(head).await.unwrap();
+    stream.flush().await.unwrap();
+    stream.write_all(tail).await.unwrap();
+}
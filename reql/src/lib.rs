@@ -8,6 +8,12 @@
 //! use unreql::r;
 //! ```
 //!
+//! or everything commonly needed, see [prelude]
+//!
+//! ```
+//! use unreql::prelude::*;
+//! ```
+//!
 //! ## Connect
 //!
 //! ```
@@ -54,7 +60,7 @@
 //! # struct User;
 //! # async fn example() -> unreql::Result<()> {
 //! # let conn = r.connect(()).await?;
-//! let mut cur = r.table("users").run::<_, User>(&conn);
+//! let mut cur = r.table("users").run::<User>(&conn);
 //! while let Ok(Some(user)) = cur.try_next().await {
 //!   // do something with user
 //!   dbg!(user);
@@ -67,6 +73,7 @@
 //! ```
 //! use unreql::{r, rjson};
 //!
+//! # #[cfg(feature = "math-logic")]
 //! # async fn example() -> unreql::Result<()> {
 //! # let conn = r.connect(()).await?;
 //! r.table("users")
@@ -75,39 +82,71 @@
 //!         "name": "Jonh",
 //!         "upd_count": r.row().g("upd_count").add(1),
 //!     }))
-//!     .run::<_, serde_json::Value>(&conn);
+//!     .run::<serde_json::Value>(&conn);
 //! # Ok(()) }
 //! ```
-
+//!
+//! ## Features
+//!
+//! The commands of some groups can be left out to make smaller builds,
+//! all of them are enabled by default:
+//!
+//! - `dates-times`: `r.now`, `r.time`, `during`...
+//! - `geospatial`: `r.point`, `distance`...
+//! - `joins`: `inner_join`, `eq_join`...
+//! - `math-logic`: `add`, `eq`, `r.random`...
+//! - `strings`: `match_`, `split`...
+//! - `http`: `r.http`
+//! - `js`: `r.js`
+//!
+//! The other commands are always available, the driver builds its own
+//! queries with them. Other features:
+//!
+//! - `blocking`: a blocking API for scripts and command line tools
+//! - `record`: record queries and replay them without a server
+//! - `indexmap`: keep the key order of objects passed to [r.expr](r::expr)
+//! - `tls`: connect over TLS, with `rustls`, see `cmd::tls`
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod broadcast;
 pub mod cmd;
 mod err;
+mod events;
+#[cfg(test)]
+mod fake_server;
+pub mod pool;
+pub mod prelude;
 mod proto;
+#[cfg(feature = "record")]
+pub mod record;
 mod tools;
 pub mod types;
 
 #[macro_use]
 mod rjson_macros;
 
-use async_net::TcpStream;
 use cmd::args::{Args, ArgsWithOpt};
-use cmd::run::Response;
 use dashmap::DashMap;
+use events::EventLog;
 use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
 use futures::lock::Mutex;
 use proto::Payload;
 use ql2::query::QueryType;
-use ql2::response::ResponseType;
+use ql2::term::TermType;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use std::borrow::Cow;
 use std::ops::Drop;
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
-use tools::StaticString;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+pub use tools::StaticString;
 use tracing::trace;
 
 pub use cmd::func::Func;
 pub use err::*;
+pub use events::{Direction, Event};
+#[cfg(feature = "indexmap")]
+pub use indexmap::IndexMap;
 pub use proto::{Command, Datum};
 pub use types::DateTime;
 pub use unreql_macros::func;
@@ -129,20 +168,70 @@ fn current_counter() -> u64 {
 /// Custom result returned by various ReQL commands
 pub type Result<T> = std::result::Result<T, Error>;
 
-type Sender = UnboundedSender<Result<(ResponseType, Response)>>;
-type Receiver = UnboundedReceiver<Result<(ResponseType, Response)>>;
+type Sender = UnboundedSender<Result<Vec<u8>>>;
+type Receiver = UnboundedReceiver<Result<Vec<u8>>>;
 
 #[derive(Debug)]
 struct InnerSession {
     db: Mutex<Cow<'static, str>>,
-    stream: Mutex<TcpStream>,
+    // see `connect::Options::default_db_required`
+    db_required: bool,
+    // see `connect::Options::warn_unordered`
+    warn_unordered: bool,
+    reader: Mutex<cmd::run::Reader>,
+    writer: Mutex<cmd::run::Writer>,
     channels: DashMap<u64, Sender>,
+    // responses to discard by token, see `cmd::run::Pending`
+    stale: DashMap<u64, usize>,
+    indexes: cmd::validate::IndexCache,
     token: AtomicU64,
+    // the index of the host connected to in `connect::Options::hosts`
+    host: AtomicUsize,
+    // dials the server again, `None` for a session opened over a stream
+    dialer: Option<cmd::connect::Dialer>,
+    // how many times the session reconnected, the connections opened
+    // before are released with their epoch
+    epoch: AtomicU64,
+    reconnecting: Mutex<()>,
     broken: AtomicBool,
+    // closed by `Session::close`, never reconnected automatically
+    closed: AtomicBool,
     change_feed: AtomicBool,
+    events: Option<EventLog>,
+    // the id of the server first seen by a pool recycling the session,
+    // see `pool::RecyclePolicy::SameServer`
+    server_id: OnceLock<String>,
 }
 
 impl InnerSession {
+    fn new(
+        stream: impl Into<cmd::stream::Stream>,
+        db: Cow<'static, str>,
+        events: Option<EventLog>,
+    ) -> Self {
+        let (reader, writer) = cmd::run::split(stream.into());
+        Self {
+            db: Mutex::new(db),
+            db_required: false,
+            warn_unordered: false,
+            reader: Mutex::new(reader),
+            writer: Mutex::new(writer),
+            channels: DashMap::new(),
+            stale: DashMap::new(),
+            indexes: DashMap::new(),
+            token: AtomicU64::new(0),
+            host: AtomicUsize::new(0),
+            dialer: None,
+            epoch: AtomicU64::new(0),
+            reconnecting: Mutex::new(()),
+            broken: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            change_feed: AtomicBool::new(false),
+            events,
+            server_id: OnceLock::new(),
+        }
+    }
+
     fn token(&self) -> u64 {
         let token = self
             .token
@@ -165,6 +254,35 @@ impl InnerSession {
         Ok(())
     }
 
+    fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+
+    // Whether the session reconnects when broken, see
+    // `connect::Options::auto_reconnect`
+    fn auto_reconnect(&self) -> bool {
+        let auto = self
+            .dialer
+            .as_ref()
+            .is_some_and(cmd::connect::Dialer::auto_reconnect);
+        auto && !self.closed.load(Ordering::SeqCst)
+    }
+
+    fn record(&self, event: impl FnOnce() -> Event) {
+        if let Some(events) = &self.events {
+            events.push(event());
+        }
+    }
+
+    // Attaches the recorded events, if they are kept, to the error of a
+    // broken connection failing a query
+    fn with_events(&self, error: Error) -> Error {
+        match &self.events {
+            Some(events) => events::attach(events, error),
+            None => error,
+        }
+    }
+
     fn mark_change_feed(&self) {
         self.change_feed.store(true, Ordering::SeqCst);
     }
@@ -193,16 +311,21 @@ pub struct Session {
 
 impl Session {
     pub fn connection(&self) -> Result<Connection> {
-        self.inner.broken()?;
+        // the first request reconnects
+        if !self.inner.auto_reconnect() {
+            self.inner.broken()?;
+        }
         self.inner.change_feed()?;
         let token = self.inner.token();
         let (tx, rx) = mpsc::unbounded();
         self.inner.channels.insert(token, tx);
-        Ok(Connection::new(self.clone(), rx, token))
+        Ok(Connection::new(self.clone(), rx, token, self.inner.epoch()))
     }
 
     /// Change the default database on this connection
     ///
+    /// The name can be any [StaticString], e.g. a `&String`.
+    ///
     /// ## Example
     ///
     /// Change the default database so that we don’t need to specify the
@@ -261,23 +384,206 @@ impl Session {
         Ok(())
     }
 
+    /// Information about the server the session is connected to
+    ///
+    /// A server answering the `SERVER_INFO` query with an error, or with
+    /// something else than the info of a server, fails with an
+    /// [Unsupported](Driver::Unsupported) error.
     pub async fn server(&self) -> Result<ServerInfo> {
+        let unsupported = |message: String| -> Error {
+            Driver::Unsupported {
+                query: "SERVER_INFO".to_owned(),
+                message,
+            }
+            .into()
+        };
         let mut conn = self.connection()?;
         let payload = Payload(QueryType::ServerInfo, None, Default::default());
         trace!("retrieving server information; token: {}", conn.token);
-        let (typ, resp) = conn.request(&payload, false).await?;
+        let (typ, resp) = match conn.request(&payload, false).await {
+            Err(Error::Compile(msg) | Error::Driver(Driver::Other(msg))) => {
+                return Err(unsupported(msg))
+            }
+            Err(Error::Runtime(error)) => return Err(unsupported(error.to_string())),
+            result => result?,
+        };
         trace!(
             "session.server() run; token: {}, response type: {:?}",
             conn.token,
             typ,
         );
-        let mut vec = serde_json::from_value::<Vec<ServerInfo>>(resp.r)?;
+        let mut vec = serde_json::from_value::<Vec<ServerInfo>>(resp.r)
+            .map_err(|error| unsupported(error.to_string()))?;
         let info = vec
             .pop()
-            .ok_or_else(|| Driver::Other("server info is empty".into()))?;
+            .ok_or_else(|| unsupported("server info is empty".into()))?;
         Ok(info)
     }
 
+    /// The current time of the server
+    ///
+    #[cfg_attr(
+        feature = "dates-times",
+        doc = " Runs [r.now](r::now) and reads the `TIME` pseudotype it returns,"
+    )]
+    #[cfg_attr(
+        not(feature = "dates-times"),
+        doc = " Runs `r.now` and reads the `TIME` pseudotype it returns,"
+    )]
+    /// keeping the timezone of the server.
+    ///
+    /// `r.now()` is evaluated once per query, so writes made by separate
+    /// queries get different times. Capture the time once and pass it to
+    /// every query instead, it is sent back as the same ReQL time.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, rjson};
+    /// # async fn example(session: unreql::Session) -> unreql::Result<()> {
+    /// let now = session.server_time().await?;
+    /// r.table("orders")
+    ///     .get(1)
+    ///     .update(rjson!({ "shipped_at": now.clone() }))
+    ///     .exec::<serde_json::Value>(&session)
+    ///     .await?;
+    /// r.table("log")
+    ///     .insert(rjson!({ "order": 1, "at": now }))
+    ///     .exec::<serde_json::Value>(&session)
+    ///     .await?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn server_time(&self) -> Result<DateTime> {
+        // not `r.now()`, which is left out without the `dates-times` feature
+        Command::new(TermType::Now).exec(self).await
+    }
+
+    /// The most recent protocol events on this session, oldest first
+    ///
+    /// Events are only recorded if the session was opened with a non-zero
+    /// [event_log_size](cmd::connect::Options::event_log_size), otherwise
+    /// the list is always empty.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, cmd::connect::Options};
+    /// # async fn example() -> unreql::Result<()> {
+    /// let session = r.connect(Options::new().event_log_size(100)).await?;
+    /// // ...
+    /// for event in session.recent_events() {
+    ///     eprintln!("{:?}", event);
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn recent_events(&self) -> Vec<Event> {
+        match &self.inner.events {
+            Some(events) => events.events(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Close the session
+    ///
+    /// Waits for the `noreply` queries of the session to be processed,
+    /// unless [SkipNoreplyWait](cmd::close::SkipNoreplyWait) is passed, then
+    /// shuts the socket down. The session and all its clones are broken
+    /// afterwards. Closing a broken session does nothing.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # async fn example() -> unreql::Result<()> {
+    /// let session = unreql::r.connect(()).await?;
+    /// // ...
+    /// session.close(()).await
+    /// # }
+    /// ```
+    ///
+    /// [Read more about this command →](cmd::close)
+    pub async fn close<T>(&self, arg: T) -> Result<()>
+    where
+        T: cmd::close::Arg,
+    {
+        if self.is_broken() {
+            return Ok(());
+        }
+        if arg.noreply_wait() {
+            self.noreply_wait().await?;
+        }
+        trace!("closing the session");
+        self.inner.closed.store(true, Ordering::SeqCst);
+        self.inner.mark_broken();
+        self.inner.writer.lock().await.shutdown()?;
+        Ok(())
+    }
+
+    /// Connect to the server again, e.g. after it restarted
+    ///
+    /// Dials the server with the [options](cmd::connect::Options) the
+    /// session was opened with, from the last host it reached, and swaps
+    /// the stream of the session. The queries waiting for a response and
+    /// the connections opened before fail with
+    /// [Reconnected](Driver::Reconnected), a running changefeed is lost.
+    /// The session and its clones then work as new.
+    ///
+    /// Only the sessions opened by [connect](r::connect) without an
+    /// explicit address can reconnect. See also
+    /// [auto_reconnect](cmd::connect::Options::auto_reconnect).
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, Driver, Error};
+    /// # async fn example(session: unreql::Session) -> unreql::Result<()> {
+    /// let count = match r.table("heroes").count(()).exec::<u64>(&session).await {
+    ///     Err(Error::Driver(Driver::ConnectionBroken | Driver::Io(..))) => {
+    ///         session.reconnect().await?;
+    ///         r.table("heroes").count(()).exec(&session).await?
+    ///     }
+    ///     count => count?,
+    /// };
+    /// # Ok(()) }
+    /// ```
+    pub async fn reconnect(&self) -> Result<()> {
+        self.redial(self.inner.epoch()).await
+    }
+
+    // Reconnects, unless the session already reconnected since `epoch`
+    async fn redial(&self, epoch: u64) -> Result<()> {
+        let Some(dialer) = &self.inner.dialer else {
+            let msg = "cannot reconnect a session opened over a stream or an address";
+            return Err(Driver::Other(msg.into()).into());
+        };
+        let _reconnecting = self.inner.reconnecting.lock().await;
+        if self.inner.epoch() != epoch {
+            return Ok(());
+        }
+        let (stream, host) = dialer.dial(self.inner.host.load(Ordering::SeqCst)).await?;
+        trace!("reconnected; host: {}", host);
+        // the read of a response on the old stream ends with it, before
+        // the reader is locked
+        let tcp = self.inner.writer.lock().await.tcp().clone();
+        let _ = tcp.shutdown(std::net::Shutdown::Both);
+        let mut reader = self.inner.reader.lock().await;
+        let mut writer = self.inner.writer.lock().await;
+        (*reader, *writer) = cmd::run::split(stream);
+        self.inner.host.store(host, Ordering::SeqCst);
+        self.inner.epoch.fetch_add(1, Ordering::SeqCst);
+        self.inner.token.store(0, Ordering::SeqCst);
+        self.inner.stale.clear();
+        let tokens: Vec<u64> = self.inner.channels.iter().map(|tx| *tx.key()).collect();
+        for token in tokens {
+            if let Some((_, tx)) = self.inner.channels.remove(&token) {
+                let _ = tx.unbounded_send(Err(Driver::Reconnected.into()));
+            }
+        }
+        self.inner.unmark_change_feed();
+        self.inner.closed.store(false, Ordering::SeqCst);
+        self.inner.broken.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
     #[doc(hidden)]
     pub fn is_broken(&self) -> bool {
         self.inner.broken.load(Ordering::SeqCst)
@@ -297,21 +603,40 @@ pub struct Connection {
     session: Session,
     rx: Arc<Mutex<Receiver>>,
     token: u64,
+    // the epoch of the session the token belongs to
+    epoch: u64,
     closed: Arc<AtomicBool>,
+    // releases the token once all the clones are dropped
+    _release: Arc<Release>,
+    #[cfg(feature = "record")]
+    recording: Option<record::Recording>,
 }
 
 impl Connection {
-    fn new(session: Session, rx: Receiver, token: u64) -> Connection {
+    fn new(session: Session, rx: Receiver, token: u64, epoch: u64) -> Connection {
         Connection {
+            _release: Arc::new(Release {
+                session: session.clone(),
+                token,
+                epoch,
+            }),
             session,
             token,
+            epoch,
             rx: Arc::new(Mutex::new(rx)),
             closed: Arc::new(AtomicBool::new(false)),
+            #[cfg(feature = "record")]
+            recording: None,
         }
     }
 
     /// Close an open connection
     ///
+    /// Stops the changefeed running on this connection, if any, by sending
+    /// a `STOP` query for its token. Unless [SkipNoreplyWait](cmd::close::SkipNoreplyWait)
+    /// is passed, it then waits for the `noreply` queries of the session to
+    /// be processed, like [noreply_wait](Session::noreply_wait).
+    ///
     /// ## Example
     ///
     /// Close an open connection, waiting for noreply writes to finish.
@@ -329,31 +654,102 @@ impl Connection {
     where
         T: cmd::close::Arg,
     {
-        if !self.session.inner.is_change_feed() {
+        if self.session.inner.is_change_feed() {
+            self.set_closed(true);
+            // `STOP` takes no term and no options
+            let payload = Payload(QueryType::Stop, None, Default::default());
+            trace!("closing a changefeed; token: {}", self.token);
+            let (typ, _) = self.request(&payload, false).await?;
+            self.session.inner.unmark_change_feed();
+            trace!(
+                "conn.close() run; token: {}, response type: {:?}",
+                self.token,
+                typ,
+            );
+        } else {
             trace!(
-                "ignoring conn.close() called on a normal connection; token: {}",
+                "no changefeed to stop on conn.close(); token: {}",
                 self.token
             );
-            return Ok(());
         }
-        self.set_closed(true);
-        let arg = if arg.noreply_wait() {
-            None
-        } else {
-            Some(r.expr(json!({ "noreply": false })))
-        };
-        let payload = Payload(QueryType::Stop, arg.as_ref(), Default::default());
-        trace!("closing a changefeed; token: {}", self.token);
-        let (typ, _) = self.request(&payload, false).await?;
-        self.session.inner.unmark_change_feed();
-        trace!(
-            "conn.close() run; token: {}, response type: {:?}",
-            self.token,
-            typ,
-        );
+        // the session is only free for another query once the feed stopped
+        if arg.noreply_wait() {
+            self.session.noreply_wait().await?;
+        }
         Ok(())
     }
 
+    /// Start a query and return its first batch of results
+    ///
+    /// A low-level API for custom streaming layers, sending the `START`
+    /// query of the protocol on the token of this connection. While
+    /// [Batch::more](cmd::run::Batch::more) is `true`, fetch the next
+    /// batches with [continue_query](Self::continue_query), or close the
+    /// cursor with [stop_query](Self::stop_query). [run](Command::run)
+    /// does all that for you.
+    ///
+    /// ## Example
+    ///
+    /// Read the first two batches of a table.
+    ///
+    /// ```
+    /// # use unreql::{r, cmd::run::Options};
+    /// # async fn example() -> unreql::Result<()> {
+    /// # let session = r.connect(()).await?;
+    /// let mut conn = session.connection()?;
+    /// let batch = conn.start_query(&r.table("heroes"), Options::default()).await?;
+    /// if batch.more {
+    ///     let next = conn.continue_query().await?;
+    ///     if next.more {
+    ///         conn.stop_query().await?;
+    ///     }
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub async fn start_query(
+        &mut self,
+        query: &Command,
+        opts: cmd::run::Options,
+    ) -> Result<cmd::run::Batch> {
+        let opts = opts.default_db(&self.session).await;
+        let noreply = opts.noreply.unwrap_or_default();
+        let payload = Payload(QueryType::Start, Some(query), opts);
+        self.batch(&payload, noreply).await
+    }
+
+    /// Fetch the next batch of the query started with
+    /// [start_query](Self::start_query)
+    ///
+    /// Sends the `CONTINUE` query of the protocol. For a changefeed, it
+    /// waits until the server has new changes.
+    pub async fn continue_query(&mut self) -> Result<cmd::run::Batch> {
+        let payload = Payload(QueryType::Continue, None, Default::default());
+        self.batch(&payload, false).await
+    }
+
+    /// Close the cursor of the query started with
+    /// [start_query](Self::start_query)
+    ///
+    /// Sends the `STOP` query of the protocol, e.g. to stop a changefeed
+    /// or to skip the remaining batches of a query.
+    pub async fn stop_query(&mut self) -> Result<()> {
+        let payload = Payload(QueryType::Stop, None, Default::default());
+        self.batch(&payload, false).await?;
+        Ok(())
+    }
+
+    // A new connection of the same session, for running a query without
+    // consuming this one
+    pub(crate) fn sibling(&self) -> Result<Connection> {
+        #[allow(unused_mut)]
+        let mut conn = self.session.connection()?;
+        #[cfg(feature = "record")]
+        {
+            conn.recording = self.recording.clone();
+        }
+        Ok(conn)
+    }
+
     fn closed(&self) -> bool {
         self.closed.load(Ordering::SeqCst)
     }
@@ -363,9 +759,22 @@ impl Connection {
     }
 }
 
-impl Drop for Connection {
+// Releases the token of a connection when its last clone is dropped
+#[derive(Debug)]
+struct Release {
+    session: Session,
+    token: u64,
+    epoch: u64,
+}
+
+impl Drop for Release {
     fn drop(&mut self) {
+        // the token may be reused since the session reconnected
+        if self.epoch != self.session.inner.epoch() {
+            return;
+        }
         self.session.inner.channels.remove(&self.token);
+        self.session.inner.stale.remove(&self.token);
         if self.session.inner.is_change_feed() {
             self.session.inner.unmark_change_feed();
         }
@@ -419,8 +828,22 @@ impl r {
     /// r.expr(json!({"a":"b"})).merge(json!({"b":[1,2,3]})).run(conn)
     /// # })
     /// ```
+    ///
+    /// With the `indexmap` feature the keys of objects keep their order,
+    /// e.g. of an [IndexMap] or a `BTreeMap`:
+    ///
+    /// ```
+    /// # #[cfg(feature = "indexmap")] {
+    /// # use unreql::{r, IndexMap};
+    /// let mut obj = IndexMap::new();
+    /// obj.insert("b", 1);
+    /// obj.insert("a", 2);
+    /// let query = r.expr(obj);
+    /// assert_eq!(serde_json::to_string(&query).unwrap(), r#"{"b":1,"a":2}"#);
+    /// # }
+    /// ```
     pub fn expr(self, arg: impl Serialize) -> Command {
-        Command::from_json(arg)
+        Command::from_expr(arg)
     }
 
     /// `r.args` is a special term that’s used to splice an array of
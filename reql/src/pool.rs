@@ -0,0 +1,275 @@
+//! Building blocks of the connection pool integrations
+//!
+//! The pool crates, such as `unreql_deadpool`, share how sessions are
+//! created and checked, so every pool behaves the same way.
+
+mod breaker;
+mod retry;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use std::{fmt, io};
+
+use async_io::Timer;
+use tracing::trace;
+
+use crate::cmd::connect::{self, TcpStream};
+use crate::{r, Driver, Result, Session};
+
+pub use breaker::{BreakerState, CircuitBreaker};
+pub use retry::RetryPolicy;
+
+type ConnectFuture = Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send>>;
+
+#[derive(Clone)]
+struct Connector(Arc<dyn Fn() -> ConnectFuture + Send + Sync>);
+
+impl fmt::Debug for Connector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Connector")
+    }
+}
+
+/// How [SessionFactory::recycle] checks a pooled session
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RecyclePolicy {
+    /// Run a trivial query.
+    #[default]
+    Ping,
+    /// Ask which server answers the session with
+    /// [server](Session::server) and evict the session with
+    /// [Driver::ServerChanged] if it is not the server first seen on it.
+    ///
+    /// Behind a proxy or a load balancer a failover can hand the
+    /// connection over to another server, e.g. one that lags behind.
+    SameServer,
+}
+
+/// Creates the sessions of a pool and checks them before reuse
+#[derive(Debug, Clone)]
+pub struct SessionFactory {
+    options: connect::Options,
+    breaker: Option<Arc<CircuitBreaker>>,
+    connector: Option<Connector>,
+    recycle_policy: RecyclePolicy,
+    retry: Option<RetryPolicy>,
+}
+
+impl SessionFactory {
+    pub fn new(options: connect::Options) -> Self {
+        Self {
+            options,
+            breaker: None,
+            connector: None,
+            recycle_policy: RecyclePolicy::default(),
+            retry: None,
+        }
+    }
+
+    /// Open the connections of new sessions with `connector`
+    ///
+    /// The closure returns a connected stream, the handshake is then done
+    /// over it with the options of the factory. `host`, `port` and `hosts`
+    /// of the options are not used.
+    pub fn with_connector<F, Fut>(mut self, connector: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<TcpStream>> + Send + 'static,
+    {
+        let connector = move || -> ConnectFuture { Box::pin(connector()) };
+        self.connector = Some(Connector(Arc::new(connector)));
+        self
+    }
+
+    /// Guard [create](Self::create) and [recycle](Self::recycle) with a
+    /// [CircuitBreaker]
+    pub fn with_circuit_breaker(mut self, threshold: u32, cool_down: Duration) -> Self {
+        self.breaker = Some(Arc::new(CircuitBreaker::new(threshold, cool_down)));
+        self
+    }
+
+    /// Set how [recycle](Self::recycle) checks a pooled session
+    pub fn with_recycle_policy(mut self, policy: RecyclePolicy) -> Self {
+        self.recycle_policy = policy;
+        self
+    }
+
+    /// Retry [create](Self::create) while the server is unreachable, as
+    /// set by `policy`
+    ///
+    /// With a circuit breaker, all the attempts of one session count as
+    /// one failure.
+    pub fn with_retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// The state of the circuit breaker, `None` if it is not enabled
+    pub fn breaker_state(&self) -> Option<BreakerState> {
+        self.breaker.as_deref().map(CircuitBreaker::state)
+    }
+
+    /// Open a new session, bypassing the circuit breaker
+    ///
+    /// Pools use it for changefeeds, which get a session of their own.
+    pub async fn new_session(&self) -> Result<Session> {
+        match &self.connector {
+            Some(connector) => {
+                // the timeout covers the connector too
+                let options = self.options.clone();
+                connect::with_timeout(options.timeout, async {
+                    let stream = (connector.0)().await?;
+                    connect::open(stream, options).await
+                })
+                .await
+            }
+            None => r.connect(self.options.clone()).await,
+        }
+    }
+
+    /// Open a new session for the pool
+    pub async fn create(&self) -> Result<Session> {
+        let Some(breaker) = &self.breaker else {
+            return self.retry_session().await;
+        };
+        breaker.check(Instant::now())?;
+        let session = self.retry_session().await;
+        breaker.record(&session, Instant::now());
+        session
+    }
+
+    // Opens a new session, retrying as set by the retry policy
+    async fn retry_session(&self) -> Result<Session> {
+        let Some(policy) = &self.retry else {
+            return self.new_session().await;
+        };
+        let mut attempt = 1;
+        loop {
+            match self.new_session().await {
+                Err(err) if policy.retries(attempt, &err) => {
+                    let delay = policy.delay(attempt);
+                    trace!(attempt, ?delay, "retrying to connect; error: {}", err);
+                    Timer::after(delay).await;
+                    attempt += 1;
+                }
+                session => return session,
+            }
+        }
+    }
+
+    /// Check that a pooled session still answers queries, as set by
+    /// the [RecyclePolicy]
+    pub async fn recycle(&self, session: &Session) -> Result<()> {
+        let server = match self.recycle_policy {
+            RecyclePolicy::Ping => r.expr(200).exec::<i64>(session).await.map(|_| None),
+            RecyclePolicy::SameServer => session.server().await.map(|info| Some(info.id)),
+        };
+        // a session answered by another server still reached the cluster
+        if let Some(breaker) = &self.breaker {
+            breaker.record(&server, Instant::now());
+        }
+        match server? {
+            Some(id) => same_server(session, id),
+            None => Ok(()),
+        }
+    }
+}
+
+// Fails if `id` is not the id of the server first seen on the session
+fn same_server(session: &Session, id: String) -> Result<()> {
+    let expected = session.inner.server_id.get_or_init(|| id.clone());
+    if *expected != id {
+        let expected = expected.clone();
+        return Err(Driver::ServerChanged {
+            expected,
+            found: id,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fake_server, Error};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // A session to a server answering `SERVER_INFO` queries with `ids`,
+    // one after another
+    async fn session(ids: &'static [&'static str]) -> Session {
+        fake_server::scripted(move |mut stream| async move {
+            for id in ids {
+                let (token, _) = fake_server::read_query(&mut stream).await;
+                let info = json!({"id": id, "proxy": false, "name": id});
+                let body = json!({"t": 5, "r": [info]}).to_string();
+                fake_server::send(&mut stream, token, &body).await;
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn same_server_evicts_on_failover() {
+        let factory = SessionFactory::new(connect::Options::default())
+            .with_recycle_policy(RecyclePolicy::SameServer)
+            .with_circuit_breaker(1, Duration::from_secs(60));
+        let session = session(&["a", "a", "b"]).await;
+        factory.recycle(&session).await.unwrap();
+        factory.recycle(&session).await.unwrap();
+        match factory.recycle(&session).await {
+            Err(Error::Driver(Driver::ServerChanged { expected, found })) => {
+                assert_eq!((expected.as_str(), found.as_str()), ("a", "b"));
+            }
+            other => panic!("expected the session to be evicted, got {:?}", other),
+        }
+        // the cluster was reached
+        assert_eq!(factory.breaker_state(), Some(BreakerState::Closed));
+    }
+
+    #[tokio::test]
+    async fn connector_timeout() {
+        let options = connect::Options::new().timeout(Duration::from_millis(50));
+        let factory = SessionFactory::new(options).with_connector(std::future::pending);
+        match factory.create().await {
+            Err(Error::Driver(Driver::ConnectTimeout(timeout))) => {
+                assert_eq!(timeout, Duration::from_millis(50));
+            }
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_until_attempts_are_exhausted() {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let policy = RetryPolicy::new()
+            .max_attempts(4)
+            .base_delay(Duration::from_millis(10))
+            .max_delay(Duration::from_millis(20));
+        let counter = attempts.clone();
+        let factory = SessionFactory::new(connect::Options::default())
+            .with_retry(policy)
+            .with_circuit_breaker(2, Duration::from_secs(60))
+            .with_connector(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { Err(io::Error::from(io::ErrorKind::ConnectionRefused)) }
+            });
+
+        let start = Instant::now();
+        match factory.create().await {
+            Err(Error::Driver(Driver::Io(io::ErrorKind::ConnectionRefused, _))) => {}
+            other => panic!("expected the connection to be refused, got {:?}", other),
+        }
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        // the delays are at least 5ms, 10ms and 10ms, at most 10ms, 20ms and 20ms
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(25), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+        // the attempts count as one failure
+        assert_eq!(factory.breaker_state(), Some(BreakerState::Closed));
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{Driver, Error};
+
+/// State of a [CircuitBreaker]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BreakerState {
+    /// Sessions are created as usual.
+    Closed,
+    /// Too many sessions failed in a row, creating new ones fails fast
+    /// with [Driver::CircuitOpen] until the cool-down is over.
+    Open,
+    /// The cool-down is over and one session is being created to probe
+    /// the cluster. Success closes the breaker, failure opens it again.
+    HalfOpen,
+}
+
+/// Stops connecting to an unavailable cluster for a while
+///
+/// After `threshold` consecutive failures the breaker opens: for
+/// `cool_down` [check](Self::check) fails right away with
+/// [Driver::CircuitOpen]. Then one attempt is allowed to probe the
+/// cluster, if it succeeds the breaker closes again, otherwise it opens
+/// for another `cool_down`.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cool_down: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    state: BreakerState,
+    failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cool_down: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cool_down,
+            inner: Mutex::new(Inner {
+                state: BreakerState::Closed,
+                failures: 0,
+                opened_at: None,
+            }),
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.lock().state
+    }
+
+    /// Call before creating a session
+    pub fn check(&self, now: Instant) -> Result<(), Error> {
+        let mut inner = self.lock();
+        match inner.state {
+            BreakerState::Closed => Ok(()),
+            // only one probe at a time, another one is allowed if the
+            // previous probe never finished within the cool-down
+            BreakerState::Open | BreakerState::HalfOpen => match inner.opened_at {
+                Some(at) if now.saturating_duration_since(at) < self.cool_down => {
+                    Err(Driver::CircuitOpen.into())
+                }
+                _ => {
+                    inner.state = BreakerState::HalfOpen;
+                    inner.opened_at = Some(now);
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    /// Call with the outcome of creating or recycling a session
+    pub fn record<T>(&self, result: &Result<T, Error>, now: Instant) {
+        let mut inner = self.lock();
+        if result.is_ok() {
+            inner.state = BreakerState::Closed;
+            inner.failures = 0;
+            inner.opened_at = None;
+            return;
+        }
+        inner.failures = inner.failures.saturating_add(1);
+        if inner.state == BreakerState::HalfOpen || inner.failures >= self.threshold {
+            inner.state = BreakerState::Open;
+            inner.opened_at = Some(now);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|x| x.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAILED: Result<(), Error> = Err(Error::Driver(Driver::ConnectionBroken));
+
+    #[test]
+    fn opens_after_threshold() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(10));
+        let now = Instant::now();
+        for _ in 0..2 {
+            breaker.check(now).unwrap();
+            breaker.record(&FAILED, now);
+        }
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        breaker.record(&FAILED, now);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(matches!(
+            breaker.check(now + Duration::from_secs(5)),
+            Err(Error::Driver(Driver::CircuitOpen))
+        ));
+    }
+
+    #[test]
+    fn success_resets_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(10));
+        let now = Instant::now();
+        breaker.record(&FAILED, now);
+        breaker.record(&Ok(()), now);
+        breaker.record(&FAILED, now);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[test]
+    fn half_open_probe_recovers() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(10));
+        let now = Instant::now();
+        breaker.record(&FAILED, now);
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        let later = now + Duration::from_secs(10);
+        breaker.check(later).unwrap();
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        // a second caller fails fast while the probe is running
+        assert!(breaker.check(later).is_err());
+
+        breaker.record(&Ok(()), later);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        breaker.check(later).unwrap();
+    }
+
+    #[test]
+    fn failed_probe_opens_again() {
+        let breaker = CircuitBreaker::new(5, Duration::from_secs(10));
+        let now = Instant::now();
+        for _ in 0..5 {
+            breaker.record(&FAILED, now);
+        }
+        let later = now + Duration::from_secs(11);
+        breaker.check(later).unwrap();
+        breaker.record(&FAILED, later);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(breaker.check(later + Duration::from_secs(9)).is_err());
+        breaker.check(later + Duration::from_secs(10)).unwrap();
+    }
+}
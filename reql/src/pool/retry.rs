@@ -0,0 +1,121 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use unreql_macros::OptionsBuilder;
+
+use crate::Error;
+
+/// How [SessionFactory::create](super::SessionFactory::create) retries to
+/// connect to a server that is temporarily unavailable
+///
+/// The delay before the second attempt is `base_delay`, it doubles before
+/// every following attempt up to `max_delay`. With `jitter` every delay is
+/// drawn between half and all of it, so that the sessions of a pool do
+/// not all reconnect at the same time.
+///
+/// Only the failures to reach the server are retried: IO errors, broken
+/// connections and [connect timeouts](crate::cmd::connect::Options::timeout).
+/// E.g. wrong credentials are returned right away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, OptionsBuilder)]
+#[non_exhaustive]
+pub struct RetryPolicy {
+    /// How many times to try to connect, the first attempt included,
+    /// by default `3`.
+    pub max_attempts: u32,
+    /// The delay before the second attempt, by default 100ms.
+    pub base_delay: Duration,
+    /// The upper bound of the delay, by default 5s.
+    pub max_delay: Duration,
+    /// Randomize the delays, by default `true`.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The delay after the failed attempt `attempt`, counted from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        if !self.jitter {
+            return delay;
+        }
+        let half = delay / 2;
+        half + half.mul_f64(random())
+    }
+
+    /// Whether another attempt follows the failed attempt `attempt` that
+    /// failed with `err`
+    pub(crate) fn retries(&self, attempt: u32, err: &Error) -> bool {
+        attempt < self.max_attempts && err.is_unreachable()
+    }
+}
+
+// A number between 0 and 1, random enough to spread reconnections
+fn random() -> f64 {
+    let hash = RandomState::new().build_hasher().finish();
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Driver;
+    use std::io;
+
+    #[test]
+    fn exponential_delays() {
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_secs(1))
+            .jitter(false);
+        let delays: Vec<_> = (1..=6).map(|attempt| policy.delay(attempt)).collect();
+        let millis = |ms: &[u64]| {
+            ms.iter()
+                .map(|ms| Duration::from_millis(*ms))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(delays, millis(&[100, 200, 400, 800, 1000, 1000]));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jittered_delays_stay_in_bounds() {
+        let policy = RetryPolicy::new()
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(300));
+        for _ in 0..100 {
+            let delay = policy.delay(1);
+            assert!(Duration::from_millis(50) <= delay && delay <= Duration::from_millis(100));
+            let delay = policy.delay(5);
+            assert!(Duration::from_millis(150) <= delay && delay <= Duration::from_millis(300));
+        }
+    }
+
+    #[test]
+    fn retries_unreachable_servers() {
+        let policy = RetryPolicy::new().max_attempts(2);
+        let refused = Error::from(io::Error::from(io::ErrorKind::ConnectionRefused));
+        assert!(policy.retries(1, &refused));
+        assert!(!policy.retries(2, &refused));
+        assert!(policy.retries(1, &Driver::ConnectTimeout(Duration::from_secs(1)).into()));
+
+        let rejected = Driver::AuthFailed {
+            code: 12,
+            message: "Wrong password".into(),
+        };
+        assert!(!policy.retries(1, &rejected.into()));
+    }
+}
@@ -0,0 +1,55 @@
+//! The types and macros most programs need, in one import
+//!
+//! The paths they are re-exported from stay available, the prelude only
+//! gathers them. The two `Options` of [connect](crate::cmd::connect) and
+//! [run](crate::cmd::run) are renamed [ConnectOptions] and [RunOptions].
+//! The stream extension traits of `futures` are imported anonymously, for
+//! the cursors returned by [run](crate::Command::run).
+//!
+//! ## Example
+//!
+//! ```
+//! use unreql::prelude::*;
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Hero {
+//!     name: String,
+//! }
+//!
+//! # #[cfg(feature = "math-logic")]
+//! # async fn example() -> Result<()> {
+//! let session = r.connect(ConnectOptions::new().db("marvel")).await?;
+//! let status: WriteStatus = r
+//!     .table("heroes")
+//!     .insert(r.with_opt(
+//!         rjson!({ "name": "Iron Man", "team": "Avengers" }),
+//!         InsertOptions::new().conflict(Conflict::Update),
+//!     ))
+//!     .exec(&session)
+//!     .await?;
+//! assert_eq!(status.errors, 0);
+//! let mut heroes = r
+//!     .table("heroes")
+//!     .filter(func!(|hero| hero.g("team").eq("Avengers")))
+//!     .run::<Hero>(&session);
+//! while let Some(hero) = heroes.try_next().await? {
+//!     println!("{}", hero.name);
+//! }
+//! # Ok(()) }
+//! ```
+
+pub use futures::{StreamExt as _, TryStreamExt as _};
+
+pub use crate::cmd::args::WithOpts;
+pub use crate::cmd::cancel::CancelToken;
+pub use crate::cmd::connect::Options as ConnectOptions;
+pub use crate::cmd::options::{
+    BetweenOptions, ChangesOptions, Conflict, DeleteOptions, Durability, FilterOptions,
+    IndexCreateOptions, InsertOptions, ReadMode, ReplaceOptions, ReturnChanges, Squash,
+    TableCreateOptions, TableOptions, UpdateOptions,
+};
+pub use crate::cmd::run::Options as RunOptions;
+pub use crate::types::{Change, DateTime, Maybe, WriteStatus};
+pub use crate::{func, r, rjson};
+pub use crate::{Availability, Driver, Error, Result, Runtime};
+pub use crate::{Command, Connection, Session};
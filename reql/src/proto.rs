@@ -1,5 +1,5 @@
 use crate::cmd::run::{Db, Options};
-use crate::{err, r, Func};
+use crate::{err, r, tools, Func};
 use ql2::query::QueryType;
 use ql2::term::TermType;
 use serde::ser::{self, Serialize, Serializer};
@@ -8,8 +8,9 @@ use std::any::{Any, TypeId};
 use std::collections::{HashMap, VecDeque};
 use std::{fmt, str};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub enum Datum {
+    #[default]
     Null,
     Bool(bool),
     Number(Number),
@@ -32,11 +33,14 @@ impl Datum {
             _ => false,
         }
     }
-}
 
-impl Default for Datum {
-    fn default() -> Self {
-        Self::Null
+    fn build_error(&self) -> Option<&super::Error> {
+        match self {
+            Datum::Command(cmd) => cmd.build_error(),
+            Datum::Array(arr) => arr.iter().find_map(Datum::build_error),
+            Datum::Object(obj) => obj.values().find_map(Datum::build_error),
+            _ => None,
+        }
     }
 }
 
@@ -58,6 +62,26 @@ impl Serialize for Datum {
     }
 }
 
+// The JSON of an argument, rejecting the floats JSON cannot represent
+// instead of sending them as `null` like `serde_json` does
+pub(crate) fn to_json<T: Serialize>(arg: &T) -> super::Result<Value> {
+    fn has_null(value: &Value) -> bool {
+        match value {
+            Value::Null => true,
+            Value::Array(arr) => arr.iter().any(has_null),
+            Value::Object(map) => map.values().any(has_null),
+            _ => false,
+        }
+    }
+    let value = serde_json::to_value(arg)?;
+    if has_null(&value) {
+        if let Some((float, path)) = crate::tools::non_finite(arg) {
+            return Err(err::Driver::NonFiniteFloat { float, path }.into());
+        }
+    }
+    Ok(value)
+}
+
 #[allow(array_into_iter)]
 #[allow(clippy::into_iter_on_ref)]
 impl<const N: usize> From<[Command; N]> for Command {
@@ -101,7 +125,11 @@ pub enum Command {
 }
 
 impl Command {
-    #[doc(hidden)]
+    /// Create a command of the given term type without arguments
+    ///
+    /// Together with [with_arg](Self::with_arg), [with_opts](Self::with_opts)
+    /// and [with_parent](Self::with_parent) this allows building commands
+    /// the driver has no method for, see [args](crate::cmd::args).
     pub fn new(typ: TermType) -> Self {
         Self::Data {
             typ,
@@ -118,14 +146,14 @@ impl Command {
         Self::new(TermType::Var).with_arg(index)
     }
 
-    #[doc(hidden)]
+    /// Prepend `parent` to the arguments, as in `parent.command(...)`
     pub fn with_parent(mut self, parent: Command) -> Self {
         self.set_change_feed(self.change_feed() || parent.change_feed());
         self.mut_args().push_front(parent);
         self
     }
 
-    #[doc(hidden)]
+    /// Append an argument
     pub fn with_arg<T>(mut self, arg: T) -> Self
     where
         T: Into<Command>,
@@ -135,7 +163,7 @@ impl Command {
         self
     }
 
-    #[doc(hidden)]
+    /// Set the options of the command, replacing the previous ones
     pub fn with_opts<T>(mut self, opts: T) -> Self
     where
         T: Serialize + 'static,
@@ -162,12 +190,78 @@ impl Command {
         self
     }
 
+    /// The term JSON the query is sent as, without running it
+    ///
+    /// The variables of the functions are numbered from 1 in the order the
+    /// functions are declared, so the JSON of a query doesn't depend on the
+    /// functions built before it and can be compared to a snapshot.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{func, r};
+    /// # use serde_json::json;
+    /// # #[cfg(not(feature = "math-logic"))]
+    /// # fn main() {}
+    /// # #[cfg(feature = "math-logic")]
+    /// # fn main() -> unreql::Result<()> {
+    /// let query = r.table("heroes").filter(func!(|hero| hero.g("age").gt(30)));
+    /// assert_eq!(
+    ///     query.to_query_json()?,
+    ///     json!([39, [[15, ["heroes"]], [69, [[2, [1]], [21, [[31, [[10, [1]], "age"]], 30]]]]]]),
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn to_query_json(&self) -> super::Result<Value> {
+        if let Some(error) = self.build_error() {
+            return Err(error.clone());
+        }
+        let query = serde_json::to_value(self)?;
+        Ok(tools::renumber_vars(&query))
+    }
+
     #[doc(hidden)]
     pub fn from_json<T>(arg: T) -> Self
     where
         T: Serialize,
     {
-        serde_json::to_value(arg).map_err(super::Error::from).into()
+        to_json(&arg).into()
+    }
+
+    #[cfg(not(feature = "indexmap"))]
+    pub(crate) fn from_expr<T>(arg: T) -> Self
+    where
+        T: Serialize,
+    {
+        Self::from_json(arg)
+    }
+
+    // Objects are kept as JSON so their keys stay in order
+    #[cfg(feature = "indexmap")]
+    pub(crate) fn from_expr<T>(arg: T) -> Self
+    where
+        T: Serialize,
+    {
+        fn datum(value: Value) -> Datum {
+            match value {
+                Value::Array(arr) => Datum::Array(arr.into_iter().map(datum).collect()),
+                Value::Object(_) => Datum::Value(wire(value)),
+                value => value.into(),
+            }
+        }
+        // the value as sent to the server, with arrays as MAKE_ARRAY terms
+        fn wire(value: Value) -> Value {
+            match value {
+                Value::Array(arr) => {
+                    let arr: Vec<_> = arr.into_iter().map(wire).collect();
+                    serde_json::json!([TermType::MakeArray as i32, arr])
+                }
+                Value::Object(map) => map.into_iter().map(|(k, v)| (k, wire(v))).collect(),
+                value => value,
+            }
+        }
+        to_json(&arg).map(datum).into()
     }
 
     #[doc(hidden)]
@@ -175,6 +269,17 @@ impl Command {
     where
         T: Serialize + Any,
     {
+        if arg.type_id() == TypeId::of::<Func>() {
+            // a stored function gets new variables every time it is used
+            let func: Box<dyn Any> = Box::new(arg);
+            return match func.downcast::<Func>() {
+                Ok(func) => func.rebound(),
+                Err(_) => {
+                    let error = super::Driver::Other("cannot downcast to Func".into());
+                    (Err(error.into()) as super::Result<Datum>).into()
+                }
+            };
+        }
         if arg.type_id() == TypeId::of::<Command>() {
             let cmd: Box<dyn Any> = Box::new(arg);
             match cmd.downcast::<Command>() {
@@ -188,7 +293,7 @@ impl Command {
                 }
             }
         } else {
-            serde_json::to_value(arg).map_err(super::Error::from).into()
+            to_json(&arg).into()
         }
     }
 
@@ -212,6 +317,27 @@ impl Command {
         Func::new(vec![1], self).into_cmd()
     }
 
+    // The first error met while building the command, e.g. an argument
+    // that could not be turned into JSON, so that it is returned as is
+    // instead of as a serialization error
+    pub(crate) fn build_error(&self) -> Option<&super::Error> {
+        fn datum_error(datum: &Option<super::Result<Datum>>) -> Option<&super::Error> {
+            match datum {
+                Some(Err(error)) => Some(error),
+                Some(Ok(datum)) => datum.build_error(),
+                None => None,
+            }
+        }
+        match self {
+            Self::Boxed(cmd) => cmd.build_error(),
+            Self::Data {
+                datum, args, opts, ..
+            } => datum_error(datum)
+                .or_else(|| args.iter().find_map(Command::build_error))
+                .or_else(|| datum_error(opts)),
+        }
+    }
+
     fn has_implicit_var_arg(&self) -> bool {
         match self {
             Self::Boxed(cmd) => cmd.has_implicit_var_arg(),
@@ -267,7 +393,7 @@ impl Command {
         }
     }
 
-    fn datum(&self) -> &Option<super::Result<Datum>> {
+    pub(crate) fn datum(&self) -> &Option<super::Result<Datum>> {
         match self {
             Self::Boxed(cmd) => cmd.datum(),
             Self::Data { datum, .. } => datum,
@@ -281,14 +407,42 @@ impl Command {
         }
     }
 
-    fn set_opts(&mut self, new_opts: super::Result<Datum>) {
+    pub(crate) fn mut_datum(&mut self) -> &mut Option<super::Result<Datum>> {
+        match self {
+            Self::Boxed(cmd) => cmd.mut_datum(),
+            Self::Data { datum, .. } => datum,
+        }
+    }
+
+    pub(crate) fn opts(&self) -> &Option<super::Result<Datum>> {
+        match self {
+            Self::Boxed(cmd) => cmd.opts(),
+            Self::Data { opts, .. } => opts,
+        }
+    }
+
+    pub(crate) fn mut_opts(&mut self) -> &mut Option<super::Result<Datum>> {
+        match self {
+            Self::Boxed(cmd) => cmd.mut_opts(),
+            Self::Data { opts, .. } => opts,
+        }
+    }
+
+    pub(crate) fn set_opts(&mut self, new_opts: super::Result<Datum>) {
         match self {
             Self::Boxed(cmd) => cmd.set_opts(new_opts),
             Self::Data { opts, .. } => *opts = Some(new_opts),
         }
     }
 
-    fn mut_args(&mut self) -> &mut VecDeque<Command> {
+    pub(crate) fn args(&self) -> &VecDeque<Command> {
+        match self {
+            Self::Boxed(cmd) => cmd.args(),
+            Self::Data { args, .. } => args,
+        }
+    }
+
+    pub(crate) fn mut_args(&mut self) -> &mut VecDeque<Command> {
         match self {
             Self::Boxed(cmd) => cmd.mut_args(),
             Self::Data { args, .. } => args,
@@ -388,6 +542,9 @@ impl Serialize for Payload<'_> {
 
 impl Payload<'_> {
     pub(crate) fn to_bytes(&self) -> Result<Vec<u8>, err::Error> {
+        if let Some(error) = self.1.and_then(Command::build_error) {
+            return Err(error.clone());
+        }
         Ok(serde_json::to_vec(self)?)
     }
 }
@@ -0,0 +1,318 @@
+//! Record queries and their responses, and replay them without a server
+//!
+//! Requires the `record` feature.
+//!
+//! ## Example
+//!
+//! Record the queries of a test run against a real server:
+//!
+//! ```
+//! # use unreql::r;
+//! # use unreql::record::Recording;
+//! # async fn example(session: &unreql::Session) -> unreql::Result<()> {
+//! let recording = Recording::new();
+//! let name: String = r.table("users").get(1).g("name").exec(recording.arg(session)).await?;
+//! recording.save("tests/fixtures/users.jsonl")?;
+//! # Ok(()) }
+//! ```
+//!
+//! And replay them later:
+//!
+//! ```
+//! # use unreql::r;
+//! # use unreql::record::{Recording, ReplayArg};
+//! # async fn example() -> unreql::Result<()> {
+//! let replay = ReplayArg::new(Recording::load("tests/fixtures/users.jsonl")?).await?;
+//! let name: String = r.table("users").get(1).g("name").exec(&replay).await?;
+//! # Ok(()) }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use async_trait::async_trait;
+use ql2::query::QueryType;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::cmd::run::{self, DEFAULT_DB};
+use crate::proto::Payload;
+use crate::{tools, Command, Connection, InnerSession, Result, Session};
+
+/// One recorded query with all the responses to it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Entry {
+    /// The query in [canonical](canonical_query) form
+    pub query: Value,
+    /// The raw bodies of the responses: the first one to the query itself,
+    /// the rest to the `CONTINUE` requests that followed it.
+    pub responses: Vec<String>,
+}
+
+impl Entry {
+    /// The entry of `query` run with `opts`, answered with `response`
+    ///
+    /// Push the responses to the `CONTINUE` requests of a cursor onto
+    /// [responses](Self::responses).
+    pub fn new(query: &Command, opts: run::Options, response: impl Into<String>) -> Result<Self> {
+        let payload = Payload(QueryType::Start, Some(query), opts).to_bytes()?;
+        let query: Value = serde_json::from_slice(&payload)?;
+        Ok(Self {
+            query: canonical_query(&query),
+            responses: vec![response.into()],
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    entries: Vec<Entry>,
+    // index of the entry of a running query by its token
+    running: HashMap<u64, usize>,
+}
+
+/// Queries and responses captured by [RecordingArg]
+///
+/// Cloning a recording gives another handle to the same entries.
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Wrap a run argument so its queries are recorded here
+    pub fn arg<A>(&self, arg: A) -> RecordingArg<A> {
+        RecordingArg {
+            arg,
+            recording: self.clone(),
+        }
+    }
+
+    pub fn from_entries(entries: Vec<Entry>) -> Self {
+        let inner = Inner {
+            entries,
+            running: HashMap::new(),
+        };
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+        }
+    }
+
+    pub fn entries(&self) -> Vec<Entry> {
+        self.lock().entries.clone()
+    }
+
+    /// Load a recording saved by [save](Self::save)
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let file = fs::File::open(path)?;
+        let mut entries = Vec::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Self::from_entries(entries))
+    }
+
+    /// Save the recording as JSON lines, one entry per line
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = fs::File::create(path)?;
+        for entry in self.lock().entries.iter() {
+            serde_json::to_writer(&mut file, entry)?;
+            file.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn record(&self, token: u64, query: &[u8], response: &[u8]) {
+        let response = String::from_utf8_lossy(response).into_owned();
+        let query = match serde_json::from_slice(query) {
+            Ok(query) => query,
+            Err(_) => return,
+        };
+        let mut inner = self.lock();
+        if is_continue(&query) {
+            if let Some(&index) = inner.running.get(&token) {
+                inner.entries[index].responses.push(response);
+            }
+            return;
+        }
+        let index = inner.entries.len();
+        inner.entries.push(Entry {
+            query: canonical_query(&query),
+            responses: vec![response],
+        });
+        inner.running.insert(token, index);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|x| x.into_inner())
+    }
+}
+
+/// A [run::Arg] that records all the queries run with it
+///
+/// Created by [Recording::arg].
+#[derive(Debug, Clone)]
+pub struct RecordingArg<A> {
+    arg: A,
+    recording: Recording,
+}
+
+#[async_trait]
+impl<A> run::Arg for RecordingArg<A>
+where
+    A: run::Arg + Send,
+{
+    async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, run::Options)> {
+        let (mut conn, opts) = self.arg.into_run_opts(for_changes).await?;
+        conn.recording = Some(self.recording);
+        Ok((conn, opts))
+    }
+}
+
+/// A [run::Arg] answering queries from a [Recording] instead of a server
+///
+/// Queries are matched by their [canonical](canonical_query) form, every
+/// recorded entry is served once, in the recorded order. A query that was
+/// not recorded fails with a client error.
+///
+/// The default database of the session is `test`. If the queries were
+/// recorded with another one, change it with
+/// `replay.session().clone().use_(db)` so the queries match.
+#[derive(Debug, Clone)]
+pub struct ReplayArg {
+    session: Session,
+}
+
+impl ReplayArg {
+    pub async fn new(recording: Recording) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let entries = recording.entries();
+        thread::spawn(move || {
+            if let Ok((stream, _)) = listener.accept() {
+                // the server is gone once the session is dropped
+                let _ = serve(stream, entries);
+            }
+        });
+        let stream = async_net::TcpStream::connect(addr).await?;
+        let inner = InnerSession::new(stream, DEFAULT_DB.into(), None);
+        Ok(Self {
+            session: Session {
+                inner: Arc::new(inner),
+            },
+        })
+    }
+
+    /// Answer `query`, run with the default options, with `response`
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::r;
+    /// # use unreql::record::ReplayArg;
+    /// # async fn example() -> unreql::Result<()> {
+    /// let query = || r.table("users").count(());
+    /// let replay = ReplayArg::single(query(), r#"{"t":1,"r":[3]}"#).await?;
+    /// let count: u64 = query().exec(&replay).await?;
+    /// # Ok(()) }
+    /// ```
+    pub async fn single(query: Command, response: impl Into<String>) -> Result<Self> {
+        let entry = Entry::new(&query, Default::default(), response)?;
+        Self::new(Recording::from_entries(vec![entry])).await
+    }
+
+    /// The session connected to the replaying server
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+}
+
+#[async_trait]
+impl run::Arg for &ReplayArg {
+    async fn into_run_opts(self, for_changes: bool) -> Result<(Connection, run::Options)> {
+        self.session.into_run_opts(for_changes).await
+    }
+}
+
+fn serve(mut stream: TcpStream, entries: Vec<Entry>) -> io::Result<()> {
+    let mut queue: VecDeque<Entry> = entries.into();
+    let mut running: HashMap<u64, VecDeque<String>> = HashMap::new();
+    loop {
+        let mut header = [0u8; 12];
+        stream.read_exact(&mut header)?;
+        let token = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let len = u32::from_le_bytes(header[8..].try_into().unwrap()) as usize;
+        let mut body = vec![0u8; len];
+        stream.read_exact(&mut body)?;
+        let query: Value = serde_json::from_slice(&body).unwrap_or_default();
+
+        let response = if is_continue(&query) {
+            running.get_mut(&token).and_then(VecDeque::pop_front)
+        } else {
+            let query = canonical_query(&query);
+            let found = queue.iter().position(|x| x.query == query);
+            found.and_then(|i| queue.remove(i)).map(|entry| {
+                let mut responses: VecDeque<_> = entry.responses.into();
+                let first = responses.pop_front();
+                running.insert(token, responses);
+                first.unwrap_or_default()
+            })
+        };
+        let response = response.unwrap_or_else(|| {
+            let msg = format!("no recorded response for query {}", query);
+            json!({"t": 16, "r": [msg]}).to_string()
+        });
+
+        let mut buf = token.to_le_bytes().to_vec();
+        buf.extend_from_slice(&(response.len() as u32).to_le_bytes());
+        buf.extend_from_slice(response.as_bytes());
+        stream.write_all(&buf)?;
+    }
+}
+
+fn is_continue(query: &Value) -> bool {
+    query.get(0).and_then(Value::as_i64) == Some(QueryType::Continue as i64)
+}
+
+/// Canonical form of a serialized query
+///
+/// Variable ids of functions depend on how many functions were built
+/// before, so they are renumbered from 1 in the order they are declared.
+pub fn canonical_query(query: &Value) -> Value {
+    tools::renumber_vars(query)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renumbers_vars() {
+        let a = json!([1, [38, [[2, [1, 2, 3]], [69, [[2, [17]], [10, [17]]]]]], {}]);
+        let b = json!([1, [38, [[2, [1, 2, 3]], [69, [[2, [42]], [10, [42]]]]]], {}]);
+        assert_eq!(canonical_query(&a), canonical_query(&b));
+        assert_eq!(
+            canonical_query(&a),
+            json!([1, [38, [[2, [1, 2, 3]], [69, [[2, [1]], [10, [1]]]]]], {}])
+        );
+    }
+
+    #[test]
+    fn keeps_other_numbers() {
+        let query = json!([1, [10, [5]], {}]);
+        assert_eq!(canonical_query(&query), query);
+    }
+}
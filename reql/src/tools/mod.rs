@@ -1,5 +1,11 @@
 mod bytes_to_string;
+mod non_finite;
+mod renumber_vars;
 mod static_string;
+mod struct_fields;
 
 pub(crate) use bytes_to_string::*;
-pub(crate) use static_string::*;
+pub(crate) use non_finite::*;
+pub(crate) use renumber_vars::*;
+pub use static_string::StaticString;
+pub(crate) use struct_fields::*;
@@ -0,0 +1,416 @@
+use serde::ser::{self, Serialize, Serializer};
+use std::fmt;
+
+// The first NaN or infinite float of `value`, which JSON cannot represent
+// and `serde_json` turns into `null`, with its JSON pointer in `value`
+pub(crate) fn non_finite<T: Serialize + ?Sized>(value: &T) -> Option<(f64, String)> {
+    match value.serialize(Floats::default()) {
+        Err(Found::NonFinite(float, mut path)) => {
+            path.reverse();
+            let path = path.iter().map(|x| format!("/{}", pointer_token(x)));
+            Some((float, path.collect()))
+        }
+        _ => None,
+    }
+}
+
+// Escapes a key for a JSON pointer, RFC 6901
+fn pointer_token(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+// The key of a map as it is written in JSON
+fn key_name<T: Serialize + ?Sized>(key: &T) -> String {
+    match serde_json::to_value(key) {
+        Ok(serde_json::Value::String(key)) => key,
+        Ok(key) => key.to_string(),
+        Err(_) => String::new(),
+    }
+}
+
+#[derive(Debug)]
+enum Found {
+    // the float with the keys leading to it, innermost first
+    NonFinite(f64, Vec<String>),
+    // the value cannot be serialized, `serde_json` reports why
+    Other,
+}
+
+impl Found {
+    fn at(self, key: impl ToString) -> Self {
+        match self {
+            Self::NonFinite(float, mut path) => {
+                path.push(key.to_string());
+                Self::NonFinite(float, path)
+            }
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for Found {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::NonFinite(float, _) => write!(f, "non-finite float {}", float),
+            Self::Other => f.write_str("cannot serialize"),
+        }
+    }
+}
+
+impl std::error::Error for Found {}
+
+impl ser::Error for Found {
+    fn custom<T: fmt::Display>(_: T) -> Self {
+        Found::Other
+    }
+}
+
+// Walks a value looking for non-finite floats, keeping track of where the
+// elements of a sequence or a map are
+#[derive(Default)]
+struct Floats {
+    index: usize,
+    key: String,
+    // the variant a tuple or a struct variant is written under
+    variant: Option<&'static str>,
+}
+
+impl Floats {
+    fn float(float: f64) -> Result<(), Found> {
+        match float.is_finite() {
+            true => Ok(()),
+            false => Err(Found::NonFinite(float, Vec::new())),
+        }
+    }
+
+    fn variant(variant: &'static str) -> Self {
+        Self {
+            variant: Some(variant),
+            ..Self::default()
+        }
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Found> {
+        let index = self.index;
+        self.index += 1;
+        self.field(index, value)
+    }
+
+    fn field<T: Serialize + ?Sized>(&self, key: impl ToString, value: &T) -> Result<(), Found> {
+        value.serialize(Floats::default()).map_err(|found| {
+            let found = found.at(key);
+            match self.variant {
+                Some(variant) => found.at(variant),
+                None => found,
+            }
+        })
+    }
+}
+
+impl Serializer for Floats {
+    type Ok = ();
+    type Error = Found;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_f32(self, v: f32) -> Result<(), Found> {
+        Self::float(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Found> {
+        Self::float(v)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_char(self, _: char) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_str(self, _: &str) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Found> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _: &'static str, _: u32, _: &'static str) -> Result<(), Found> {
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), Found> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Found> {
+        value.serialize(self).map_err(|found| found.at(variant))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self, Found> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self, Found> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, _: usize) -> Result<Self, Found> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Self, Found> {
+        Ok(Self::variant(variant))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self, Found> {
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self, Found> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<Self, Found> {
+        Ok(Self::variant(variant))
+    }
+}
+
+impl ser::SerializeSeq for Floats {
+    type Ok = ();
+    type Error = Found;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Found> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Found> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for Floats {
+    type Ok = ();
+    type Error = Found;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Found> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Found> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for Floats {
+    type Ok = ();
+    type Error = Found;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Found> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Found> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for Floats {
+    type Ok = ();
+    type Error = Found;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Found> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Found> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for Floats {
+    type Ok = ();
+    type Error = Found;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Found> {
+        self.key = key_name(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Found> {
+        self.field(&self.key, value)
+    }
+
+    fn end(self) -> Result<(), Found> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for Floats {
+    type Ok = ();
+    type Error = Found;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Found> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Found> {
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for Floats {
+    type Ok = ();
+    type Error = Found;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Found> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Found> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::collections::HashMap;
+
+    #[derive(Serialize)]
+    struct Point {
+        x: f64,
+        y: Option<f32>,
+    }
+
+    #[derive(Serialize)]
+    enum Shape {
+        Circle { center: Point, radius: f64 },
+    }
+
+    #[test]
+    fn finds_non_finite_floats() {
+        assert_eq!(non_finite(&1.5), None);
+        assert_eq!(non_finite(&"NaN"), None);
+        assert!(non_finite(&f64::NAN).unwrap().0.is_nan());
+        let point = Point {
+            x: 0.0,
+            y: Some(f32::NEG_INFINITY),
+        };
+        assert_eq!(non_finite(&point), Some((f64::NEG_INFINITY, "/y".into())));
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(non_finite(&f64::INFINITY), Some((f64::INFINITY, "".into())));
+        let found = non_finite(&[1.0, 2.0, f64::INFINITY]);
+        assert_eq!(found, Some((f64::INFINITY, "/2".into())));
+        let map = HashMap::from([(
+            "a/b",
+            vec![
+                Point { x: 1.0, y: None },
+                Point {
+                    x: f64::NAN,
+                    y: None,
+                },
+            ],
+        )]);
+        let (float, path) = non_finite(&map).unwrap();
+        assert!(float.is_nan());
+        assert_eq!(path, "/a~1b/1/x");
+        let map = HashMap::from([(7, f64::NEG_INFINITY)]);
+        assert_eq!(non_finite(&map), Some((f64::NEG_INFINITY, "/7".into())));
+        let shape = Shape::Circle {
+            center: Point { x: 0.0, y: None },
+            radius: f64::INFINITY,
+        };
+        let found = non_finite(&[shape]);
+        assert_eq!(found, Some((f64::INFINITY, "/0/Circle/radius".into())));
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+const VAR: u64 = 10;
+const FUNC: u64 = 69;
+const MAKE_ARRAY: u64 = 2;
+
+// Renumbers the variables of the functions of a serialized query from 1,
+// in the order the functions are declared
+pub(crate) fn renumber_vars(query: &Value) -> Value {
+    let mut vars = HashMap::new();
+    normalize_vars(query, &mut vars)
+}
+
+fn normalize_vars(value: &Value, vars: &mut HashMap<u64, u64>) -> Value {
+    let Value::Array(arr) = value else {
+        return match value {
+            Value::Object(obj) => obj
+                .iter()
+                .map(|(k, v)| (k.clone(), normalize_vars(v, vars)))
+                .collect(),
+            _ => value.clone(),
+        };
+    };
+    match (arr.first().and_then(Value::as_u64), arr.get(1)) {
+        // [FUNC, [[MAKE_ARRAY, [ids...]], body]]
+        (Some(FUNC), Some(Value::Array(args))) => {
+            let ids = args
+                .first()
+                .filter(|x| x.get(0).and_then(Value::as_u64) == Some(MAKE_ARRAY))
+                .and_then(|x| x.get(1))
+                .and_then(Value::as_array);
+            if let Some(ids) = ids {
+                let ids: Vec<_> = ids
+                    .iter()
+                    .map(|id| match id.as_u64() {
+                        Some(id) => {
+                            let next = vars.len() as u64 + 1;
+                            json!(*vars.entry(id).or_insert(next))
+                        }
+                        None => id.clone(),
+                    })
+                    .collect();
+                let body = args[1..].iter().map(|x| normalize_vars(x, vars));
+                let args: Vec<_> = std::iter::once(json!([MAKE_ARRAY, ids]))
+                    .chain(body)
+                    .collect();
+                return json!([FUNC, args]);
+            }
+        }
+        // [VAR, [id]]
+        (Some(VAR), Some(Value::Array(args))) if args.len() == 1 => {
+            if let Some(id) = args[0].as_u64().and_then(|x| vars.get(&x)) {
+                return json!([VAR, [id]]);
+            }
+        }
+        _ => {}
+    }
+    let arr = arr.iter().map(|x| normalize_vars(x, vars)).collect();
+    Value::Array(arr)
+}
@@ -1,5 +1,18 @@
 use std::borrow::Cow;
+use std::sync::Arc;
 
+/// A string that can be kept for the lifetime of a session
+///
+/// Taken by [Session::use_](crate::Session::use_) and by the name-like
+/// fields of the options, e.g. [connect::Options::db](crate::cmd::connect::Options::db).
+/// A `&'static str` or an owned `Cow` is kept as is, the other strings are
+/// copied, so a borrowed `&String` can be passed without cloning it first.
+///
+/// The arguments of the commands, e.g. the name passed to
+/// [r.table](crate::r::table), take `impl Serialize + 'static` instead:
+/// a `&'static str`, a `String`, a `Cow<'static, str>` or an `Arc<str>`.
+/// They are checked for commands and functions by type, so they cannot
+/// borrow, and a `&String` has to be cloned.
 pub trait StaticString {
     fn static_string(self) -> Cow<'static, str>;
 }
@@ -16,6 +29,18 @@ impl StaticString for String {
     }
 }
 
+impl StaticString for &String {
+    fn static_string(self) -> Cow<'static, str> {
+        Cow::Owned(self.clone())
+    }
+}
+
+impl StaticString for Cow<'static, str> {
+    fn static_string(self) -> Cow<'static, str> {
+        self
+    }
+}
+
 impl StaticString for &Cow<'static, str> {
     fn static_string(self) -> Cow<'static, str> {
         match self {
@@ -24,3 +49,15 @@ impl StaticString for &Cow<'static, str> {
         }
     }
 }
+
+impl StaticString for Arc<str> {
+    fn static_string(self) -> Cow<'static, str> {
+        Cow::Owned(self.to_string())
+    }
+}
+
+impl StaticString for &Arc<str> {
+    fn static_string(self) -> Cow<'static, str> {
+        Cow::Owned(self.to_string())
+    }
+}
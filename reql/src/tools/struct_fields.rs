@@ -0,0 +1,76 @@
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use std::fmt;
+
+// The serialized names of the fields of a struct, `None` if `T` is not
+// deserialized from a struct (e.g. a map or a flattened struct)
+pub(crate) fn struct_fields<'de, T: Deserialize<'de>>() -> Option<&'static [&'static str]> {
+    let mut fields = None;
+    let _ = T::deserialize(FieldNames(&mut fields));
+    fields
+}
+
+struct FieldNames<'a>(&'a mut Option<&'static [&'static str]>);
+
+#[derive(Debug)]
+struct Stop;
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("stop")
+    }
+}
+
+impl std::error::Error for Stop {}
+
+impl de::Error for Stop {
+    fn custom<T: fmt::Display>(_: T) -> Self {
+        Stop
+    }
+}
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = Stop;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Stop> {
+        Err(Stop)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Stop> {
+        *self.0 = Some(fields);
+        Err(Stop)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    struct User {
+        id: u32,
+        #[serde(rename = "name")]
+        user: String,
+        #[serde(default)]
+        posts: Vec<u32>,
+    }
+
+    #[test]
+    fn names_of_struct_fields() {
+        assert_eq!(struct_fields::<User>(), Some(&["id", "name", "posts"][..]));
+        assert_eq!(struct_fields::<serde_json::Value>(), None);
+        assert_eq!(struct_fields::<u32>(), None);
+    }
+}
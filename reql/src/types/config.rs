@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_with::skip_serializing_none;
+use unreql_macros::OptionsBuilder;
+
+use crate::cmd::options::Durability;
+
+/// A document of the `rethinkdb.table_config` system table
+///
+/// Returned by `r.table(...).config()`. Every field is optional so the
+/// same type describes a partial update, only the fields that are set
+/// are written.
+///
+/// ## Example
+/// Make writes to a table acknowledged before they reach the disk.
+///
+/// ```
+/// # use unreql::{r, Session};
+/// # use unreql::cmd::options::Durability;
+/// # use unreql::types::{TableConfig, WriteStatus};
+/// # async fn example(conn: &Session) -> unreql::Result<()> {
+/// let config = TableConfig::new().durability(Durability::Soft);
+/// let status: WriteStatus = r.table("events").config().update(config).exec(conn).await?;
+///
+/// let config: TableConfig = r.table("events").config().exec(conn).await?;
+/// assert_eq!(config.durability, Some(Durability::Soft));
+/// # Ok(()) }
+/// ```
+#[skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, OptionsBuilder)]
+#[non_exhaustive]
+pub struct TableConfig {
+    pub id: Option<uuid::Uuid>,
+    pub name: Option<String>,
+    /// The name of the database of the table
+    pub db: Option<String>,
+    /// The primary key, it cannot be changed
+    pub primary_key: Option<String>,
+    pub shards: Option<Vec<Shard>>,
+    /// The secondary indexes, they cannot be changed here
+    pub indexes: Option<Vec<String>>,
+    pub write_acks: Option<WriteAcks>,
+    /// The write hook, it is never written back: change it with
+    /// `set_write_hook`
+    #[serde(skip_serializing)]
+    pub write_hook: Option<WriteHook>,
+    pub durability: Option<Durability>,
+}
+
+/// A shard of a [TableConfig]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Shard {
+    pub primary_replica: String,
+    pub replicas: Vec<String>,
+    #[serde(default)]
+    pub nonvoting_replicas: Vec<String>,
+}
+
+/// How many replicas acknowledge a write before it succeeds
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum WriteAcks {
+    Majority,
+    Single,
+}
+
+/// The write hook of a [TableConfig]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WriteHook {
+    /// The function, as a binary object
+    pub function: Value,
+    /// The function as ReQL source
+    pub query: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::r;
+
+    const CONFIG: &str = r#"{
+        "db": "marvel",
+        "durability": "hard",
+        "id": "6d0b2c9e-4e3f-4a4b-9d57-4b0f3c8a8c21",
+        "indexes": ["code_name"],
+        "name": "heroes",
+        "primary_key": "id",
+        "shards": [{
+            "nonvoting_replicas": [],
+            "primary_replica": "jeeves",
+            "replicas": ["jeeves", "alfred"]
+        }],
+        "write_acks": "majority",
+        "write_hook": {
+            "function": {"$reql_type$": "BINARY", "data": "AAAA"},
+            "query": "setWriteHook(function(_var1, _var2, _var3) { return _var3; })"
+        }
+    }"#;
+
+    #[test]
+    fn table_config() {
+        let config: TableConfig = serde_json::from_str(CONFIG).unwrap();
+        assert_eq!(config.name.as_deref(), Some("heroes"));
+        assert_eq!(config.durability, Some(Durability::Hard));
+        assert_eq!(config.write_acks, Some(WriteAcks::Majority));
+        let shards = config.shards.as_deref().unwrap();
+        assert_eq!(shards[0].replicas, ["jeeves", "alfred"]);
+        assert!(config.write_hook.unwrap().query.starts_with("setWriteHook"));
+    }
+
+    #[test]
+    fn write_hook_is_not_written() {
+        let config: TableConfig = serde_json::from_str(CONFIG).unwrap();
+        let written = serde_json::to_value(config).unwrap();
+        assert!(written.get("write_hook").is_none());
+        assert_eq!(written["durability"], "hard");
+    }
+
+    #[test]
+    fn partial_update() {
+        let config = TableConfig::new().durability(Durability::Soft);
+        let query = r.table("heroes").config().update(config);
+        assert_eq!(
+            serde_json::to_string(&query).unwrap(),
+            r#"[53,[[174,[[15,["heroes"]]]],{"durability":"soft"}]]"#
+        );
+    }
+}
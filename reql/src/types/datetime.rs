@@ -1,7 +1,7 @@
 use std::ops::Deref;
 
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
-use time::{error::ComponentRange, format_description, OffsetDateTime, UtcOffset};
+use time::{error::ComponentRange, macros::format_description, OffsetDateTime, UtcOffset};
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct DateTime(OffsetDateTime);
@@ -69,12 +69,7 @@ impl<'de> Deserialize<'de> for DateTime {
         D: Deserializer<'de>,
     {
         let time = Time::deserialize(deserializer)?;
-        let format = match format_description::parse("[offset_hour]:[offset_minute]") {
-            Ok(fmt) => fmt,
-            Err(error) => {
-                return Err(de::Error::custom(error));
-            }
-        };
+        let format = format_description!("[offset_hour]:[offset_minute]");
         let offset = match UtcOffset::parse(&time.timezone, &format) {
             Ok(offset) => offset,
             Err(error) => {
@@ -0,0 +1,108 @@
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+
+/// The result of a [group](crate::Command::group) query
+///
+/// The server returns grouped data as a `GROUPED_DATA` pseudotype, with
+/// one `[group, values]` pair per group. `G` is the type of the group key
+/// (a tuple or a `Vec` when grouping by several fields) and `V` the type
+/// of the values of a group: the documents of the group, what `map`
+/// turned them into, or a single value after a reduction like `count`.
+///
+/// ## Example
+/// The points scored in each game, by player.
+///
+/// ```
+/// # use unreql::{func, r, Session};
+/// # use unreql::types::GroupedData;
+/// # async fn example(conn: &Session) -> unreql::Result<()> {
+/// let points: GroupedData<String, Vec<u32>> = r.table("games")
+///   .group("player")
+///   .map(func!(|game| game.g("points")))
+///   .exec(conn)
+///   .await?;
+/// for group in points {
+///     println!("{}: {:?}", group.group, group.values);
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupedData<G, V>(pub Vec<GroupedResult<G, V>>);
+
+/// One group of [GroupedData]
+///
+/// Also reads the `{"group": ..., "reduction": ...}` objects returned
+/// by [ungroup](crate::Command::ungroup).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupedResult<G, V> {
+    pub group: G,
+    pub values: V,
+}
+
+impl<G, V> GroupedData<G, V> {
+    pub fn into_vec(self) -> Vec<GroupedResult<G, V>> {
+        self.0
+    }
+}
+
+impl<G, V> IntoIterator for GroupedData<G, V> {
+    type Item = GroupedResult<G, V>;
+    type IntoIter = std::vec::IntoIter<GroupedResult<G, V>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[derive(Deserialize)]
+struct Pseudotype<T> {
+    #[serde(rename = "$reql_type$")]
+    reql_type: String,
+    data: T,
+}
+
+impl<'de, G, V> Deserialize<'de> for GroupedData<G, V>
+where
+    G: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let grouped = Pseudotype::<Vec<GroupedResult<G, V>>>::deserialize(deserializer)?;
+        if grouped.reql_type != "GROUPED_DATA" {
+            let msg = format!("expected GROUPED_DATA, found {}", grouped.reql_type);
+            return Err(de::Error::custom(msg));
+        }
+        Ok(Self(grouped.data))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Group<G, V> {
+    Pair(G, V),
+    Object {
+        group: G,
+        #[serde(alias = "reduction")]
+        values: V,
+    },
+}
+
+impl<'de, G, V> Deserialize<'de> for GroupedResult<G, V>
+where
+    G: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (group, values) = match Group::deserialize(deserializer)? {
+            Group::Pair(group, values) => (group, values),
+            Group::Object { group, values } => (group, values),
+        };
+        Ok(Self { group, values })
+    }
+}
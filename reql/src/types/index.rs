@@ -0,0 +1,28 @@
+use serde::Deserialize;
+
+/// The status of a secondary index
+///
+/// Returned by [index_status](crate::Command::index_status) and
+/// [index_wait](crate::Command::index_wait), one per index.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct IndexStatus {
+    pub index: String,
+    /// Whether the index can be used by queries
+    pub ready: bool,
+    /// How much of the index is built, between `0` and `1`, only sent
+    /// while it is not ready
+    #[serde(default)]
+    pub progress: Option<f64>,
+    #[serde(default)]
+    pub multi: bool,
+    #[serde(default)]
+    pub geo: bool,
+    /// Whether the index was built by an older server and should be
+    /// rebuilt
+    #[serde(default)]
+    pub outdated: bool,
+    /// The ReQL of the index function, as text
+    #[serde(default)]
+    pub query: Option<String>,
+}
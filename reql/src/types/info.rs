@@ -0,0 +1,133 @@
+use serde::{de, Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Result of the [info](crate::Command::info) command
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Info {
+    Table(TableInfo),
+    Db(DbInfo),
+    Value(ValueInfo),
+}
+
+/// Information about a table returned by `r.table(...).info()`
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TableInfo {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub db: DbInfo,
+    pub primary_key: String,
+    #[serde(default)]
+    pub doc_count_estimates: Vec<u64>,
+    #[serde(default)]
+    pub indexes: Vec<String>,
+}
+
+/// Information about a database returned by `r.db(...).info()`
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DbInfo {
+    pub id: uuid::Uuid,
+    pub name: String,
+}
+
+/// Information about any other ReQL value
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ValueInfo {
+    #[serde(rename = "type")]
+    pub typ: String,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+impl TableInfo {
+    /// Sum of the per-shard document count estimates
+    pub fn doc_count_estimate(&self) -> u64 {
+        self.doc_count_estimates.iter().sum()
+    }
+}
+
+impl<'de> Deserialize<'de> for Info {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+        let info = match value.get("type").and_then(Value::as_str) {
+            Some("TABLE") => Self::Table(TableInfo::deserialize(value).map_err(de::Error::custom)?),
+            Some("DB") => Self::Db(DbInfo::deserialize(value).map_err(de::Error::custom)?),
+            _ => Self::Value(ValueInfo::deserialize(value).map_err(de::Error::custom)?),
+        };
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TABLE: &str = r#"{
+        "db": {"id": "a39e5c0c-8f4e-4a42-8b8a-9a2b9c7c0f11", "name": "marvel", "type": "DB"},
+        "doc_count_estimates": [12, 30],
+        "id": "6d0b2c9e-4e3f-4a4b-9d57-4b0f3c8a8c21",
+        "indexes": ["code_name", "superpowers"],
+        "name": "heroes",
+        "primary_key": "id",
+        "type": "TABLE"
+    }"#;
+
+    const DB: &str = r#"{
+        "id": "a39e5c0c-8f4e-4a42-8b8a-9a2b9c7c0f11",
+        "name": "marvel",
+        "type": "DB"
+    }"#;
+
+    #[test]
+    fn table_info() {
+        let info: Info = serde_json::from_str(TABLE).unwrap();
+        let Info::Table(table) = info else {
+            panic!("expected table info, got {:?}", info);
+        };
+        assert_eq!(table.name, "heroes");
+        assert_eq!(table.primary_key, "id");
+        assert_eq!(table.db.name, "marvel");
+        assert_eq!(table.indexes, ["code_name", "superpowers"]);
+        assert_eq!(table.doc_count_estimate(), 42);
+    }
+
+    #[test]
+    fn table_info_without_optional_fields() {
+        let fixture = r#"{
+            "db": {"id": "a39e5c0c-8f4e-4a42-8b8a-9a2b9c7c0f11", "name": "test", "type": "DB"},
+            "id": "6d0b2c9e-4e3f-4a4b-9d57-4b0f3c8a8c21",
+            "name": "users",
+            "primary_key": "email",
+            "type": "TABLE"
+        }"#;
+        let table: TableInfo = serde_json::from_str(fixture).unwrap();
+        assert_eq!(table.primary_key, "email");
+        assert!(table.indexes.is_empty());
+        assert_eq!(table.doc_count_estimate(), 0);
+    }
+
+    #[test]
+    fn db_info() {
+        let info: Info = serde_json::from_str(DB).unwrap();
+        let Info::Db(db) = info else {
+            panic!("expected db info, got {:?}", info);
+        };
+        assert_eq!(db.name, "marvel");
+    }
+
+    #[test]
+    fn value_info() {
+        let info: Info = serde_json::from_str(r#"{"type": "NUMBER", "value": "1"}"#).unwrap();
+        let expected = ValueInfo {
+            typ: "NUMBER".into(),
+            value: Some("1".into()),
+        };
+        assert_eq!(info, Info::Value(expected));
+    }
+}
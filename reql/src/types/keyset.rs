@@ -0,0 +1,12 @@
+use serde_json::Value;
+
+/// A page of [paginate_after](crate::Command::paginate_after)
+///
+/// `next` is the index key of the last document of the page, to pass
+/// back to `paginate_after` for the following page. It is `None` once a
+/// page comes back shorter than the page size, there is nothing after it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeysetPage<T> {
+    pub items: Vec<T>,
+    pub next: Option<Value>,
+}
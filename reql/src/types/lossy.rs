@@ -0,0 +1,43 @@
+use serde::ser::{self, Serialize, Serializer};
+
+/// A value whose NaN and infinite floats are sent as `null`
+///
+/// JSON has no NaN or infinite numbers, so the driver refuses to send
+/// them with a [NonFiniteFloat](crate::Driver::NonFiniteFloat) error.
+/// Wrap a value in `Lossy` where a missing measure is better stored as
+/// `null` than the write failing.
+///
+/// ## Example
+///
+/// ```
+/// # use unreql::r;
+/// # use unreql::types::Lossy;
+/// # use serde_json::json;
+/// #[derive(serde::Serialize)]
+/// struct Reading {
+///     sensor: u32,
+///     value: f64,
+/// }
+///
+/// let reading = Reading { sensor: 1, value: f64::NAN };
+/// let query = r.table("readings").insert(Lossy(reading));
+/// assert_eq!(
+///     query.to_query_json()?,
+///     json!([56, [[15, ["readings"]], {"sensor": 1, "value": null}]]),
+/// );
+/// # Ok::<(), unreql::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Lossy<T>(pub T);
+
+impl<T: Serialize> Serialize for Lossy<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // `serde_json` writes the floats it cannot represent as `null`
+        serde_json::to_value(&self.0)
+            .map_err(ser::Error::custom)?
+            .serialize(serializer)
+    }
+}
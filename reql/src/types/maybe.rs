@@ -0,0 +1,64 @@
+use serde::{Deserialize, Deserializer};
+
+/// A field that can be missing, `null` or set
+///
+/// Used by [Change](super::Change) to tell a deleted document, sent with
+/// `new_val: null`, from a document without `new_val` at all, like the
+/// `state` documents of a changefeed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Maybe<T> {
+    /// The field is not in the document
+    #[default]
+    Absent,
+    /// The field is `null`
+    Null,
+    Value(T),
+}
+
+impl<T> Maybe<T> {
+    pub fn is_absent(&self) -> bool {
+        matches!(self, Self::Absent)
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    /// The value, `None` if the field is missing or `null`
+    pub fn as_ref(&self) -> Option<&T> {
+        match self {
+            Self::Value(value) => Some(value),
+            Self::Absent | Self::Null => None,
+        }
+    }
+
+    /// The value, `None` if the field is missing or `null`
+    pub fn into_option(self) -> Option<T> {
+        match self {
+            Self::Value(value) => Some(value),
+            Self::Absent | Self::Null => None,
+        }
+    }
+}
+
+impl<T> From<Maybe<T>> for Option<T> {
+    fn from(maybe: Maybe<T>) -> Self {
+        maybe.into_option()
+    }
+}
+
+// A missing field is not deserialized at all, it needs `#[serde(default)]`
+impl<'de, T> Deserialize<'de> for Maybe<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => Self::Value(value),
+            None => Self::Null,
+        })
+    }
+}
@@ -1,18 +1,61 @@
+mod config;
 mod datetime;
+mod grouped;
+mod index;
+mod info;
+mod keyset;
+mod lossy;
+mod maybe;
+mod stats;
 
 use serde::Deserialize;
 use serde_json::Value;
 
+pub use config::{Shard, TableConfig, WriteAcks, WriteHook};
 pub use datetime::DateTime;
+pub use grouped::{GroupedData, GroupedResult};
+pub use index::IndexStatus;
+pub use info::{DbInfo, Info, TableInfo, ValueInfo};
+pub use keyset::KeysetPage;
+pub use lossy::Lossy;
+pub use maybe::Maybe;
+pub use stats::QueryStats;
 
+/// A document of a changefeed, or a change returned by a write run with
+/// `return_changes`
+///
+/// `old_val` and `new_val` are [Absent](Maybe::Absent) from the `state`
+/// documents, and [Null](Maybe::Null) for the side of an inserted or a
+/// deleted document. [old_value](Self::old_value) and
+/// [new_value](Self::new_value) read them as options.
 #[derive(Debug, Deserialize)]
+#[serde(bound(deserialize = "OldVal: Deserialize<'de>, NewVal: Deserialize<'de>"))]
 pub struct Change<OldVal = Value, NewVal = OldVal> {
-    pub old_val: Option<OldVal>,
-    pub new_val: Option<NewVal>,
+    #[serde(default)]
+    pub old_val: Maybe<OldVal>,
+    #[serde(default)]
+    pub new_val: Maybe<NewVal>,
+    /// The `type` field sent with `include_types`
+    #[serde(alias = "type")]
     pub result_type: Option<String>,
     pub old_offset: Option<usize>,
     pub new_offset: Option<usize>,
     pub state: Option<String>,
+    /// Why the write failed, set for the failed documents of a write run
+    /// with [ReturnChanges::Always](crate::cmd::options::ReturnChanges::Always)
+    pub error: Option<String>,
+}
+
+impl<OldVal, NewVal> Change<OldVal, NewVal> {
+    /// The old value, `None` if it is missing or `null`
+    pub fn old_value(&self) -> Option<&OldVal> {
+        self.old_val.as_ref()
+    }
+
+    /// The new value, `None` if it is missing or `null`
+    pub fn new_value(&self) -> Option<&NewVal> {
+        self.new_val.as_ref()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,3 +71,44 @@ pub struct WriteStatus<OldVal = Value, NewVal = OldVal> {
     pub warnings: Option<Vec<String>>,
     pub changes: Option<Vec<Change<OldVal, NewVal>>>,
 }
+
+impl<OldVal, NewVal> WriteStatus<OldVal, NewVal> {
+    /// The status, or a [WriteFailed](crate::Runtime::WriteFailed) error if
+    /// some documents could not be written
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use unreql::{r, rjson, Session};
+    /// # use unreql::types::WriteStatus;
+    /// # async fn example(conn: &Session) -> unreql::Result<()> {
+    /// let status: WriteStatus = r.table("users")
+    ///   .insert(rjson!({"id": 1, "name": "Ann"}))
+    ///   .exec(conn)
+    ///   .await?;
+    /// let inserted = status.check()?.inserted;
+    /// # Ok(()) }
+    /// ```
+    pub fn check(self) -> crate::Result<Self> {
+        if self.errors > 0 {
+            let error = crate::Runtime::WriteFailed {
+                errors: self.errors,
+                first_error: self.first_error,
+            };
+            return Err(error.into());
+        }
+        Ok(self)
+    }
+}
+
+/// The result of [reconfigure](crate::Command::reconfigure)
+///
+/// `config_changes` hold the old and new table configurations, with
+/// `dry_run` the new one is only proposed and `reconfigured` is `0`.
+#[derive(Debug, Deserialize)]
+pub struct ReconfigureResult {
+    pub reconfigured: u32,
+    pub config_changes: Vec<Change>,
+    #[serde(default)]
+    pub status_changes: Vec<Change>,
+}
@@ -0,0 +1,119 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// Measures of a query run with
+/// [exec_with_stats](crate::Command::exec_with_stats)
+///
+/// The server time is summed from the profile the server returns when
+/// the query is run with `profile` set, the others are measured by the
+/// driver. Times are in milliseconds, so the stats serialize to compact
+/// JSON, e.g. for a log line.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct QueryStats {
+    /// Time spent by the server running the query
+    pub server_ms: f64,
+    /// Time from the start of the query to its last response read
+    pub total_ms: f64,
+    /// Time spent getting a connection, e.g. waiting for a free one of
+    /// a pool
+    pub wait_ms: f64,
+    /// Number of results returned by the server, an atom counting as one
+    pub rows: usize,
+    /// Number of responses read
+    pub batches: usize,
+    /// Size of the responses read, in bytes
+    pub bytes: usize,
+}
+
+impl QueryStats {
+    pub(crate) fn add_batch(&mut self, results: &Value, profile: Option<&Value>, bytes: usize) {
+        self.rows += results.as_array().map_or(0, Vec::len);
+        self.batches += 1;
+        self.bytes += bytes;
+        self.server_ms += profile.map_or(0.0, profile_ms);
+    }
+}
+
+// The duration of the tasks of a profile, run one after another, with
+// the tasks of `parallel_tasks` lasting as long as their longest branch
+fn profile_ms(profile: &Value) -> f64 {
+    match profile {
+        Value::Array(tasks) => tasks.iter().map(task_ms).sum(),
+        task => task_ms(task),
+    }
+}
+
+fn task_ms(task: &Value) -> f64 {
+    if let Some(duration) = task["duration(ms)"].as_f64() {
+        return duration;
+    }
+    if let Some(mean) = task["mean_duration(ms)"].as_f64() {
+        let samples = task["n_samples"].as_f64().unwrap_or(1.0);
+        return mean * samples;
+    }
+    match task["parallel_tasks"].as_array() {
+        Some(branches) => branches.iter().map(profile_ms).fold(0.0, f64::max),
+        None => profile_ms(&task["sub_tasks"]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn sequential_tasks() {
+        let profile = json!([
+            {"description": "Evaluating table.", "duration(ms)": 0.25, "sub_tasks": []},
+            {"description": "Evaluating count.", "duration(ms)": 1.5, "sub_tasks": [
+                {"description": "Perform read.", "duration(ms)": 1.25, "sub_tasks": []}
+            ]},
+        ]);
+        assert_eq!(profile_ms(&profile), 1.75);
+    }
+
+    #[test]
+    fn parallel_tasks() {
+        let profile = json!([
+            {"description": "Evaluating table.", "duration(ms)": 0.5, "sub_tasks": []},
+            {"parallel_tasks": [
+                [
+                    {"description": "Perform read on shard.", "duration(ms)": 2.0, "sub_tasks": []},
+                    {"description": "Do range scan.", "duration(ms)": 1.0, "sub_tasks": []}
+                ],
+                [{"description": "Perform read on shard.", "duration(ms)": 2.5, "sub_tasks": []}]
+            ]},
+        ]);
+        assert_eq!(profile_ms(&profile), 3.5);
+    }
+
+    #[test]
+    fn sampled_tasks() {
+        let profile = json!([
+            {"description": "Evaluating filter.", "mean_duration(ms)": 0.5, "n_samples": 4},
+            {"sub_tasks": [{"description": "Evaluating datum.", "duration(ms)": 0.25}]},
+        ]);
+        assert_eq!(profile_ms(&profile), 2.25);
+    }
+
+    #[test]
+    fn batches() {
+        let mut stats = QueryStats::default();
+        let profile = json!([{"description": "Evaluating table.", "duration(ms)": 0.5}]);
+        stats.add_batch(&json!([1, 2, 3]), Some(&profile), 40);
+        stats.add_batch(&json!([4]), None, 20);
+        assert_eq!(stats.rows, 4);
+        assert_eq!(stats.batches, 2);
+        assert_eq!(stats.bytes, 60);
+        assert_eq!(stats.server_ms, 0.5);
+        assert_eq!(
+            serde_json::to_value(&stats).unwrap(),
+            json!({
+                "server_ms": 0.5, "total_ms": 0.0, "wait_ms": 0.0,
+                "rows": 4, "batches": 2, "bytes": 60,
+            })
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use serde_json::{json, to_value};
+use unreql::{args_vec, func, r};
+
+#[test]
+fn args_tuple_of_five() {
+    let query = r.object(r.args(("a", 1, "b", true, "c")));
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([143, ["a", 1, "b", true, "c"]])
+    );
+}
+
+#[test]
+fn args_tuple_of_twelve() {
+    let query = r.object(r.args((
+        "a",
+        1,
+        "b",
+        2.5,
+        "c",
+        true,
+        "d",
+        [1, 2],
+        "e",
+        r.now(),
+        "f",
+        "six",
+    )));
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([
+            143,
+            [
+                "a",
+                1,
+                "b",
+                2.5,
+                "c",
+                true,
+                "d",
+                [2, [1, 2]],
+                "e",
+                [103],
+                "f",
+                "six"
+            ]
+        ])
+    );
+}
+
+#[test]
+fn args_tuple_with_opt() {
+    let args = r.with_opt(r.args((1, 2, 3, 4, 5, 6, 7)), r.index("nums"));
+    let query = r.table("t").get_all(args);
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([78, [[15, ["t"]], 1, 2, 3, 4, 5, 6, 7], {"index": "nums"}])
+    );
+}
+
+#[test]
+fn args_vec_macro() {
+    let query = r.branch_ext(args_vec![
+        r.expr(1).gt(2),
+        "a",
+        r.expr(2).gt(3),
+        "b",
+        r.expr(3).gt(4),
+        "c",
+        r.expr(4).gt(5),
+        "d",
+        r.expr(5).gt(6),
+        "e",
+        r.expr(6).gt(7),
+        "f",
+        r.expr(7).gt(8),
+        "g",
+        "none",
+    ]);
+    let json = to_value(&query).unwrap();
+    assert_eq!(json[0], json!(65));
+    assert_eq!(json[1].as_array().unwrap().len(), 15);
+    assert_eq!(json[1][0], json!([21, [1, 2]]));
+    assert_eq!(json[1][14], json!("none"));
+
+    // functions get new variables
+    let query = r.expr([1, 2]).map(args_vec![func!(|x| x.mul(2))]);
+    let json = to_value(&query).unwrap();
+    let var = &json[1][1][1][0][1][0];
+    assert_eq!(
+        json[1][1],
+        json!([69, [[2, [var]], [26, [[10, [var]], 2]]]])
+    );
+
+    let query = r.object(args_vec![]);
+    assert_eq!(to_value(&query).unwrap(), json!([143]));
+}
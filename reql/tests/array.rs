@@ -0,0 +1,39 @@
+use serde_json::to_string;
+use unreql::{func, r};
+
+#[tokio::test]
+async fn array_of_commands() -> unreql::Result<()> {
+    let query = r.array([r.expr(1), r.expr(2).add(1)]);
+    assert_eq!(r#"[2,[1,[24,[2,1]]]]"#, to_string(&query).unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn array_of_vec() -> unreql::Result<()> {
+    let query = r.array(vec![r.table("posts").count(()), r.expr("a")]);
+    assert_eq!(
+        r#"[2,[[43,[[15,["posts"]]]],"a"]]"#,
+        to_string(&query).unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn array_of_values() -> unreql::Result<()> {
+    let query = r.array([1, 2, 3]);
+    assert_eq!(r#"[2,[1,2,3]]"#, to_string(&query).unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn array_in_concat_map() -> unreql::Result<()> {
+    let query = r
+        .expr([1, 2])
+        .concat_map(func!(|x| r.array([x.clone(), x.mul(2)])));
+    // the function body is a MAKE_ARRAY of two terms, not a nested array
+    assert_eq!(
+        r#"[40,[[2,[1,2]],[69,[[2,[1]],[2,[[10,[1]],[26,[[10,[1]],2]]]]]]]]"#,
+        to_string(&query).unwrap()
+    );
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use serde_json::{json, to_value, Value};
+use unreql::cmd::options::CompoundBound;
+use unreql::r;
+
+const BETWEEN: u32 = 182;
+const MAKE_ARRAY: u32 = 2;
+const MINVAL: u32 = 180;
+const MAXVAL: u32 = 181;
+
+fn between(lower: CompoundBound, upper: CompoundBound) -> Value {
+    let query = r.table("t").between_compound(lower, upper, "ab");
+    to_value(query).unwrap()
+}
+
+fn key(items: Value) -> Value {
+    json!([MAKE_ARRAY, items])
+}
+
+#[test]
+fn prefix_bounds_are_padded() {
+    let query = between(CompoundBound::prefix((1,)), CompoundBound::prefix((5,)));
+    let expected = json!([
+        BETWEEN,
+        [[15, ["t"]], key(json!([1, [MINVAL]])), key(json!([5, [MAXVAL]]))],
+        {"index": "ab"},
+    ]);
+    assert_eq!(query, expected);
+}
+
+#[test]
+fn open_prefix_bounds_exclude_the_prefix() {
+    let lower = CompoundBound::prefix((1,)).open();
+    let upper = CompoundBound::prefix(vec![5]).open();
+    let query = between(lower, upper);
+    assert_eq!(query[1][1], key(json!([1, [MAXVAL]])));
+    assert_eq!(query[1][2], key(json!([5, [MINVAL]])));
+    assert_eq!(query[2], json!({"index": "ab"}));
+}
+
+#[test]
+fn exact_bounds_keep_their_status() {
+    let query = between(
+        CompoundBound::exact((1, "c")),
+        CompoundBound::exact((5, "e")),
+    );
+    let expected = json!([
+        BETWEEN,
+        [[15, ["t"]], key(json!([1, "c"])), key(json!([5, "e"]))],
+        {"index": "ab", "left_bound": "closed", "right_bound": "closed"},
+    ]);
+    assert_eq!(query, expected);
+
+    let lower = CompoundBound::exact([1, 2]).open();
+    let upper = CompoundBound::exact([5, 6]).open();
+    let query = between(lower, upper);
+    assert_eq!(
+        query[2],
+        json!({"index": "ab", "left_bound": "open", "right_bound": "open"})
+    );
+}
+
+#[test]
+fn exact_and_prefix_mixed() {
+    let query = between(
+        CompoundBound::exact((1, "c")),
+        CompoundBound::prefix(("z",)),
+    );
+    assert_eq!(query[1][1], key(json!([1, "c"])));
+    assert_eq!(query[1][2], key(json!(["z", [MAXVAL]])));
+    assert_eq!(query[2], json!({"index": "ab", "left_bound": "closed"}));
+}
+
+#[test]
+fn non_finite_floats_are_rejected() {
+    let query = r.table("t").between_compound(
+        CompoundBound::exact((1, f64::NAN)),
+        CompoundBound::prefix((f64::INFINITY,)),
+        "ab",
+    );
+    match query.to_query_json() {
+        Err(unreql::Error::Driver(unreql::Driver::NonFiniteFloat { path, .. })) => {
+            assert_eq!(path, "/1");
+        }
+        other => panic!("expected a non-finite float, got {:?}", other),
+    }
+}
@@ -0,0 +1,84 @@
+use serde_json::{json, Value};
+use unreql::types::{Change, Maybe};
+
+fn change(doc: Value) -> Change {
+    serde_json::from_value(doc).unwrap()
+}
+
+#[test]
+fn insert_and_delete() {
+    let insert = change(json!({"old_val": null, "new_val": {"id": 1}}));
+    assert!(insert.old_val.is_null());
+    assert_eq!(insert.new_val, Maybe::Value(json!({"id": 1})));
+
+    let delete = change(json!({"old_val": {"id": 1}, "new_val": null}));
+    assert_eq!(delete.old_value(), Some(&json!({"id": 1})));
+    assert!(delete.new_val.is_null());
+    assert_eq!(delete.new_value(), None);
+
+    let update = change(json!({"old_val": {"id": 1}, "new_val": {"id": 1, "a": 1}}));
+    assert!(update.old_value().is_some());
+    assert!(update.new_value().is_some());
+}
+
+#[test]
+fn state_documents() {
+    for state in ["initializing", "ready"] {
+        let doc = change(json!({"state": state}));
+        assert!(doc.old_val.is_absent());
+        assert!(doc.new_val.is_absent());
+        assert_eq!(doc.state.as_deref(), Some(state));
+    }
+    let doc = change(json!({"state": "ready", "type": "state"}));
+    assert!(doc.new_val.is_absent());
+    assert_eq!(doc.result_type.as_deref(), Some("state"));
+}
+
+#[test]
+fn initial_and_uninitial_values() {
+    // `include_initial` values have no `old_val`
+    let initial = change(json!({"new_val": {"id": 1}, "type": "initial"}));
+    assert!(initial.old_val.is_absent());
+    assert!(initial.new_value().is_some());
+
+    // with `squash` and `limit` a value can leave the initial results
+    let uninitial = change(json!({"old_val": {"id": 1}, "type": "uninitial"}));
+    assert!(uninitial.old_value().is_some());
+    assert!(uninitial.new_val.is_absent());
+}
+
+#[test]
+fn offsets_of_ordered_feeds() {
+    let moved = change(json!({
+        "old_val": {"id": 1, "score": 1},
+        "new_val": {"id": 1, "score": 9},
+        "old_offset": 2,
+        "new_offset": 0,
+    }));
+    assert_eq!(moved.old_offset, Some(2));
+    assert_eq!(moved.new_offset, Some(0));
+
+    let removed = change(json!({"old_val": {"id": 1}, "new_val": null, "old_offset": 0}));
+    assert!(removed.new_val.is_null());
+    assert_eq!(removed.new_offset, None);
+}
+
+#[test]
+fn failed_write_of_return_changes_always() {
+    let failed = change(
+        json!({"old_val": {"id": 1}, "new_val": {"id": 1}, "error": "Duplicate primary key"}),
+    );
+    assert_eq!(failed.error.as_deref(), Some("Duplicate primary key"));
+}
+
+#[test]
+fn typed_values() {
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Hero {
+        id: u32,
+    }
+
+    let change: Change<Hero> = serde_json::from_value(json!({"new_val": {"id": 1}})).unwrap();
+    assert!(change.old_val.is_absent());
+    assert_eq!(change.new_val.into_option(), Some(Hero { id: 1 }));
+}
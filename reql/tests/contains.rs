@@ -0,0 +1,66 @@
+use serde_json::{json, to_value};
+use unreql::cmd::args::ContainsArg;
+use unreql::{func, r};
+
+#[test]
+fn contains_values_and_funcs() {
+    let args: Vec<ContainsArg> = vec![
+        "loki".into(),
+        json!(7).into(),
+        func!(|x| x.eq("hulk")).into(),
+    ];
+    let query = r.expr(["hulk", "thor"]).contains(r.args(args));
+    let json = to_value(&query).unwrap();
+    let func = &json[1][3];
+    let var = &func[1][0][1][0];
+    assert_eq!(
+        json,
+        json!([
+            93,
+            [
+                [2, ["hulk", "thor"]],
+                "loki",
+                7,
+                [69, [[2, [var]], [17, [[10, [var]], "hulk"]]]]
+            ]
+        ])
+    );
+}
+
+#[test]
+fn contains_row_predicate() {
+    let args: Vec<ContainsArg> = vec![r.row().gt(2).into(), json!(1).into()];
+    let query = r.expr([1, 2, 3]).contains(r.args(args));
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([93, [[2, [1, 2, 3]], [69, [[2, [1]], [21, [[13], 2]]]], 1]])
+    );
+}
+
+#[test]
+fn has_fields_mixed() {
+    let args: Vec<ContainsArg> = vec!["name".into(), json!({"stats": {"hp": true}}).into()];
+    let query = r.table("marvel").has_fields(r.args(args));
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([32, [[15, ["marvel"]], "name", {"stats": {"hp": true}}]])
+    );
+}
+
+#[test]
+fn with_fields_mixed() {
+    let args: Vec<ContainsArg> = vec![String::from("id").into(), json!({"stats": true}).into()];
+    let query = r.table("marvel").with_fields(r.args(args));
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([96, [[15, ["marvel"]], "id", {"stats": true}]])
+    );
+}
+
+#[test]
+fn empty_args() {
+    let query = r
+        .table("marvel")
+        .has_fields(r.args(Vec::<ContainsArg>::new()));
+    assert_eq!(to_value(&query).unwrap(), json!([32, [[15, ["marvel"]]]]));
+}
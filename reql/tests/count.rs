@@ -1,5 +1,5 @@
 use serde_json::to_string;
-use unreql::{r, rjson};
+use unreql::{func, r, rjson};
 
 #[tokio::test]
 async fn count_query() -> unreql::Result<()> {
@@ -10,3 +10,13 @@ async fn count_query() -> unreql::Result<()> {
     );
     Ok(())
 }
+
+#[tokio::test]
+async fn count_predicate_query() -> unreql::Result<()> {
+    let query = r.table("users").count(func!(|user| user.g("age").gt(18)));
+    assert_eq!(
+        r#"[43,[[15,["users"]],[69,[[2,[1]],[21,[[31,[[10,[1]],"age"]],18]]]]]]"#,
+        to_string(&query).unwrap()
+    );
+    Ok(())
+}
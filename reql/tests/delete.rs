@@ -0,0 +1,13 @@
+use serde_json::{json, to_value};
+use unreql::cmd::options::{DeleteOptions, ReturnChanges};
+use unreql::r;
+
+#[test]
+fn delete_return_changes_query() {
+    let opts = DeleteOptions::new().return_changes(ReturnChanges::Bool(true));
+    let query = r.table("comments").get(7).delete(opts);
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([54, [[16, [[15, ["comments"]], 7]]], {"return_changes": true}])
+    );
+}
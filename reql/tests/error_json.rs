@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::{json, to_value};
+use unreql::{r, Error, Runtime};
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Refused {
+    code: String,
+    missing: u32,
+}
+
+#[test]
+fn error_json_query() {
+    let query = r.error_json(json!({ "code": "INSUFFICIENT_FUNDS" }));
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([12, [r#"{"code":"INSUFFICIENT_FUNDS"}"#]])
+    );
+}
+
+#[test]
+fn error_json_non_finite() {
+    let query = r.error_json(HashMap::from([("missing", f64::NAN)]));
+    assert!(to_value(&query).is_err());
+}
+
+#[test]
+fn user_payload_round_trip() {
+    let query = r.error_json(json!({ "code": "INSUFFICIENT_FUNDS", "missing": 100 }));
+    let message = to_value(&query).unwrap()[1][0].as_str().unwrap().to_owned();
+    let error = Error::Runtime(Runtime::User(message));
+    let refused = error.user_payload::<Refused>().unwrap().unwrap();
+    assert_eq!(
+        refused,
+        Refused {
+            code: "INSUFFICIENT_FUNDS".into(),
+            missing: 100
+        }
+    );
+}
+
+#[test]
+fn user_payload_of_other_errors() {
+    let error = Error::Runtime(Runtime::QueryLogic(r#"{"code":"X","missing":1}"#.into()));
+    assert!(error.user_payload::<Refused>().is_none());
+    let error = Error::Compile("syntax".into());
+    assert!(error.user_payload::<Refused>().is_none());
+    let error = Error::Runtime(Runtime::User("impossible code path".into()));
+    assert!(error.user_payload::<Refused>().unwrap().is_err());
+}
@@ -10,3 +10,98 @@ async fn expr_query() -> unreql::Result<()> {
     assert_eq!(val, Some(Value::String("hello".into())));
     Ok(())
 }
+
+#[test]
+fn expr_rejects_non_finite_floats() {
+    #[derive(serde::Serialize)]
+    struct Point {
+        x: f32,
+        y: Option<f64>,
+    }
+
+    for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+        let err = serde_json::to_string(&r.expr(value)).unwrap_err();
+        assert!(err.to_string().contains("JSON has no NaN"), "{}", err);
+    }
+    let point = Point {
+        x: f32::INFINITY,
+        y: None,
+    };
+    let query = r.table("points").insert(point);
+    let err = serde_json::to_string(&query).unwrap_err();
+    assert!(err.to_string().contains("cannot send inf"), "{}", err);
+    assert!(serde_json::to_string(&r.expr([1.0, f64::NAN])).is_err());
+
+    // a missing value is still sent as null
+    let point = Point { x: 1.5, y: None };
+    let query = serde_json::to_value(r.expr(point)).unwrap();
+    assert_eq!(query, serde_json::json!({"x": 1.5, "y": null}));
+}
+
+fn non_finite_path(query: unreql::Command) -> (f64, String) {
+    match query.to_query_json() {
+        Err(unreql::Error::Driver(unreql::Driver::NonFiniteFloat { float, path })) => (float, path),
+        other => panic!("expected a non-finite float, got {:?}", other),
+    }
+}
+
+#[test]
+fn non_finite_float_paths() {
+    use std::collections::HashMap;
+    use unreql::cmd::options::JsOptions;
+    use unreql::rjson;
+
+    #[derive(serde::Serialize)]
+    struct Reading {
+        sensor: u32,
+        values: Vec<f64>,
+    }
+
+    let (float, path) = non_finite_path(r.expr(f64::NAN));
+    assert!(float.is_nan());
+    assert_eq!(path, "");
+    let (float, path) = non_finite_path(r.expr(f64::NEG_INFINITY));
+    assert_eq!((float, path.as_str()), (f64::NEG_INFINITY, ""));
+
+    let reading = Reading {
+        sensor: 1,
+        values: vec![1.5, f64::INFINITY],
+    };
+    let (_, path) = non_finite_path(r.table("readings").insert(reading));
+    assert_eq!(path, "/values/1");
+    let readings = HashMap::from([("kitchen", [0.0, f64::NAN])]);
+    let (_, path) = non_finite_path(r.expr(readings));
+    assert_eq!(path, "/kitchen/1");
+
+    // the path is relative to the value of `rjson!` holding the float
+    let doc = rjson!({ "sensor": 1, "values": [1.5, f64::NAN] });
+    let (float, path) = non_finite_path(r.table("readings").insert(doc));
+    assert!(float.is_nan());
+    assert_eq!(path, "");
+
+    let opts = JsOptions::new().timeout(f64::INFINITY);
+    let (_, path) = non_finite_path(r.js(r.with_opt("1 + 1", opts)));
+    assert_eq!(path, "/timeout");
+
+    let err = r.expr(Reading {
+        sensor: 1,
+        values: vec![f64::INFINITY],
+    });
+    let err = err.to_query_json().unwrap_err().to_string();
+    assert!(err.contains("cannot send inf at /values/0"), "{}", err);
+}
+
+#[test]
+fn lossy_non_finite_floats() -> unreql::Result<()> {
+    use serde_json::json;
+    use unreql::types::Lossy;
+
+    let query = r.expr(Lossy(f64::NAN)).to_query_json()?;
+    assert_eq!(query, Value::Null);
+    let query = r.expr(Lossy([1.5, f64::INFINITY])).to_query_json()?;
+    assert_eq!(query, json!([2, [1.5, null]]));
+    let doc = json!({ "sensor": 1 });
+    let query = r.expr(Lossy(doc.clone())).to_query_json()?;
+    assert_eq!(query, doc);
+    Ok(())
+}
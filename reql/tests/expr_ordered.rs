@@ -0,0 +1,41 @@
+#![cfg(feature = "indexmap")]
+
+use std::collections::BTreeMap;
+
+use serde_json::json;
+use unreql::{r, IndexMap};
+
+#[test]
+fn index_map_keeps_order() {
+    let mut obj = IndexMap::new();
+    obj.insert("zeta", json!(1));
+    obj.insert("alpha", json!([1, 2]));
+    obj.insert("mid", json!({"y": 1, "x": 2}));
+    let query = r.expr(obj);
+    assert_eq!(
+        serde_json::to_string(&query).unwrap(),
+        r#"{"zeta":1,"alpha":[2,[1,2]],"mid":{"y":1,"x":2}}"#
+    );
+}
+
+#[test]
+fn btree_map_keeps_order() {
+    let obj: BTreeMap<_, _> = [("c", 3), ("a", 1), ("b", 2)].into_iter().collect();
+    let query = r.expr(obj);
+    assert_eq!(
+        serde_json::to_string(&query).unwrap(),
+        r#"{"a":1,"b":2,"c":3}"#
+    );
+}
+
+#[test]
+fn objects_in_array() {
+    let mut obj = IndexMap::new();
+    obj.insert("b", 1);
+    obj.insert("a", 2);
+    let query = r.expr(vec![obj]).merge(json!({"c": 3}));
+    assert_eq!(
+        serde_json::to_string(&query).unwrap(),
+        r#"[35,[[2,[{"b":1,"a":2}]],{"c":3}]]"#
+    );
+}
@@ -0,0 +1,34 @@
+use serde_json::{json, to_value};
+use unreql::r;
+
+#[test]
+fn audit_queries() {
+    let audit = r
+        .table("users")
+        .filter_audit(r.row().g("age").gt(18), ["age", "name"]);
+    let filtered = r.table("users").filter(r.row().g("age").gt(18));
+    assert_eq!(
+        to_value(audit.query()).unwrap(),
+        to_value(filtered).unwrap()
+    );
+
+    let missing = r
+        .table("users")
+        .filter(r.row().has_fields(r.args(["age", "name"])).not())
+        .count(());
+    assert_eq!(
+        to_value(audit.missing_query()).unwrap(),
+        to_value(missing).unwrap()
+    );
+}
+
+#[test]
+fn missing_query_counts_documents_lacking_any_field() {
+    let audit = r
+        .table("users")
+        .filter_audit(r.row().g("age").gt(18), ["age"]);
+    let query = to_value(audit.missing_query()).unwrap();
+    // COUNT(FILTER(TABLE, FUNC(NOT(HAS_FIELDS(VAR, "age")))))
+    assert_eq!(query[0], json!(43));
+    assert_eq!(query[1][0][0], json!(39));
+}
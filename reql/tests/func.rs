@@ -0,0 +1,92 @@
+use serde_json::{json, to_value, Value};
+use unreql::{func, r, Func};
+
+// The ids of the parameters of the functions in `term`
+fn params(term: &Value, ids: &mut Vec<u64>) {
+    let Value::Array(items) = term else {
+        return;
+    };
+    if items[0] == 69 {
+        for id in items[1][0][1].as_array().unwrap() {
+            ids.push(id.as_u64().unwrap());
+        }
+    }
+    items.iter().for_each(|item| params(item, ids));
+}
+
+#[test]
+fn stored_func_gets_new_vars() {
+    let adult = Func::try_from(func!(|user| user.g("age").ge(18))).unwrap();
+    let users = to_value(r.table("users").filter(adult.clone())).unwrap();
+    let admins = to_value(r.table("admins").filter(adult)).unwrap();
+
+    let (mut first, mut second) = (Vec::new(), Vec::new());
+    params(&users, &mut first);
+    params(&admins, &mut second);
+    assert_eq!((first.len(), second.len()), (1, 1));
+    assert_ne!(first, second);
+
+    // the variable refers to the renumbered parameter
+    let id = first[0];
+    assert_eq!(
+        users,
+        json!([
+            39,
+            [
+                [15, ["users"]],
+                [69, [[2, [id]], [22, [[31, [[10, [id]], "age"]], 18]]]]
+            ]
+        ])
+    );
+}
+
+#[test]
+fn nested_stored_func_does_not_shadow() {
+    let pred = Func::try_from(func!(|x| x.gt(1))).unwrap();
+    let query = r
+        .expr([1, 2])
+        .filter(pred.clone())
+        .map(func!(|x| r.expr([1, 2]).filter(pred.clone()).count(x)));
+    let query = to_value(query).unwrap();
+
+    let mut ids = Vec::new();
+    params(&query, &mut ids);
+    assert_eq!(ids.len(), 3);
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), 3);
+}
+
+#[test]
+fn compose() {
+    let age = Func::try_from(func!(|user| user.g("age"))).unwrap();
+    let adult = Func::try_from(func!(|age| age.ge(18))).unwrap();
+    let query = to_value(r.table("users").filter(age.compose(adult))).unwrap();
+
+    let mut ids = Vec::new();
+    params(&query, &mut ids);
+    let [outer, adult, age] = ids[..] else {
+        panic!("expected 3 functions, got {ids:?}");
+    };
+    let age = json!([69, [[2, [age]], [31, [[10, [age]], "age"]]]]);
+    let adult = json!([69, [[2, [adult]], [22, [[10, [adult]], 18]]]]);
+    assert_eq!(
+        query,
+        json!([
+            39,
+            [
+                [15, ["users"]],
+                [
+                    69,
+                    [[2, [outer]], [64, [adult, [64, [age, [10, [outer]]]]]]]
+                ]
+            ]
+        ])
+    );
+}
+
+#[test]
+fn row_is_a_func() {
+    assert!(Func::try_from(r.row().g("age").ge(18)).is_ok());
+    assert!(Func::try_from(r.expr(1)).is_err());
+}
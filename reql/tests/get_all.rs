@@ -0,0 +1,25 @@
+use serde_json::{json, to_value};
+use unreql::r;
+
+#[test]
+fn get_all_by_index_query() {
+    let query = r
+        .table("marvel")
+        .get_all(r.with_opt("avengers", r.index("team")));
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([78, [[15, ["marvel"]], "avengers"], {"index": "team"}])
+    );
+}
+
+#[test]
+fn get_all_keys_by_index_query() {
+    let teams = r.args(["avengers", "x-men"]);
+    let query = r
+        .table("marvel")
+        .get_all(r.with_opt(teams, r.index("team")));
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([78, [[15, ["marvel"]], "avengers", "x-men"], {"index": "team"}])
+    );
+}
@@ -0,0 +1,27 @@
+use serde_json::to_string;
+use unreql::{r, rjson};
+
+#[tokio::test]
+async fn get_or_else_value() -> unreql::Result<()> {
+    let query = r
+        .table("users")
+        .get_or_else(10, rjson!({ "name": "anonymous" }));
+    assert_eq!(
+        r#"[92,[[16,[[15,["users"]],10]],{"name":"anonymous"}]]"#,
+        to_string(&query).unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_or_else_query() -> unreql::Result<()> {
+    let query = r
+        .table("posts")
+        .get_or_else(1, r.table("posts_archive").get(1));
+    let expected = r
+        .table("posts")
+        .get(1)
+        .default(r.table("posts_archive").get(1));
+    assert_eq!(to_string(&expected).unwrap(), to_string(&query).unwrap());
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use serde::Deserialize;
+use serde_json::{json, to_value};
+use unreql::types::{GroupedData, GroupedResult};
+use unreql::{func, r};
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Game {
+    id: u32,
+    player: String,
+    points: u32,
+}
+
+#[test]
+fn group_map_query() {
+    let query = r
+        .table("games")
+        .group("player")
+        .map(func!(|game| game.g("points")));
+    let json = to_value(&query).unwrap();
+    let var = &json[1][1][1][0][1][0];
+    assert_eq!(
+        json,
+        json!([
+            38,
+            [
+                [144, [[15, ["games"]], "player"]],
+                [69, [[2, [var]], [31, [[10, [var]], "points"]]]]
+            ]
+        ])
+    );
+}
+
+#[test]
+fn grouped_documents() {
+    let data = json!({
+        "$reql_type$": "GROUPED_DATA",
+        "data": [
+            ["Alice", [{"id": 5, "player": "Alice", "points": 7}]],
+            ["Bob", [{"id": 2, "player": "Bob", "points": 15}]],
+        ],
+    });
+    let grouped: GroupedData<String, Vec<Game>> = serde_json::from_value(data).unwrap();
+    let grouped = grouped.into_vec();
+    assert_eq!(grouped.len(), 2);
+    assert_eq!(grouped[0].group, "Alice");
+    assert_eq!(
+        grouped[1].values,
+        [Game {
+            id: 2,
+            player: "Bob".into(),
+            points: 15
+        }]
+    );
+}
+
+#[test]
+fn grouped_after_map() {
+    let data = json!({
+        "$reql_type$": "GROUPED_DATA",
+        "data": [["Alice", [7, 12]], ["Bob", [15, 3, 1]]],
+    });
+    let grouped: GroupedData<String, Vec<u32>> = serde_json::from_value(data).unwrap();
+    let points: Vec<(String, u32)> = grouped
+        .into_iter()
+        .map(|group| (group.group, group.values.iter().sum()))
+        .collect();
+    assert_eq!(points, [("Alice".into(), 19), ("Bob".into(), 19)]);
+}
+
+#[test]
+fn grouped_after_reduction_by_several_fields() {
+    let data = json!({
+        "$reql_type$": "GROUPED_DATA",
+        "data": [[["Alice", "free"], 2], [["Bob", "basket"], 1]],
+    });
+    let grouped: GroupedData<(String, String), u64> = serde_json::from_value(data).unwrap();
+    assert_eq!(
+        grouped.0[0],
+        GroupedResult {
+            group: ("Alice".into(), "free".into()),
+            values: 2
+        }
+    );
+}
+
+#[test]
+fn ungrouped_results() {
+    let data = json!([
+        {"group": "Alice", "reduction": 2},
+        {"group": "Bob", "reduction": 1},
+    ]);
+    let groups: Vec<GroupedResult<String, u64>> = serde_json::from_value(data).unwrap();
+    assert_eq!(groups[1].group, "Bob");
+    assert_eq!(groups[1].values, 1);
+}
+
+#[test]
+fn not_grouped_data() {
+    let data = json!({"$reql_type$": "TIME", "data": []});
+    let err = serde_json::from_value::<GroupedData<String, u64>>(data).unwrap_err();
+    assert!(err.to_string().contains("expected GROUPED_DATA"));
+}
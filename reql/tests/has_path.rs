@@ -0,0 +1,73 @@
+use serde_json::{json, to_string, to_value};
+use unreql::{r, rjson};
+
+#[test]
+fn has_path_query() {
+    let query = r.table("players").has_path(&["games_won", "championships"]);
+    let documented = r
+        .table("players")
+        .has_fields(rjson!({"games_won": {"championships": true}}));
+    assert_eq!(to_value(&query).unwrap(), to_value(&documented).unwrap());
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([32, [[15, ["players"]], {"games_won": {"championships": true}}]])
+    );
+
+    let query = r.table("players").has_path(&["games_won"]);
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([32, [[15, ["players"]], {"games_won": true}]])
+    );
+
+    let query = r.table("players").has_path(&["a", "b", "c"]);
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([32, [[15, ["players"]], {"a": {"b": {"c": true}}}]])
+    );
+}
+
+#[test]
+fn has_path_empty() {
+    let err = to_string(&r.table("players").has_path(&[])).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("the path of a field cannot be empty"));
+}
+
+#[test]
+fn has_any_path_query() {
+    let query = r
+        .row()
+        .has_any_path(&[&["games_won", "playoffs"], &["games_won", "championships"]]);
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([66, [
+            [32, [[13], {"games_won": {"playoffs": true}}]],
+            [32, [[13], {"games_won": {"championships": true}}]],
+        ]])
+    );
+
+    let query = r.row().has_any_path(&[&["games_won"]]);
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([32, [[13], {"games_won": true}]])
+    );
+
+    assert_eq!(to_value(r.row().has_any_path(&[])).unwrap(), json!(false));
+}
+
+#[test]
+fn filter_has_path_query() {
+    let query = r
+        .table("players")
+        .filter_has_path(&["games_won", "championships"]);
+    let json = to_value(&query).unwrap();
+    let var = &json[1][1][1][0][1][0];
+    assert_eq!(
+        json,
+        json!([39, [
+            [15, ["players"]],
+            [69, [[2, [var]], [32, [[13], {"games_won": {"championships": true}}]]]],
+        ]])
+    );
+}
@@ -1,7 +1,8 @@
-use serde_json::to_string;
+use serde_json::{from_str, to_string};
 use unreql::{
-    cmd::options::{Conflict, InsertOptions},
+    cmd::options::{Conflict, InsertOptions, ReturnChanges},
     r, rjson,
+    types::WriteStatus,
 };
 
 #[tokio::test]
@@ -38,3 +39,38 @@ async fn insert_with_opt() -> unreql::Result<()> {
     );
     Ok(())
 }
+
+#[tokio::test]
+async fn insert_return_changes_always() -> unreql::Result<()> {
+    let cmd = r.table("table").insert(r.with_opt(
+        rjson!([{"id": 1}, {"id": 2}]),
+        InsertOptions::new().return_changes(ReturnChanges::Always),
+    ));
+    assert_eq!(
+        r#"[56,[[15,["table"]],[2,[{"id":1},{"id":2}]]],{"return_changes":"always"}]"#,
+        to_string(&cmd).unwrap()
+    );
+
+    let status: WriteStatus = from_str(
+        r#"{
+            "inserted": 1, "replaced": 0, "unchanged": 0, "skipped": 0,
+            "deleted": 0, "errors": 1,
+            "first_error": "Duplicate primary key `id`",
+            "changes": [
+                {"old_val": null, "new_val": {"id": 1}},
+                {
+                    "old_val": {"id": 2}, "new_val": {"id": 2},
+                    "error": "Duplicate primary key `id`"
+                }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let changes = status.changes.unwrap();
+    assert_eq!(changes[0].error, None);
+    assert_eq!(
+        changes[1].error.as_deref(),
+        Some("Duplicate primary key `id`")
+    );
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use serde::Serialize;
+use serde_json::{json, to_value};
+use unreql::{r, rjson};
+
+#[derive(Serialize)]
+struct Data {
+    age: u8,
+    job: &'static str,
+}
+
+#[tokio::test]
+async fn literal_typed_struct() -> unreql::Result<()> {
+    let query = r.table("users").get(1).update(rjson!({
+        "data": r.literal(Data { age: 19, job: "Engineer" }),
+    }));
+    assert_eq!(
+        json!([53,[[16,[[15,["users"]],1]],{"data":[137,[{"age":19,"job":"Engineer"}]]}]]),
+        to_value(&query).unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn literal_json() -> unreql::Result<()> {
+    let query = r.literal(rjson!({ "age": 19, "job": "Engineer" }));
+    assert_eq!(
+        json!([137, [{"age": 19, "job": "Engineer"}]]),
+        to_value(&query).unwrap()
+    );
+    Ok(())
+}
@@ -0,0 +1,62 @@
+// Names of databases, tables and indexes can be passed as any owned or
+// `'static` string
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use serde_json::{json, to_value};
+use unreql::cmd::connect::Options;
+use unreql::{r, Session};
+
+#[test]
+fn str_and_string() {
+    let name = String::from("heroes");
+    let by_str = r
+        .db("marvel")
+        .table("heroes")
+        .get_all(r.with_opt(1, r.index("code")));
+    let by_string = r
+        .db(String::from("marvel"))
+        .table(name.clone())
+        .get_all(r.with_opt(1, r.index(String::from("code"))));
+    assert_eq!(to_value(&by_str).unwrap(), to_value(&by_string).unwrap());
+}
+
+#[test]
+fn cow_and_arc() {
+    let db: Cow<'static, str> = Cow::Owned("marvel".into());
+    let table: Arc<str> = Arc::from("heroes");
+    let index: Arc<str> = Arc::from("code");
+    let query = r.db(db).table(table).get_all(r.with_opt(1, r.index(index)));
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([78, [[15, [[14, ["marvel"]], "heroes"]], 1], {"index": "code"}])
+    );
+}
+
+#[test]
+fn connect_options() {
+    let name = String::from("marvel");
+    let arc: Arc<str> = Arc::from("marvel");
+    for opts in [
+        Options::new().db("marvel"),
+        Options::new().db(name.clone()),
+        Options::new().db(&name),
+        Options::new().db(Cow::Borrowed("marvel")),
+        Options::new().db(arc.clone()),
+        Options::new().db(&arc),
+    ] {
+        assert_eq!(opts.db, "marvel");
+    }
+}
+
+// Only has to compile, there is no server to run it against
+#[allow(dead_code)]
+async fn use_names(session: &mut Session) {
+    let name = String::from("marvel");
+    session.use_("marvel").await;
+    session.use_(name.clone()).await;
+    session.use_(&name).await;
+    session.use_(Cow::Borrowed("marvel")).await;
+    session.use_(Arc::<str>::from("marvel")).await;
+}
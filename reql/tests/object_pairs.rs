@@ -0,0 +1,50 @@
+use serde_json::{json, to_value};
+use unreql::{func, r};
+
+#[test]
+fn object_of_commands() {
+    let query = r.object_pairs([("id", r.expr(1)), ("next", r.expr(2).add(1))]);
+    assert_eq!(
+        json!([143, ["id", 1, "next", [24, [2, 1]]]]),
+        to_value(&query).unwrap()
+    );
+}
+
+#[test]
+fn object_of_vec() {
+    let pairs = vec![(String::from("posts"), r.table("posts").count(()))];
+    let query = r.object_pairs(pairs);
+    assert_eq!(
+        json!([143, ["posts", [43, [[15, ["posts"]]]]]]),
+        to_value(&query).unwrap()
+    );
+}
+
+#[test]
+fn object_of_values() {
+    let query = r.object_pairs([("a", [1, 2]), ("b", [3, 4])]);
+    assert_eq!(
+        json!([143, ["a", [2, [1, 2]], "b", [2, [3, 4]]]]),
+        to_value(&query).unwrap()
+    );
+}
+
+#[test]
+fn object_of_nothing() {
+    let query = r.object_pairs(Vec::<(&str, i32)>::new());
+    assert_eq!(json!([143]), to_value(&query).unwrap());
+}
+
+#[test]
+fn object_in_map() {
+    let query = r
+        .table("posts")
+        .map(func!(|post| r.object_pairs([("title", post.g("title"))])));
+    let json = to_value(&query).unwrap();
+    assert_eq!(json[0], json!(38));
+    // the function body is an OBJECT term with the field as its value
+    let body = &json[1][1][1][1];
+    assert_eq!(body[0], json!(143));
+    assert_eq!(body[1][0], json!("title"));
+    assert_eq!(body[1][1][0], json!(31));
+}
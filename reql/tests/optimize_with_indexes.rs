@@ -0,0 +1,183 @@
+use serde_json::{json, to_string};
+use unreql::{
+    cmd::options::FilterOptions,
+    func, r, rjson,
+    types::{IndexStatus, TableInfo},
+};
+
+fn users_info() -> TableInfo {
+    serde_json::from_value(json!({
+        "db": {"id": "a39e5c0c-8f4e-4a42-8b8a-9a2b9c7c0f11", "name": "test", "type": "DB"},
+        "doc_count_estimates": [100],
+        "id": "6d0b2c9e-4e3f-4a4b-9d57-4b0f3c8a8c21",
+        "indexes": ["email", "login", "tags", "upper_nickname"],
+        "name": "users",
+        "primary_key": "id",
+        "type": "TABLE"
+    }))
+    .unwrap()
+}
+
+fn index_status(index: &str, query: &str, multi: bool) -> IndexStatus {
+    serde_json::from_value(json!({
+        "index": index,
+        "ready": true,
+        "multi": multi,
+        "geo": false,
+        "outdated": false,
+        "query": query
+    }))
+    .unwrap()
+}
+
+fn users_indexes() -> Vec<IndexStatus> {
+    vec![
+        index_status(
+            "email",
+            r#"indexCreate('email', function(_var1) { return _var1("email"); })"#,
+            false,
+        ),
+        index_status(
+            "login",
+            r#"indexCreate('login', function(_var1) { return _var1("name"); })"#,
+            false,
+        ),
+        index_status(
+            "tags",
+            r#"indexCreate('tags', function(_var1) { return _var1("tags"); }, {multi: true})"#,
+            true,
+        ),
+        index_status(
+            "upper_nickname",
+            r#"indexCreate('upper_nickname', function(_var1) { return _var1("nickname").upcase(); })"#,
+            false,
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn rewrite_filter_by_primary_key() -> unreql::Result<()> {
+    let info = users_info();
+    let query = r
+        .table("users")
+        .filter(json!({ "id": 42 }))
+        .optimize_with_indexes(&info, &users_indexes(), "test");
+    let expected = r.table("users").get_all(42);
+    assert_eq!(to_string(&expected).unwrap(), to_string(&query).unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn rewrite_filter_by_index() -> unreql::Result<()> {
+    let info = users_info();
+    let query = r
+        .table("users")
+        .filter(rjson!({ "email": "bob@example.com" }))
+        .optimize_with_indexes(&info, &users_indexes(), "test");
+    let expected = r
+        .table("users")
+        .get_all(r.with_opt("bob@example.com", r.index("email")));
+    assert_eq!(to_string(&expected).unwrap(), to_string(&query).unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn rewrite_filter_by_index_named_after_another_field() -> unreql::Result<()> {
+    let info = users_info();
+    let query = r
+        .table("users")
+        .filter(rjson!({ "name": "Bob" }))
+        .optimize_with_indexes(&info, &users_indexes(), "test");
+    let expected = r
+        .table("users")
+        .get_all(r.with_opt("Bob", r.index("login")));
+    assert_eq!(to_string(&expected).unwrap(), to_string(&query).unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn keep_filter_on_index_not_ready() -> unreql::Result<()> {
+    let info = users_info();
+    let mut indexes = users_indexes();
+    for status in &mut indexes {
+        status.ready = false;
+    }
+    let query = r
+        .table("users")
+        .filter(rjson!({ "email": "bob@example.com" }));
+    let before = to_string(&query).unwrap();
+    let after = to_string(&query.optimize_with_indexes(&info, &indexes, "test")).unwrap();
+    assert_eq!(before, after);
+    Ok(())
+}
+
+#[tokio::test]
+async fn keep_table_of_another_default_db() -> unreql::Result<()> {
+    // without a db the table is the one of the default db, not of `info`
+    let info = users_info();
+    let query = r.table("users").filter(rjson!({ "id": 42 }));
+    let before = to_string(&query).unwrap();
+    let after = to_string(&query.optimize_with_indexes(&info, &users_indexes(), "prod")).unwrap();
+    assert_eq!(before, after);
+    Ok(())
+}
+
+#[tokio::test]
+async fn rewrite_nested_filter() -> unreql::Result<()> {
+    let info = users_info();
+    let query = r
+        .db("test")
+        .table("users")
+        .filter(rjson!({ "id": 42 }))
+        .limit(1)
+        .optimize_with_indexes(&info, &users_indexes(), "test");
+    let expected = r.db("test").table("users").get_all(42).limit(1);
+    assert_eq!(to_string(&expected).unwrap(), to_string(&query).unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn keep_non_rewritable_filters() -> unreql::Result<()> {
+    let info = users_info();
+    let queries = [
+        // not an indexed field
+        r.table("users").filter(rjson!({ "age": 42 })),
+        // more than one field
+        r.table("users").filter(rjson!({ "id": 42, "name": "Bob" })),
+        // non-literal value
+        r.table("users")
+            .filter(rjson!({ "id": r.expr("BOB").downcase() })),
+        // arrays are not rewritten
+        r.table("users").filter(rjson!({ "id": ["admin"] })),
+        // function predicate
+        r.table("users").filter(func!(|user| user.g("id").eq(42))),
+        // filter with options
+        r.table("users")
+            .filter(r.with_opt(rjson!({ "id": 42 }), FilterOptions::new().default(true))),
+        // another table
+        r.table("posts").filter(rjson!({ "id": 42 })),
+        // another database
+        r.db("prod").table("users").filter(rjson!({ "id": 42 })),
+        // a computed index is not the field
+        r.table("users").filter(rjson!({ "nickname": "BOB" })),
+    ];
+    for query in queries {
+        let before = to_string(&query).unwrap();
+        let after =
+            to_string(&query.optimize_with_indexes(&info, &users_indexes(), "test")).unwrap();
+        assert_eq!(before, after);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn keep_scalar_filter_on_multi_index() -> unreql::Result<()> {
+    // `tags` is a multi index: get_all("admin") would match the users
+    // tagged "admin", the filter only the ones whose tags are "admin"
+    let info = users_info();
+    let query = r.table("users").filter(rjson!({ "tags": "admin" }));
+    let before = to_string(&query).unwrap();
+    let after = to_string(&query.optimize_with_indexes(&info, &users_indexes(), "test")).unwrap();
+    assert_eq!(before, after);
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use serde_json::to_string;
+use unreql::r;
+
+#[tokio::test]
+async fn order_by_index() -> unreql::Result<()> {
+    let query = r.table("posts").order_by(r.index("date"));
+    assert_eq!(
+        r#"[41,[[15,["posts"]]],{"index":"date"}]"#,
+        to_string(&query).unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn order_by_index_desc() -> unreql::Result<()> {
+    let query = r.table("posts").order_by(r.index(r.desc("date")));
+    assert_eq!(
+        r#"[41,[[15,["posts"]]],{"index":[74,["date"]]}]"#,
+        to_string(&query).unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn order_by_field_with_index() -> unreql::Result<()> {
+    let query = r
+        .table("posts")
+        .order_by(r.with_opt("title", r.index(r.desc("date"))));
+    assert_eq!(
+        r#"[41,[[15,["posts"]],"title"],{"index":[74,["date"]]}]"#,
+        to_string(&query).unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn order_by_multiple_orders_in_index() -> unreql::Result<()> {
+    let query = r
+        .table("posts")
+        .order_by(r.index([r.asc("date"), r.desc("title")]));
+    let err = to_string(&query).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("multiple orders in a compound index"));
+
+    let query = r
+        .table("posts")
+        .order_by(r.index(r.array([r.asc("date"), r.desc("title")])));
+    let err = to_string(&query).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("multiple orders in a compound index"));
+    Ok(())
+}
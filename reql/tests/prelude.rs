@@ -0,0 +1,54 @@
+// Only the prelude is imported. These only need to compile, the checks
+// are never run.
+
+use unreql::prelude::*;
+
+#[derive(serde::Deserialize)]
+struct Hero {
+    name: String,
+}
+
+#[test]
+fn query_with_options_func_and_typed_exec() {
+    async fn check(session: &Session) -> Result<Vec<String>> {
+        let status: WriteStatus<Hero> = r
+            .table("heroes")
+            .insert(
+                r.with_opt(
+                    rjson!({ "name": "Iron Man", "team": "Avengers" }),
+                    InsertOptions::new()
+                        .conflict(Conflict::Update)
+                        .return_changes(ReturnChanges::Bool(true)),
+                ),
+            )
+            .exec(session)
+            .await?;
+        let changes: Vec<Change<Hero>> = status.changes.unwrap_or_default();
+        let mut names: Vec<String> = changes
+            .into_iter()
+            .filter_map(|change| change.new_value().map(|hero| hero.name.clone()))
+            .collect();
+
+        let opts = RunOptions::new()
+            .read_mode(ReadMode::Outdated)
+            .cancel(CancelToken::new());
+        let mut heroes = r
+            .table("heroes")
+            .filter(func!(|hero| hero.g("team").eq("Avengers")))
+            .run::<Hero>(r.args((session, opts)));
+        while let Some(hero) = heroes.try_next().await? {
+            names.push(hero.name);
+        }
+        let _: DateTime = session.server_time().await?;
+        Ok(names)
+    }
+
+    async fn errors(session: &Session) -> bool {
+        matches!(
+            r.table("heroes").count(()).exec::<u64>(session).await,
+            Err(Error::Driver(Driver::ConnectionBroken))
+        )
+    }
+
+    let _ = (check, errors, r.connect(ConnectOptions::new()));
+}
@@ -0,0 +1,59 @@
+use futures::TryStreamExt;
+use serde_json::{json, Value};
+use unreql::func;
+use unreql::r;
+use unreql::record::{Entry, Recording, ReplayArg};
+
+// What a server answered to the queries of `run_queries`
+fn server_recording() -> Recording {
+    let user = r#"{"t":1,"r":[{"id":1,"name":"Ann"}]}"#;
+    let user = Entry::new(&r.table("users").get(1), Default::default(), user).unwrap();
+    let mut ids = Entry::new(&users_over(18), Default::default(), r#"{"t":3,"r":[1,2]}"#).unwrap();
+    ids.responses.push(r#"{"t":2,"r":[3]}"#.into());
+    Recording::from_entries(vec![user, ids])
+}
+
+fn users_over(age: u8) -> unreql::Command {
+    r.table("users")
+        .filter(func!(|user| user.g("age").gt(age)))
+        .g("id")
+}
+
+async fn run_queries<A>(arg: impl Fn() -> A) -> unreql::Result<(Value, Vec<u64>)>
+where
+    A: unreql::cmd::run::Arg,
+{
+    let user = r.table("users").get(1).exec(arg()).await?;
+    let ids = users_over(18).run::<u64>(arg()).try_collect().await?;
+    Ok((user, ids))
+}
+
+#[tokio::test]
+async fn record_and_replay() -> unreql::Result<()> {
+    // record against a replaying server standing in for a real one
+    let server = ReplayArg::new(server_recording()).await?;
+    let recording = Recording::new();
+    let recorded = run_queries(|| recording.arg(&server)).await?;
+    assert_eq!(recorded.0, json!({"id": 1, "name": "Ann"}));
+    assert_eq!(recorded.1, [1, 2, 3]);
+    assert_eq!(recording.entries(), server_recording().entries());
+
+    let path = std::env::temp_dir().join("unreql_record_and_replay.jsonl");
+    recording.save(&path)?;
+    let loaded = Recording::load(&path)?;
+    std::fs::remove_file(&path)?;
+    assert_eq!(loaded.entries(), recording.entries());
+
+    let replay = ReplayArg::new(loaded).await?;
+    let replayed = run_queries(|| &replay).await?;
+    assert_eq!(replayed, recorded);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_query() -> unreql::Result<()> {
+    let replay = ReplayArg::new(Recording::new()).await?;
+    let res = r.table("users").exec::<Value>(&replay).await;
+    assert!(res.is_err());
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use serde_json::{json, to_value};
+use unreql::r;
+
+#[test]
+fn reshard_queries() {
+    let reshard = r.table("users").reshard(2, 3);
+    assert_eq!(
+        to_value(reshard.query()).unwrap(),
+        json!([176, [[15, ["users"]]], {"shards": 2, "replicas": 3}])
+    );
+    assert_eq!(
+        to_value(reshard.dry_run().query()).unwrap(),
+        json!([176, [[15, ["users"]]], {"shards": 2, "replicas": 3, "dry_run": true}])
+    );
+}
@@ -0,0 +1,36 @@
+use serde_json::to_string;
+use unreql::cmd::run::{Format, Options};
+
+#[tokio::test]
+async fn binary_format() -> unreql::Result<()> {
+    let opts = Options::new().binary_format(Format::Raw);
+    assert_eq!(r#"{"binary_format":"raw"}"#, to_string(&opts).unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn formats_together() -> unreql::Result<()> {
+    let opts = Options::new()
+        .time_format(Format::Raw)
+        .binary_format(Format::Native);
+    assert_eq!(
+        r#"{"time_format":"raw","binary_format":"native"}"#,
+        to_string(&opts).unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn tag_is_not_sent() -> unreql::Result<()> {
+    let opts = Options::new().tag("req-42").noreply(true);
+    assert_eq!(opts.tag.as_deref(), Some("req-42"));
+    assert_eq!(r#"{"noreply":true}"#, to_string(&opts).unwrap());
+    Ok(())
+}
+
+#[tokio::test]
+async fn db_override() -> unreql::Result<()> {
+    let opts = Options::new().db("tenant_42");
+    assert_eq!(r#"{"db":[14,["tenant_42"]]}"#, to_string(&opts).unwrap());
+    Ok(())
+}
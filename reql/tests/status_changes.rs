@@ -0,0 +1,25 @@
+use serde_json::to_string;
+use unreql::{cmd::options::ChangesOptions, r};
+
+#[tokio::test]
+async fn status_changes_query() -> unreql::Result<()> {
+    let query = r.table("heroes").status_changes(());
+    assert_eq!(
+        r#"[152,[[16,[[15,[[14,["rethinkdb"]],"table_status"]],[31,[[174,[[15,["heroes"]]]],"id"]]]]]]"#,
+        to_string(&query).unwrap()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn status_changes_with_db_and_opts() -> unreql::Result<()> {
+    let query = r
+        .db("marvel")
+        .table("heroes")
+        .status_changes(ChangesOptions::new().include_initial(true));
+    assert_eq!(
+        r#"[152,[[16,[[15,[[14,["rethinkdb"]],"table_status"]],[31,[[174,[[15,[[14,["marvel"]],"heroes"]]]],"id"]]]]],{"include_initial":true}]"#,
+        to_string(&query).unwrap()
+    );
+    Ok(())
+}
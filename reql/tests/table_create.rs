@@ -0,0 +1,76 @@
+use serde_json::{json, to_value};
+use unreql::cmd::options::{Replicas, TableCreateOptions};
+use unreql::{r, Driver, Error};
+
+fn message(opts: &TableCreateOptions) -> String {
+    match opts.validate() {
+        Err(Error::Driver(Driver::Other(msg))) => msg,
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
+
+#[test]
+fn zero_replicas() {
+    let opts = TableCreateOptions::new().replicas_int(0);
+    assert_eq!(message(&opts), "`replicas` must be at least 1");
+}
+
+#[test]
+fn empty_replicas_map() {
+    let opts = TableCreateOptions::new().replicas_tagged(Vec::<(&str, u8)>::new(), "us");
+    assert_eq!(message(&opts), "`replicas` map must not be empty");
+}
+
+#[test]
+fn unknown_primary_tag() {
+    let opts = TableCreateOptions::new().replicas_tagged([("us", 2), ("eu", 1)], "asia");
+    assert_eq!(
+        message(&opts),
+        "`primary_replica_tag` `asia` is not a tag of the `replicas` map"
+    );
+}
+
+#[test]
+fn primary_tag_without_replicas() {
+    let opts = TableCreateOptions::new().replicas_tagged([("us", 0), ("eu", 1)], "us");
+    assert_eq!(
+        message(&opts),
+        "`primary_replica_tag` `us` must have at least 1 replica"
+    );
+}
+
+#[test]
+fn invalid_options_fail_serialization() {
+    let opts = TableCreateOptions::new().replicas_tagged([("us", 2)], "eu");
+    let query = r.table_create(r.with_opt("users", opts));
+    let error = to_value(&query).unwrap_err();
+    assert!(error.to_string().contains("`eu` is not a tag"));
+}
+
+#[test]
+fn valid_options_pass_through() {
+    let opts = TableCreateOptions::new().replicas_int(3).shards(2);
+    assert!(opts.validate().is_ok());
+    assert_eq!(
+        to_value(r.table_create(r.with_opt("users", opts))).unwrap(),
+        json!([60, ["users"], {"shards": 2, "replicas": 3}])
+    );
+
+    let opts = TableCreateOptions::new().replicas_tagged([("us", 2)], "us");
+    assert_eq!(
+        opts.replicas,
+        Some(Replicas::Map {
+            replicas: [("us".into(), 2)].into_iter().collect(),
+            primary_replica_tag: "us".into(),
+        })
+    );
+    assert_eq!(
+        to_value(r.table_create(r.with_opt("users", opts))).unwrap(),
+        json!([60, ["users"], {"replicas": {"us": 2}, "primary_replica_tag": "us"}])
+    );
+}
+
+#[test]
+fn no_replicas_is_valid() {
+    assert!(TableCreateOptions::new().validate().is_ok());
+}
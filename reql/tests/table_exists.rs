@@ -0,0 +1,11 @@
+use serde_json::json;
+use unreql::r;
+
+#[test]
+fn table_exists_checks_the_list_on_the_server() -> unreql::Result<()> {
+    let query = r.db("heroes").table_exists("marvel").to_query_json()?;
+    assert_eq!(query, json!([93, [[62, [[14, ["heroes"]]]], "marvel"]]));
+    let query = r.table_exists("marvel").to_query_json()?;
+    assert_eq!(query, json!([93, [[62], "marvel"]]));
+    Ok(())
+}
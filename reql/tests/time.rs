@@ -0,0 +1,114 @@
+use serde_json::to_string;
+use unreql::r;
+
+#[test]
+fn time_query() {
+    let query = r.time(1986, 11, 3, "Z");
+    assert_eq!(to_string(&query).unwrap(), r#"[136,[1986,11,3,"Z"]]"#);
+
+    let query = r.time_ext(1986, 11, 3, 9, 30, 15.5, "-07:00");
+    assert_eq!(
+        to_string(&query).unwrap(),
+        r#"[136,[1986,11,3,9,30,15.5,"-07:00"]]"#
+    );
+}
+
+#[test]
+fn time_valid_ranges() {
+    for month in 1..=12 {
+        for day in 1..=31 {
+            assert!(to_string(&r.time(2013, month, day, "Z")).is_ok());
+        }
+    }
+    for year in [1400, 1970, 2013, 9999] {
+        assert!(to_string(&r.time(year, 1, 1, "Z")).is_ok());
+    }
+    for hour in 0..=23 {
+        for minutes in 0..=59 {
+            for seconds in [0.0, 30.25, 59.999] {
+                let query = r.time_ext(2013, 1, 1, hour, minutes, seconds, "Z");
+                assert!(to_string(&query).is_ok());
+            }
+        }
+    }
+    for sign in ["+", "-"] {
+        for hh in 0..=23 {
+            for mm in [0, 30, 45, 59] {
+                let tz = format!("{sign}{hh:02}:{mm:02}");
+                assert!(to_string(&r.time(2013, 1, 1, tz)).is_ok());
+            }
+        }
+    }
+}
+
+#[test]
+fn time_invalid_arguments() {
+    let cases = [
+        (
+            r.time(2013, 13, 1, "Z"),
+            "month must be an integer between 1 and 12, got 13",
+        ),
+        (
+            r.time(2013, 0, 1, "Z"),
+            "month must be an integer between 1 and 12, got 0",
+        ),
+        (
+            r.time(2013, 1, 40, "Z"),
+            "day must be an integer between 1 and 31, got 40",
+        ),
+        (
+            r.time(2013, 1, 0, "Z"),
+            "day must be an integer between 1 and 31, got 0",
+        ),
+        (
+            r.time(1399, 1, 1, "Z"),
+            "year must be an integer between 1400 and 9999, got 1399",
+        ),
+        (
+            r.time(2013, 1.5, 1, "Z"),
+            "month must be an integer between 1 and 12, got 1.5",
+        ),
+        (
+            r.time(2013, "1", 1, "Z"),
+            r#"month must be an integer between 1 and 12, got "1""#,
+        ),
+        (r.time(2013, 1, 1, "+25:00"), r#"got "+25:00""#),
+        (r.time(2013, 1, 1, "+01:60"), r#"got "+01:60""#),
+        (r.time(2013, 1, 1, "01:00"), r#"got "01:00""#),
+        (r.time(2013, 1, 1, "+1:00"), r#"got "+1:00""#),
+        (r.time(2013, 1, 1, "UTC"), r#"got "UTC""#),
+        (
+            r.time(2013, 1, 1, 0),
+            "timezone must be 'Z' or have the format ±[hh]:[mm], got 0",
+        ),
+        (
+            r.time_ext(2013, 1, 1, 24, 0, 0, "Z"),
+            "hour must be an integer between 0 and 23, got 24",
+        ),
+        (
+            r.time_ext(2013, 1, 1, 0, 60, 0, "Z"),
+            "minutes must be an integer between 0 and 59, got 60",
+        ),
+        (
+            r.time_ext(2013, 1, 1, 0, 0, 60, "Z"),
+            "seconds must be a number between 0 and 60 (excluded), got 60",
+        ),
+        (
+            r.time_ext(2013, 1, 1, 0, 0, -1, "Z"),
+            "seconds must be a number between 0 and 60 (excluded), got -1",
+        ),
+    ];
+    for (query, message) in cases {
+        let err = to_string(&query).unwrap_err().to_string();
+        assert!(err.contains("invalid r.time argument"), "{err}");
+        assert!(err.contains(message), "{err} does not contain {message}");
+    }
+}
+
+#[test]
+fn time_expressions_are_not_checked() {
+    let month = r.expr(6).add(7);
+    let day = r.table("days").get(1).g("day");
+    let query = r.time(2013, month, day, r.expr("+25").add(":00"));
+    assert!(to_string(&query).is_ok());
+}
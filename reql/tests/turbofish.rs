@@ -0,0 +1,44 @@
+// These only need to compile, the checks are never run.
+
+use futures::TryStreamExt;
+use serde_json::Value;
+use unreql::{r, Connection, Session};
+
+#[test]
+fn result_type_only_with_session() {
+    async fn check(conn: &Session) -> unreql::Result<()> {
+        r.table("test").run::<Value>(conn).try_next().await?;
+        r.table("test").get(1).exec::<Value>(conn).await?;
+        r.table("test").exec_to_vec::<Value>(conn).await?;
+        r.table("test").get_all(1).exec_array::<Value>(conn).await?;
+        r.db_list().exec::<Vec<String>>(conn).await?;
+        Ok(())
+    }
+    let _ = check;
+}
+
+#[test]
+fn result_type_only_with_connection() {
+    async fn check(conn: Connection) -> unreql::Result<()> {
+        r.table("test")
+            .run::<Value>(conn.clone())
+            .try_next()
+            .await?;
+        r.table("test").get(1).exec::<Value>(conn.clone()).await?;
+        r.db_list().exec_to_vec::<String>(conn).await?;
+        Ok(())
+    }
+    let _ = check;
+}
+
+#[test]
+fn borrowed_connection() {
+    async fn check(mut conn: Connection) -> unreql::Result<()> {
+        r.table("test").run::<Value>(&conn).try_next().await?;
+        r.table("test").get(1).exec::<Value>(&conn).await?;
+        r.table("test").exec_to_vec::<Value>(&mut conn).await?;
+        r.db_list().exec::<Vec<String>>(conn).await?;
+        Ok(())
+    }
+    let _ = check;
+}
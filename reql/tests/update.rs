@@ -41,22 +41,32 @@ async fn update_json_with_func_row() -> unreql::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn update_non_atomic() -> unreql::Result<()> {
+    let cmd = r
+        .table("posts")
+        .get(1)
+        .update_non_atomic(rjson!({ "numComments": r.table("comments").count(()) }));
+
+    assert_eq!(
+        r#"[53,[[16,[[15,["posts"]],1]],{"numComments":[43,[[15,["comments"]]]]}],{"non_atomic":true}]"#,
+        to_string(&cmd).unwrap()
+    );
+    Ok(())
+}
+
 #[tokio::test]
 async fn update_in_db() -> unreql::Result<()> {
     let conn = r.connect(()).await?;
     let table = "users_test";
 
-    let _ = r
-        .table_create(table)
-        .run::<_, Value>(&conn)
-        .try_next()
-        .await;
+    let _ = r.table_create(table).run::<Value>(&conn).try_next().await;
 
     let _ = r
         .table(table)
         .get(1)
         .delete(())
-        .run::<_, Value>(&conn)
+        .run::<Value>(&conn)
         .try_next()
         .await;
 
@@ -67,16 +77,11 @@ async fn update_in_db() -> unreql::Result<()> {
             "name": "Ivan",
             "upd_count": 3,
         }))
-        .run::<_, Value>(&conn)
+        .run::<Value>(&conn)
         .try_next()
         .await?;
 
-    let user = r
-        .table(table)
-        .get(1)
-        .run::<_, Value>(&conn)
-        .try_next()
-        .await?;
+    let user = r.table(table).get(1).run::<Value>(&conn).try_next().await?;
     assert_eq!(user, Some(json!({"id": 1, "name": "Ivan", "upd_count": 3})));
 
     let _ = r
@@ -86,16 +91,11 @@ async fn update_in_db() -> unreql::Result<()> {
             "name": "John",
             "upd_count": r.row().g("upd_count").add(1),
         }))
-        .run::<_, Value>(&conn)
+        .run::<Value>(&conn)
         .try_next()
         .await?;
 
-    let user = r
-        .table(table)
-        .get(1)
-        .run::<_, Value>(&conn)
-        .try_next()
-        .await?;
+    let user = r.table(table).get(1).run::<Value>(&conn).try_next().await?;
     assert_eq!(user, Some(json!({"id": 1, "name": "John", "upd_count": 4})));
 
     Ok(())
@@ -0,0 +1,31 @@
+use serde::Deserialize;
+use serde_json::to_value;
+use unreql::r;
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, PartialEq)]
+struct UserPosts {
+    id: u32,
+    user: String,
+    posts: Vec<u32>,
+}
+
+#[test]
+fn projects_struct_fields() {
+    let typed = r.table("users").with_fields_of::<UserPosts>();
+    let plain = r
+        .table("users")
+        .with_fields(r.args(["id", "user", "posts"]));
+    assert_eq!(to_value(typed).unwrap(), to_value(plain).unwrap());
+}
+
+#[test]
+fn rejects_non_struct() {
+    let query = r.table("users").with_fields_of::<serde_json::Value>();
+    let error = to_value(query).unwrap_err().to_string();
+    assert!(
+        error.contains("is not a struct with named fields"),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,36 @@
+use serde_json::{json, to_value};
+use unreql::{func, r};
+
+#[test]
+fn set_write_hook_query() {
+    let query = r
+        .table("comments")
+        .set_write_hook(func!(|_context, _old_val, new_val| new_val));
+    let json = to_value(&query).unwrap();
+    let vars = &json[1][1][1][0][1];
+    assert_eq!(
+        json,
+        json!([
+            189,
+            [[15, ["comments"]], [69, [[2, vars], [10, [vars[2]]]]]]
+        ])
+    );
+}
+
+#[test]
+fn remove_write_hook_query() {
+    let query = r.table("comments").set_write_hook(json!(null));
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([189, [[15, ["comments"]], null]])
+    );
+}
+
+#[test]
+fn get_write_hook_query() {
+    let query = r.table("comments").get_write_hook();
+    assert_eq!(
+        to_value(&query).unwrap(),
+        json!([190, [[15, ["comments"]]]])
+    );
+}